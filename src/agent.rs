@@ -1,16 +1,67 @@
+use crate::checkpoint::{CheckpointStore, TurnRecorder};
+use crate::config::{AgentProfile, PermissionAction};
 use crate::context::FileAccessTracker;
-use crate::error::Result;
+use crate::context::compaction::Compactor;
+use crate::context::diff::FileDiff;
+use crate::memory::MemorySystem;
+use crate::error::{NexusError, Result};
 use tracing::{debug, info, warn};
-use crate::providers::{CompletionRequest, Message, Role};
+use crate::providers::{CacheControl, CompletionRequest, CompletionResponse, Message, Provider, Role, StreamChunk, Usage};
+use crate::providers::model_capabilities::ModelRegistry;
 use crate::providers::retry::retry_with_backoff;
 use crate::providers::token_budget::TokenBudget;
 use crate::sandbox::SandboxManager;
-use crate::sandbox::hydration::{Hydrator, HydrationPlan, FileChange};
-use crate::executor::tools::{ToolCall, ToolResult, parse_tool_calls};
+use crate::sandbox::hydration::Hydrator;
+use crate::executor::approval::{ApprovalPolicy, Approver};
+use crate::executor::registry::{ToolContext, ToolRegistry};
+use crate::executor::review::{ChangeReviewer, ProposedChange, Review};
+use crate::executor::tools::{ToolResult, parse_tool_calls};
+use async_trait::async_trait;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Arc;
 use std::time::Duration;
+use tokio::sync::{mpsc, RwLock};
 
-/// Maximum tool-calling turns before forcing termination
-const MAX_TURNS: usize = 20;
+/// Progress events emitted by the streaming agent loop
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AgentEvent {
+    /// Incremental text from the model
+    TextDelta { text: String },
+    /// A tool call is about to run
+    ToolCallStarted {
+        id: String,
+        name: String,
+        arguments: serde_json::Value,
+    },
+    /// A tool call is waiting for the user's approval
+    ApprovalRequested {
+        id: String,
+        name: String,
+        arguments: serde_json::Value,
+        /// The command or path the permission rules matched against
+        subject: Option<String>,
+    },
+    /// A tool call finished
+    ToolCallFinished {
+        id: String,
+        name: String,
+        success: bool,
+        output: String,
+        error: Option<String>,
+    },
+    /// A tool proposed a file write, shown before it is reviewed
+    FileDiff(FileDiff),
+    /// Token usage for one model turn
+    Usage(Usage),
+    /// Older history was compacted to stay within the context window
+    Compacted {
+        messages_compacted: usize,
+        tokens_before: u32,
+        tokens_after: u32,
+    },
+}
 
 /// The Agent runs multi-turn conversations with tool calling
 pub struct Agent {
@@ -18,6 +69,14 @@ pub struct Agent {
     hydrator: Hydrator,
     working_dir: std::path::PathBuf,
     file_tracker: FileAccessTracker,
+    compactor: Compactor,
+    memory: Option<Arc<RwLock<MemorySystem>>>,
+    profile: AgentProfile,
+    tools: ToolRegistry,
+    approval: ApprovalPolicy,
+    approver: Option<Arc<dyn Approver>>,
+    reviewer: Option<Arc<dyn ChangeReviewer>>,
+    checkpoints: Option<Arc<CheckpointStore>>,
 }
 
 impl Agent {
@@ -27,9 +86,49 @@ impl Agent {
             hydrator: Hydrator::new()?,
             working_dir,
             file_tracker: FileAccessTracker::new(),
+            compactor: Compactor::default(),
+            memory: None,
+            profile: AgentProfile::default(),
+            tools: ToolRegistry::builtin(),
+            approval: ApprovalPolicy::allow_all(),
+            approver: None,
+            reviewer: None,
+            checkpoints: None,
         })
     }
 
+    /// Use `profile` for turn, token, time and per-tool limits
+    pub fn with_profile(mut self, profile: AgentProfile) -> Self {
+        self.profile = profile;
+        self
+    }
+
+    /// Check every tool call against `policy`, asking `approver` when a rule
+    /// says to. Without an approver those calls are refused.
+    pub fn with_approval(mut self, policy: ApprovalPolicy, approver: Option<Arc<dyn Approver>>) -> Self {
+        self.approval = policy;
+        self.approver = approver;
+        self
+    }
+
+    /// Show every proposed file write to `reviewer` before it happens
+    pub fn with_reviewer(mut self, reviewer: Arc<dyn ChangeReviewer>) -> Self {
+        self.reviewer = Some(reviewer);
+        self
+    }
+
+    /// Record each task's file writes as an undoable checkpoint in `store`
+    pub fn with_checkpoints(mut self, store: Arc<CheckpointStore>) -> Self {
+        self.checkpoints = Some(store);
+        self
+    }
+
+    /// Keep compaction summaries in `memory` so they outlive the conversation
+    pub fn with_memory(mut self, memory: Arc<RwLock<MemorySystem>>) -> Self {
+        self.memory = Some(memory);
+        self
+    }
+
     /// Get a reference to the file access tracker
     pub fn file_tracker(&self) -> &FileAccessTracker {
         &self.file_tracker
@@ -39,49 +138,133 @@ impl Agent {
     pub async fn run_task(
         &self,
         messages: &mut Vec<Message>,
-        provider: &dyn crate::providers::Provider,
+        provider: &dyn Provider,
         model: String,
     ) -> Result<String> {
-        let mut budget = TokenBudget::default();
+        self.run_with_timeout(messages, provider, model, None).await
+    }
+
+    /// Run a task like `run_task`, streaming the response and reporting progress
+    /// (text deltas, tool calls, usage) on `events` as it happens.
+    pub async fn run_task_streaming(
+        &self,
+        messages: &mut Vec<Message>,
+        provider: &dyn Provider,
+        model: String,
+        events: mpsc::Sender<AgentEvent>,
+    ) -> Result<String> {
+        self.run_with_timeout(messages, provider, model, Some(&events)).await
+    }
+
+    async fn run_with_timeout(
+        &self,
+        messages: &mut Vec<Message>,
+        provider: &dyn Provider,
+        model: String,
+        events: Option<&mpsc::Sender<AgentEvent>>,
+    ) -> Result<String> {
+        // Fail before spending anything on a model that can't see the images
+        if messages.iter().any(Message::has_images) && !ModelRegistry::current().accepts_images(&model) {
+            return Err(NexusError::VisionUnsupported { model });
+        }
+
+        let label = messages.iter().rev()
+            .find(|m| m.role == Role::User)
+            .map(|m| m.text_content())
+            .unwrap_or_default();
+        let recorder = self.checkpoints.as_ref().map(|store| store.begin(&label)).transpose()?;
+
+        let result = match self.profile.timeout() {
+            None => self.run_loop(messages, provider, model, events, recorder.as_ref()).await,
+            Some(limit) => {
+                match tokio::time::timeout(limit, self.run_loop(messages, provider, model, events, recorder.as_ref())).await {
+                    Ok(result) => result,
+                    Err(_) => {
+                        warn!(timeout_secs = limit.as_secs(), "Agent task timed out");
+                        Ok(format!("[Agent stopped: time limit of {}s reached.]", limit.as_secs()))
+                    }
+                }
+            }
+        };
+
+        // Record whatever the turn wrote, even if it failed part-way
+        match recorder.map(|r| r.commit()).transpose() {
+            Ok(Some(Some(checkpoint))) => info!(id = checkpoint.id, files = checkpoint.files.len(), "Recorded checkpoint"),
+            Ok(_) => {}
+            Err(e) => warn!(error = %e, "Failed to record checkpoint"),
+        }
+        result
+    }
+
+    async fn run_loop(
+        &self,
+        messages: &mut Vec<Message>,
+        provider: &dyn Provider,
+        model: String,
+        events: Option<&mpsc::Sender<AgentEvent>>,
+        checkpoint: Option<&TurnRecorder<'_>>,
+    ) -> Result<String> {
+        let mut budget = TokenBudget::for_model(&model)
+            .with_limits(self.profile.max_input_tokens(), self.profile.max_total_tokens());
+        let max_turns = self.profile.max_turns();
+        let mut tool_calls_made: HashMap<String, usize> = HashMap::new();
+        let reviewer = EventReviewer { inner: self.reviewer.as_deref(), events };
+        let tools = self.tools.definitions();
 
         // Agent loop: keep going until no more tool calls (with safety limit)
-        for turn in 0..MAX_TURNS {
+        for turn in 0..max_turns {
             // Check token budget before sending
             if !budget.can_continue() {
                 warn!(used = budget.used_input_tokens + budget.used_output_tokens, remaining = budget.remaining(), "Token budget exhausted");
                 return Ok(format!("[Agent stopped: token budget exhausted after {} turns. Used {} tokens.]", turn, budget.used_input_tokens + budget.used_output_tokens));
             }
 
+            // Compact history before it overflows the model's context window
+            if self.compactor.needs_compaction(messages, &model) {
+                self.compact(messages, provider, &model, &mut budget, events).await;
+            }
+
             // Estimate input tokens
-            let input_estimate: u32 = messages.iter().map(|m| TokenBudget::estimate_tokens(&m.content)).sum();
+            let input_estimate = budget.count_messages(messages);
 
             // Send request to AI with tools
             let request = CompletionRequest {
                 model: model.clone(),
                 messages: messages.clone(),
-                temperature: Some(0.7),
+                temperature: Some(self.profile.temperature()),
                 max_tokens: Some(budget.dynamic_max_tokens()),
-                stream: Some(false),
-                tools: Some(crate::executor::tools::get_available_tools()),
+                stream: Some(events.is_some()),
+                tools: Some(tools.clone()),
                 extra_params: None,
+                // Cache up to the newest message so the next turn reads it back
+                cache: Some(CacheControl::prefix(messages.len())),
             };
 
             let response = retry_with_backoff(3, Duration::from_secs(1), || {
                 let req = request.clone();
-                async move { provider.complete(req).await }
+                async move {
+                    match events {
+                        Some(events) => stream_completion(provider, req, events).await,
+                        None => provider.complete(req).await,
+                    }
+                }
             })
             .await?;
 
             // Record token usage
-            let output_estimate = TokenBudget::estimate_tokens(&response.content);
+            let output_estimate = budget.count_tokens(&response.content);
             if let Some(ref usage) = response.usage {
                 budget.record_usage(usage.prompt_tokens, usage.completion_tokens);
             } else {
                 budget.record_usage(input_estimate, output_estimate);
             }
             info!(input_tokens = budget.used_input_tokens, output_tokens = budget.used_output_tokens, remaining = budget.remaining(), "Token usage");
+            if let (Some(events), Some(usage)) = (events, response.usage.clone()) {
+                let _ = events.send(AgentEvent::Usage(usage)).await;
+            }
 
             // Check if response has tool calls (use native tool_calls if available, fallback to parsing)
+            let native_tool_calls = response.tool_calls.is_some();
             let tool_calls = response.tool_calls.clone().unwrap_or_else(|| {
                 // Fallback: parse from content for providers without native tool calling
                 parse_tool_calls(&response.content)
@@ -89,11 +272,7 @@ impl Agent {
 
             if tool_calls.is_empty() {
                 // No tools - AI is done, return final answer
-                messages.push(Message {
-                    role: Role::Assistant,
-                    content: response.content.clone(),
-                    name: None,
-                });
+                messages.push(Message::assistant(response.content.clone()));
                 return Ok(response.content);
             }
 
@@ -101,8 +280,52 @@ impl Agent {
             info!(count = tool_calls.len(), "Executing tools");
             let mut tool_results = Vec::new();
 
-            for tool_call in tool_calls {
-                let result = self.execute_tool(&tool_call).await?;
+            for tool_call in &tool_calls {
+                let read_only = self.tools.is_read_only(&tool_call.name);
+                if let Some(events) = events
+                    && self.approver.is_some()
+                    && self.approval.decide(tool_call, read_only) == PermissionAction::Ask
+                {
+                    let _ = events.send(AgentEvent::ApprovalRequested {
+                        id: tool_call.id.clone(),
+                        name: tool_call.name.clone(),
+                        arguments: tool_call.arguments.clone(),
+                        subject: self.approval.subject(tool_call),
+                    }).await;
+                }
+                let permission = self.approval.check(tool_call, read_only, self.approver.as_deref()).await;
+
+                if let Some(events) = events {
+                    let _ = events.send(AgentEvent::ToolCallStarted {
+                        id: tool_call.id.clone(),
+                        name: tool_call.name.clone(),
+                        arguments: tool_call.arguments.clone(),
+                    }).await;
+                }
+
+                let result = match permission {
+                    Ok(()) => {
+                        self.tools
+                            .execute_with_limits(tool_call, &self.tool_context_with(&reviewer, checkpoint), &self.profile, &mut tool_calls_made)
+                            .await
+                    }
+                    Err(reason) => ToolResult {
+                        tool_call_id: tool_call.id.clone(),
+                        success: false,
+                        output: String::new(),
+                        error: Some(reason),
+                    },
+                };
+
+                if let Some(events) = events {
+                    let _ = events.send(AgentEvent::ToolCallFinished {
+                        id: tool_call.id.clone(),
+                        name: tool_call.name.clone(),
+                        success: result.success,
+                        output: result.output.clone(),
+                        error: result.error.clone(),
+                    }).await;
+                }
 
                 // Log to stderr (not stdout) so it doesn't pollute chat responses
                 if result.success {
@@ -114,187 +337,388 @@ impl Agent {
                 tool_results.push(result);
             }
 
-            // Add assistant message (with tool calls)
-            messages.push(Message {
-                role: Role::Assistant,
-                content: response.content,
-                name: None,
-            });
-
-            // Add tool results as system messages
-            for result in &tool_results {
-                let tool_content = format!(
-                    "Tool '{}' result:\nSuccess: {}\nOutput: {}\nError: {}",
-                    result.tool_call_id,
-                    result.success,
-                    result.output,
-                    result.error.as_ref().unwrap_or(&"None".to_string())
-                );
-                
-                messages.push(Message {
-                    role: Role::Tool,
-                    content: tool_content,
-                    name: Some(result.tool_call_id.clone()),
-                });
+            if native_tool_calls {
+                // Native calls are threaded back as tool_result parts tied to each call
+                messages.push(Message::assistant_with_tool_calls(response.content, &tool_calls));
+                messages.push(Message::tool_results(&tool_results));
+            } else {
+                // Text-parsed calls have nothing to tie results to, so report them as text
+                messages.push(Message::assistant(response.content));
+                let report = tool_results.iter().zip(&tool_calls).map(|(result, call)| {
+                    format!(
+                        "Tool '{}' result:\nSuccess: {}\nOutput: {}\nError: {}",
+                        call.name,
+                        result.success,
+                        result.output,
+                        result.error.as_deref().unwrap_or("None")
+                    )
+                }).collect::<Vec<_>>().join("\n\n");
+                messages.push(Message::user(report));
             }
 
             // Loop continues - AI will see tool results and respond
             info!("AI analyzing tool results");
         }
 
-        // Safety: if we exhausted MAX_TURNS without a final response
-        Ok(format!("[Agent reached max turns limit ({}). Last tool results were processed but no final summary was generated.]", MAX_TURNS))
+        // Safety: if we exhausted the turn limit without a final response
+        Ok(format!("[Agent reached max turns limit ({}). Last tool results were processed but no final summary was generated.]", max_turns))
     }
 
-    async fn execute_tool(&self, tool_call: &ToolCall) -> Result<ToolResult> {
-        let result = match tool_call.name.as_str() {
-            "execute_command" => {
-                let command = tool_call.arguments.get("command")
-                    .and_then(|c| c.as_str())
-                    .unwrap_or_default();
-                
-                // Use network for package managers
-                let is_package_command = command.contains("npm") || 
-                                        command.contains("pip") || 
-                                        command.contains("apk") || 
-                                        command.contains("apt") || 
-                                        command.contains("dnf") || 
-                                        command.contains("yarn") || 
-                                        command.contains("cargo install");
-                
-                let shadow_result = if is_package_command {
-                    self.sandbox.shadow_run_with_network(command, &self.working_dir).await?
-                } else {
-                    self.sandbox.shadow_run(command, &self.working_dir).await?
-                };
-                
-                ToolResult {
-                    tool_call_id: tool_call.id.clone(),
-                    success: shadow_result.success,
-                    output: format!("stdout: {}\nstderr: {}", shadow_result.stdout, shadow_result.stderr),
-                    error: if shadow_result.success { None } else { 
-                        Some(format!("Exit code: {}", shadow_result.exit_code)) 
-                    },
-                }
+    async fn compact(
+        &self,
+        messages: &mut Vec<Message>,
+        provider: &dyn Provider,
+        model: &str,
+        budget: &mut TokenBudget,
+        events: Option<&mpsc::Sender<AgentEvent>>,
+    ) {
+        let result = match self.compactor.compact(messages, provider, model).await {
+            Ok(Some(result)) => result,
+            Ok(None) => return,
+            Err(e) => {
+                warn!(error = %e, "Context compaction failed");
+                return;
             }
-            "create_file" => {
-                let path = tool_call.arguments.get("path")
-                    .and_then(|p| p.as_str())
-                    .unwrap_or_default();
-                let content = tool_call.arguments.get("content")
-                    .and_then(|c| c.as_str())
-                    .unwrap_or_default();
-                
-                let change = FileChange {
-                    path: std::path::PathBuf::from(path),
-                    content: content.to_string(),
-                    backup_path: None,
-                };
-                
-                let plan = HydrationPlan {
-                    files_to_create: vec![change],
-                    files_to_update: Vec::new(),
-                    files_to_delete: Vec::new(),
-                    directories_to_create: Vec::new(),
-                };
-                
-                match self.hydrator.execute_plan(&plan) {
-                    Ok(_) => ToolResult {
-                        tool_call_id: tool_call.id.clone(),
-                        success: true,
-                        output: format!("Created file: {}", path),
-                        error: None,
-                    },
-                    Err(e) => ToolResult {
-                        tool_call_id: tool_call.id.clone(),
-                        success: false,
-                        output: String::new(),
-                        error: Some(e.to_string()),
-                    }
+        };
+        info!(
+            messages = result.messages_compacted,
+            trimmed = result.outputs_trimmed,
+            before = result.tokens_before,
+            after = result.tokens_after,
+            "Compacted conversation history"
+        );
+
+        if let Some(usage) = &result.usage {
+            budget.record_usage(usage.prompt_tokens, usage.completion_tokens);
+        }
+        if let (Some(memory), Some(summary)) = (&self.memory, &result.summary)
+            && let Err(e) = memory.write().await.record_compaction(summary, result.messages_compacted).await
+        {
+            warn!(error = %e, "Failed to store compaction summary in memory");
+        }
+        if let Some(events) = events {
+            let _ = events.send(AgentEvent::Compacted {
+                messages_compacted: result.messages_compacted,
+                tokens_before: result.tokens_before,
+                tokens_after: result.tokens_after,
+            }).await;
+        }
+    }
+
+    fn tool_context(&self) -> ToolContext<'_> {
+        ToolContext {
+            working_dir: &self.working_dir,
+            sandbox: &self.sandbox,
+            hydrator: &self.hydrator,
+            file_tracker: &self.file_tracker,
+            reviewer: None,
+            checkpoint: None,
+            test_report: None,
+        }
+    }
+
+    /// Context for the agent loop, where writes go through `reviewer` and are
+    /// recorded in `checkpoint`
+    fn tool_context_with<'a>(
+        &'a self,
+        reviewer: &'a EventReviewer<'a>,
+        checkpoint: Option<&'a TurnRecorder<'a>>,
+    ) -> ToolContext<'a> {
+        let reviewer = (reviewer.inner.is_some() || reviewer.events.is_some()).then_some(reviewer as &dyn ChangeReviewer);
+        ToolContext { reviewer, checkpoint, ..self.tool_context() }
+    }
+}
+
+/// Reports proposed writes as `FileDiff` events, then defers to the agent's
+/// reviewer, accepting everything if it has none
+struct EventReviewer<'a> {
+    inner: Option<&'a dyn ChangeReviewer>,
+    events: Option<&'a mpsc::Sender<AgentEvent>>,
+}
+
+#[async_trait]
+impl ChangeReviewer for EventReviewer<'_> {
+    async fn review(&self, change: &ProposedChange<'_>) -> Review {
+        if let Some(events) = self.events {
+            let _ = events.send(AgentEvent::FileDiff(change.diff.clone())).await;
+        }
+        match self.inner {
+            Some(reviewer) => reviewer.review(change).await,
+            None => Review::Accept,
+        }
+    }
+}
+
+/// Run one streamed completion, forwarding text deltas to `events` and assembling
+/// the chunks into a `CompletionResponse`.
+async fn stream_completion(
+    provider: &dyn Provider,
+    request: CompletionRequest,
+    events: &mpsc::Sender<AgentEvent>,
+) -> Result<CompletionResponse> {
+    let (tx, mut rx) = mpsc::channel(64);
+    let mut response = CompletionResponse::new(String::new(), request.model.clone(), String::new());
+    let mut tool_calls = Vec::new();
+
+    let (result, ()) = tokio::join!(provider.complete_stream(request, tx), async {
+        while let Some(chunk) = rx.recv().await {
+            match chunk {
+                StreamChunk::ContentDelta(text) => {
+                    let _ = events.send(AgentEvent::TextDelta { text: text.clone() }).await;
+                    response.content.push_str(&text);
                 }
+                StreamChunk::ToolCall(call) => tool_calls.push(call),
+                // Providers may report cumulative usage several times; keep the latest
+                StreamChunk::Usage(usage) => response.usage = Some(usage),
+                StreamChunk::Done => break,
             }
-            "edit_file" => {
-                let path = tool_call.arguments.get("path")
-                    .and_then(|p| p.as_str())
-                    .unwrap_or_default();
-                let content = tool_call.arguments.get("content")
-                    .and_then(|c| c.as_str())
-                    .unwrap_or_default();
-                
-                let full_path = self.working_dir.join(path);
-                
-                // Check for staleness before editing
-                if let Err(e) = self.file_tracker.check_staleness(&full_path) {
-                    return Ok(ToolResult {
-                        tool_call_id: tool_call.id.clone(),
-                        success: false,
-                        output: String::new(),
-                        error: Some(e.to_string()),
-                    });
-                }
-                
-                let change = FileChange {
-                    path: std::path::PathBuf::from(path),
-                    content: content.to_string(),
-                    backup_path: None,
-                };
-                
-                let plan = HydrationPlan {
-                    files_to_create: Vec::new(),
-                    files_to_update: vec![change],
-                    files_to_delete: Vec::new(),
-                    directories_to_create: Vec::new(),
-                };
-                
-                match self.hydrator.execute_plan(&plan) {
-                    Ok(_) => ToolResult {
-                        tool_call_id: tool_call.id.clone(),
-                        success: true,
-                        output: format!("Updated file: {}", path),
-                        error: None,
-                    },
-                    Err(e) => ToolResult {
-                        tool_call_id: tool_call.id.clone(),
-                        success: false,
-                        output: String::new(),
-                        error: Some(e.to_string()),
-                    }
-                }
+        }
+    });
+
+    if let Err(e) = result {
+        // Text already shown to the user can't be taken back, so don't retry
+        if !response.content.is_empty() {
+            return Err(NexusError::ApiRequest(format!("Stream interrupted after partial output: {}", e)));
+        }
+        return Err(e);
+    }
+
+    if !tool_calls.is_empty() {
+        response.tool_calls = Some(tool_calls);
+    }
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executor::tools::ToolCall;
+    use crate::providers::ProviderInfo;
+
+    /// Streams a fixed reply as several chunks
+    struct ChunkedProvider;
+
+    #[async_trait]
+    impl Provider for ChunkedProvider {
+        fn info(&self) -> ProviderInfo {
+            ProviderInfo {
+                name: "chunked".to_string(),
+                display_name: "Chunked".to_string(),
+                supports_oauth: false,
+                default_model: "test".to_string(),
+                available_models: vec![],
             }
-            "read_file" => {
-                let path = tool_call.arguments.get("path")
-                    .and_then(|p| p.as_str())
-                    .unwrap_or_default();
-                
-                let full_path = self.working_dir.join(path);
-                match std::fs::read_to_string(&full_path) {
-                    Ok(content) => {
-                        // Record that we read this file for staleness detection
-                        self.file_tracker.record_read(&full_path);
-                        
-                        ToolResult {
-                            tool_call_id: tool_call.id.clone(),
-                            success: true,
-                            output: content,
-                            error: None,
-                        }
-                    }
-                    Err(e) => ToolResult {
-                        tool_call_id: tool_call.id.clone(),
-                        success: false,
-                        output: String::new(),
-                        error: Some(format!("Failed to read file: {}", e)),
-                    }
-                }
+        }
+
+        async fn complete(&self, _request: CompletionRequest) -> Result<CompletionResponse> {
+            unreachable!("streaming requests should not fall back to complete()")
+        }
+
+        async fn complete_stream(&self, _request: CompletionRequest, tx: mpsc::Sender<StreamChunk>) -> Result<()> {
+            let _ = tx.send(StreamChunk::ContentDelta("Reading ".to_string())).await;
+            let _ = tx.send(StreamChunk::ContentDelta("the file.".to_string())).await;
+            let _ = tx.send(StreamChunk::ToolCall(ToolCall {
+                id: "call_1".to_string(),
+                name: "read_file".to_string(),
+                arguments: serde_json::json!({"path": "Cargo.toml"}),
+            })).await;
+            let _ = tx.send(StreamChunk::Usage(Usage { prompt_tokens: 5, completion_tokens: 1, total_tokens: 6, ..Usage::default() })).await;
+            let _ = tx.send(StreamChunk::Usage(Usage { prompt_tokens: 10, completion_tokens: 4, total_tokens: 14, ..Usage::default() })).await;
+            let _ = tx.send(StreamChunk::Done).await;
+            Ok(())
+        }
+
+        async fn authenticate(&mut self) -> Result<()> {
+            Ok(())
+        }
+
+        async fn refresh_auth(&mut self) -> Result<()> {
+            Ok(())
+        }
+
+        fn is_authenticated(&self) -> bool {
+            true
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stream_completion_assembles_chunks() {
+        let (events_tx, mut events_rx) = mpsc::channel(16);
+        let request = CompletionRequest {
+            model: "test".to_string(),
+            messages: vec![Message::user("read Cargo.toml")],
+            temperature: None,
+            max_tokens: None,
+            stream: Some(true),
+            tools: None,
+            extra_params: None,
+            cache: None,
+        };
+
+        let response = stream_completion(&ChunkedProvider, request, &events_tx).await.unwrap();
+        drop(events_tx);
+
+        assert_eq!(response.content, "Reading the file.");
+        assert_eq!(response.tool_calls.unwrap()[0].name, "read_file");
+        assert_eq!(response.usage.unwrap().total_tokens, 14);
+
+        let mut deltas = Vec::new();
+        while let Some(event) = events_rx.recv().await {
+            if let AgentEvent::TextDelta { text } = event {
+                deltas.push(text);
             }
-            _ => ToolResult {
-                tool_call_id: tool_call.id.clone(),
-                success: false,
-                output: String::new(),
-                error: Some(format!("Unknown tool: {}", tool_call.name)),
+        }
+        assert_eq!(deltas, vec!["Reading ", "the file."]);
+    }
+
+    #[tokio::test]
+    async fn test_profile_limits_turns_and_tool_calls() {
+        let profile = AgentProfile {
+            max_turns: Some(3),
+            tools: HashMap::from([("read_file".to_string(), crate::config::ToolLimits { max_calls: Some(1), timeout_secs: None })]),
+            ..AgentProfile::default()
+        };
+        let agent = Agent::new(std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"))).unwrap().with_profile(profile);
+        let mut messages = vec![Message::user("read Cargo.toml")];
+        let (events_tx, mut events_rx) = mpsc::channel(64);
+
+        // The stub asks for read_file on every turn, so only the turn limit stops it
+        let result = agent.run_task_streaming(&mut messages, &ChunkedProvider, "test".to_string(), events_tx).await.unwrap();
+        assert!(result.contains("max turns limit (3)"));
+
+        let mut finished = Vec::new();
+        while let Some(event) = events_rx.recv().await {
+            if let AgentEvent::ToolCallFinished { success, error, .. } = event {
+                finished.push((success, error));
+            }
+        }
+        assert_eq!(finished.len(), 3);
+        assert!(finished[0].0);
+        assert!(finished[1..].iter().all(|(success, error)| !success && error.as_deref().unwrap().contains("limit of 1 calls")));
+    }
+
+    #[tokio::test]
+    async fn test_str_replace_chains_and_detects_stale_files() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let file = dir.path().join("lib.rs");
+        std::fs::write(&file, "fn a() {}\nfn b() {}\n").unwrap();
+        let agent = Agent::new(dir.path().to_path_buf()).unwrap();
+        let call = |name: &str, arguments: serde_json::Value| ToolCall {
+            id: "call".to_string(),
+            name: name.to_string(),
+            arguments,
+        };
+
+        agent.tools.execute(&call("read_file", serde_json::json!({"path": "lib.rs"})), &agent.tool_context()).await;
+        let first = agent.tools.execute(&call("str_replace", serde_json::json!({
+            "path": "lib.rs", "old_str": "fn a() {}", "new_str": "fn a() -> u8 { 1 }"
+        })), &agent.tool_context()).await;
+        assert!(first.success, "{:?}", first.error);
+
+        // The tool's own write doesn't make the file stale
+        let second = agent.tools.execute(&call("apply_patch", serde_json::json!({
+            "path": "lib.rs", "patch": "@@ -2 +2 @@\n-fn b() {}\n+fn b() -> u8 { 2 }\n"
+        })), &agent.tool_context()).await;
+        assert!(second.success, "{:?}", second.error);
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "fn a() -> u8 { 1 }\nfn b() -> u8 { 2 }\n");
+
+        // An outside change since the last read is refused
+        std::thread::sleep(Duration::from_millis(20));
+        std::fs::write(&file, "fn changed() {}\n").unwrap();
+        let stale = agent.tools.execute(&call("str_replace", serde_json::json!({
+            "path": "lib.rs", "old_str": "fn changed() {}", "new_str": ""
+        })), &agent.tool_context()).await;
+        assert!(!stale.success);
+        assert!(stale.error.unwrap().contains("modified since you last read it"));
+    }
+
+    #[tokio::test]
+    async fn test_denied_tool_calls_are_reported_to_the_model() {
+        let policy = ApprovalPolicy::new(
+            &crate::config::PermissionConfig {
+                default: None,
+                rules: vec![crate::config::PermissionRule {
+                    tool: "read_file".to_string(),
+                    pattern: Some("*.toml".to_string()),
+                    action: crate::config::PermissionAction::Deny,
+                }],
+            },
+            true,
+            std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR")),
+        ).unwrap();
+        let agent = Agent::new(std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"))).unwrap()
+            .with_profile(AgentProfile { max_turns: Some(1), ..AgentProfile::default() })
+            .with_approval(policy, None);
+        let mut messages = vec![Message::user("read Cargo.toml")];
+        let (events_tx, mut events_rx) = mpsc::channel(64);
+
+        agent.run_task_streaming(&mut messages, &ChunkedProvider, "test".to_string(), events_tx).await.unwrap();
+
+        let mut errors = Vec::new();
+        while let Some(event) = events_rx.recv().await {
+            if let AgentEvent::ToolCallFinished { error, .. } = event {
+                errors.push(error.unwrap_or_default());
             }
+        }
+        assert_eq!(errors, vec!["Permission denied: 'read_file' is not allowed for Cargo.toml"]);
+    }
+
+    #[tokio::test]
+    async fn test_run_task_against_mock_provider() {
+        use crate::providers::mock::{Cassette, MockProvider};
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let cassette: Cassette = serde_json::from_value(serde_json::json!({
+            "interactions": [
+                {"response": {
+                    "content": "Creating the notes.",
+                    "tool_calls": [{"name": "create_file", "arguments": {"path": "notes.txt", "content": "remember\n"}}]
+                }},
+                {"when": "notes.txt", "response": {"content": "Done."}}
+            ]
+        })).unwrap();
+        let provider = MockProvider::new(cassette);
+        let agent = Agent::new(dir.path().to_path_buf()).unwrap();
+        let mut messages = vec![Message::user("write some notes")];
+
+        let result = agent.run_task(&mut messages, &provider, "mock".to_string()).await.unwrap();
+
+        assert_eq!(result, "Done.");
+        assert_eq!(std::fs::read_to_string(dir.path().join("notes.txt")).unwrap(), "remember\n");
+    }
+
+    #[tokio::test]
+    async fn test_images_rejected_for_non_vision_model() {
+        use crate::providers::ContentPart;
+        use crate::providers::mock::{Cassette, MockProvider};
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let provider = MockProvider::new(Cassette::default());
+        let agent = Agent::new(dir.path().to_path_buf()).unwrap();
+        let image = ContentPart::Image { media_type: "image/png".to_string(), data: "iVBORw0KGgo=".to_string() };
+        let mut messages = vec![Message::user_with_images("fix this layout", vec![image])];
+
+        let err = agent.run_task(&mut messages, &provider, "deepseek-chat".to_string()).await.unwrap_err();
+        assert!(matches!(err, NexusError::VisionUnsupported { ref model } if model == "deepseek-chat"));
+
+        // A local model the registry knows nothing about is refused too
+        let err = agent.run_task(&mut messages, &provider, "llama3.2:3b".to_string()).await.unwrap_err();
+        assert!(matches!(err, NexusError::VisionUnsupported { ref model } if model == "llama3.2:3b"));
+    }
+
+    #[test]
+    fn test_agent_event_serializes_with_type_tag() {
+        let event = AgentEvent::ToolCallStarted {
+            id: "call_1".to_string(),
+            name: "read_file".to_string(),
+            arguments: serde_json::json!({"path": "a.rs"}),
         };
-        
-        Ok(result)
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["type"], "tool_call_started");
+        assert_eq!(json["name"], "read_file");
+
+        let usage = serde_json::to_value(AgentEvent::Usage(Usage { prompt_tokens: 1, completion_tokens: 2, total_tokens: 3, ..Usage::default() })).unwrap();
+        assert_eq!(usage["type"], "usage");
+        assert_eq!(usage["total_tokens"], 3);
     }
 }
@@ -0,0 +1,271 @@
+//! Undo checkpoints for agent file changes
+//!
+//! Every agent turn that writes files becomes a checkpoint holding a copy of
+//! each file as it was before the turn first touched it. Restoring checkpoint
+//! `n` undoes that turn and every later one, newest first. Checkpoints live
+//! under `~/.config/nexus/checkpoints/<project>`, so they work whether or not
+//! the project is a git repository.
+
+use crate::error::{NexusError, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+const MANIFEST: &str = "checkpoint.json";
+
+/// What a turn did to a file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileChangeKind {
+    Created,
+    Updated,
+    Deleted,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CheckpointFile {
+    /// Path relative to the project
+    pub path: PathBuf,
+    pub change: FileChangeKind,
+    /// Copy of the file before the turn, relative to the checkpoint directory;
+    /// `None` if the file did not exist
+    pub backup: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub id: usize,
+    pub created_at: DateTime<Utc>,
+    /// The prompt that started the turn, shortened for display
+    pub label: String,
+    pub files: Vec<CheckpointFile>,
+}
+
+/// Checkpoints for one project
+pub struct CheckpointStore {
+    dir: PathBuf,
+    project_dir: PathBuf,
+}
+
+impl CheckpointStore {
+    pub fn new(project_dir: &Path) -> Result<Self> {
+        let project_dir = project_dir.canonicalize().unwrap_or_else(|_| project_dir.to_path_buf());
+        let hash = Sha256::digest(project_dir.to_string_lossy().as_bytes());
+        let key: String = hash.iter().take(8).map(|b| format!("{:02x}", b)).collect();
+        let dir = std::env::var("HOME")
+            .map(|h| PathBuf::from(h).join(".config/nexus/checkpoints"))
+            .unwrap_or_else(|_| PathBuf::from("~/.config/nexus/checkpoints"))
+            .join(key);
+        Self::with_dir(dir, project_dir)
+    }
+
+    pub fn with_dir(dir: PathBuf, project_dir: PathBuf) -> Result<Self> {
+        fs::create_dir_all(&dir)?;
+        Ok(Self { dir, project_dir })
+    }
+
+    /// Start recording a turn; nothing is saved until [`TurnRecorder::commit`]
+    pub fn begin(&self, label: &str) -> Result<TurnRecorder<'_>> {
+        let staging = self.dir.join(format!("pending-{}", uuid::Uuid::new_v4().simple()));
+        fs::create_dir_all(&staging)?;
+        Ok(TurnRecorder {
+            store: self,
+            staging,
+            label: shorten(label),
+            files: Mutex::new(Vec::new()),
+        })
+    }
+
+    /// All checkpoints, oldest first
+    pub fn list(&self) -> Result<Vec<Checkpoint>> {
+        let mut checkpoints: Vec<Checkpoint> = fs::read_dir(&self.dir)?
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_name().to_string_lossy().parse::<usize>().is_ok())
+            .filter_map(|entry| {
+                let content = fs::read_to_string(entry.path().join(MANIFEST)).ok()?;
+                serde_json::from_str(&content).ok()
+            })
+            .collect();
+        checkpoints.sort_by_key(|c| c.id);
+        Ok(checkpoints)
+    }
+
+    /// Undo the most recent checkpoint, if there is one
+    pub fn undo(&self) -> Result<Option<Checkpoint>> {
+        let Some(last) = self.list()?.pop() else {
+            return Ok(None);
+        };
+        Ok(self.restore(last.id)?.into_iter().next())
+    }
+
+    /// Put the project back to how it was before checkpoint `id`, undoing it
+    /// and every later checkpoint. Returns the undone checkpoints, newest first.
+    pub fn restore(&self, id: usize) -> Result<Vec<Checkpoint>> {
+        let mut undone: Vec<Checkpoint> = self.list()?.into_iter().filter(|c| c.id >= id).collect();
+        if undone.first().is_none_or(|c| c.id != id) {
+            return Err(NexusError::Configuration(format!("Checkpoint not found: {}", id)));
+        }
+        undone.reverse();
+
+        for checkpoint in &undone {
+            let dir = self.checkpoint_dir(checkpoint.id);
+            for file in &checkpoint.files {
+                let target = self.project_dir.join(&file.path);
+                match &file.backup {
+                    Some(backup) => {
+                        if let Some(parent) = target.parent() {
+                            fs::create_dir_all(parent)?;
+                        }
+                        fs::copy(dir.join(backup), &target)?;
+                    }
+                    None if target.exists() => fs::remove_file(&target)?,
+                    None => {}
+                }
+            }
+            fs::remove_dir_all(&dir)?;
+        }
+        Ok(undone)
+    }
+
+    fn checkpoint_dir(&self, id: usize) -> PathBuf {
+        self.dir.join(id.to_string())
+    }
+}
+
+/// Collects the files one turn touches
+pub struct TurnRecorder<'a> {
+    store: &'a CheckpointStore,
+    staging: PathBuf,
+    label: String,
+    files: Mutex<Vec<(PathBuf, Option<PathBuf>)>>,
+}
+
+impl TurnRecorder<'_> {
+    /// Remember `path` as it is now, before it is written. Later writes to the
+    /// same path in this turn keep the first copy.
+    pub fn snapshot(&self, path: &Path) -> Result<()> {
+        let relative = path.strip_prefix(&self.store.project_dir).unwrap_or(path).to_path_buf();
+        let mut files = self.files.lock().unwrap_or_else(|e| e.into_inner());
+        if files.iter().any(|(p, _)| *p == relative) {
+            return Ok(());
+        }
+
+        let backup = match path.is_file() {
+            true => {
+                let name = PathBuf::from("files").join(files.len().to_string());
+                fs::create_dir_all(self.staging.join("files"))?;
+                fs::copy(path, self.staging.join(&name))?;
+                Some(name)
+            }
+            false => None,
+        };
+        files.push((relative, backup));
+        Ok(())
+    }
+
+    /// Save the turn as a checkpoint. Turns that changed nothing are dropped.
+    pub fn commit(self) -> Result<Option<Checkpoint>> {
+        let project_dir = &self.store.project_dir;
+        let files: Vec<CheckpointFile> = self.files.lock().unwrap_or_else(|e| e.into_inner())
+            .drain(..)
+            .filter_map(|(path, backup)| {
+                let exists = project_dir.join(&path).exists();
+                let change = match (&backup, exists) {
+                    (None, true) => FileChangeKind::Created,
+                    (Some(_), true) => FileChangeKind::Updated,
+                    (Some(_), false) => FileChangeKind::Deleted,
+                    (None, false) => return None,
+                };
+                Some(CheckpointFile { path, change, backup })
+            })
+            .collect();
+
+        if files.is_empty() {
+            let _ = fs::remove_dir_all(&self.staging);
+            return Ok(None);
+        }
+
+        let id = self.store.list()?.last().map_or(1, |c| c.id + 1);
+        let checkpoint = Checkpoint {
+            id,
+            created_at: Utc::now(),
+            label: self.label.clone(),
+            files,
+        };
+        fs::write(self.staging.join(MANIFEST), serde_json::to_string_pretty(&checkpoint)?)?;
+        fs::rename(&self.staging, self.store.checkpoint_dir(id))?;
+        Ok(Some(checkpoint))
+    }
+}
+
+impl Drop for TurnRecorder<'_> {
+    fn drop(&mut self) {
+        // Already renamed if committed
+        let _ = fs::remove_dir_all(&self.staging);
+    }
+}
+
+fn shorten(label: &str) -> String {
+    let first = label.lines().next().unwrap_or_default();
+    if first.chars().count() > 60 {
+        format!("{}...", first.chars().take(60).collect::<String>())
+    } else {
+        first.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn store(project: &TempDir, data: &TempDir) -> CheckpointStore {
+        CheckpointStore::with_dir(data.path().to_path_buf(), project.path().to_path_buf()).unwrap()
+    }
+
+    #[test]
+    fn test_restore_undoes_later_turns_in_order() {
+        let (project, data) = (TempDir::new().unwrap(), TempDir::new().unwrap());
+        let store = store(&project, &data);
+        let a = project.path().join("a.txt");
+        fs::write(&a, "v1").unwrap();
+
+        // Turn 1 updates a.txt twice and creates src/b.txt
+        let turn = store.begin("first turn").unwrap();
+        turn.snapshot(&a).unwrap();
+        fs::write(&a, "v2").unwrap();
+        turn.snapshot(&a).unwrap();
+        fs::write(&a, "v3").unwrap();
+        let b = project.path().join("src/b.txt");
+        turn.snapshot(&b).unwrap();
+        fs::create_dir_all(b.parent().unwrap()).unwrap();
+        fs::write(&b, "new").unwrap();
+        let first = turn.commit().unwrap().unwrap();
+        assert_eq!(first.id, 1);
+        assert_eq!(first.files.iter().map(|f| f.change).collect::<Vec<_>>(), vec![FileChangeKind::Updated, FileChangeKind::Created]);
+
+        // Turn 2 deletes a.txt
+        let turn = store.begin("second turn").unwrap();
+        turn.snapshot(&a).unwrap();
+        fs::remove_file(&a).unwrap();
+        assert_eq!(turn.commit().unwrap().unwrap().files[0].change, FileChangeKind::Deleted);
+
+        // A turn without writes leaves no checkpoint
+        assert!(store.begin("read only").unwrap().commit().unwrap().is_none());
+        assert_eq!(store.list().unwrap().len(), 2);
+
+        let undone = store.undo().unwrap().unwrap();
+        assert_eq!(undone.id, 2);
+        assert_eq!(fs::read_to_string(&a).unwrap(), "v3");
+
+        let undone = store.restore(1).unwrap();
+        assert_eq!(undone.len(), 1);
+        assert_eq!(fs::read_to_string(&a).unwrap(), "v1");
+        assert!(!b.exists());
+        assert!(store.list().unwrap().is_empty());
+        assert!(store.restore(1).is_err());
+    }
+}
@@ -5,7 +5,8 @@ use directories::ProjectDirs;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OAuthStatus {
@@ -24,6 +25,9 @@ pub struct NexusConfig {
 
     #[serde(default)]
     pub ui: UiConfig,
+
+    #[serde(default)]
+    pub agent: AgentProfile,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -38,6 +42,19 @@ pub struct ProviderConfig {
     pub base_url: Option<String>,
     pub default_model: Option<String>,
     pub timeout_secs: Option<u64>,
+    /// Scripted responses for the `mock` provider (JSON or YAML)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cassette: Option<PathBuf>,
+    /// Extra HTTP headers for `openai_compatible` providers
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub headers: HashMap<String, String>,
+    /// Models to offer for `openai_compatible` providers, in addition to any
+    /// the server lists
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub models: Vec<String>,
+    /// Providers to try, in order, when this one is down or rate limited
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fallback: Vec<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -47,6 +64,11 @@ pub enum ProviderType {
     Openrouter,
     Google,
     Claude,
+    /// OpenAI or any server with an OpenAI-style API (vLLM, llama.cpp,
+    /// LM Studio, Ollama)
+    OpenaiCompatible,
+    /// Replays a cassette instead of calling an API
+    Mock,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -59,6 +81,195 @@ pub struct UiConfig {
     pub command_timeout_secs: u64,
 }
 
+/// Limits for agent runs.
+///
+/// Every field is optional so profiles can be layered: the global `[agent]`
+/// section, then the project's `.nexus/config.toml`, then command-line flags.
+/// Unset fields fall back to the built-in defaults.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AgentProfile {
+    /// Maximum tool-calling turns per task
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_turns: Option<usize>,
+    /// Cap on cumulative prompt tokens per task
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_input_tokens: Option<u32>,
+    /// Cap on cumulative prompt + completion tokens per task
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_total_tokens: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    /// Wall-clock limit for a whole task
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_secs: Option<u64>,
+    /// Per-tool limits, keyed by tool name
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub tools: HashMap<String, ToolLimits>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ToolLimits {
+    /// Maximum calls to this tool per task
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_calls: Option<usize>,
+    /// Time limit for a single call
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_secs: Option<u64>,
+}
+
+impl AgentProfile {
+    pub const DEFAULT_MAX_TURNS: usize = 20;
+    pub const DEFAULT_MAX_INPUT_TOKENS: u32 = 100_000;
+    pub const DEFAULT_MAX_TOTAL_TOKENS: u32 = 200_000;
+    pub const DEFAULT_TEMPERATURE: f32 = 0.7;
+
+    /// Layer `overrides` on top of this profile; fields set in `overrides` win.
+    pub fn merged_with(&self, overrides: &AgentProfile) -> AgentProfile {
+        let mut tools = self.tools.clone();
+        for (name, limits) in &overrides.tools {
+            let entry = tools.entry(name.clone()).or_default();
+            entry.max_calls = limits.max_calls.or(entry.max_calls);
+            entry.timeout_secs = limits.timeout_secs.or(entry.timeout_secs);
+        }
+
+        AgentProfile {
+            max_turns: overrides.max_turns.or(self.max_turns),
+            max_input_tokens: overrides.max_input_tokens.or(self.max_input_tokens),
+            max_total_tokens: overrides.max_total_tokens.or(self.max_total_tokens),
+            temperature: overrides.temperature.or(self.temperature),
+            timeout_secs: overrides.timeout_secs.or(self.timeout_secs),
+            tools,
+        }
+    }
+
+    pub fn max_turns(&self) -> usize {
+        self.max_turns.unwrap_or(Self::DEFAULT_MAX_TURNS)
+    }
+
+    pub fn max_input_tokens(&self) -> u32 {
+        self.max_input_tokens.unwrap_or(Self::DEFAULT_MAX_INPUT_TOKENS)
+    }
+
+    pub fn max_total_tokens(&self) -> u32 {
+        self.max_total_tokens.unwrap_or(Self::DEFAULT_MAX_TOTAL_TOKENS)
+    }
+
+    pub fn temperature(&self) -> f32 {
+        self.temperature.unwrap_or(Self::DEFAULT_TEMPERATURE)
+    }
+
+    /// Wall-clock limit for a task, if any
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout_secs.map(Duration::from_secs)
+    }
+
+    /// Whether `tool` may run again after `calls_so_far` calls this task
+    pub fn allows_tool_call(&self, tool: &str, calls_so_far: usize) -> bool {
+        self.tools.get(tool)
+            .and_then(|limits| limits.max_calls)
+            .is_none_or(|max| calls_so_far < max)
+    }
+
+    /// Time limit for a single call to `tool`, if any
+    pub fn tool_timeout(&self, tool: &str) -> Option<Duration> {
+        self.tools.get(tool)
+            .and_then(|limits| limits.timeout_secs)
+            .map(Duration::from_secs)
+    }
+}
+
+/// What to do with a tool call
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PermissionAction {
+    Allow,
+    Ask,
+    Deny,
+}
+
+/// One allow / ask / deny rule for tool calls
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PermissionRule {
+    /// Tool name; glob syntax, so `"*"` matches every tool
+    pub tool: String,
+    /// Glob matched against the call's command or path (relative to the
+    /// project), e.g. `"src/**"` or `"rm *"`. Unset matches every call.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pattern: Option<String>,
+    pub action: PermissionAction,
+}
+
+/// Tool-call permissions, under `[permissions]` in `.nexus/config.toml`.
+///
+/// Rules are checked in order and the last one that matches wins, like
+/// `.gitignore`, so broad rules go first and exceptions after them.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PermissionConfig {
+    /// Action when no rule matches. Unset means read-only tools are allowed
+    /// and the rest follow `ui.confirm_dangerous_commands`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<PermissionAction>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rules: Vec<PermissionRule>,
+}
+
+/// Settings checked into a project as `.nexus/config.toml`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ProjectConfig {
+    #[serde(default)]
+    pub agent: AgentProfile,
+
+    #[serde(default)]
+    pub permissions: PermissionConfig,
+}
+
+impl ProjectConfig {
+    /// Load the nearest `.nexus/config.toml` at or above `dir`.
+    ///
+    /// A project without one gets the default (empty) config.
+    pub fn load(dir: &Path) -> Result<Self> {
+        let Some(path) = Self::find(dir) else {
+            return Ok(Self::default());
+        };
+
+        let content = fs::read_to_string(&path)?;
+        toml::from_str(&content).map_err(|e| {
+            NexusError::Configuration(format!("Failed to parse {}: {}", path.display(), e))
+        })
+    }
+
+    /// Path of the nearest `.nexus/config.toml` at or above `dir`, if any
+    pub fn find(dir: &Path) -> Option<PathBuf> {
+        dir.ancestors().map(|d| d.join(".nexus/config.toml")).find(|p| p.is_file())
+    }
+
+    /// Append `rule` to the nearest project config, creating
+    /// `dir/.nexus/config.toml` if there is none.
+    ///
+    /// The rule is appended as text so the rest of the file, comments
+    /// included, is left alone; being last, it takes precedence.
+    pub fn append_permission_rule(dir: &Path, rule: &PermissionRule) -> Result<PathBuf> {
+        let path = Self::find(dir).unwrap_or_else(|| dir.join(".nexus/config.toml"));
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let body = toml::to_string(rule)
+            .map_err(|e| NexusError::Configuration(format!("Failed to serialize rule: {}", e)))?;
+        let mut content = fs::read_to_string(&path).unwrap_or_default();
+        if !content.is_empty() && !content.ends_with('\n') {
+            content.push('\n');
+        }
+        if !content.is_empty() {
+            content.push('\n');
+        }
+        content.push_str("[[permissions.rules]]\n");
+        content.push_str(&body);
+        fs::write(&path, content)?;
+        Ok(path)
+    }
+}
+
 fn default_true() -> bool {
     true
 }
@@ -73,6 +284,7 @@ impl Default for NexusConfig {
             default_provider: None,
             providers: HashMap::new(),
             ui: UiConfig::default(),
+            agent: AgentProfile::default(),
         }
     }
 }
@@ -183,6 +395,7 @@ impl ConfigManager {
                 "openrouter" => ProviderType::Openrouter,
                 "google" => ProviderType::Google,
                 "claude" | "anthropic" => ProviderType::Claude,
+                "openai" | "openai_compatible" => ProviderType::OpenaiCompatible,
                 _ => return Err(NexusError::Configuration(format!("Unknown provider type: {}", provider_name))),
             };
 
@@ -199,6 +412,10 @@ impl ConfigManager {
                     base_url: None,
                     default_model: None,
                     timeout_secs: None,
+                    cassette: None,
+                    headers: HashMap::new(),
+                    models: Vec::new(),
+                    fallback: Vec::new(),
                 },
             );
         }
@@ -248,11 +465,20 @@ impl ConfigManager {
                 if let Some(ref val) = resolved.oauth_refresh_token {
                     resolved.oauth_refresh_token = Some(secret_store::resolve_secret(val)?);
                 }
+                for value in resolved.headers.values_mut() {
+                    *value = secret_store::resolve_secret(value)?;
+                }
                 Ok(Some(resolved))
             }
         }
     }
 
+    /// The global agent profile with `project_dir`'s project config layered on top
+    pub fn agent_profile(&self, project_dir: &Path) -> Result<AgentProfile> {
+        let project = ProjectConfig::load(project_dir)?;
+        Ok(self.config.agent.merged_with(&project.agent))
+    }
+
     pub fn list_providers(&self) -> Vec<&String> {
         self.config.providers.keys().collect()
     }
@@ -383,3 +609,72 @@ impl ConfigManager {
         Ok(config)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_profile_layering() {
+        let global = AgentProfile {
+            max_turns: Some(30),
+            temperature: Some(0.2),
+            tools: HashMap::from([("execute_command".to_string(), ToolLimits { max_calls: Some(5), timeout_secs: Some(60) })]),
+            ..AgentProfile::default()
+        };
+        let flags = AgentProfile {
+            max_turns: Some(80),
+            tools: HashMap::from([("execute_command".to_string(), ToolLimits { max_calls: Some(10), timeout_secs: None })]),
+            ..AgentProfile::default()
+        };
+
+        let profile = global.merged_with(&flags);
+        assert_eq!(profile.max_turns(), 80);
+        assert_eq!(profile.temperature(), 0.2);
+        assert_eq!(profile.max_total_tokens(), AgentProfile::DEFAULT_MAX_TOTAL_TOKENS);
+        assert!(profile.allows_tool_call("execute_command", 9));
+        assert!(!profile.allows_tool_call("execute_command", 10));
+        assert!(profile.allows_tool_call("read_file", 1_000));
+        assert_eq!(profile.tool_timeout("execute_command"), Some(Duration::from_secs(60)));
+    }
+
+    #[test]
+    fn test_project_config_found_from_subdirectory() {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir_all(dir.path().join(".nexus")).unwrap();
+        fs::create_dir_all(dir.path().join("src/deep")).unwrap();
+        fs::write(
+            dir.path().join(".nexus/config.toml"),
+            "[agent]\nmax_turns = 50\ntimeout_secs = 600\n\n[agent.tools.read_file]\nmax_calls = 100\n",
+        ).unwrap();
+
+        let project = ProjectConfig::load(&dir.path().join("src/deep")).unwrap();
+        assert_eq!(project.agent.max_turns, Some(50));
+        assert_eq!(project.agent.timeout(), Some(Duration::from_secs(600)));
+        assert_eq!(project.agent.tools["read_file"].max_calls, Some(100));
+
+        let empty = TempDir::new().unwrap();
+        assert_eq!(ProjectConfig::load(empty.path()).unwrap().agent, AgentProfile::default());
+    }
+
+    #[test]
+    fn test_append_permission_rule_keeps_existing_content() {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir_all(dir.path().join(".nexus")).unwrap();
+        fs::write(
+            dir.path().join(".nexus/config.toml"),
+            "# team defaults\n[permissions]\ndefault = \"ask\"\n\n[[permissions.rules]]\ntool = \"execute_command\"\npattern = \"rm *\"\naction = \"deny\"\n",
+        ).unwrap();
+
+        let rule = PermissionRule { tool: "edit_file".to_string(), pattern: Some("src/**".to_string()), action: PermissionAction::Allow };
+        ProjectConfig::append_permission_rule(dir.path(), &rule).unwrap();
+
+        let content = fs::read_to_string(dir.path().join(".nexus/config.toml")).unwrap();
+        assert!(content.starts_with("# team defaults\n"));
+        let project = ProjectConfig::load(dir.path()).unwrap();
+        assert_eq!(project.permissions.default, Some(PermissionAction::Ask));
+        assert_eq!(project.permissions.rules.len(), 2);
+        assert_eq!(project.permissions.rules[1], rule);
+    }
+}
@@ -0,0 +1,398 @@
+//! Context-window compaction for long agent conversations
+//!
+//! Once a conversation nears the model's context window, the older turns are
+//! summarized by the model and replaced with a single summary message. The system
+//! prompt and the most recent turns are kept verbatim. A single long turn (one
+//! prompt, many tool rounds) is split between rounds instead, keeping its prompt
+//! and latest rounds. Either way every tool call stays next to its result.
+
+use crate::error::Result;
+use crate::providers::model_capabilities::ModelRegistry;
+use crate::providers::tokenizer::counter_for_model;
+use crate::providers::{CompletionRequest, ContentPart, Message, Provider, Role, Usage};
+use tracing::warn;
+
+/// Context window assumed for models missing from the capabilities table
+const DEFAULT_CONTEXT_WINDOW: u32 = 128_000;
+
+/// Prefix of the message that replaces compacted turns
+const SUMMARY_PREFIX: &str = "[Summary of earlier conversation]";
+
+const SUMMARY_PROMPT: &str = "You are compacting the history of a coding session so it fits in the model's context window. \
+Summarize the transcript below for the assistant that will continue the work. Keep the user's goals and \
+constraints, decisions made, files read or changed, commands run and their outcomes, and any open problems. \
+Be concise and factual. Do not address the user.";
+
+/// Outcome of a compaction pass
+#[derive(Debug, Clone)]
+pub struct CompactionResult {
+    /// Summary that replaced older turns, if any were summarized
+    pub summary: Option<String>,
+    /// Number of messages replaced by the summary
+    pub messages_compacted: usize,
+    /// Number of tool outputs that were shortened
+    pub outputs_trimmed: usize,
+    pub tokens_before: u32,
+    pub tokens_after: u32,
+    /// Tokens spent producing the summary
+    pub usage: Option<Usage>,
+}
+
+/// Decides when and how to shrink a conversation
+#[derive(Debug, Clone)]
+pub struct Compactor {
+    /// Fraction of the context window at which compaction kicks in
+    pub threshold: f32,
+    /// Number of most recent user turns kept verbatim, or of tool rounds when
+    /// the conversation is a single turn
+    pub keep_recent_turns: usize,
+    /// Tool outputs longer than this many characters are trimmed
+    pub max_tool_output_chars: usize,
+}
+
+impl Default for Compactor {
+    fn default() -> Self {
+        Self {
+            threshold: 0.8,
+            keep_recent_turns: 2,
+            max_tool_output_chars: 2_000,
+        }
+    }
+}
+
+impl Compactor {
+    /// Context window of `model`, falling back to a conservative default.
+    ///
+    /// Provider-prefixed ids such as `anthropic/claude-sonnet-4-5` are looked up by
+    /// their final segment as well.
+    pub fn context_window(model: &str) -> u32 {
+        let registry = ModelRegistry::current();
+        registry.get(model)
+            .or_else(|| model.rsplit('/').next().and_then(|id| registry.get(id)))
+            .map(|cap| cap.context_window)
+            .filter(|&window| window > 0)
+            .unwrap_or(DEFAULT_CONTEXT_WINDOW)
+    }
+
+    /// Whether `messages` are close enough to `model`'s context window to compact
+    pub fn needs_compaction(&self, messages: &[Message], model: &str) -> bool {
+        let tokens = counter_for_model(model).count_messages(messages);
+        tokens as f32 >= Self::context_window(model) as f32 * self.threshold
+    }
+
+    /// Compact `messages` in place.
+    ///
+    /// Older turns are summarized with `provider`. If summarizing fails, only tool
+    /// outputs are trimmed. Returns `None` when there was nothing to compact.
+    pub async fn compact(
+        &self,
+        messages: &mut Vec<Message>,
+        provider: &dyn Provider,
+        model: &str,
+    ) -> Result<Option<CompactionResult>> {
+        let counter = counter_for_model(model);
+        let tokens_before = counter.count_messages(messages);
+        let mut summary = None;
+        let mut messages_compacted = 0;
+        let mut usage = None;
+
+        if let Some((start, end)) = self.compactable_range(messages) {
+            let transcript = self.render_transcript(&messages[start..end]);
+            match self.summarize(&transcript, provider, model).await {
+                Ok((text, summary_usage)) => {
+                    messages.splice(start..end, [Message::user(format!("{}\n{}", SUMMARY_PREFIX, text))]);
+                    messages_compacted = end - start;
+                    summary = Some(text);
+                    usage = summary_usage;
+                }
+                Err(e) => warn!(error = %e, "Summarizing history failed, trimming tool outputs only"),
+            }
+        }
+
+        // The latest message may hold results the model has not seen yet, so leave it whole
+        let outputs_trimmed = match messages.len() {
+            0 => 0,
+            len => self.trim_tool_outputs(&mut messages[..len - 1]),
+        };
+
+        if summary.is_none() && outputs_trimmed == 0 {
+            return Ok(None);
+        }
+
+        Ok(Some(CompactionResult {
+            summary,
+            messages_compacted,
+            outputs_trimmed,
+            tokens_before,
+            tokens_after: counter.count_messages(messages),
+            usage,
+        }))
+    }
+
+    /// Range of messages to summarize: everything after the leading system prompt
+    /// up to the start of the most recent `keep_recent_turns` turns. With too few
+    /// turns for that, the tool rounds of the latest turn are split instead.
+    fn compactable_range(&self, messages: &[Message]) -> Option<(usize, usize)> {
+        let start = messages.iter().take_while(|m| m.role == Role::System).count();
+        let turns = Self::turn_starts(&messages[start..]);
+        let keep = self.keep_recent_turns.max(1);
+        if turns.len() > keep {
+            let end = start + turns[turns.len() - keep];
+            if end > start {
+                return Some((start, end));
+            }
+        }
+
+        // Keep the latest prompt and its most recent rounds; summarize the rounds in between
+        let prompt = start + *turns.last()?;
+        let rounds = Self::round_starts(&messages[prompt + 1..]);
+        if rounds.len() <= keep {
+            return None;
+        }
+        let end = prompt + 1 + rounds[rounds.len() - keep];
+        Some((prompt + 1, end))
+    }
+
+    /// Indices of assistant messages that open a tool round: the first reply to
+    /// the prompt and every reply that follows tool results or an earlier
+    /// summary. Splitting there never separates a tool call from its result.
+    fn round_starts(messages: &[Message]) -> Vec<usize> {
+        messages.iter().enumerate()
+            .filter(|(_, m)| m.role == Role::Assistant)
+            .filter(|(i, _)| *i == 0 || Self::is_tool_output(&messages[i - 1])
+                || messages[i - 1].text_content().starts_with(SUMMARY_PREFIX))
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    /// Tool results, native or reported as text
+    fn is_tool_output(message: &Message) -> bool {
+        message.role != Role::Assistant
+            && (message.content.iter().any(|p| matches!(p, ContentPart::ToolResult { .. }))
+                || message.text_content().starts_with("Tool '"))
+    }
+
+    /// Indices of user prompts that open a turn.
+    ///
+    /// Tool results and text-reported tool output are sent as user messages too, but
+    /// they belong to the preceding assistant message and never start a turn.
+    /// Neither does an earlier compaction's summary.
+    pub fn turn_starts(messages: &[Message]) -> Vec<usize> {
+        messages.iter().enumerate()
+            .filter(|(_, m)| m.role == Role::User && !Self::is_tool_output(m))
+            .filter(|(_, m)| !m.text_content().starts_with(SUMMARY_PREFIX))
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    /// Shorten oversized tool outputs, returning how many were trimmed
+    pub fn trim_tool_outputs(&self, messages: &mut [Message]) -> usize {
+        let mut trimmed = 0;
+        for message in messages.iter_mut() {
+            let is_report = message.role == Role::User && message.text_content().starts_with("Tool '");
+            for part in message.content.iter_mut() {
+                let text = match part {
+                    ContentPart::ToolResult { content, .. } => content,
+                    ContentPart::Text { text } if is_report => text,
+                    _ => continue,
+                };
+                if let Some(short) = self.truncate(text) {
+                    *text = short;
+                    trimmed += 1;
+                }
+            }
+        }
+        trimmed
+    }
+
+    fn truncate(&self, text: &str) -> Option<String> {
+        let total = text.chars().count();
+        if total <= self.max_tool_output_chars {
+            return None;
+        }
+        // Keep the head and tail, where errors and summaries usually are
+        let half = self.max_tool_output_chars / 2;
+        let head: String = text.chars().take(half).collect();
+        let tail: String = text.chars().skip(total - half).collect();
+        Some(format!("{}\n[... {} characters trimmed ...]\n{}", head, total - 2 * half, tail))
+    }
+
+    fn render_transcript(&self, messages: &[Message]) -> String {
+        let mut transcript = String::new();
+        for message in messages {
+            let role = match message.role {
+                Role::System => "System",
+                Role::User => "User",
+                Role::Assistant => "Assistant",
+                Role::Tool | Role::Function => "Tool",
+            };
+            for part in &message.content {
+                let line = match part {
+                    ContentPart::Text { text } => self.truncate(text).unwrap_or_else(|| text.clone()),
+                    ContentPart::Image { media_type, .. } => format!("[image: {}]", media_type),
+                    ContentPart::ToolUse { name, input, .. } => format!("[called {} with {}]", name, input),
+                    ContentPart::ToolResult { content, is_error, .. } => {
+                        let content = self.truncate(content).unwrap_or_else(|| content.clone());
+                        if *is_error { format!("[tool error] {}", content) } else { format!("[tool result] {}", content) }
+                    }
+                };
+                transcript.push_str(&format!("{}: {}\n\n", role, line));
+            }
+        }
+        transcript
+    }
+
+    async fn summarize(&self, transcript: &str, provider: &dyn Provider, model: &str) -> Result<(String, Option<Usage>)> {
+        let request = CompletionRequest {
+            temperature: Some(0.2),
+            max_tokens: Some(2048),
+            stream: Some(false),
+            ..CompletionRequest::new(model.to_string(), vec![Message::system(SUMMARY_PROMPT), Message::user(transcript)])
+        };
+        let response = provider.complete(request).await?;
+        Ok((response.content.trim().to_string(), response.usage))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executor::tools::{ToolCall, ToolResult};
+    use crate::providers::{CompletionResponse, ProviderInfo};
+    use async_trait::async_trait;
+
+    /// Answers every request with a fixed summary
+    struct SummaryProvider;
+
+    #[async_trait]
+    impl Provider for SummaryProvider {
+        fn info(&self) -> ProviderInfo {
+            ProviderInfo {
+                name: "summary".to_string(),
+                display_name: "Summary".to_string(),
+                supports_oauth: false,
+                default_model: "test".to_string(),
+                available_models: vec![],
+            }
+        }
+
+        async fn complete(&self, request: CompletionRequest) -> Result<CompletionResponse> {
+            assert!(request.tools.is_none());
+            Ok(CompletionResponse::new("summary_1".to_string(), request.model, "User asked to read main.rs; it was empty.".to_string()))
+        }
+
+        async fn authenticate(&mut self) -> Result<()> {
+            Ok(())
+        }
+
+        async fn refresh_auth(&mut self) -> Result<()> {
+            Ok(())
+        }
+
+        fn is_authenticated(&self) -> bool {
+            true
+        }
+    }
+
+    fn conversation() -> Vec<Message> {
+        let call = ToolCall {
+            id: "toolu_1".to_string(),
+            name: "read_file".to_string(),
+            arguments: serde_json::json!({"path": "main.rs"}),
+        };
+        vec![
+            Message::system("system prompt"),
+            Message::user("read main.rs"),
+            Message::assistant_with_tool_calls("", std::slice::from_ref(&call)),
+            Message::tool_results(&[ToolResult {
+                tool_call_id: call.id,
+                success: true,
+                output: "x".repeat(5_000),
+                error: None,
+            }]),
+            Message::assistant("It is empty."),
+            Message::user("add a test"),
+            Message::assistant("Done."),
+            Message::user("run it"),
+            Message::assistant("Passed."),
+        ]
+    }
+
+    #[test]
+    fn test_context_window_lookup() {
+        assert_eq!(Compactor::context_window("claude-sonnet-4-5"), ModelRegistry::current().get("claude-sonnet-4-5").unwrap().context_window);
+        assert_eq!(Compactor::context_window("anthropic/claude-sonnet-4-5"), Compactor::context_window("claude-sonnet-4-5"));
+        assert_eq!(Compactor::context_window("unknown-model"), DEFAULT_CONTEXT_WINDOW);
+    }
+
+    #[test]
+    fn test_needs_compaction_near_window() {
+        let messages = conversation();
+        let tokens = counter_for_model("gpt-4o").count_messages(&messages) as f32;
+        let window = Compactor::context_window("gpt-4o") as f32;
+
+        let eager = Compactor { threshold: tokens / window, ..Compactor::default() };
+        assert!(eager.needs_compaction(&messages, "gpt-4o"));
+        assert!(!Compactor::default().needs_compaction(&messages, "gpt-4o"));
+    }
+
+    #[test]
+    fn test_range_keeps_turns_whole() {
+        let compactor = Compactor::default();
+        // Three turns, the last two are kept: the first turn including its tool pair goes
+        assert_eq!(compactor.compactable_range(&conversation()), Some((1, 5)));
+
+        let few = Compactor { keep_recent_turns: 3, ..Compactor::default() };
+        assert_eq!(few.compactable_range(&conversation()), None);
+    }
+
+    #[tokio::test]
+    async fn test_single_long_turn_splits_between_rounds() {
+        let mut messages = vec![Message::system("system prompt"), Message::user("fix every warning")];
+        for i in 0..4 {
+            let call = ToolCall { id: format!("toolu_{}", i), name: "read_file".to_string(), arguments: serde_json::json!({"path": "main.rs"}) };
+            messages.push(Message::assistant_with_tool_calls("", std::slice::from_ref(&call)));
+            messages.push(Message::tool_results(&[ToolResult { tool_call_id: call.id, success: true, output: "ok".to_string(), error: None }]));
+        }
+        let compactor = Compactor::default();
+        // The prompt stays; the first two rounds go and the last two are kept
+        assert_eq!(compactor.compactable_range(&messages), Some((2, 6)));
+
+        let result = compactor.compact(&mut messages, &SummaryProvider, "test").await.unwrap().unwrap();
+        assert_eq!(result.messages_compacted, 4);
+        assert_eq!(messages[1].text_content(), "fix every warning");
+        assert!(messages[2].text_content().starts_with(SUMMARY_PREFIX));
+        assert_eq!(messages[3].role, Role::Assistant);
+        // Too little left to compact again, so later turns don't retry
+        assert_eq!(compactor.compactable_range(&messages), None);
+    }
+
+    #[test]
+    fn test_trim_tool_outputs() {
+        let compactor = Compactor::default();
+        let mut messages = conversation();
+        assert_eq!(compactor.trim_tool_outputs(&mut messages), 1);
+        match &messages[3].content[0] {
+            ContentPart::ToolResult { content, .. } => {
+                assert!(content.contains("characters trimmed"));
+                assert!(content.chars().count() < 2_100);
+            }
+            other => panic!("unexpected part: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_compact_replaces_old_turns_with_summary() {
+        let compactor = Compactor::default();
+        let mut messages = conversation();
+        let result = compactor.compact(&mut messages, &SummaryProvider, "test").await.unwrap().unwrap();
+
+        assert_eq!(result.messages_compacted, 4);
+        assert!(result.tokens_after < result.tokens_before);
+        assert_eq!(messages.len(), 6);
+        assert_eq!(messages[0].role, Role::System);
+        assert!(messages[1].text_content().starts_with(SUMMARY_PREFIX));
+        assert!(messages[1].text_content().contains("main.rs"));
+        assert_eq!(messages[2].text_content(), "add a test");
+    }
+}
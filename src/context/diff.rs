@@ -1,35 +1,472 @@
 /// Differential updates - only send changed content
-use crate::error::Result;
+use crate::error::{NexusError, Result};
+use serde::Serialize;
+use similar::{Algorithm, ChangeTag, TextDiff};
 
-/// Compute diff between old and new content
+/// Unchanged lines shown around each change
+const CONTEXT_LINES: usize = 3;
+
+/// A proposed change to one file, in a form both the terminal and `--json`
+/// consumers can render
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FileDiff {
+    pub path: String,
+    /// The file does not exist yet
+    pub created: bool,
+    pub added: usize,
+    pub removed: usize,
+    pub hunks: Vec<DiffHunk>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DiffHunk {
+    pub old_start: usize,
+    pub old_lines: usize,
+    pub new_start: usize,
+    pub new_lines: usize,
+    pub lines: Vec<DiffLine>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", content = "text", rename_all = "snake_case")]
+pub enum DiffLine {
+    Context(String),
+    Add(String),
+    Remove(String),
+}
+
+impl FileDiff {
+    /// Diff `old` (`None` for a new file) against `new` using the patience
+    /// algorithm, which keeps moved blocks and braces readable
+    pub fn new(path: impl Into<String>, old: Option<&str>, new: &str) -> Self {
+        let diff = TextDiff::configure()
+            .algorithm(Algorithm::Patience)
+            .diff_lines(old.unwrap_or(""), new);
+
+        let mut added = 0;
+        let mut removed = 0;
+        let hunks = diff.grouped_ops(CONTEXT_LINES).into_iter().map(|group| {
+            let (first, last) = (&group[0], &group[group.len() - 1]);
+            let old_range = first.old_range().start..last.old_range().end;
+            let new_range = first.new_range().start..last.new_range().end;
+
+            let lines = group.iter().flat_map(|op| diff.iter_changes(op)).map(|change| {
+                let text = change.value().trim_end_matches(['\r', '\n']).to_string();
+                match change.tag() {
+                    ChangeTag::Equal => DiffLine::Context(text),
+                    ChangeTag::Insert => {
+                        added += 1;
+                        DiffLine::Add(text)
+                    }
+                    ChangeTag::Delete => {
+                        removed += 1;
+                        DiffLine::Remove(text)
+                    }
+                }
+            }).collect();
+
+            DiffHunk {
+                old_start: hunk_start(old_range.start, old_range.len()),
+                old_lines: old_range.len(),
+                new_start: hunk_start(new_range.start, new_range.len()),
+                new_lines: new_range.len(),
+                lines,
+            }
+        }).collect();
+
+        Self {
+            path: path.into(),
+            created: old.is_none(),
+            added,
+            removed,
+            hunks,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hunks.is_empty()
+    }
+
+    /// The diff in unified format, with `---`/`+++` file headers
+    pub fn unified(&self) -> String {
+        let old_name = match self.created {
+            true => "/dev/null".to_string(),
+            false => format!("a/{}", self.path),
+        };
+        let mut out = format!("--- {}\n+++ b/{}\n", old_name, self.path);
+        for hunk in &self.hunks {
+            out.push_str(&format!(
+                "@@ -{},{} +{},{} @@\n",
+                hunk.old_start, hunk.old_lines, hunk.new_start, hunk.new_lines
+            ));
+            for line in &hunk.lines {
+                let (prefix, text) = match line {
+                    DiffLine::Context(text) => (' ', text),
+                    DiffLine::Add(text) => ('+', text),
+                    DiffLine::Remove(text) => ('-', text),
+                };
+                out.push(prefix);
+                out.push_str(text);
+                out.push('\n');
+            }
+        }
+        out
+    }
+}
+
+/// 1-based start line for a hunk header; an empty range names the line before it
+fn hunk_start(start: usize, len: usize) -> usize {
+    match len {
+        0 => start,
+        _ => start + 1,
+    }
+}
+
+/// Compute a unified diff between old and new content
 pub fn compute_diff(old_content: &str, new_content: &str) -> String {
-    // Simple line-by-line diff for now
-    // In production, use a proper diff library like `similar`
-    let old_lines: Vec<&str> = old_content.lines().collect();
-    let new_lines: Vec<&str> = new_content.lines().collect();
+    FileDiff::new("file", Some(old_content), new_content).unified()
+}
 
-    let mut diff = String::from("Changes:\n");
+/// Most context lines a hunk may drop from each end when it does not apply as-is
+const MAX_FUZZ: usize = 2;
 
-    // Find added lines
-    for (i, line) in new_lines.iter().enumerate() {
-        if i >= old_lines.len() || old_lines[i] != *line {
-            diff.push_str(&format!("+ {}\n", line));
+/// Anchors longer than this are compared line by line when looking for the
+/// closest match, which keeps the search fast on large files
+const CHAR_COMPARE_MAX_LINES: usize = 10;
+
+/// Replace the single occurrence of `old` in `content` with `new`.
+///
+/// Fails if `old` is missing or matches more than once. A missing anchor reports
+/// the closest match in the file so the caller can correct it.
+pub fn str_replace(content: &str, old: &str, new: &str) -> Result<String> {
+    if old.is_empty() {
+        return Err(NexusError::Patch("old_str must not be empty".to_string()));
+    }
+
+    let starts: Vec<usize> = content.match_indices(old).map(|(i, _)| i).collect();
+    match starts.as_slice() {
+        [start] => Ok(format!("{}{}{}", &content[..*start], new, &content[start + old.len()..])),
+        [] => {
+            let lines: Vec<&str> = content.lines().collect();
+            let anchor: Vec<&str> = old.lines().collect();
+            Err(NexusError::Patch(format!(
+                "old_str was not found in the file.{}",
+                describe_closest(&lines, &anchor)
+            )))
+        }
+        _ => {
+            let line_numbers: Vec<String> = starts.iter()
+                .map(|&i| (content[..i].matches('\n').count() + 1).to_string())
+                .collect();
+            Err(NexusError::Patch(format!(
+                "old_str matches {} times (at lines {}). Include more surrounding lines so it matches exactly once.",
+                starts.len(),
+                line_numbers.join(", ")
+            )))
         }
     }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum HunkLine {
+    Context(String),
+    Remove(String),
+    Add(String),
+}
+
+#[derive(Debug, Clone)]
+struct Hunk {
+    header: String,
+    /// 1-based line the hunk claims to start at, 0 if the header has no numbers
+    old_start: usize,
+    lines: Vec<HunkLine>,
+}
 
-    // Find removed lines
-    for (i, line) in old_lines.iter().enumerate() {
-        if i >= new_lines.len() || new_lines[i] != *line {
-            diff.push_str(&format!("- {}\n", line));
+impl Hunk {
+    /// The hunk with up to `fuzz` context lines dropped from each end
+    fn fuzzed(&self, fuzz: usize) -> Option<Vec<&HunkLine>> {
+        let leading = self.lines.iter().take_while(|l| matches!(l, HunkLine::Context(_))).count();
+        let trailing = self.lines.iter().rev().take_while(|l| matches!(l, HunkLine::Context(_))).count();
+        if fuzz > 0 && leading.min(fuzz) == 0 && trailing.min(fuzz) == 0 {
+            // Nothing to drop, so this fuzz level is the same as the last
+            return None;
         }
+        let front = leading.min(fuzz);
+        let back = trailing.min(fuzz).min(self.lines.len() - front);
+        Some(self.lines[front..self.lines.len() - back].iter().collect())
     }
+}
+
+/// Text a hunk expects to find in the file: its context and removed lines
+fn before_lines<'a>(lines: &[&'a HunkLine]) -> Vec<&'a str> {
+    lines.iter().filter_map(|line| match line {
+        HunkLine::Context(text) | HunkLine::Remove(text) => Some(text.as_str()),
+        HunkLine::Add(_) => None,
+    }).collect()
+}
+
+fn parse_hunks(diff: &str) -> Result<Vec<Hunk>> {
+    let mut hunks: Vec<Hunk> = Vec::new();
+    let mut in_hunk = false;
+    let mut lines = diff.lines().peekable();
+
+    while let Some(line) = lines.next() {
+        if line.starts_with("@@") {
+            hunks.push(Hunk {
+                header: line.to_string(),
+                old_start: parse_old_start(line),
+                lines: Vec::new(),
+            });
+            in_hunk = true;
+            continue;
+        }
+
+        // File headers end the current hunk
+        let is_file_header = line.starts_with("diff ")
+            || line.starts_with("index ")
+            || (line.starts_with("--- ") && lines.peek().is_some_and(|next| next.starts_with("+++ ")));
+        if is_file_header {
+            if line.starts_with("--- ") {
+                lines.next();
+            }
+            in_hunk = false;
+            continue;
+        }
+
+        let Some(hunk) = hunks.last_mut().filter(|_| in_hunk) else {
+            continue;
+        };
+        if let Some(text) = line.strip_prefix('+') {
+            hunk.lines.push(HunkLine::Add(text.to_string()));
+        } else if let Some(text) = line.strip_prefix('-') {
+            hunk.lines.push(HunkLine::Remove(text.to_string()));
+        } else if line.starts_with('\\') {
+            // "\ No newline at end of file"
+        } else {
+            // Context lines whose leading space was dropped are accepted as-is
+            hunk.lines.push(HunkLine::Context(line.strip_prefix(' ').unwrap_or(line).to_string()));
+        }
+    }
+
+    if hunks.is_empty() {
+        return Err(NexusError::Patch(
+            "No hunks found. Expected a unified diff with '@@ -start,count +start,count @@' headers.".to_string(),
+        ));
+    }
+    Ok(hunks)
+}
 
-    diff
+/// Old-file start line from a `@@ -12,5 +12,7 @@` header
+fn parse_old_start(header: &str) -> usize {
+    header.split_whitespace()
+        .find_map(|part| part.strip_prefix('-'))
+        .and_then(|range| range.split(',').next())
+        .and_then(|start| start.parse().ok())
+        .unwrap_or(0)
 }
 
-/// Apply a diff to old content to get new content
+/// Apply a unified diff to `old_content`.
+///
+/// Hunks are located near the line their header names, searching the rest of
+/// the file if they moved. A hunk that does not match exactly is retried ignoring
+/// whitespace, then with up to `MAX_FUZZ` context lines dropped from each end. A
+/// hunk that still fails produces an error showing the closest match.
 pub fn apply_diff(old_content: &str, diff: &str) -> Result<String> {
-    // TODO: Implement proper diff application
-    // For now, just return the diff content as-is
-    Ok(diff.to_string())
+    let hunks = parse_hunks(diff)?;
+    let mut lines: Vec<String> = old_content.lines().map(String::from).collect();
+    let mut offset: isize = 0;
+    let mut min_pos = 0;
+
+    for (index, hunk) in hunks.iter().enumerate() {
+        let expected = (hunk.old_start.saturating_sub(1) as isize + offset).max(0) as usize;
+
+        let found = (0..=MAX_FUZZ)
+            .filter_map(|fuzz| hunk.fuzzed(fuzz))
+            .flat_map(|trimmed| [(trimmed.clone(), false), (trimmed, true)])
+            .find_map(|(trimmed, loose)| {
+                locate(&lines, &before_lines(&trimmed), expected, min_pos, loose).map(|pos| (trimmed, pos))
+            });
+
+        let Some((trimmed, pos)) = found else {
+            let file: Vec<&str> = lines.iter().map(String::as_str).collect();
+            let all: Vec<&HunkLine> = hunk.lines.iter().collect();
+            return Err(NexusError::Patch(format!(
+                "Hunk {} ({}) does not match the file.{}",
+                index + 1,
+                hunk.header,
+                describe_closest(&file, &before_lines(&all))
+            )));
+        };
+
+        // Keep the file's own context lines, which may differ in whitespace
+        let mut replacement = Vec::new();
+        let mut cursor = pos;
+        for line in &trimmed {
+            match line {
+                HunkLine::Context(_) => {
+                    replacement.push(lines[cursor].clone());
+                    cursor += 1;
+                }
+                HunkLine::Remove(_) => cursor += 1,
+                HunkLine::Add(text) => replacement.push(text.clone()),
+            }
+        }
+
+        let removed = cursor - pos;
+        let added = replacement.len();
+        lines.splice(pos..cursor, replacement);
+        offset += added as isize - removed as isize;
+        min_pos = pos + added;
+    }
+
+    // `lines()` dropped the `\r`s; put the file's own line ending back
+    let eol = if old_content.contains("\r\n") { "\r\n" } else { "\n" };
+    let mut result = lines.join(eol);
+    if old_content.ends_with('\n') || (old_content.is_empty() && !result.is_empty()) {
+        result.push_str(eol);
+    }
+    Ok(result)
+}
+
+/// Position of `before` in `lines` at or after `min_pos`, nearest to `expected`
+fn locate(lines: &[String], before: &[&str], expected: usize, min_pos: usize, loose: bool) -> Option<usize> {
+    if before.is_empty() {
+        return Some(expected.clamp(min_pos, lines.len()));
+    }
+    if before.len() > lines.len() {
+        return None;
+    }
+
+    let same = |a: &str, b: &str| if loose { a.split_whitespace().eq(b.split_whitespace()) } else { a == b };
+    (min_pos..=lines.len() - before.len())
+        .filter(|&pos| before.iter().enumerate().all(|(i, line)| same(&lines[pos + i], line)))
+        .min_by_key(|&pos| pos.abs_diff(expected))
+}
+
+/// The window of `haystack` most similar to `needle`, as (start line, similarity)
+fn closest_match(haystack: &[&str], needle: &[&str]) -> Option<(usize, f32)> {
+    if haystack.is_empty() || needle.is_empty() {
+        return None;
+    }
+    let window = needle.len().min(haystack.len());
+    let target = needle.join("\n");
+
+    (0..=haystack.len() - window)
+        .map(|start| {
+            let candidate = haystack[start..start + window].join("\n");
+            let ratio = if needle.len() <= CHAR_COMPARE_MAX_LINES {
+                TextDiff::from_chars(candidate.as_str(), target.as_str()).ratio()
+            } else {
+                TextDiff::from_lines(candidate.as_str(), target.as_str()).ratio()
+            };
+            (start, ratio)
+        })
+        .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal))
+}
+
+/// Human-readable description of the closest match, for error messages
+fn describe_closest(haystack: &[&str], needle: &[&str]) -> String {
+    match closest_match(haystack, needle) {
+        Some((start, ratio)) if ratio > 0.0 => {
+            let end = (start + needle.len()).min(haystack.len());
+            format!(
+                "\nClosest match (lines {}-{}, {:.0}% similar):\n{}",
+                start + 1,
+                end,
+                ratio * 100.0,
+                haystack[start..end].join("\n")
+            )
+        }
+        _ => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FILE: &str = "fn main() {\n    let x = 1;\n    let y = 2;\n    println!(\"{}\", x + y);\n}\n";
+
+    #[test]
+    fn test_file_diff_hunks_and_round_trip() {
+        let updated = FILE.replace("let y = 2;", "let y = 3;").replace("}\n", "}\n\nfn other() {}\n");
+        let diff = FileDiff::new("main.rs", Some(FILE), &updated);
+        assert_eq!((diff.added, diff.removed), (3, 1));
+        assert_eq!(diff.hunks.len(), 1);
+        assert_eq!((diff.hunks[0].old_start, diff.hunks[0].old_lines), (1, 5));
+        assert!(diff.unified().starts_with("--- a/main.rs\n+++ b/main.rs\n@@ -1,5 +1,7 @@\n fn main() {\n"));
+
+        // The rendered diff applies back onto the original
+        assert_eq!(apply_diff(FILE, &diff.unified()).unwrap(), updated);
+
+        let created = FileDiff::new("new.rs", None, "a\nb\n");
+        assert!(created.created);
+        assert!(created.unified().starts_with("--- /dev/null\n+++ b/new.rs\n@@ -0,0 +1,2 @@\n+a\n+b\n"));
+        assert!(FileDiff::new("same.rs", Some(FILE), FILE).is_empty());
+
+        let json = serde_json::to_value(&created).unwrap();
+        assert_eq!(json["hunks"][0]["lines"][0], serde_json::json!({"kind": "add", "text": "a"}));
+    }
+
+    #[test]
+    fn test_str_replace_unique_anchor() {
+        let updated = str_replace(FILE, "let y = 2;", "let y = 40;").unwrap();
+        assert!(updated.contains("let y = 40;"));
+        assert!(updated.ends_with("}\n"));
+    }
+
+    #[test]
+    fn test_str_replace_ambiguous_anchor() {
+        let err = str_replace(FILE, "    let", "    let mut").unwrap_err().to_string();
+        assert!(err.contains("matches 2 times (at lines 2, 3)"), "{}", err);
+    }
+
+    #[test]
+    fn test_str_replace_reports_closest_match() {
+        let err = str_replace(FILE, "let y = 3;", "let y = 4;").unwrap_err().to_string();
+        assert!(err.contains("Closest match (lines 3-3"), "{}", err);
+        assert!(err.contains("let y = 2;"));
+    }
+
+    #[test]
+    fn test_apply_diff_with_moved_hunk() {
+        // Header says line 10, but the hunk is near the top
+        let diff = "--- a/main.rs\n+++ b/main.rs\n@@ -10,3 +10,3 @@\n     let x = 1;\n-    let y = 2;\n+    let y = 3;\n     println!(\"{}\", x + y);\n";
+        let updated = apply_diff(FILE, diff).unwrap();
+        assert_eq!(updated, FILE.replace("let y = 2;", "let y = 3;"));
+    }
+
+    #[test]
+    fn test_apply_diff_ignores_whitespace_and_fuzzes_context() {
+        // Context has wrong indentation and one bogus trailing line
+        let diff = "@@ -1,4 +1,4 @@\n fn main() {\n let x = 1;\n-    let y = 2;\n+    let y = 5;\n     println!(\"{}\", x * y);\n";
+        let updated = apply_diff(FILE, diff).unwrap();
+        assert!(updated.contains("    let x = 1;\n    let y = 5;\n"));
+    }
+
+    #[test]
+    fn test_apply_diff_multiple_hunks_and_insertions() {
+        let diff = "@@ -1,2 +1,3 @@\n+use std::fmt;\n fn main() {\n     let x = 1;\n@@ -4,2 +5,3 @@\n     println!(\"{}\", x + y);\n+    println!(\"done\");\n }\n";
+        let updated = apply_diff(FILE, diff).unwrap();
+        assert!(updated.starts_with("use std::fmt;\nfn main() {"));
+        assert!(updated.ends_with("    println!(\"done\");\n}\n"));
+    }
+
+    #[test]
+    fn test_apply_diff_keeps_crlf_line_endings() {
+        let crlf = FILE.replace('\n', "\r\n");
+        let diff = "@@ -2,2 +2,2 @@\n     let x = 1;\n-    let y = 2;\n+    let y = 3;\n";
+        let updated = apply_diff(&crlf, diff).unwrap();
+        assert_eq!(updated, crlf.replace("let y = 2;", "let y = 3;"));
+    }
+
+    #[test]
+    fn test_apply_diff_failure_shows_closest_match() {
+        let diff = "@@ -2,2 +2,2 @@\n-    let z = 9;\n-    let w = 8;\n+    let z = 0;\n";
+        let err = apply_diff(FILE, diff).unwrap_err().to_string();
+        assert!(err.contains("Hunk 1 (@@ -2,2 +2,2 @@) does not match"), "{}", err);
+        assert!(err.contains("Closest match"));
+
+        assert!(apply_diff(FILE, "not a diff").is_err());
+    }
 }
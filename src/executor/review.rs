@@ -0,0 +1,110 @@
+//! Review of proposed file writes
+//!
+//! Write tools show every change to a [`ChangeReviewer`] before touching the
+//! disk. The reviewer can accept it, reject it, or hand back edited content to
+//! write instead.
+
+use crate::context::diff::FileDiff;
+use async_trait::async_trait;
+use crossterm::style::Stylize;
+use dialoguer::Select;
+use std::process::Command;
+
+/// A write a tool wants to make
+pub struct ProposedChange<'a> {
+    pub diff: &'a FileDiff,
+    /// Full content the file would have afterwards
+    pub content: &'a str,
+}
+
+/// What to do with a proposed write
+#[derive(Debug, Clone, PartialEq)]
+pub enum Review {
+    Accept,
+    Reject,
+    /// Write this content instead of the proposal
+    Edit(String),
+}
+
+/// Decides whether proposed writes go ahead
+#[async_trait]
+pub trait ChangeReviewer: Send + Sync {
+    async fn review(&self, change: &ProposedChange<'_>) -> Review;
+}
+
+/// Shows a colored diff on the terminal and asks what to do
+pub struct TerminalReviewer;
+
+#[async_trait]
+impl ChangeReviewer for TerminalReviewer {
+    async fn review(&self, change: &ProposedChange<'_>) -> Review {
+        eprintln!("\n{}", colorize(change.diff));
+
+        // dialoguer and $EDITOR block; keep the runtime's other tasks going
+        tokio::task::block_in_place(|| {
+            let items = ["Accept", "Reject", "Edit in $EDITOR"];
+            let selection = Select::new()
+                .with_prompt(format!("Write {}?", change.diff.path))
+                .items(items)
+                .default(0)
+                .interact_opt();
+
+            match selection {
+                Ok(Some(0)) => Review::Accept,
+                Ok(Some(2)) => match edit_in_editor(&change.diff.path, change.content) {
+                    Ok(edited) => Review::Edit(edited),
+                    Err(e) => {
+                        eprintln!("✗ Could not edit the change: {}", e);
+                        Review::Reject
+                    }
+                },
+                _ => Review::Reject,
+            }
+        })
+    }
+}
+
+/// The diff in unified format with ANSI colors
+pub fn colorize(diff: &FileDiff) -> String {
+    let header = format!("{} (+{} -{})", diff.path, diff.added, diff.removed);
+    let mut out = format!("{}\n", header.bold());
+    // Skip the ---/+++ file headers; the summary line above names the file
+    for line in diff.unified().lines().skip(2) {
+        let rendered = match line.chars().next() {
+            Some('@') => line.cyan().to_string(),
+            Some('+') => line.green().to_string(),
+            Some('-') => line.red().to_string(),
+            _ => line.dim().to_string(),
+        };
+        out.push_str(&rendered);
+        out.push('\n');
+    }
+    out
+}
+
+/// Open `content` in `$VISUAL` / `$EDITOR` (falling back to `vi`) and return
+/// what the user saved
+fn edit_in_editor(path: &str, content: &str) -> std::io::Result<String> {
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_string());
+    let mut words = shell_words::split(&editor)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+    if words.is_empty() {
+        return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "$EDITOR is empty"));
+    }
+
+    // Keep the file name so the editor picks the right syntax highlighting
+    let name = std::path::Path::new(path).file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+    let temp = std::env::temp_dir().join(format!("nexus-{}-{}", uuid::Uuid::new_v4(), name));
+    std::fs::write(&temp, content)?;
+
+    let program = words.remove(0);
+    let status = Command::new(program).args(words).arg(&temp).status();
+    let edited = status.and_then(|status| match status.success() {
+        true => std::fs::read_to_string(&temp),
+        false => Err(std::io::Error::other(format!("editor exited with {}", status))),
+    });
+    let _ = std::fs::remove_file(&temp);
+    edited
+}
@@ -0,0 +1,331 @@
+//! Read-only codebase navigation tools
+//!
+//! `grep`, `glob`, `list_dir`, `find_symbol` and ranged `read_file` run directly
+//! on the host, since they never modify the tree and a Docker round-trip per
+//! lookup would make exploration slow. Walks honour `.gitignore`, `.ignore` and
+//! hidden-file rules, and every path is confined to the working directory.
+
+use crate::error::{NexusError, Result};
+use globset::{Glob, GlobMatcher};
+use ignore::WalkBuilder;
+use regex::{Regex, RegexBuilder};
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+const MAX_LIST_ENTRIES: usize = 1000;
+const MAX_LIST_DEPTH: usize = 5;
+/// Longest line echoed back in grep output
+const MAX_LINE_CHARS: usize = 240;
+
+/// Read a file, optionally only lines `start_line..=end_line` (1-based).
+///
+/// Without a range the raw content is returned. With one, lines are numbered so
+/// the model can cite them. Returns the resolved path for staleness tracking.
+pub fn read_file(
+    root: &Path,
+    path: &str,
+    start_line: Option<usize>,
+    end_line: Option<usize>,
+) -> Result<(PathBuf, String)> {
+    let path = resolve(root, path)?;
+    let content = std::fs::read_to_string(&path)?;
+    if start_line.is_none() && end_line.is_none() {
+        return Ok((path, content));
+    }
+
+    let total = content.lines().count();
+    let start = start_line.unwrap_or(1).max(1);
+    let end = end_line.unwrap_or(total).min(total);
+    if start > end {
+        return Err(NexusError::Configuration(format!(
+            "Invalid line range {}-{} for a file with {} lines",
+            start, end, total
+        )));
+    }
+
+    let mut output = format!("Lines {}-{} of {}:\n", start, end, total);
+    for (i, line) in content.lines().enumerate().take(end).skip(start - 1) {
+        output.push_str(&format!("{:>6} | {}\n", i + 1, line));
+    }
+    Ok((path, output))
+}
+
+/// Search file contents for a regex, optionally restricted to a subdirectory
+/// and a filename glob such as `*.rs`.
+pub fn grep(
+    root: &Path,
+    pattern: &str,
+    path: Option<&str>,
+    include: Option<&str>,
+    case_insensitive: bool,
+    max_results: usize,
+) -> Result<String> {
+    let regex = RegexBuilder::new(pattern)
+        .case_insensitive(case_insensitive)
+        .build()
+        .map_err(|e| NexusError::Configuration(format!("Invalid regex '{}': {}", pattern, e)))?;
+    let include = include.map(compile_glob).transpose()?;
+    let root = resolve(root, ".")?;
+    let base = resolve(&root, path.unwrap_or("."))?;
+
+    let mut matches = Vec::new();
+    let mut truncated = false;
+    'files: for file in walk_files(&base, usize::MAX) {
+        let rel = relative(&root, &file);
+        if let Some(matcher) = &include
+            && !matches_glob(matcher, &rel)
+        {
+            continue;
+        }
+        // Binary and non-UTF-8 files are skipped
+        let Ok(content) = std::fs::read_to_string(&file) else { continue };
+        for (i, line) in content.lines().enumerate() {
+            if regex.is_match(line) {
+                if matches.len() == max_results {
+                    truncated = true;
+                    break 'files;
+                }
+                matches.push(format!("{}:{}: {}", rel, i + 1, clip(line.trim_end())));
+            }
+        }
+    }
+
+    Ok(format_results(matches, truncated, &format!("No matches for '{}'", pattern)))
+}
+
+/// List files matching a glob such as `src/**/*.rs`, relative to the root
+pub fn glob(root: &Path, pattern: &str, max_results: usize) -> Result<String> {
+    let matcher = compile_glob(pattern)?;
+    let root = resolve(root, ".")?;
+
+    let mut paths: Vec<String> = walk_files(&root, usize::MAX)
+        .map(|file| relative(&root, &file))
+        .filter(|rel| matches_glob(&matcher, rel))
+        .collect();
+    paths.sort();
+
+    let truncated = paths.len() > max_results;
+    paths.truncate(max_results);
+    Ok(format_results(paths, truncated, &format!("No files match '{}'", pattern)))
+}
+
+/// Show a directory tree down to `depth` levels, directories marked with `/`
+pub fn list_dir(root: &Path, path: &str, depth: usize) -> Result<String> {
+    let root = resolve(root, ".")?;
+    let dir = resolve(&root, path)?;
+    if !dir.is_dir() {
+        return Err(io_error(ErrorKind::NotADirectory, format!("Not a directory: {}", path)));
+    }
+
+    let depth = depth.clamp(1, MAX_LIST_DEPTH);
+    let mut entries: Vec<String> = walker(&dir, depth)
+        .build()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.depth() > 0)
+        .map(|entry| {
+            let rel = relative(&root, entry.path());
+            match entry.file_type().is_some_and(|t| t.is_dir()) {
+                true => format!("{}/", rel),
+                false => rel,
+            }
+        })
+        .collect();
+    entries.sort();
+
+    let truncated = entries.len() > MAX_LIST_ENTRIES;
+    entries.truncate(MAX_LIST_ENTRIES);
+    Ok(format_results(entries, truncated, &format!("{} is empty", path)))
+}
+
+/// Find where a symbol is declared.
+///
+/// Matches declaration keywords across the languages we usually see (Rust,
+/// Python, JavaScript/TypeScript, Go), so results are definitions
+/// rather than every mention. `kind` narrows the keyword, e.g. `fn` or `class`.
+pub fn find_symbol(root: &Path, name: &str, kind: Option<&str>, max_results: usize) -> Result<String> {
+    let regex = symbol_regex(name, kind)?;
+    let root = resolve(root, ".")?;
+
+    let mut matches = Vec::new();
+    let mut truncated = false;
+    'files: for file in walk_files(&root, usize::MAX) {
+        let Ok(content) = std::fs::read_to_string(&file) else { continue };
+        for (i, line) in content.lines().enumerate() {
+            if regex.is_match(line) {
+                if matches.len() == max_results {
+                    truncated = true;
+                    break 'files;
+                }
+                matches.push(format!("{}:{}: {}", relative(&root, &file), i + 1, clip(line.trim())));
+            }
+        }
+    }
+
+    Ok(format_results(matches, truncated, &format!("No declaration of '{}' found", name)))
+}
+
+fn symbol_regex(name: &str, kind: Option<&str>) -> Result<Regex> {
+    let keywords = match kind {
+        Some(kind) if !kind.is_empty() => regex::escape(kind),
+        _ => "fn|struct|enum|trait|type|const|static|mod|macro_rules!|class|def|function|interface|func|var|let"
+            .to_string(),
+    };
+    let name = regex::escape(name);
+    // Modifiers such as `pub(crate)`, `export default`, `async` and `public static`
+    let modifiers = r"(?:(?:pub(?:\([^)]*\))?|export|default|async|unsafe|extern|public|private|protected|static|abstract|final|declare)\s+)*";
+    let pattern = format!(
+        r"^\s*(?:{modifiers}(?:{keywords})\s+(?:\([^)]*\)\s*)?\*?{name}\b|impl(?:<[^>]*>)?\s+(?:[\w:<>]+\s+for\s+)?{name}\b)"
+    );
+    Regex::new(&pattern).map_err(|e| NexusError::Configuration(format!("Invalid symbol name '{}': {}", name, e)))
+}
+
+/// Resolve `path` against `root`, refusing anything that escapes it
+fn resolve(root: &Path, path: &str) -> Result<PathBuf> {
+    let root = root.canonicalize()?;
+    let full = root.join(path).canonicalize()
+        .map_err(|e| io_error(e.kind(), format!("{}: {}", path, e)))?;
+    if !full.starts_with(&root) {
+        return Err(io_error(ErrorKind::PermissionDenied, format!("Path is outside the working directory: {}", path)));
+    }
+    Ok(full)
+}
+
+fn io_error(kind: ErrorKind, message: String) -> NexusError {
+    NexusError::Io(std::io::Error::new(kind, message))
+}
+
+fn walker(base: &Path, max_depth: usize) -> WalkBuilder {
+    let mut builder = WalkBuilder::new(base);
+    builder
+        .max_depth(Some(max_depth))
+        // Respect .gitignore even in trees that are not git checkouts
+        .require_git(false)
+        .filter_entry(|entry| entry.file_name() != ".git");
+    builder
+}
+
+fn walk_files(base: &Path, max_depth: usize) -> impl Iterator<Item = PathBuf> {
+    walker(base, max_depth)
+        .build()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_some_and(|t| t.is_file()))
+        .map(|entry| entry.into_path())
+}
+
+fn compile_glob(pattern: &str) -> Result<GlobMatcher> {
+    Glob::new(pattern)
+        .map(|g| g.compile_matcher())
+        .map_err(|e| NexusError::Configuration(format!("Invalid glob '{}': {}", pattern, e)))
+}
+
+/// Patterns without a `/` match the file name at any depth, like `--include`
+fn matches_glob(matcher: &GlobMatcher, rel: &str) -> bool {
+    if matcher.is_match(rel) {
+        return true;
+    }
+    !matcher.glob().glob().contains('/') && rel.rsplit('/').next().is_some_and(|name| matcher.is_match(name))
+}
+
+fn relative(root: &Path, path: &Path) -> String {
+    path.strip_prefix(root).unwrap_or(path).to_string_lossy().replace('\\', "/")
+}
+
+fn clip(line: &str) -> String {
+    if line.chars().count() > MAX_LINE_CHARS {
+        format!("{}...", line.chars().take(MAX_LINE_CHARS).collect::<String>())
+    } else {
+        line.to_string()
+    }
+}
+
+fn format_results(lines: Vec<String>, truncated: bool, empty: &str) -> String {
+    if lines.is_empty() {
+        return empty.to_string();
+    }
+    let count = lines.len();
+    let mut output = lines.join("\n");
+    if truncated {
+        output.push_str(&format!("\n... (showing first {} results; narrow the search)", count));
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn sample_tree() -> TempDir {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("src/nested")).unwrap();
+        fs::create_dir_all(root.join("target")).unwrap();
+        fs::write(root.join(".gitignore"), "target/\n").unwrap();
+        fs::write(root.join("src/lib.rs"), "pub struct Config;\n\npub(crate) fn load_config() -> Config {\n    Config\n}\n").unwrap();
+        fs::write(root.join("src/nested/app.py"), "class Loader:\n    def load_config(self):\n        return load_config()\n").unwrap();
+        fs::write(root.join("target/generated.rs"), "fn load_config() {}\n").unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_grep_respects_gitignore_and_include() {
+        let dir = sample_tree();
+        let output = grep(dir.path(), "load_config", None, None, false, 100).unwrap();
+        assert!(output.contains("src/lib.rs:3:"));
+        assert!(output.contains("src/nested/app.py:2:"));
+        assert!(!output.contains("target/"));
+
+        let output = grep(dir.path(), "LOAD_CONFIG", None, Some("*.py"), true, 100).unwrap();
+        assert!(!output.contains("lib.rs"));
+        assert!(output.contains("app.py:3:"));
+
+        let output = grep(dir.path(), "load_config", None, None, false, 1).unwrap();
+        assert!(output.contains("showing first 1 results"));
+    }
+
+    #[test]
+    fn test_glob_and_list_dir() {
+        let dir = sample_tree();
+        assert_eq!(glob(dir.path(), "src/**/*.rs", 100).unwrap(), "src/lib.rs");
+        assert_eq!(glob(dir.path(), "*.py", 100).unwrap(), "src/nested/app.py");
+
+        let listing = list_dir(dir.path(), ".", 1).unwrap();
+        assert_eq!(listing, "src/");
+        let listing = list_dir(dir.path(), "src", 2).unwrap();
+        assert_eq!(listing.lines().collect::<Vec<_>>(), vec!["src/lib.rs", "src/nested/", "src/nested/app.py"]);
+    }
+
+    #[test]
+    fn test_find_symbol_matches_declarations_only() {
+        let dir = sample_tree();
+        let output = find_symbol(dir.path(), "load_config", None, 100).unwrap();
+        assert!(output.contains("src/lib.rs:3: pub(crate) fn load_config()"));
+        assert!(output.contains("app.py:2: def load_config"));
+        assert!(!output.contains("app.py:3"));
+
+        let output = find_symbol(dir.path(), "Config", Some("struct"), 100).unwrap();
+        assert_eq!(output, "src/lib.rs:1: pub struct Config;");
+        assert!(find_symbol(dir.path(), "Loader", None, 100).unwrap().contains("class Loader"));
+    }
+
+    #[test]
+    fn test_read_file_line_range() {
+        let dir = sample_tree();
+        let (_, whole) = read_file(dir.path(), "src/lib.rs", None, None).unwrap();
+        assert!(whole.starts_with("pub struct Config;"));
+
+        let (_, ranged) = read_file(dir.path(), "src/lib.rs", Some(3), Some(4)).unwrap();
+        assert_eq!(ranged, "Lines 3-4 of 5:\n     3 | pub(crate) fn load_config() -> Config {\n     4 |     Config\n");
+
+        assert!(read_file(dir.path(), "src/lib.rs", Some(9), None).is_err());
+    }
+
+    #[test]
+    fn test_paths_confined_to_root() {
+        let dir = sample_tree();
+        let root = dir.path().join("src");
+        assert!(read_file(&root, "../.gitignore", None, None).is_err());
+        assert!(list_dir(&root, "..", 1).is_err());
+    }
+}
@@ -1,4 +1,5 @@
 use crate::error::Result;
+use crate::executor::registry::ToolRegistry;
 use regex::Regex;
 use serde::{Deserialize, Serialize};
 
@@ -27,100 +28,20 @@ pub struct ToolResult {
     pub error: Option<String>,
 }
 
+impl ToolResult {
+    /// Render the result as text for the model
+    pub fn render(&self) -> String {
+        match &self.error {
+            Some(error) if self.output.is_empty() => format!("Error: {}", error),
+            Some(error) => format!("{}\nError: {}", self.output, error),
+            None => self.output.clone(),
+        }
+    }
+}
+
 /// Available tools for the AI
 pub fn get_available_tools() -> Vec<Tool> {
-    vec![
-        Tool {
-            name: "execute_command".to_string(),
-            description: "Execute a shell command in the sandbox. Use this when the user asks you to run a command, install packages, or perform system operations. The command will be run in an isolated Docker container first for safety.".to_string(),
-            parameters: serde_json::json!({
-                "type": "object",
-                "properties": {
-                    "command": {
-                        "type": "string",
-                        "description": "The shell command to execute (e.g., 'npm install express', 'mkdir newdir')"
-                    },
-                    "reason": {
-                        "type": "string",
-                        "description": "Brief explanation of why this command needs to run"
-                    }
-                },
-                "required": ["command", "reason"]
-            }),
-        },
-        Tool {
-            name: "create_file".to_string(),
-            description: "Create a new file with the given content. Use this when the user asks you to create a file or write code.".to_string(),
-            parameters: serde_json::json!({
-                "type": "object",
-                "properties": {
-                    "path": {
-                        "type": "string",
-                        "description": "The file path to create (e.g., 'src/main.js')"
-                    },
-                    "content": {
-                        "type": "string",
-                        "description": "The full content to write to the file"
-                    },
-                    "reason": {
-                        "type": "string",
-                        "description": "Brief explanation of what this file contains"
-                    }
-                },
-                "required": ["path", "content", "reason"]
-            }),
-        },
-        Tool {
-            name: "edit_file".to_string(),
-            description: "Edit an existing file. Use this when the user asks you to modify, update, or change a file.".to_string(),
-            parameters: serde_json::json!({
-                "type": "object",
-                "properties": {
-                    "path": {
-                        "type": "string",
-                        "description": "The file path to edit"
-                    },
-                    "content": {
-                        "type": "string",
-                        "description": "The new full content of the file"
-                    },
-                    "reason": {
-                        "type": "string",
-                        "description": "Brief explanation of what changed and why"
-                    }
-                },
-                "required": ["path", "content", "reason"]
-            }),
-        },
-        Tool {
-            name: "read_file".to_string(),
-            description: "Read the contents of a file. Use this when you need to see what's in a file before editing it.".to_string(),
-            parameters: serde_json::json!({
-                "type": "object",
-                "properties": {
-                    "path": {
-                        "type": "string",
-                        "description": "The file path to read"
-                    }
-                },
-                "required": ["path"]
-            }),
-        },
-        Tool {
-            name: "run_tests".to_string(),
-            description: "Run the test suite for the project. Use this after making changes to verify they work.".to_string(),
-            parameters: serde_json::json!({
-                "type": "object",
-                "properties": {
-                    "command": {
-                        "type": "string",
-                        "description": "The test command to run (e.g., 'npm test', 'cargo test', 'pytest')"
-                    }
-                },
-                "required": ["command"]
-            }),
-        },
-    ]
+    ToolRegistry::builtin().definitions()
 }
 
 /// Convert tool calls to a system prompt message for models that don't support native tool calling
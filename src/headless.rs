@@ -0,0 +1,575 @@
+//! Headless agent server speaking NDJSON over stdio
+//!
+//! `nexus serve --stdio` lets editors and other tools drive the agent. Each
+//! input line is one JSON request and each output line one JSON event. Every
+//! event carries a `type` tag, and events about a session also carry its
+//! `session_id`. The server announces [`PROTOCOL_VERSION`] in its first
+//! `ready` event; clients may pin it in `new_session`.
+//!
+//! Requests:
+//! - `new_session { working_dir?, protocol_version? }` → `session_started`
+//! - `user_message { session_id, text }` → agent events, then `done` or `error`
+//! - `approve_tool { session_id, id, decision, pattern? }` answers the
+//!   `approval_requested` event for tool call `id`; `decision` is `allow`,
+//!   `always` or `deny`
+//! - `cancel { session_id }` → `cancelled`
+//!
+//! Agent events are the same as `nexus chat --json` prints: `text_delta`,
+//! `approval_requested`, `tool_call_started`, `tool_call_finished`,
+//! `file_diff`, `usage` and `compacted`.
+
+use crate::agent::{Agent, AgentEvent};
+use crate::checkpoint::CheckpointStore;
+use crate::config::{AgentProfile, ProjectConfig};
+use crate::error::Result;
+use crate::executor::approval::{Approval, ApprovalPolicy, Approver};
+use crate::executor::tools::{ToolCall, create_tool_system_prompt};
+use crate::memory::MemorySystem;
+use crate::providers::{Message, Provider};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::sync::{RwLock, mpsc, oneshot};
+use tokio::task::JoinHandle;
+
+/// Bumped whenever a request or event changes incompatibly
+pub const PROTOCOL_VERSION: u32 = 1;
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Request {
+    NewSession {
+        #[serde(default)]
+        working_dir: Option<PathBuf>,
+        #[serde(default)]
+        protocol_version: Option<u32>,
+    },
+    UserMessage {
+        session_id: String,
+        text: String,
+    },
+    ApproveTool {
+        session_id: String,
+        /// The tool call id from `approval_requested`
+        id: String,
+        decision: Decision,
+        /// With `always`: glob for the calls to allow from now on
+        #[serde(default)]
+        pattern: Option<String>,
+    },
+    Cancel {
+        session_id: String,
+    },
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Decision {
+    Allow,
+    Always,
+    Deny,
+}
+
+/// Events only the server emits; agent progress uses [`AgentEvent`]
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ServerEvent {
+    Ready {
+        protocol_version: u32,
+        version: &'static str,
+    },
+    SessionStarted {
+        working_dir: PathBuf,
+    },
+    Done {
+        response: String,
+    },
+    Cancelled,
+    Error {
+        message: String,
+    },
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+enum Event {
+    Server(ServerEvent),
+    Agent(AgentEvent),
+}
+
+/// One output line
+#[derive(Serialize)]
+struct Envelope {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    session_id: Option<String>,
+    #[serde(flatten)]
+    event: Event,
+}
+
+/// Approvals a session's turn is waiting on, keyed by request id
+#[derive(Default)]
+struct PendingApprovals {
+    waiting: HashMap<String, oneshot::Sender<Approval>>,
+    /// Ids sent in `approval_requested`, so an answer may arrive before the turn waits on it
+    requested: HashSet<String>,
+    /// Answers that arrived before the turn started waiting
+    answered: HashMap<String, Approval>,
+    /// Input has ended, so nothing can be approved any more
+    closed: bool,
+}
+
+impl PendingApprovals {
+    /// Hand an answer to the turn, or keep it until the turn asks. False if
+    /// nothing was requested under `id` or it was already answered.
+    fn answer(&mut self, id: &str, answer: Approval) -> bool {
+        if let Some(waiting) = self.waiting.remove(id) {
+            self.requested.remove(id);
+            let _ = waiting.send(answer);
+            true
+        } else if self.requested.contains(id) && !self.answered.contains_key(id) {
+            self.answered.insert(id.to_string(), answer);
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Writes events for one session (or none) to the output task
+#[derive(Clone)]
+struct Emitter {
+    out: mpsc::UnboundedSender<String>,
+    session_id: Option<String>,
+}
+
+impl Emitter {
+    fn emit(&self, event: Event) {
+        let envelope = Envelope {
+            session_id: self.session_id.clone(),
+            event,
+        };
+        if let Ok(line) = serde_json::to_string(&envelope) {
+            let _ = self.out.send(line);
+        }
+    }
+
+    fn server(&self, event: ServerEvent) {
+        self.emit(Event::Server(event));
+    }
+
+    fn error(&self, message: impl Into<String>) {
+        self.server(ServerEvent::Error { message: message.into() });
+    }
+}
+
+/// Waits for the `approve_tool` answering a call. The agent has already sent
+/// `approval_requested` on its event stream, so it stays in order with the
+/// text before it; a client quick enough to answer before we get here has
+/// its answer kept in `answered`.
+struct ProtocolApprover {
+    pending: Arc<Mutex<PendingApprovals>>,
+}
+
+#[async_trait]
+impl Approver for ProtocolApprover {
+    async fn approve(&self, call: &ToolCall, _subject: Option<&str>) -> Approval {
+        let (tx, rx) = oneshot::channel();
+        {
+            let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+            if pending.closed {
+                return Approval::Deny;
+            }
+            if let Some(answer) = pending.answered.remove(&call.id) {
+                pending.requested.remove(&call.id);
+                return answer;
+            }
+            pending.waiting.insert(call.id.clone(), tx);
+        }
+        // A client that goes away without answering declines
+        rx.await.unwrap_or(Approval::Deny)
+    }
+}
+
+struct SessionState {
+    working_dir: PathBuf,
+    messages: Arc<tokio::sync::Mutex<Vec<Message>>>,
+    pending: Arc<Mutex<PendingApprovals>>,
+    turn: Option<Turn>,
+}
+
+struct Turn {
+    handle: JoinHandle<()>,
+    /// History length before the turn, restored if it is cancelled
+    start: usize,
+}
+
+/// Runs agent sessions on behalf of a client
+pub struct HeadlessServer {
+    provider: Arc<dyn Provider>,
+    model: String,
+    profile: AgentProfile,
+    confirm_dangerous: bool,
+    memory: Option<Arc<RwLock<MemorySystem>>>,
+    checkpoints: bool,
+}
+
+impl HeadlessServer {
+    pub fn new(provider: Arc<dyn Provider>, model: String) -> Self {
+        Self {
+            provider,
+            model,
+            profile: AgentProfile::default(),
+            confirm_dangerous: true,
+            memory: None,
+            checkpoints: true,
+        }
+    }
+
+    /// Base agent limits; each session's project config is layered on top
+    pub fn with_profile(mut self, profile: AgentProfile) -> Self {
+        self.profile = profile;
+        self
+    }
+
+    /// Whether calls that modify the project ask when no permission rule matches
+    pub fn with_confirm_dangerous(mut self, confirm: bool) -> Self {
+        self.confirm_dangerous = confirm;
+        self
+    }
+
+    pub fn with_memory(mut self, memory: Arc<RwLock<MemorySystem>>) -> Self {
+        self.memory = Some(memory);
+        self
+    }
+
+    /// Whether to record turns as undo checkpoints
+    pub fn with_checkpoints(mut self, enabled: bool) -> Self {
+        self.checkpoints = enabled;
+        self
+    }
+
+    /// Serve requests from `input` until it closes, writing events to `output`.
+    ///
+    /// Turns still running at end of input are allowed to finish; approvals
+    /// they are waiting on are declined.
+    pub async fn run<R, W>(self, input: R, mut output: W) -> Result<()>
+    where
+        R: AsyncBufRead + Unpin,
+        W: AsyncWrite + Unpin + Send + 'static,
+    {
+        let (out_tx, mut out_rx) = mpsc::unbounded_channel::<String>();
+        let writer = tokio::spawn(async move {
+            while let Some(line) = out_rx.recv().await {
+                if output.write_all(line.as_bytes()).await.is_err()
+                    || output.write_all(b"\n").await.is_err()
+                    || output.flush().await.is_err()
+                {
+                    break;
+                }
+            }
+        });
+
+        let global = Emitter { out: out_tx.clone(), session_id: None };
+        global.server(ServerEvent::Ready {
+            protocol_version: PROTOCOL_VERSION,
+            version: env!("CARGO_PKG_VERSION"),
+        });
+
+        let mut sessions: HashMap<String, SessionState> = HashMap::new();
+        let mut lines = input.lines();
+        while let Some(line) = lines.next_line().await? {
+            if line.trim().is_empty() {
+                continue;
+            }
+            match serde_json::from_str::<Request>(&line) {
+                Ok(request) => self.handle(request, &mut sessions, &out_tx).await,
+                Err(e) => global.error(format!("Invalid request: {}", e)),
+            }
+        }
+
+        for session in sessions.values_mut() {
+            {
+                let mut pending = session.pending.lock().unwrap_or_else(|e| e.into_inner());
+                pending.closed = true;
+                pending.waiting.clear();
+                pending.answered.clear();
+            }
+            if let Some(turn) = session.turn.take() {
+                let _ = turn.handle.await;
+            }
+        }
+        drop(global);
+        drop(out_tx);
+        let _ = writer.await;
+        Ok(())
+    }
+
+    async fn handle(
+        &self,
+        request: Request,
+        sessions: &mut HashMap<String, SessionState>,
+        out: &mpsc::UnboundedSender<String>,
+    ) {
+        let emitter = |session_id: &str| Emitter { out: out.clone(), session_id: Some(session_id.to_string()) };
+
+        match request {
+            Request::NewSession { working_dir, protocol_version } => {
+                if let Some(version) = protocol_version.filter(|v| *v != PROTOCOL_VERSION) {
+                    let global = Emitter { out: out.clone(), session_id: None };
+                    global.error(format!("Unsupported protocol version {}; this server speaks {}", version, PROTOCOL_VERSION));
+                    return;
+                }
+                let working_dir = match working_dir.map_or_else(std::env::current_dir, Ok) {
+                    Ok(dir) => dir,
+                    Err(e) => return Emitter { out: out.clone(), session_id: None }.error(e.to_string()),
+                };
+                let session_id = uuid::Uuid::new_v4().simple().to_string();
+                emitter(&session_id).server(ServerEvent::SessionStarted { working_dir: working_dir.clone() });
+                sessions.insert(session_id, SessionState {
+                    working_dir,
+                    messages: Arc::new(tokio::sync::Mutex::new(vec![Message::system(create_tool_system_prompt())])),
+                    pending: Arc::default(),
+                    turn: None,
+                });
+            }
+
+            Request::UserMessage { session_id, text } => {
+                let emitter = emitter(&session_id);
+                let Some(session) = sessions.get_mut(&session_id) else {
+                    return emitter.error(format!("Unknown session: {}", session_id));
+                };
+                if session.turn.as_ref().is_some_and(|t| !t.handle.is_finished()) {
+                    return emitter.error("Session is busy; wait for `done` or send `cancel`");
+                }
+                match self.start_turn(session, text, emitter.clone()).await {
+                    Ok(turn) => session.turn = Some(turn),
+                    Err(e) => emitter.error(e.to_string()),
+                }
+            }
+
+            Request::ApproveTool { session_id, id, decision, pattern } => {
+                let emitter = emitter(&session_id);
+                let Some(session) = sessions.get(&session_id) else {
+                    return emitter.error(format!("No pending approval for tool call {}", id));
+                };
+                let answer = match decision {
+                    Decision::Allow => Approval::Once,
+                    Decision::Always => Approval::Always { pattern },
+                    Decision::Deny => Approval::Deny,
+                };
+                let accepted = session.pending.lock().unwrap_or_else(|e| e.into_inner()).answer(&id, answer);
+                if !accepted {
+                    emitter.error(format!("No pending approval for tool call {}", id));
+                }
+            }
+
+            Request::Cancel { session_id } => {
+                let emitter = emitter(&session_id);
+                let Some(session) = sessions.get_mut(&session_id) else {
+                    return emitter.error(format!("Unknown session: {}", session_id));
+                };
+                let Some(turn) = session.turn.take().filter(|t| !t.handle.is_finished()) else {
+                    return emitter.error("Nothing to cancel");
+                };
+                turn.handle.abort();
+                let _ = turn.handle.await;
+                {
+                    let mut pending = session.pending.lock().unwrap_or_else(|e| e.into_inner());
+                    pending.waiting.clear();
+                    pending.requested.clear();
+                    pending.answered.clear();
+                }
+                // Drop the partial turn so no tool call is left without a result
+                session.messages.lock().await.truncate(turn.start);
+                emitter.server(ServerEvent::Cancelled);
+            }
+        }
+    }
+
+    async fn start_turn(&self, session: &SessionState, text: String, emitter: Emitter) -> Result<Turn> {
+        let project = ProjectConfig::load(&session.working_dir)?;
+        let policy = ApprovalPolicy::new(&project.permissions, self.confirm_dangerous, session.working_dir.clone())?;
+        let approver = ProtocolApprover { pending: session.pending.clone() };
+        let mut agent = Agent::new(session.working_dir.clone())?
+            .with_profile(self.profile.merged_with(&project.agent))
+            .with_approval(policy, Some(Arc::new(approver)));
+        if let Some(memory) = &self.memory {
+            agent = agent.with_memory(memory.clone());
+        }
+        if self.checkpoints {
+            agent = agent.with_checkpoints(Arc::new(CheckpointStore::new(&session.working_dir)?));
+        }
+
+        let messages = session.messages.clone();
+        let start = messages.lock().await.len();
+        let provider = self.provider.clone();
+        let model = self.model.clone();
+        let pending = session.pending.clone();
+
+        let handle = tokio::spawn(async move {
+            let mut messages = messages.lock().await;
+            messages.push(Message::user(text));
+
+            let (events_tx, mut events_rx) = mpsc::channel::<AgentEvent>(256);
+            let forward = {
+                let emitter = emitter.clone();
+                tokio::spawn(async move {
+                    while let Some(event) = events_rx.recv().await {
+                        // Known before the client sees the id, so a fast answer is accepted
+                        if let AgentEvent::ApprovalRequested { id, .. } = &event {
+                            pending.lock().unwrap_or_else(|e| e.into_inner()).requested.insert(id.clone());
+                        }
+                        emitter.emit(Event::Agent(event));
+                    }
+                })
+            };
+
+            let result = agent.run_task_streaming(&mut messages, &*provider, model, events_tx).await;
+            let _ = forward.await;
+            match result {
+                Ok(response) => emitter.server(ServerEvent::Done { response }),
+                Err(e) => emitter.error(e.to_string()),
+            }
+        });
+
+        Ok(Turn { handle, start })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::{CompletionRequest, CompletionResponse, ProviderInfo, Role, StreamChunk, Usage};
+    use tokio::io::{AsyncBufReadExt, BufReader};
+
+    /// Creates a file on the first turn, then answers once the tool result is in
+    struct ScriptedProvider;
+
+    #[async_trait]
+    impl Provider for ScriptedProvider {
+        fn info(&self) -> ProviderInfo {
+            ProviderInfo {
+                name: "scripted".to_string(),
+                display_name: "Scripted".to_string(),
+                supports_oauth: false,
+                default_model: "test".to_string(),
+                available_models: vec![],
+            }
+        }
+
+        async fn complete(&self, _request: CompletionRequest) -> Result<CompletionResponse> {
+            unreachable!("the server always streams")
+        }
+
+        async fn complete_stream(&self, request: CompletionRequest, tx: mpsc::Sender<StreamChunk>) -> Result<()> {
+            if request.messages.last().unwrap().role == Role::User {
+                let _ = tx.send(StreamChunk::ContentDelta("Creating it.".to_string())).await;
+                let _ = tx.send(StreamChunk::ToolCall(ToolCall {
+                    id: "call_1".to_string(),
+                    name: "create_file".to_string(),
+                    arguments: serde_json::json!({"path": "hello.txt", "content": "hi\n"}),
+                })).await;
+            } else {
+                let _ = tx.send(StreamChunk::ContentDelta("Done.".to_string())).await;
+            }
+            let _ = tx.send(StreamChunk::Usage(Usage { prompt_tokens: 10, completion_tokens: 2, total_tokens: 12, ..Usage::default() })).await;
+            let _ = tx.send(StreamChunk::Done).await;
+            Ok(())
+        }
+
+        async fn authenticate(&mut self) -> Result<()> {
+            Ok(())
+        }
+
+        async fn refresh_auth(&mut self) -> Result<()> {
+            Ok(())
+        }
+
+        fn is_authenticated(&self) -> bool {
+            true
+        }
+    }
+
+    #[tokio::test]
+    async fn test_answer_before_approver_waits() {
+        let pending = Arc::new(Mutex::new(PendingApprovals::default()));
+        let approver = ProtocolApprover { pending: pending.clone() };
+        let call = ToolCall { id: "call_1".to_string(), name: "create_file".to_string(), arguments: serde_json::json!({}) };
+
+        {
+            let mut pending = pending.lock().unwrap();
+            assert!(!pending.answer("call_1", Approval::Once), "not requested yet");
+            pending.requested.insert("call_1".to_string());
+            // The client answers as soon as it sees the event, before the turn waits
+            assert!(pending.answer("call_1", Approval::Once));
+            assert!(!pending.answer("call_1", Approval::Deny), "already answered");
+        }
+        assert!(matches!(approver.approve(&call, None).await, Approval::Once));
+        let pending = pending.lock().unwrap();
+        assert!(pending.requested.is_empty() && pending.answered.is_empty());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_stdio_session_end_to_end() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let (mut client_in, server_in) = tokio::io::duplex(64 * 1024);
+        let (server_out, client_out) = tokio::io::duplex(64 * 1024);
+        let server = HeadlessServer::new(Arc::new(ScriptedProvider), "test".to_string()).with_checkpoints(false);
+        let running = tokio::spawn(server.run(BufReader::new(server_in), server_out));
+        let mut events = BufReader::new(client_out).lines();
+        let mut next = async || -> serde_json::Value {
+            serde_json::from_str(&events.next_line().await.unwrap().unwrap()).unwrap()
+        };
+        let send = async |client: &mut tokio::io::DuplexStream, request: serde_json::Value| {
+            client.write_all(format!("{}\n", request).as_bytes()).await.unwrap();
+        };
+
+        let ready = next().await;
+        assert_eq!(ready["type"], "ready");
+        assert_eq!(ready["protocol_version"], PROTOCOL_VERSION);
+
+        send(&mut client_in, serde_json::json!({"type": "new_session", "working_dir": dir.path(), "protocol_version": 1})).await;
+        let started = next().await;
+        assert_eq!(started["type"], "session_started");
+        let session_id = started["session_id"].as_str().unwrap().to_string();
+
+        send(&mut client_in, serde_json::json!({"type": "user_message", "session_id": session_id, "text": "create hello.txt"})).await;
+        let mut types = Vec::new();
+        let approval = loop {
+            let event = next().await;
+            assert_eq!(event["session_id"], session_id.as_str());
+            types.push(event["type"].as_str().unwrap().to_string());
+            if event["type"] == "approval_requested" {
+                break event;
+            }
+        };
+        assert_eq!(approval["name"], "create_file");
+        assert_eq!(approval["subject"], "hello.txt");
+
+        send(&mut client_in, serde_json::json!({
+            "type": "approve_tool", "session_id": session_id, "id": approval["id"], "decision": "allow"
+        })).await;
+        let done = loop {
+            let event = next().await;
+            types.push(event["type"].as_str().unwrap().to_string());
+            if event["type"] == "done" {
+                break event;
+            }
+        };
+        assert_eq!(done["response"], "Done.");
+        assert_eq!(std::fs::read_to_string(dir.path().join("hello.txt")).unwrap(), "hi\n");
+        assert_eq!(types, vec![
+            "text_delta", "usage", "approval_requested", "tool_call_started", "file_diff",
+            "tool_call_finished", "text_delta", "usage", "done",
+        ]);
+
+        send(&mut client_in, serde_json::json!({"type": "cancel", "session_id": "nope"})).await;
+        assert_eq!(next().await["type"], "error");
+
+        drop(client_in);
+        running.await.unwrap().unwrap();
+    }
+}
@@ -3,9 +3,17 @@ use crate::error::{NexusError, Result};
 use crate::providers::{Message, CompletionRequest, CompletionResponse};
 use crate::config::ConfigManager;
 use crate::providers::create_provider;
+use crate::providers::model_capabilities::ModelRegistry;
 use std::path::PathBuf;
 use std::fs;
 
+/// Where the hierarchy, escalation policy and escalation log live
+pub fn config_dir() -> PathBuf {
+    std::env::var("HOME")
+        .map(|h| PathBuf::from(h).join(".config/nexus"))
+        .unwrap_or_else(|_| PathBuf::from("~/.config/nexus"))
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ModelTier {
     pub model_id: String,
@@ -13,6 +21,10 @@ pub struct ModelTier {
     pub max_tokens: Option<u32>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub max_cost_per_request: Option<f64>,
+    /// Configured provider that serves this model. Inferred from the model id
+    /// when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provider: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -39,6 +51,7 @@ impl ModelHierarchy {
                     model_id: "openrouter/auto:free".to_string(),
                     max_tokens: None,
                     max_cost_per_request: None,
+                    provider: None,
                 },
             ],
             daily: vec![
@@ -46,6 +59,7 @@ impl ModelHierarchy {
                     model_id: "gemini-1.5-flash".to_string(),
                     max_tokens: None,
                     max_cost_per_request: None,
+                    provider: None,
                 },
             ],
             planning: vec![
@@ -53,11 +67,13 @@ impl ModelHierarchy {
                     model_id: "gemini-1.5-pro".to_string(),
                     max_tokens: None,
                     max_cost_per_request: None,
+                    provider: None,
                 },
                 ModelTier {
                     model_id: "claude-sonnet-4-5".to_string(),
                     max_tokens: None,
                     max_cost_per_request: None,
+                    provider: None,
                 },
             ],
             coding: vec![
@@ -65,11 +81,13 @@ impl ModelHierarchy {
                     model_id: "claude-sonnet-4-5".to_string(),
                     max_tokens: None,
                     max_cost_per_request: None,
+                    provider: None,
                 },
                 ModelTier {
                     model_id: "claude-opus-4-6".to_string(),
                     max_tokens: None,
                     max_cost_per_request: None,
+                    provider: None,
                 },
             ],
             review: vec![
@@ -77,6 +95,7 @@ impl ModelHierarchy {
                     model_id: "claude-sonnet-4-5".to_string(),
                     max_tokens: None,
                     max_cost_per_request: None,
+                    provider: None,
                 },
             ],
         }
@@ -90,6 +109,7 @@ impl ModelHierarchy {
                     model_id: "openrouter/auto:free".to_string(),
                     max_tokens: None,
                     max_cost_per_request: None,
+                    provider: None,
                 },
             ],
             daily: vec![
@@ -97,6 +117,7 @@ impl ModelHierarchy {
                     model_id: "gemini-1.5-flash".to_string(),
                     max_tokens: None,
                     max_cost_per_request: None,
+                    provider: None,
                 },
             ],
             planning: vec![
@@ -104,11 +125,13 @@ impl ModelHierarchy {
                     model_id: "gemini-1.5-flash".to_string(),
                     max_tokens: None,
                     max_cost_per_request: None,
+                    provider: None,
                 },
                 ModelTier {
                     model_id: "gpt-4o-mini".to_string(),
                     max_tokens: None,
                     max_cost_per_request: None,
+                    provider: None,
                 },
             ],
             coding: vec![
@@ -116,6 +139,7 @@ impl ModelHierarchy {
                     model_id: "gemini-1.5-pro".to_string(),
                     max_tokens: None,
                     max_cost_per_request: Some(0.5),
+                    provider: None,
                 },
             ],
             review: vec![
@@ -123,6 +147,7 @@ impl ModelHierarchy {
                     model_id: "gemini-1.5-flash".to_string(),
                     max_tokens: None,
                     max_cost_per_request: None,
+                    provider: None,
                 },
             ],
         }
@@ -136,6 +161,7 @@ impl ModelHierarchy {
                     model_id: "gemini-1.5-flash".to_string(),
                     max_tokens: None,
                     max_cost_per_request: None,
+                    provider: None,
                 },
             ],
             daily: vec![
@@ -143,6 +169,7 @@ impl ModelHierarchy {
                     model_id: "gemini-1.5-pro".to_string(),
                     max_tokens: None,
                     max_cost_per_request: None,
+                    provider: None,
                 },
             ],
             planning: vec![
@@ -150,11 +177,13 @@ impl ModelHierarchy {
                     model_id: "claude-opus-4-6".to_string(),
                     max_tokens: None,
                     max_cost_per_request: None,
+                    provider: None,
                 },
                 ModelTier {
                     model_id: "o1".to_string(),
                     max_tokens: None,
                     max_cost_per_request: None,
+                    provider: None,
                 },
             ],
             coding: vec![
@@ -162,6 +191,7 @@ impl ModelHierarchy {
                     model_id: "claude-opus-4-6".to_string(),
                     max_tokens: None,
                     max_cost_per_request: None,
+                    provider: None,
                 },
             ],
             review: vec![
@@ -169,6 +199,7 @@ impl ModelHierarchy {
                     model_id: "claude-sonnet-4-5".to_string(),
                     max_tokens: None,
                     max_cost_per_request: None,
+                    provider: None,
                 },
             ],
         }
@@ -182,6 +213,7 @@ impl ModelHierarchy {
                     model_id: "openrouter/auto:free".to_string(),
                     max_tokens: None,
                     max_cost_per_request: None,
+                    provider: None,
                 },
             ],
             daily: vec![
@@ -189,6 +221,7 @@ impl ModelHierarchy {
                     model_id: "gemini-1.5-flash".to_string(),
                     max_tokens: None,
                     max_cost_per_request: None,
+                    provider: None,
                 },
             ],
             planning: vec![
@@ -196,6 +229,7 @@ impl ModelHierarchy {
                     model_id: "claude-haiku-3-5".to_string(),
                     max_tokens: None,
                     max_cost_per_request: None,
+                    provider: None,
                 },
             ],
             coding: vec![
@@ -203,6 +237,7 @@ impl ModelHierarchy {
                     model_id: "claude-sonnet-4-5".to_string(),
                     max_tokens: None,
                     max_cost_per_request: None,
+                    provider: None,
                 },
             ],
             review: vec![
@@ -210,6 +245,7 @@ impl ModelHierarchy {
                     model_id: "gemini-1.5-flash".to_string(),
                     max_tokens: None,
                     max_cost_per_request: None,
+                    provider: None,
                 },
             ],
         }
@@ -223,6 +259,7 @@ impl ModelHierarchy {
                     model_id: "claude-haiku-3-5".to_string(),
                     max_tokens: None,
                     max_cost_per_request: None,
+                    provider: None,
                 },
             ],
             daily: vec![
@@ -230,6 +267,7 @@ impl ModelHierarchy {
                     model_id: "claude-haiku-3-5".to_string(),
                     max_tokens: None,
                     max_cost_per_request: None,
+                    provider: None,
                 },
             ],
             planning: vec![
@@ -237,11 +275,13 @@ impl ModelHierarchy {
                     model_id: "claude-sonnet-4-5".to_string(),
                     max_tokens: None,
                     max_cost_per_request: None,
+                    provider: None,
                 },
                 ModelTier {
                     model_id: "claude-opus-4-6".to_string(),
                     max_tokens: None,
                     max_cost_per_request: None,
+                    provider: None,
                 },
             ],
             coding: vec![
@@ -249,11 +289,13 @@ impl ModelHierarchy {
                     model_id: "claude-sonnet-4-5".to_string(),
                     max_tokens: None,
                     max_cost_per_request: None,
+                    provider: None,
                 },
                 ModelTier {
                     model_id: "claude-opus-4-6".to_string(),
                     max_tokens: None,
                     max_cost_per_request: None,
+                    provider: None,
                 },
             ],
             review: vec![
@@ -261,6 +303,7 @@ impl ModelHierarchy {
                     model_id: "claude-sonnet-4-5".to_string(),
                     max_tokens: None,
                     max_cost_per_request: None,
+                    provider: None,
                 },
             ],
         }
@@ -277,15 +320,43 @@ impl ModelHierarchy {
         }
     }
 
-    pub fn get_tier(&self, category: TaskCategory, tier_index: usize) -> Option<&ModelTier> {
-        let tiers = match category {
+    /// Build tiers from the capability registry: the `per_category` best
+    /// `candidates` for each category, ordered cheapest first so escalation
+    /// climbs towards the stronger models
+    pub fn ranked(registry: &ModelRegistry, candidates: &[String], per_category: usize) -> Self {
+        let tiers = |ranked: Vec<String>| -> Vec<ModelTier> {
+            let cost = |id: &String| registry.get(id).map(|m| m.cost_per_1m_tokens).unwrap_or(0.0);
+            let mut picked: Vec<String> = ranked.into_iter().take(per_category).collect();
+            picked.sort_by(|a, b| cost(a).partial_cmp(&cost(b)).unwrap_or(std::cmp::Ordering::Equal));
+            picked.into_iter().map(|model_id| ModelTier {
+                model_id,
+                max_tokens: None,
+                max_cost_per_request: None,
+                provider: None,
+            }).collect()
+        };
+        Self {
+            heartbeat: tiers(registry.rank_for_heartbeat(candidates)),
+            daily: tiers(registry.rank_for_review(candidates)),
+            planning: tiers(registry.rank_for_planning(candidates)),
+            coding: tiers(registry.rank_for_coding(candidates)),
+            review: tiers(registry.rank_for_review(candidates)),
+        }
+    }
+
+    /// Tiers for `category`, cheapest first
+    pub fn tiers(&self, category: TaskCategory) -> &[ModelTier] {
+        match category {
             TaskCategory::Heartbeat => &self.heartbeat,
             TaskCategory::Daily => &self.daily,
             TaskCategory::Planning => &self.planning,
             TaskCategory::Coding => &self.coding,
             TaskCategory::Review => &self.review,
-        };
-        tiers.get(tier_index)
+        }
+    }
+
+    pub fn get_tier(&self, category: TaskCategory, tier_index: usize) -> Option<&ModelTier> {
+        self.tiers(category).get(tier_index)
     }
 
     pub fn save(&self, config_dir: &PathBuf) -> Result<()> {
@@ -306,7 +377,8 @@ impl ModelHierarchy {
     }
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
 pub enum TaskCategory {
     Heartbeat,  // Proactive checks, simple automation
     Daily,      // Simple queries, file reads, status
@@ -316,6 +388,14 @@ pub enum TaskCategory {
 }
 
 impl TaskCategory {
+    pub const ALL: [TaskCategory; 5] = [
+        TaskCategory::Heartbeat,
+        TaskCategory::Daily,
+        TaskCategory::Planning,
+        TaskCategory::Coding,
+        TaskCategory::Review,
+    ];
+
     pub fn as_str(&self) -> &'static str {
         match self {
             TaskCategory::Heartbeat => "heartbeat",
@@ -0,0 +1,491 @@
+//! Cost accounting for completions
+//!
+//! Every completion's token usage is priced and appended to
+//! `~/.config/nexus/usage.jsonl`, keyed by provider, model, project, session
+//! and task category. Prices come from the provider's model listing
+//! ([`ModelPricing`]) when it has one, otherwise from the model registry: its
+//! listed prompt and completion prices, or the blended
+//! `ModelCapabilities::cost_per_1m_tokens` estimate.
+//!
+//! [`Meter`] records usage and refuses new requests once today's spend reaches
+//! the escalation policy's `daily_budget_limit`. [`MeteredProvider`] puts a
+//! meter in front of any provider; the model router records through its own.
+
+use crate::error::{NexusError, Result};
+use crate::hierarchy::{self, classify_task, EscalationPolicy, TaskCategory};
+use crate::providers::model_capabilities::ModelRegistry;
+use crate::providers::{CompletionRequest, CompletionResponse, ModelInfo, ModelPricing, Provider, ProviderInfo, Role, StreamChunk, Usage};
+use async_trait::async_trait;
+use chrono::{DateTime, Local, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc;
+use tracing::warn;
+
+/// One priced completion
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UsageEntry {
+    pub at: DateTime<Utc>,
+    pub provider: String,
+    pub model: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub project: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session: Option<String>,
+    pub category: TaskCategory,
+    pub prompt_tokens: u32,
+    pub completion_tokens: u32,
+    /// Part of `prompt_tokens` read from the prompt cache
+    #[serde(default)]
+    pub cache_read_tokens: u32,
+    /// Part of `prompt_tokens` written to the prompt cache
+    #[serde(default)]
+    pub cache_write_tokens: u32,
+    /// USD, or `None` if no price is known for the model
+    pub cost: Option<f64>,
+}
+
+/// Append-only usage log
+pub struct CostLedger {
+    path: PathBuf,
+}
+
+impl CostLedger {
+    pub fn open(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    pub fn default_path() -> PathBuf {
+        hierarchy::config_dir().join("usage.jsonl")
+    }
+
+    pub fn record(&self, entry: &UsageEntry) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        writeln!(file, "{}", serde_json::to_string(entry)?)?;
+        Ok(())
+    }
+
+    /// All recorded entries, oldest first. Lines that don't parse are skipped.
+    pub fn entries(&self) -> Result<Vec<UsageEntry>> {
+        if !self.path.exists() {
+            return Ok(Vec::new());
+        }
+        Ok(fs::read_to_string(&self.path)?
+            .lines()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect())
+    }
+
+    /// Total spend on `day`, in local time
+    pub fn spent_on(&self, day: NaiveDate) -> Result<f64> {
+        Ok(self.entries()?
+            .iter()
+            .filter(|e| e.at.with_timezone(&Local).date_naive() == day)
+            .filter_map(|e| e.cost)
+            .sum())
+    }
+}
+
+/// Cached prompt tokens relative to the normal prompt price. These are
+/// Anthropic's rates; Gemini and OpenAI discount reads similarly and don't
+/// charge extra for writes.
+const CACHE_READ_RATE: f64 = 0.1;
+const CACHE_WRITE_RATE: f64 = 1.25;
+
+/// The cost of `usage` on `model`. `pricing` from the provider's listing wins
+/// over what `registry` knows.
+pub fn price(model: &str, usage: &Usage, pricing: Option<&ModelPricing>, registry: &ModelRegistry) -> Option<f64> {
+    let per_token = |per_million: f64, tokens: f64| per_million * tokens / 1_000_000.0;
+    // Prompt tokens weighted by what the cache did with them
+    let uncached = usage.prompt_tokens.saturating_sub(usage.cache_read_tokens + usage.cache_write_tokens);
+    let prompt_tokens = uncached as f64
+        + usage.cache_read_tokens as f64 * CACHE_READ_RATE
+        + usage.cache_write_tokens as f64 * CACHE_WRITE_RATE;
+    let completion_tokens = usage.completion_tokens as f64;
+    let split = |prompt: Option<f64>, completion: Option<f64>| {
+        (prompt.is_some() || completion.is_some()).then(|| {
+            per_token(prompt.unwrap_or(0.0), prompt_tokens) + per_token(completion.unwrap_or(0.0), completion_tokens)
+        })
+    };
+    if let Some(cost) = pricing.and_then(|p| split(p.prompt, p.completion)) {
+        return Some(cost);
+    }
+    let capabilities = registry.get(model)?;
+    split(capabilities.prompt_cost_per_1m, capabilities.completion_cost_per_1m)
+        .or_else(|| Some(per_token(capabilities.cost_per_1m_tokens, prompt_tokens + completion_tokens)))
+}
+
+/// What usage report rows are grouped by besides the period
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GroupBy {
+    Provider,
+    Model,
+    Project,
+    Session,
+    Category,
+}
+
+impl std::str::FromStr for GroupBy {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "provider" => Ok(GroupBy::Provider),
+            "model" => Ok(GroupBy::Model),
+            "project" => Ok(GroupBy::Project),
+            "session" => Ok(GroupBy::Session),
+            "category" => Ok(GroupBy::Category),
+            _ => Err(format!("Unknown grouping: '{}'. Valid: provider, model, project, session, category", s)),
+        }
+    }
+}
+
+impl GroupBy {
+    fn key(&self, entry: &UsageEntry) -> String {
+        match self {
+            GroupBy::Provider => entry.provider.clone(),
+            GroupBy::Model => entry.model.clone(),
+            GroupBy::Project => entry.project.clone().unwrap_or_else(|| "-".to_string()),
+            GroupBy::Session => entry.session.clone().unwrap_or_else(|| "-".to_string()),
+            GroupBy::Category => entry.category.as_str().to_string(),
+        }
+    }
+}
+
+/// One row of the usage report
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct UsageRow {
+    /// `2026-03-14` for daily rows, `2026-W11` for weekly ones
+    pub period: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+    pub requests: usize,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub cache_read_tokens: u64,
+    pub cost: f64,
+    /// Requests whose model had no known price
+    pub unpriced: usize,
+}
+
+/// Roll `entries` since `since` up into daily or weekly rows, newest period
+/// first and costliest group first within a period
+pub fn rollup(entries: &[UsageEntry], weekly: bool, by: Option<GroupBy>, since: DateTime<Utc>) -> Vec<UsageRow> {
+    let mut rows: BTreeMap<(String, Option<String>), UsageRow> = BTreeMap::new();
+    for entry in entries.iter().filter(|e| e.at >= since) {
+        let local = entry.at.with_timezone(&Local);
+        let period = if weekly {
+            local.format("%G-W%V").to_string()
+        } else {
+            local.format("%Y-%m-%d").to_string()
+        };
+        let group = by.map(|by| by.key(entry));
+        let row = rows.entry((period.clone(), group.clone())).or_insert_with(|| UsageRow {
+            period,
+            group,
+            ..UsageRow::default()
+        });
+        row.requests += 1;
+        row.prompt_tokens += entry.prompt_tokens as u64;
+        row.completion_tokens += entry.completion_tokens as u64;
+        row.cache_read_tokens += entry.cache_read_tokens as u64;
+        match entry.cost {
+            Some(cost) => row.cost += cost,
+            None => row.unpriced += 1,
+        }
+    }
+    let mut rows: Vec<_> = rows.into_values().collect();
+    rows.sort_by(|a, b| b.period.cmp(&a.period).then(b.cost.total_cmp(&a.cost)));
+    rows
+}
+
+/// Records usage to a ledger and enforces the daily budget
+pub struct Meter {
+    ledger: CostLedger,
+    /// USD per local day; zero or less disables the limit
+    daily_budget: f64,
+    project: Option<String>,
+    session: Mutex<Option<String>>,
+    /// Listed prices per provider name, fetched on first use
+    prices: tokio::sync::Mutex<HashMap<String, HashMap<String, ModelPricing>>>,
+    registry: Arc<ModelRegistry>,
+    /// Today's spend, read from the ledger once and kept up to date by `record`
+    spent_today: Mutex<Option<(NaiveDate, f64)>>,
+}
+
+impl Meter {
+    pub fn new(ledger: CostLedger, daily_budget: f64) -> Self {
+        Self {
+            ledger,
+            daily_budget,
+            project: None,
+            session: Mutex::new(None),
+            prices: tokio::sync::Mutex::new(HashMap::new()),
+            registry: ModelRegistry::current(),
+            spent_today: Mutex::new(None),
+        }
+    }
+
+    /// A meter on the default ledger with the escalation policy's budget,
+    /// attributing usage to the current directory
+    pub fn from_config() -> Result<Self> {
+        let policy = EscalationPolicy::load(&hierarchy::config_dir())?;
+        let meter = Self::new(CostLedger::open(CostLedger::default_path()), policy.daily_budget_limit);
+        Ok(match std::env::current_dir() {
+            Ok(dir) => meter.with_project(dir.display().to_string()),
+            Err(_) => meter,
+        })
+    }
+
+    pub fn with_project(mut self, project: String) -> Self {
+        self.project = Some(project);
+        self
+    }
+
+    /// Price models from `registry` instead of the shared one
+    #[cfg(test)]
+    pub fn with_registry(mut self, registry: Arc<ModelRegistry>) -> Self {
+        self.registry = registry;
+        self
+    }
+
+    /// Attribute usage from now on to session `id`
+    pub fn set_session(&self, id: &str) {
+        *self.session.lock().unwrap_or_else(|e| e.into_inner()) = Some(id.to_string());
+    }
+
+    /// Fails once today's spend has reached the daily budget
+    pub fn check_budget(&self) -> Result<()> {
+        if self.daily_budget <= 0.0 {
+            return Ok(());
+        }
+        let today = Local::now().date_naive();
+        let mut cached = self.spent_today.lock().unwrap_or_else(|e| e.into_inner());
+        let spent = match *cached {
+            Some((day, spent)) if day == today => spent,
+            _ => {
+                let spent = self.ledger.spent_on(today)?;
+                *cached = Some((today, spent));
+                spent
+            }
+        };
+        if spent >= self.daily_budget {
+            return Err(NexusError::BudgetExceeded { spent, limit: self.daily_budget });
+        }
+        Ok(())
+    }
+
+    /// Price and record one completion. A ledger that can't be written is
+    /// logged rather than failing the request that already succeeded.
+    pub async fn record(
+        &self,
+        provider_name: &str,
+        provider: &(dyn Provider + Send + Sync),
+        category: TaskCategory,
+        model: &str,
+        usage: &Usage,
+    ) {
+        let pricing = self.listed_price(provider_name, provider, model).await;
+        let entry = UsageEntry {
+            at: Utc::now(),
+            provider: provider_name.to_string(),
+            model: model.to_string(),
+            project: self.project.clone(),
+            session: self.session.lock().unwrap_or_else(|e| e.into_inner()).clone(),
+            category,
+            prompt_tokens: usage.prompt_tokens,
+            completion_tokens: usage.completion_tokens,
+            cache_read_tokens: usage.cache_read_tokens,
+            cache_write_tokens: usage.cache_write_tokens,
+            cost: price(model, usage, pricing.as_ref(), &self.registry),
+        };
+        if let Err(e) = self.ledger.record(&entry) {
+            warn!(error = %e, "Could not write usage ledger");
+        }
+        // The money is spent whether or not the ledger could be written
+        let mut cached = self.spent_today.lock().unwrap_or_else(|e| e.into_inner());
+        if let (Some((day, spent)), Some(cost)) = (cached.as_mut(), entry.cost)
+            && *day == entry.at.with_timezone(&Local).date_naive()
+        {
+            *spent += cost;
+        }
+    }
+
+    async fn listed_price(&self, provider_name: &str, provider: &(dyn Provider + Send + Sync), model: &str) -> Option<ModelPricing> {
+        let mut prices = self.prices.lock().await;
+        if !prices.contains_key(provider_name) {
+            let listed = match provider.list_available_models().await {
+                Ok(models) => models.into_iter()
+                    .filter_map(|m| m.pricing.map(|pricing| (m.id, pricing)))
+                    .collect(),
+                Err(_) => HashMap::new(),
+            };
+            prices.insert(provider_name.to_string(), listed);
+        }
+        prices[provider_name].get(model).cloned()
+    }
+}
+
+/// Meters a provider: checks the budget before each request and records the
+/// usage it reports. Requests are categorized from their last user message.
+pub struct MeteredProvider {
+    name: String,
+    inner: Box<dyn Provider + Send + Sync>,
+    meter: Arc<Meter>,
+}
+
+impl MeteredProvider {
+    pub fn new(name: impl Into<String>, inner: Box<dyn Provider + Send + Sync>, meter: Arc<Meter>) -> Self {
+        Self { name: name.into(), inner, meter }
+    }
+
+    fn category(request: &CompletionRequest) -> TaskCategory {
+        let input = request.messages.iter().rev()
+            .find(|m| m.role == Role::User)
+            .map(|m| m.text_content())
+            .unwrap_or_default();
+        classify_task(&input, false)
+    }
+}
+
+#[async_trait]
+impl Provider for MeteredProvider {
+    fn info(&self) -> ProviderInfo {
+        self.inner.info()
+    }
+
+    async fn complete(&self, request: CompletionRequest) -> Result<CompletionResponse> {
+        self.meter.check_budget()?;
+        let category = Self::category(&request);
+        let model = request.model.clone();
+        let response = self.inner.complete(request).await?;
+        if let Some(usage) = &response.usage {
+            self.meter.record(&self.name, self.inner.as_ref(), category, &model, usage).await;
+        }
+        Ok(response)
+    }
+
+    async fn complete_stream(&self, request: CompletionRequest, tx: mpsc::Sender<StreamChunk>) -> Result<()> {
+        self.meter.check_budget()?;
+        let category = Self::category(&request);
+        let model = request.model.clone();
+
+        let (inner_tx, mut inner_rx) = mpsc::channel(64);
+        let forward = async {
+            let mut usage = None;
+            while let Some(chunk) = inner_rx.recv().await {
+                if let StreamChunk::Usage(u) = &chunk {
+                    usage = Some(u.clone());
+                }
+                let _ = tx.send(chunk).await;
+            }
+            usage
+        };
+        let (result, usage) = tokio::join!(self.inner.complete_stream(request, inner_tx), forward);
+        if let Some(usage) = &usage {
+            self.meter.record(&self.name, self.inner.as_ref(), category, &model, usage).await;
+        }
+        result
+    }
+
+    async fn list_available_models(&self) -> Result<Vec<ModelInfo>> {
+        self.inner.list_available_models().await
+    }
+
+    async fn authenticate(&mut self) -> Result<()> {
+        self.inner.authenticate().await
+    }
+
+    async fn refresh_auth(&mut self) -> Result<()> {
+        self.inner.refresh_auth().await
+    }
+
+    fn is_authenticated(&self) -> bool {
+        self.inner.is_authenticated()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::mock::{Cassette, Interaction, MockProvider, ScriptedResponse};
+    use crate::providers::Message;
+
+    fn request(model: &str, text: &str) -> CompletionRequest {
+        CompletionRequest::new(model.to_string(), vec![Message::user(text)])
+    }
+
+    fn replies(count: usize, prompt_tokens: u32, completion_tokens: u32) -> Box<dyn Provider + Send + Sync> {
+        let interaction = Interaction {
+            response: ScriptedResponse {
+                content: "done".to_string(),
+                usage: Some(Usage { prompt_tokens, completion_tokens, total_tokens: prompt_tokens + completion_tokens, ..Usage::default() }),
+                ..ScriptedResponse::default()
+            },
+            ..Interaction::default()
+        };
+        Box::new(MockProvider::new(Cassette { interactions: vec![interaction; count] }))
+    }
+
+    #[test]
+    fn test_price_prefers_listed_pricing() {
+        let usage = Usage { prompt_tokens: 1_000_000, completion_tokens: 200_000, total_tokens: 1_200_000, ..Usage::default() };
+        let listed = ModelPricing { prompt: Some(3.0), completion: Some(15.0) };
+        let registry = ModelRegistry::builtin();
+        assert_eq!(price("claude-sonnet-4-5", &usage, Some(&listed), &registry), Some(6.0));
+        // Falls back to the capability table's blended rate
+        assert_eq!(price("claude-sonnet-4-5", &usage, None, &registry), Some(3.6));
+        assert_eq!(price("unknown-model", &usage, None, &registry), None);
+    }
+
+    #[test]
+    fn test_price_discounts_cache_reads_and_charges_writes() {
+        let listed = ModelPricing { prompt: Some(3.0), completion: Some(15.0) };
+        let registry = ModelRegistry::builtin();
+        let usage = Usage {
+            prompt_tokens: 1_000_000,
+            completion_tokens: 0,
+            total_tokens: 1_000_000,
+            cache_read_tokens: 800_000,
+            cache_write_tokens: 100_000,
+        };
+        // 100k uncached at $3, 800k read at $0.30, 100k written at $3.75
+        let cost = price("claude-sonnet-4-5", &usage, Some(&listed), &registry).unwrap();
+        assert!((cost - (0.3 + 0.24 + 0.375)).abs() < 1e-9);
+    }
+
+    #[tokio::test]
+    async fn test_records_usage_and_enforces_budget() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("usage.jsonl");
+        // Sonnet is $3 per million tokens, so each request costs $1.50
+        let meter = Meter::new(CostLedger::open(path.clone()), 2.0)
+            .with_project("/work/app".to_string())
+            .with_registry(Arc::new(ModelRegistry::builtin()));
+        let meter = Arc::new(meter);
+        meter.set_session("abc123");
+        let provider = MeteredProvider::new("claude", replies(3, 400_000, 100_000), meter);
+
+        provider.complete(request("claude-sonnet-4-5", "implement login")).await.unwrap();
+        provider.complete(request("claude-sonnet-4-5", "what is this")).await.unwrap();
+        let err = provider.complete(request("claude-sonnet-4-5", "one more")).await.unwrap_err();
+        assert!(matches!(err, NexusError::BudgetExceeded { limit, .. } if limit == 2.0));
+
+        let entries = CostLedger::open(path).entries().unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].category, TaskCategory::Coding);
+        assert_eq!(entries[0].session.as_deref(), Some("abc123"));
+        assert_eq!(entries[0].project.as_deref(), Some("/work/app"));
+
+        let rows = rollup(&entries, false, Some(GroupBy::Category), DateTime::<Utc>::MIN_UTC);
+        let summary: Vec<_> = rows.iter().map(|r| (r.group.as_deref().unwrap(), r.requests, r.cost)).collect();
+        assert_eq!(summary, vec![("coding", 1, 1.5), ("daily", 1, 1.5)]);
+    }
+}
@@ -1,31 +1,45 @@
 mod agent;
+mod checkpoint;
 mod config;
 mod context;
 mod daemon;
 mod error;
 mod executor;
+mod headless;
 mod hierarchy;
+mod ledger;
 mod memory;
 mod mcp;
 mod oauth;
 mod providers;
+mod router;
 mod sandbox;
 mod secret_store;
+mod session;
 mod swarm;
 mod watcher;
 
 use anyhow::Result;
-use clap::{Parser, Subcommand};
-use config::{ConfigManager, ProviderConfig, ProviderType};
+use clap::{Args, Parser, Subcommand};
+use checkpoint::{Checkpoint, CheckpointStore, FileChangeKind};
+use config::{AgentProfile, ConfigManager, ProviderConfig, ProviderType, ToolLimits};
 use dialoguer::{Confirm, Input, Select};
+use executor::approval::{ApprovalPolicy, Approver, TerminalApprover};
+use executor::review::TerminalReviewer;
 use executor::tools::create_tool_system_prompt;
 use memory::MemorySystem;
 use sandbox::SandboxManager;
-use providers::{create_provider, list_available_providers, Message, Role, create_provider_arc};
+use session::{Session, SessionStore, SessionSummary};
+use providers::{create_provider, list_available_providers, ContentPart, Message, Provider};
+use providers::fallback::{self, ServedLog};
+use providers::model_capabilities::ModelRegistry;
+use hierarchy::TaskCategory;
+use router::ModelRouter;
+use ledger::{Meter, MeteredProvider};
 use crate::mcp::get_builtin_server_configs;
 use swarm::SwarmOrchestrator;
 use std::env;
-use std::io::{self, BufRead, Write};
+use std::io::{self, BufRead, IsTerminal, Write};
 use std::sync::Arc;
 
 // ============================================================================
@@ -45,10 +59,16 @@ struct Cli {
 
 #[derive(Subcommand)]
 enum Commands {
-    /// Send a chat message (non-interactive)
+    /// Send a chat message (non-interactive). With --json, progress events are
+    /// printed as NDJSON lines, followed by the final result envelope.
     Chat {
         /// The message to send
         message: String,
+        /// Attach an image (PNG, JPEG, GIF or WebP); repeat for several
+        #[arg(long = "image", value_name = "PATH")]
+        images: Vec<std::path::PathBuf>,
+        #[command(flatten)]
+        limits: AgentLimitArgs,
     },
     /// Scan the current project repository
     Scan {
@@ -84,6 +104,55 @@ enum Commands {
         #[command(subcommand)]
         action: HierarchyAction,
     },
+    /// Manage saved REPL sessions
+    Sessions {
+        #[command(subcommand)]
+        action: SessionsAction,
+    },
+    /// Resume a saved REPL session
+    Resume {
+        /// Session ID (or a unique prefix of it)
+        id: String,
+    },
+    /// Run a headless agent server for editors and other tools, reading JSON
+    /// requests and writing NDJSON events
+    Serve {
+        /// Speak the protocol over stdin/stdout
+        #[arg(long)]
+        stdio: bool,
+        /// Don't record agent turns as undo checkpoints
+        #[arg(long)]
+        no_checkpoints: bool,
+        #[command(flatten)]
+        limits: AgentLimitArgs,
+    },
+    /// Undo the agent's file changes in the current project
+    Undo {
+        /// Restore the state before this checkpoint, undoing it and every
+        /// later one (defaults to the most recent checkpoint)
+        #[arg(long = "to", value_name = "N")]
+        to: Option<usize>,
+        /// List checkpoints instead of undoing anything
+        #[arg(long, conflicts_with = "to")]
+        list: bool,
+    },
+    /// Show spending recorded in the cost ledger
+    Usage {
+        /// Roll up by week instead of by day
+        #[arg(long)]
+        weekly: bool,
+        /// Also group by provider, model, project, session or category
+        #[arg(long, value_name = "KEY")]
+        by: Option<String>,
+        /// How many days back to include
+        #[arg(long, default_value = "30")]
+        days: i64,
+    },
+    /// Inspect or refresh the model capability registry
+    Models {
+        #[command(subcommand)]
+        action: ModelsAction,
+    },
     /// OAuth authentication flow (PKCE)
     #[command(name = "oauth")]
     OAuth {
@@ -92,11 +161,72 @@ enum Commands {
     },
 }
 
+/// Per-invocation overrides for the agent profile
+#[derive(Args, Debug, Default)]
+struct AgentLimitArgs {
+    /// Maximum tool-calling turns
+    #[arg(long)]
+    max_turns: Option<usize>,
+    /// Cap on cumulative prompt tokens
+    #[arg(long)]
+    max_input_tokens: Option<u32>,
+    /// Cap on cumulative prompt + completion tokens
+    #[arg(long)]
+    max_total_tokens: Option<u32>,
+    /// Sampling temperature
+    #[arg(long)]
+    temperature: Option<f32>,
+    /// Wall-clock limit for the whole task, in seconds
+    #[arg(long = "timeout", value_name = "SECS")]
+    timeout_secs: Option<u64>,
+    /// Limit calls to one tool, e.g. --tool-limit execute_command=10 (repeatable)
+    #[arg(long = "tool-limit", value_name = "TOOL=N", value_parser = parse_tool_limit)]
+    tool_limits: Vec<(String, usize)>,
+}
+
+impl AgentLimitArgs {
+    fn to_profile(&self) -> AgentProfile {
+        AgentProfile {
+            max_turns: self.max_turns,
+            max_input_tokens: self.max_input_tokens,
+            max_total_tokens: self.max_total_tokens,
+            temperature: self.temperature,
+            timeout_secs: self.timeout_secs,
+            tools: self.tool_limits.iter()
+                .map(|(name, max)| (name.clone(), ToolLimits { max_calls: Some(*max), timeout_secs: None }))
+                .collect(),
+        }
+    }
+}
+
+fn parse_tool_limit(value: &str) -> std::result::Result<(String, usize), String> {
+    let (name, max) = value.split_once('=')
+        .ok_or_else(|| format!("expected TOOL=N, got '{}'", value))?;
+    let max = max.parse::<usize>()
+        .map_err(|_| format!("invalid call limit '{}'", max))?;
+    Ok((name.to_string(), max))
+}
+
+#[derive(Subcommand)]
+enum SessionsAction {
+    /// List saved sessions, most recent first
+    List,
+}
+
+#[derive(Subcommand)]
+enum ModelsAction {
+    /// List known models with their merged capabilities
+    List,
+    /// Pull model listings from every configured provider into the registry
+    Refresh,
+}
+
 #[derive(Subcommand)]
 enum HierarchyAction {
     /// Show current hierarchy configuration
     Show,
-    /// Set hierarchy from preset (balanced, budget, premium, speed, claude-only)
+    /// Set hierarchy from preset (balanced, budget, premium, speed, claude-only),
+    /// or "ranked" to build it from the model capability registry
     SetPreset {
         preset: String,
     },
@@ -119,7 +249,7 @@ enum HierarchyAction {
         /// Max escalation steps
         #[arg(long)]
         max_escalations: Option<usize>,
-        /// Daily budget limit in USD
+        /// Daily budget limit in USD (0 disables it)
         #[arg(long)]
         budget_limit: Option<f64>,
     },
@@ -228,16 +358,89 @@ fn json_output(success: bool, data: serde_json::Value, error: Option<&str>) -> S
     }).to_string()
 }
 
+/// Render streaming agent events as they arrive.
+///
+/// In JSON mode each event is printed as one NDJSON line. Otherwise model text is
+/// streamed to stdout, and tool activity and usage go to stderr so they stay out of
+/// piped output. Resolves to the text streamed since the last tool call, so callers
+/// can tell whether the final answer has already been shown.
+fn spawn_event_renderer(
+    json_mode: bool,
+) -> (tokio::sync::mpsc::Sender<agent::AgentEvent>, tokio::task::JoinHandle<String>) {
+    use agent::AgentEvent;
+
+    let (tx, mut rx) = tokio::sync::mpsc::channel::<AgentEvent>(256);
+    let handle = tokio::spawn(async move {
+        let mut streamed = String::new();
+        while let Some(event) = rx.recv().await {
+            if json_mode {
+                if let Ok(line) = serde_json::to_string(&event) {
+                    println!("{}", line);
+                }
+                if let AgentEvent::TextDelta { text } = &event {
+                    streamed.push_str(text);
+                }
+                continue;
+            }
+
+            match event {
+                AgentEvent::TextDelta { text } => {
+                    print!("{}", text);
+                    let _ = io::stdout().flush();
+                    streamed.push_str(&text);
+                }
+                AgentEvent::ToolCallStarted { name, arguments, .. } => {
+                    if !streamed.is_empty() && !streamed.ends_with('\n') {
+                        println!();
+                    }
+                    streamed.clear();
+                    let args = arguments.to_string();
+                    let args = if args.chars().count() > 80 {
+                        format!("{}...", args.chars().take(80).collect::<String>())
+                    } else {
+                        args
+                    };
+                    eprintln!("  → {} {}", name, args);
+                }
+                AgentEvent::ToolCallFinished { name, success, error, .. } => {
+                    if success {
+                        eprintln!("  ✓ {}", name);
+                    } else {
+                        eprintln!("  ✗ {}: {}", name, error.unwrap_or_else(|| "failed".to_string()));
+                    }
+                }
+                AgentEvent::Compacted { tokens_before, tokens_after, .. } => {
+                    eprintln!("  [compacted context: {} → {} tokens]", tokens_before, tokens_after);
+                }
+                // The reviewer and approver show these when they ask
+                AgentEvent::FileDiff(_) | AgentEvent::ApprovalRequested { .. } => {}
+                AgentEvent::Usage(usage) => {
+                    if !streamed.is_empty() && !streamed.ends_with('\n') {
+                        println!();
+                        streamed.push('\n');
+                    }
+                    eprintln!("  [{} prompt + {} completion tokens]", usage.prompt_tokens, usage.completion_tokens);
+                }
+            }
+        }
+        streamed
+    });
+
+    (tx, handle)
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
     // Check for --json flag before initializing logging
     let json_mode = std::env::args().any(|arg| arg == "--json");
+    // stdout carries protocol events in `serve --stdio`
+    let stdio_mode = std::env::args().any(|arg| arg == "--stdio");
 
     // Initialize structured logging
     let env_filter = tracing_subscriber::EnvFilter::try_from_default_env()
         .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("nexus=info"));
 
-    if json_mode {
+    if json_mode || stdio_mode {
         // In JSON mode: send logs to stderr with no ANSI colors
         tracing_subscriber::fmt()
             .with_env_filter(env_filter)
@@ -268,7 +471,7 @@ async fn main() -> Result<()> {
     }
 
     // No subcommand → fall through to existing REPL
-    run_repl().await
+    run_repl(None).await
 }
 
 // ============================================================================
@@ -758,7 +961,7 @@ async fn run_command(command: Commands, json_mode: bool) -> Result<()> {
                                     description: None,
                                     context_length: None,
                                     pricing: None,
-                                    supports_vision: false,
+                                    supports_vision: None,
                                     supports_streaming: false,
                                     supports_function_calling: false,
                                 })
@@ -846,17 +1049,14 @@ async fn run_command(command: Commands, json_mode: bool) -> Result<()> {
                     let request = providers::CompletionRequest {
                         model: model.clone(),
                         messages: vec![
-                            Message {
-                                role: Role::User,
-                                content: "Say hello in one word".to_string(),
-                                name: None,
-                            },
+                            Message::user("Say hello in one word"),
                         ],
                         temperature: Some(0.0),
                         max_tokens: Some(10),
                         stream: Some(false),
                 tools: None,
                         extra_params: None,
+                        cache: None,
                     };
 
                     match prov.complete(request).await {
@@ -1089,7 +1289,20 @@ async fn run_command(command: Commands, json_mode: bool) -> Result<()> {
                         println!("Running proactive tasks...");
                     }
 
-                    match daemon::run_proactive_tasks().await {
+                    // The heartbeat check runs on the hierarchy's heartbeat tier
+                    let config_manager = ConfigManager::new()?;
+                    let heartbeat = match config_manager.get().default_provider.clone() {
+                        Some(name) => match ModelRouter::from_config(&config_manager, &name).await {
+                            Ok(router) => Some(Arc::new(router).provider(Some(TaskCategory::Heartbeat))),
+                            Err(e) => {
+                                eprintln!("Skipping model heartbeat: {}", e);
+                                None
+                            }
+                        },
+                        None => None,
+                    };
+
+                    match daemon::run_proactive_tasks(heartbeat).await {
                         Ok(()) => {
                             if json_mode {
                                 println!("{}", json_output(true, serde_json::json!({
@@ -1122,9 +1335,7 @@ async fn run_command(command: Commands, json_mode: bool) -> Result<()> {
         Commands::Hierarchy { action } => {
             use hierarchy::{ModelHierarchy, EscalationPolicy, TaskCategory};
 
-            let config_dir = std::env::var("HOME")
-                .map(|h| std::path::PathBuf::from(h).join(".config/nexus"))
-                .unwrap_or_else(|_| std::path::PathBuf::from("~/.config/nexus"));
+            let config_dir = hierarchy::config_dir();
 
             match action {
                 HierarchyAction::Show => {
@@ -1141,8 +1352,17 @@ async fn run_command(command: Commands, json_mode: bool) -> Result<()> {
                     }
                 }
                 HierarchyAction::SetPreset { preset } => {
-                    let hierarchy = ModelHierarchy::from_preset(&preset)
-                        .ok_or_else(|| anyhow::anyhow!("Unknown preset: '{}'. Valid: balanced, budget, premium, speed, claude-only", preset))?;
+                    let hierarchy = if preset == "ranked" {
+                        let registry = ModelRegistry::current();
+                        let known: Vec<String> = registry.all().into_iter()
+                            .filter(|m| m.context_window > 0)
+                            .map(|m| m.id.clone())
+                            .collect();
+                        ModelHierarchy::ranked(&registry, &known, 3)
+                    } else {
+                        ModelHierarchy::from_preset(&preset)
+                            .ok_or_else(|| anyhow::anyhow!("Unknown preset: '{}'. Valid: balanced, budget, premium, speed, claude-only, ranked", preset))?
+                    };
 
                     hierarchy.save(&config_dir)?;
 
@@ -1174,6 +1394,7 @@ async fn run_command(command: Commands, json_mode: bool) -> Result<()> {
                             model_id: "".to_string(),
                             max_tokens: None,
                             max_cost_per_request: None,
+                            provider: None,
                         });
                     }
 
@@ -1227,6 +1448,84 @@ async fn run_command(command: Commands, json_mode: bool) -> Result<()> {
                 }
             }
         }
+        Commands::Models { action } => {
+            let config_dir = hierarchy::config_dir();
+            match action {
+                ModelsAction::List => {
+                    let registry = ModelRegistry::current();
+                    if json_mode {
+                        println!("{}", json_output(true, serde_json::json!({
+                            "refreshed_at": registry.refreshed_at(),
+                            "models": registry.all(),
+                        }), None));
+                    } else {
+                        match registry.refreshed_at() {
+                            Some(at) => println!("Model registry (refreshed {}):", at.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M")),
+                            None => println!("Model registry (built-in table, run `nexus models refresh` to update):"),
+                        }
+                        for model in registry.all() {
+                            let context = if model.context_window > 0 {
+                                format!("{}k", model.context_window / 1000)
+                            } else {
+                                "?".to_string()
+                            };
+                            let mut features = Vec::new();
+                            if model.supports_tools { features.push("tools"); }
+                            if model.supports_vision == Some(true) { features.push("vision"); }
+                            println!("  {:<40} {:<12} ctx {:>6}  ${:>7.2}/1M  {}",
+                                model.id, model.provider, context, model.cost_per_1m_tokens, features.join(","));
+                        }
+                    }
+                }
+                ModelsAction::Refresh => {
+                    let config_manager = ConfigManager::new()?;
+                    let mut registry = ModelRegistry::load(&config_dir)?;
+                    let mut names: Vec<String> = config_manager.list_providers().into_iter().cloned().collect();
+                    names.sort();
+
+                    let mut results = Vec::new();
+                    for name in names {
+                        let listing = async {
+                            let config = config_manager.get_provider_resolved(&name)?
+                                .ok_or_else(|| anyhow::anyhow!("Provider '{}' not found", name))?;
+                            let mut provider = create_provider(&config.provider_type, &config)?;
+                            if !provider.is_authenticated() {
+                                provider.authenticate().await?;
+                            }
+                            let models = provider.list_available_models().await?;
+                            Ok::<_, anyhow::Error>((provider.info().name, models))
+                        }.await;
+                        match listing {
+                            Ok((kind, models)) => {
+                                registry.merge_listing(&kind, &models);
+                                if !json_mode {
+                                    println!("✓ {}: {} models", name, models.len());
+                                }
+                                results.push(serde_json::json!({ "provider": name, "models": models.len() }));
+                            }
+                            Err(e) => {
+                                if !json_mode {
+                                    eprintln!("✗ {}: {}", name, e);
+                                }
+                                results.push(serde_json::json!({ "provider": name, "error": e.to_string() }));
+                            }
+                        }
+                    }
+
+                    registry.save(&config_dir)?;
+                    let total = registry.all().len();
+                    ModelRegistry::set_current(registry);
+                    if json_mode {
+                        println!("{}", json_output(true, serde_json::json!({
+                            "providers": results,
+                            "known_models": total,
+                        }), None));
+                    } else {
+                        println!("Registry now knows {} models", total);
+                    }
+                }
+            }
+        }
         Commands::OAuth { action } => {
             match action {
                 OAuthAction::GetUrl { provider } => {
@@ -1396,7 +1695,93 @@ async fn run_command(command: Commands, json_mode: bool) -> Result<()> {
                 }
             }
         }
-        Commands::Chat { message } => {
+        Commands::Sessions { action } => {
+            match action {
+                SessionsAction::List => {
+                    let sessions = SessionStore::new()?.list()?;
+                    if json_mode {
+                        println!("{}", json_output(true, serde_json::to_value(&sessions)?, None));
+                    } else {
+                        print_sessions(&sessions);
+                    }
+                }
+            }
+        }
+        Commands::Resume { id } => {
+            run_repl(Some(id)).await?;
+        }
+        Commands::Serve { stdio, no_checkpoints, limits } => {
+            if !stdio {
+                anyhow::bail!("Only the stdio transport is supported; run `nexus serve --stdio`");
+            }
+            let config_manager = ConfigManager::new()?;
+            let provider_name = config_manager.get().default_provider.clone()
+                .ok_or_else(|| anyhow::anyhow!("No default provider configured. Run 'nexus' interactively first to set up."))?;
+            let provider_config = config_manager.get_provider_resolved(&provider_name)?
+                .ok_or_else(|| anyhow::anyhow!("Provider not found: {}", provider_name))?;
+            let chain = fallback::create_chain(&config_manager, &provider_name, None)?;
+            let provider: Arc<dyn Provider + Send + Sync> =
+                Arc::new(MeteredProvider::new(&provider_name, chain, Arc::new(Meter::from_config()?)));
+            let model = provider_config.default_model
+                .unwrap_or_else(|| provider.info().default_model.clone());
+
+            let memory_path = std::env::var("HOME")
+                .map(|h| std::path::PathBuf::from(h).join(".config/nexus/memory"))
+                .unwrap_or_else(|_| std::path::PathBuf::from("~/.config/nexus/memory"));
+            let mut server = headless::HeadlessServer::new(provider, model)
+                .with_profile(config_manager.get().agent.merged_with(&limits.to_profile()))
+                .with_confirm_dangerous(config_manager.get().ui.confirm_dangerous_commands)
+                .with_checkpoints(!no_checkpoints);
+            if let Ok(memory) = MemorySystem::new(memory_path) {
+                server = server.with_memory(Arc::new(tokio::sync::RwLock::new(memory)));
+            }
+            server.run(tokio::io::BufReader::new(tokio::io::stdin()), tokio::io::stdout()).await?;
+        }
+        Commands::Undo { to, list } => {
+            let store = CheckpointStore::new(&std::env::current_dir()?)?;
+            if list {
+                let checkpoints = store.list()?;
+                if json_mode {
+                    println!("{}", json_output(true, serde_json::to_value(&checkpoints)?, None));
+                } else {
+                    print_checkpoints(&checkpoints);
+                }
+                return Ok(());
+            }
+
+            let undone = match to {
+                Some(id) => store.restore(id)?,
+                None => store.undo()?.into_iter().collect(),
+            };
+            if json_mode {
+                println!("{}", json_output(true, serde_json::to_value(&undone)?, None));
+            } else if undone.is_empty() {
+                println!("Nothing to undo.");
+            } else {
+                print_undone(&undone);
+            }
+        }
+        Commands::Usage { weekly, by, days } => {
+            let group = match by.as_deref() {
+                Some(key) => Some(key.parse::<ledger::GroupBy>().map_err(anyhow::Error::msg)?),
+                None => None,
+            };
+            let cost_ledger = ledger::CostLedger::open(ledger::CostLedger::default_path());
+            let since = chrono::Utc::now() - chrono::Duration::days(days);
+            let rows = ledger::rollup(&cost_ledger.entries()?, weekly, group, since);
+            let spent_today = cost_ledger.spent_on(chrono::Local::now().date_naive())?;
+            let daily_budget = hierarchy::EscalationPolicy::load(&hierarchy::config_dir())?.daily_budget_limit;
+            if json_mode {
+                println!("{}", json_output(true, serde_json::json!({
+                    "rows": rows,
+                    "spent_today": spent_today,
+                    "daily_budget": daily_budget,
+                }), None));
+            } else {
+                print_usage(&rows, spent_today, daily_budget);
+            }
+        }
+        Commands::Chat { message, images, limits } => {
             // Non-interactive chat requires a configured provider
             let config_manager = ConfigManager::new()?;
             let provider_name = config_manager.get().default_provider.clone()
@@ -1406,7 +1791,8 @@ async fn run_command(command: Commands, json_mode: bool) -> Result<()> {
                 .ok_or_else(|| anyhow::anyhow!("Provider not found: {}", provider_name))?
                 .clone();
 
-            let mut provider = create_provider(&provider_config.provider_type, &provider_config)?;
+            let chain = fallback::create_chain(&config_manager, &provider_name, None)?;
+            let mut provider = MeteredProvider::new(&provider_name, chain, Arc::new(Meter::from_config()?));
 
             if !provider.is_authenticated() {
                 provider.authenticate().await?;
@@ -1415,12 +1801,17 @@ async fn run_command(command: Commands, json_mode: bool) -> Result<()> {
             let model = provider_config.default_model
                 .unwrap_or_else(|| provider.info().default_model.clone());
 
+            let images = images.iter()
+                .map(|path| ContentPart::image_file(path))
+                .collect::<std::result::Result<Vec<_>, _>>()?;
+
             // Load memory context
             let memory_path = std::env::var("HOME")
                 .map(|h| std::path::PathBuf::from(h).join(".config/nexus/memory"))
                 .unwrap_or_else(|_| std::path::PathBuf::from("~/.config/nexus/memory"));
 
-            let memory_context = if let Ok(mem) = MemorySystem::new(memory_path.clone()) {
+            let memory = MemorySystem::new(memory_path.clone()).ok();
+            let memory_context = if let Some(mem) = &memory {
                 match mem.get_context_for_query(&message).await {
                     Ok(context) => format!("\n\n{}", context.format_for_llm()),
                     Err(_) => String::new(),
@@ -1436,27 +1827,40 @@ async fn run_command(command: Commands, json_mode: bool) -> Result<()> {
             };
 
             let mut messages = vec![
-                Message {
-                    role: Role::System,
-                    content: system_prompt,
-                    name: None,
-                },
-                Message {
-                    role: Role::User,
-                    content: message.clone(),
-                    name: None,
-                },
+                Message::system(system_prompt),
+                Message::user_with_images(message.clone(), images),
             ];
 
-            let agent = agent::Agent::new(std::env::current_dir()?)?;
-            match agent.run_task(&mut messages, &*provider, model).await {
+            let profile = config_manager.agent_profile(&std::env::current_dir()?)?
+                .merged_with(&limits.to_profile());
+            // Only prompt when someone is at the terminal to answer
+            let interactive = !json_mode && io::stdin().is_terminal();
+            let approver: Option<Arc<dyn Approver>> = interactive.then(|| Arc::new(TerminalApprover) as Arc<dyn Approver>);
+            let policy = ApprovalPolicy::load(&std::env::current_dir()?, config_manager.get().ui.confirm_dangerous_commands)?;
+            let mut agent = agent::Agent::new(std::env::current_dir()?)?
+                .with_profile(profile)
+                .with_approval(policy, approver)
+                .with_checkpoints(Arc::new(CheckpointStore::new(&std::env::current_dir()?)?));
+            if interactive && config_manager.get().ui.show_diff_preview {
+                agent = agent.with_reviewer(Arc::new(TerminalReviewer));
+            }
+            if let Some(memory) = memory {
+                agent = agent.with_memory(Arc::new(tokio::sync::RwLock::new(memory)));
+            }
+            let (events, renderer) = spawn_event_renderer(json_mode);
+            let result = agent.run_task_streaming(&mut messages, &provider, model, events).await;
+            let streamed = renderer.await.unwrap_or_default();
+
+            match result {
                 Ok(response) => {
                     if json_mode {
                         println!("{}", json_output(true, serde_json::json!({
                             "response": response,
                         }), None));
-                    } else {
+                    } else if streamed.trim() != response.trim() {
                         println!("{}", response);
+                    } else if !streamed.ends_with('\n') {
+                        println!();
                     }
                 }
                 Err(e) => {
@@ -1478,7 +1882,7 @@ async fn run_command(command: Commands, json_mode: bool) -> Result<()> {
 // Interactive REPL (existing functionality, unchanged)
 // ============================================================================
 
-async fn run_repl() -> Result<()> {
+async fn run_repl(resume: Option<String>) -> Result<()> {
     let mut config_manager = ConfigManager::new()?;
 
     // Check if we have a default provider configured
@@ -1489,13 +1893,22 @@ async fn run_repl() -> Result<()> {
         setup_wizard(&mut config_manager).await?;
     }
 
-    // Let user select a provider
-    let provider_name = select_provider(&config_manager)?;
-    let provider_config = config_manager.get_provider(&provider_name)
-        .ok_or_else(|| anyhow::anyhow!("Provider not found: {}", provider_name))?
-        .clone();
+    let session_store = SessionStore::new()?;
+    let checkpoints = Arc::new(CheckpointStore::new(&std::env::current_dir()?)?);
+    let resumed = match resume {
+        Some(id) => Some(session_store.load(&id)?),
+        None => None,
+    };
 
-    let mut provider = create_provider(&provider_config.provider_type, &provider_config)?;
+    // A resumed session keeps its provider if it is still configured
+    let provider_name = match resumed.as_ref().filter(|s| config_manager.get_provider(&s.provider).is_some()) {
+        Some(session) => session.provider.clone(),
+        None => select_provider(&config_manager)?,
+    };
+    let served_log = ServedLog::default();
+    let chain = fallback::create_chain(&config_manager, &provider_name, Some(served_log.clone()))?;
+    let meter = Arc::new(Meter::from_config()?);
+    let mut provider = MeteredProvider::new(&provider_name, chain, meter.clone());
 
     // Authenticate if needed
     if !provider.is_authenticated() {
@@ -1515,12 +1928,6 @@ async fn run_repl() -> Result<()> {
         MemorySystem::new(memory_path)?
     ));
 
-    // Create provider arc for watcher
-    let provider_arc = create_provider_arc(
-        &provider_config.provider_type,
-        &provider_config,
-    )?;
-
     let model = config_manager.get()
         .providers.get(&provider_name)
         .and_then(|p| p.default_model.clone())
@@ -1534,13 +1941,21 @@ async fn run_repl() -> Result<()> {
 
     // REPL loop
     let stdin = io::stdin();
-    let mut messages: Vec<Message> = vec![
-        Message {
-            role: Role::System,
-            content: create_tool_system_prompt(),
-            name: None,
+    let mut session = match resumed {
+        Some(session) => {
+            println!("Resumed session {} ({} turns)\n", session.id, session.turn_count());
+            session
         }
-    ];
+        None => Session::new(
+            &provider_name,
+            &model,
+            std::env::current_dir()?,
+            vec![Message::system(create_tool_system_prompt())],
+        ),
+    };
+
+    // Images queued by /attach for the next message
+    let mut attachments: Vec<(String, ContentPart)> = Vec::new();
 
     loop {
         print!("nexus> ");
@@ -1741,29 +2156,24 @@ async fn run_repl() -> Result<()> {
                 println!("\n[SWARM] Initializing parallel agent execution...");
                 println!("Task: {}", task);
 
-                // Get the model for swarm execution
-                let info = provider.info();
-                let model = config_manager.get()
-                    .providers.get(&provider_name)
-                    .and_then(|p| p.default_model.clone())
-                    .unwrap_or(info.default_model.clone());
-
-                // Create swarm config with default settings
-                let swarm_config = swarm::SwarmConfig::default();
+                // Create swarm config, with worker limits from the agent profile
+                let agent_profile = config_manager.agent_profile(&working_dir)?;
+                let mut swarm_config = swarm::SwarmConfig::default();
+                if let Some(timeout_secs) = agent_profile.timeout_secs {
+                    swarm_config.task_timeout_secs = timeout_secs;
+                }
+                swarm_config.agent_profile = agent_profile;
 
                 // Create orchestrator with Arc-wrapped provider for swarm
-                let provider_config = config_manager.get_provider(&provider_name)
-                    .ok_or_else(|| anyhow::anyhow!("Provider not found: {}", provider_name))?
-                    .clone();
-                let provider_arc = providers::create_provider_arc(
-                    &provider_config.provider_type,
-                    &provider_config,
-                )?;
-                let orchestrator = swarm::SwarmOrchestrator::new(
-                    swarm_config,
-                    provider_arc,
-                    model.clone(),
-                )?;
+                // Each swarm role runs on its tier of the model hierarchy
+                let router = match ModelRouter::from_config(&config_manager, &provider_name).await {
+                    Ok(router) => Arc::new(router),
+                    Err(e) => {
+                        eprintln!("✗ Failed to set up model routing: {}", e);
+                        continue;
+                    }
+                };
+                let orchestrator = swarm::SwarmOrchestrator::routed(swarm_config, router)?;
 
                 // Create swarm task
                 let swarm_task = swarm::SwarmTask::new(&task, &working_dir);
@@ -1794,13 +2204,22 @@ async fn run_repl() -> Result<()> {
 
             "/watch start" => {
                 if watcher_engine.is_none() {
+                    // The healer writes fixes on the hierarchy's coding tiers
+                    let router = match ModelRouter::from_config(&config_manager, &provider_name).await {
+                        Ok(router) => Arc::new(router),
+                        Err(e) => {
+                            eprintln!("✗ Failed to set up model routing: {}", e);
+                            continue;
+                        }
+                    };
                     match watcher::WatcherEngine::new(
                         watcher::WatcherEngineConfig::default(),
                         memory.clone(),
-                        provider_arc.clone(),
-                        model.clone(),
+                        router.provider(Some(TaskCategory::Coding)),
+                        router.model_for(TaskCategory::Coding).to_string(),
                     ).await {
-                        Ok(mut engine) => {
+                        Ok(engine) => {
+                            let mut engine = engine.with_router(router);
                             if let Err(e) = engine.start().await {
                                 eprintln!("✗ Failed to start watcher: {}", e);
                             } else {
@@ -1976,28 +2395,191 @@ async fn run_repl() -> Result<()> {
                 continue;
             }
 
+            "/sessions" => {
+                match session_store.list() {
+                    Ok(sessions) => print_sessions(&sessions),
+                    Err(e) => eprintln!("✗ Failed to list sessions: {}", e),
+                }
+                continue;
+            }
+
+            "/resume" => {
+                let sessions = match session_store.list() {
+                    Ok(sessions) => sessions,
+                    Err(e) => {
+                        eprintln!("✗ Failed to list sessions: {}", e);
+                        continue;
+                    }
+                };
+                let sessions: Vec<&SessionSummary> = sessions.iter().filter(|s| s.id != session.id).take(20).collect();
+                if sessions.is_empty() {
+                    println!("No other saved sessions.");
+                    continue;
+                }
+                let items: Vec<String> = sessions.iter()
+                    .map(|s| format!("{}  {}  {} turns  {}", &s.id[..8.min(s.id.len())], s.updated_at.format("%Y-%m-%d %H:%M"), s.turns, s.title))
+                    .collect();
+                let selection = Select::new()
+                    .with_prompt("Select a session to resume")
+                    .items(&items)
+                    .default(0)
+                    .interact()?;
+                match session_store.load(&sessions[selection].id) {
+                    Ok(loaded) => switch_session(&mut session, loaded, &provider_name),
+                    Err(e) => eprintln!("✗ Failed to load session: {}", e),
+                }
+                continue;
+            }
+
+            cmd if cmd.starts_with("/resume ") => {
+                let id = cmd.trim_start_matches("/resume ").trim();
+                match session_store.load(id) {
+                    Ok(loaded) => switch_session(&mut session, loaded, &provider_name),
+                    Err(e) => eprintln!("✗ Failed to load session: {}", e),
+                }
+                continue;
+            }
+
+            cmd if cmd == "/fork" || cmd.starts_with("/fork ") => {
+                let arg = cmd.trim_start_matches("/fork").trim();
+                let turns = if arg.is_empty() {
+                    None
+                } else {
+                    match arg.parse::<usize>() {
+                        Ok(n) => Some(n),
+                        Err(_) => {
+                            println!("Usage: /fork [turn]");
+                            continue;
+                        }
+                    }
+                };
+                match session.fork(turns) {
+                    Ok(mut forked) => {
+                        session_store.save(&mut forked)?;
+                        println!("✓ Forked session {} at turn {} → {}", session.id, forked.turn_count(), forked.id);
+                        session = forked;
+                    }
+                    Err(e) => eprintln!("✗ {}", e),
+                }
+                continue;
+            }
+
+            "/checkpoints" => {
+                match checkpoints.list() {
+                    Ok(list) => print_checkpoints(&list),
+                    Err(e) => eprintln!("✗ Failed to list checkpoints: {}", e),
+                }
+                continue;
+            }
+
+            "/undo" => {
+                match checkpoints.undo() {
+                    Ok(Some(undone)) => print_undone(&[undone]),
+                    Ok(None) => println!("Nothing to undo."),
+                    Err(e) => eprintln!("✗ Undo failed: {}", e),
+                }
+                continue;
+            }
+
+            cmd if cmd == "/restore" || cmd.starts_with("/restore ") => {
+                let Ok(id) = cmd.trim_start_matches("/restore").trim().parse::<usize>() else {
+                    println!("Usage: /restore <checkpoint>  (see /checkpoints)");
+                    continue;
+                };
+                match checkpoints.restore(id) {
+                    Ok(undone) => print_undone(&undone),
+                    Err(e) => eprintln!("✗ Restore failed: {}", e),
+                }
+                continue;
+            }
+
+            "/attach" => {
+                if attachments.is_empty() {
+                    println!("Usage: /attach <image>  (/attach clear drops queued images)");
+                } else {
+                    println!("Attached to your next message:");
+                    for (path, _) in &attachments {
+                        println!("  {}", path);
+                    }
+                }
+                continue;
+            }
+            "/attach clear" => {
+                attachments.clear();
+                println!("✓ Attachments cleared");
+                continue;
+            }
+            cmd if cmd.starts_with("/attach ") => {
+                let path = cmd.trim_start_matches("/attach ").trim();
+                let model = repl_model(&config_manager, &provider_name, &provider);
+                if !ModelRegistry::current().accepts_images(&model) {
+                    eprintln!("✗ {}", error::NexusError::VisionUnsupported { model });
+                    continue;
+                }
+                match ContentPart::image_file(std::path::Path::new(path)) {
+                    Ok(image) => {
+                        println!("✓ Attached {} to your next message", path);
+                        attachments.push((path.to_string(), image));
+                    }
+                    Err(e) => eprintln!("✗ {}", e),
+                }
+                continue;
+            }
+
             "" => continue,
             _ => {}
         }
 
-        // Add user message
-        messages.push(Message {
-            role: Role::User,
-            content: input.to_string(),
-            name: None,
-        });
+        // The model may have changed since the images were attached
+        let model = repl_model(&config_manager, &provider_name, &provider);
+        if !attachments.is_empty() && !ModelRegistry::current().accepts_images(&model) {
+            eprintln!("✗ {}", error::NexusError::VisionUnsupported { model });
+            continue;
+        }
+        let images = std::mem::take(&mut attachments).into_iter().map(|(_, image)| image).collect();
+
+        // Add user message and save before running, so a crash keeps the prompt
+        session.push_user_turn(Message::user_with_images(input.to_string(), images));
+        session_store.save(&mut session)?;
 
         // Create agent and run the task
-        let agent = agent::Agent::new(std::env::current_dir()?)?;
-        let info = provider.info();
-        let model = config_manager.get()
-            .providers.get(&provider_name)
-            .and_then(|p| p.default_model.clone())
-            .unwrap_or(info.default_model.clone());
-
-        match agent.run_task(&mut messages, &*provider, model).await {
+        let profile = config_manager.agent_profile(&std::env::current_dir()?)?;
+        let policy = ApprovalPolicy::load(&std::env::current_dir()?, config_manager.get().ui.confirm_dangerous_commands)?;
+        let mut agent = agent::Agent::new(std::env::current_dir()?)?
+            .with_memory(memory.clone())
+            .with_profile(profile)
+            .with_approval(policy, Some(Arc::new(TerminalApprover)))
+            .with_checkpoints(checkpoints.clone());
+        if config_manager.get().ui.show_diff_preview {
+            agent = agent.with_reviewer(Arc::new(TerminalReviewer));
+        }
+
+        println!();
+        let (events, renderer) = spawn_event_renderer(false);
+        session.model = model.clone();
+        meter.set_session(&session.id);
+        let result = agent.run_task_streaming(&mut session.messages, &provider, model, events).await;
+        let streamed = renderer.await.unwrap_or_default();
+        // Compaction may have rewritten the history under the turn markers
+        session.reindex_turns();
+
+        let served = std::mem::take(&mut *served_log.lock().unwrap_or_else(|e| e.into_inner()));
+        if let Some(last) = served.last().filter(|last| !last.skipped.is_empty()) {
+            println!("\n(answered by fallback provider {} with {})", last.provider, last.model);
+        }
+        session.served.extend(served);
+
+        if let Err(e) = session_store.save(&mut session) {
+            eprintln!("✗ Failed to save session: {}", e);
+        }
+
+        match result {
             Ok(final_response) => {
-                println!("\n{}", final_response);
+                if streamed.trim() != final_response.trim() {
+                    println!("\n{}", final_response);
+                } else if !streamed.ends_with('\n') {
+                    println!();
+                }
             }
             Err(e) => {
                 eprintln!("Error: {}", e);
@@ -2023,10 +2605,14 @@ async fn setup_wizard(config_manager: &mut ConfigManager) -> Result<()> {
         .interact()?;
 
     let selected = &available_providers[selection];
-    let provider_name = selected.name.clone();
+    let mut provider_name = selected.name.clone();
 
     // Get provider-specific configuration
-    let provider_config = if selected.supports_oauth {
+    let provider_config = if selected.name == "openai_compatible" {
+        let (name, config) = configure_openai_compatible_provider().await?;
+        provider_name = name;
+        config
+    } else if selected.supports_oauth {
         configure_oauth_provider(&selected.name).await?
     } else {
         configure_api_key_provider(&selected.name).await?
@@ -2070,9 +2656,52 @@ async fn configure_api_key_provider(name: &str) -> Result<ProviderConfig> {
         base_url: None,
         default_model: None,
         timeout_secs: Some(60),
+        cassette: None,
+        headers: Default::default(),
+        models: Vec::new(),
+        fallback: Vec::new(),
     })
 }
 
+/// Ask for an OpenAI-compatible endpoint; returns the name to save it under
+async fn configure_openai_compatible_provider() -> Result<(String, ProviderConfig)> {
+    let name: String = Input::new()
+        .with_prompt("Name for this provider (e.g. openai, ollama, vllm)")
+        .default("openai".to_string())
+        .interact_text()?;
+    let base_url: String = Input::new()
+        .with_prompt("Base URL (Ollama: http://localhost:11434/v1, LM Studio: http://localhost:1234/v1)")
+        .default("https://api.openai.com/v1".to_string())
+        .interact_text()?;
+    let api_key: String = Input::new()
+        .with_prompt("API key (leave empty for local servers)")
+        .allow_empty(true)
+        .interact_text()?;
+    let default_model: String = Input::new()
+        .with_prompt("Default model")
+        .allow_empty(true)
+        .interact_text()?;
+
+    let config = ProviderConfig {
+        provider_type: ProviderType::OpenaiCompatible,
+        api_key: (!api_key.is_empty()).then_some(api_key),
+        oauth_token: None,
+        oauth_client_id: None,
+        oauth_client_secret: None,
+        oauth_refresh_token: None,
+        oauth_expires_at: None,
+        base_url: Some(base_url),
+        default_model: (!default_model.is_empty()).then_some(default_model),
+        // Local models on modest hardware can be slow
+        timeout_secs: Some(300),
+        cassette: None,
+        headers: Default::default(),
+        models: Vec::new(),
+        fallback: Vec::new(),
+    };
+    Ok((name, config))
+}
+
 async fn configure_oauth_provider(name: &str) -> Result<ProviderConfig> {
     println!("OAuth configuration for {} not yet fully implemented.", name);
     println!("Falling back to API key authentication.");
@@ -2110,6 +2739,102 @@ fn select_provider(config_manager: &ConfigManager) -> Result<String> {
     Ok(providers[selection].clone())
 }
 
+fn print_checkpoints(checkpoints: &[Checkpoint]) {
+    if checkpoints.is_empty() {
+        println!("No checkpoints for this project.");
+        return;
+    }
+    println!("Checkpoints (oldest first):");
+    for c in checkpoints {
+        println!("  {:>3}  {}  {}", c.id, c.created_at.format("%Y-%m-%d %H:%M"), c.label);
+        for file in &c.files {
+            let mark = match file.change {
+                FileChangeKind::Created => '+',
+                FileChangeKind::Updated => '~',
+                FileChangeKind::Deleted => '-',
+            };
+            println!("         {} {}", mark, file.path.display());
+        }
+    }
+}
+
+fn print_undone(undone: &[Checkpoint]) {
+    for c in undone {
+        println!("✓ Undid checkpoint {} ({} files): {}", c.id, c.files.len(), c.label);
+    }
+}
+
+fn print_usage(rows: &[ledger::UsageRow], spent_today: f64, daily_budget: f64) {
+    if daily_budget > 0.0 {
+        println!("Today: ${:.2} of ${:.2} daily budget", spent_today, daily_budget);
+    } else {
+        println!("Today: ${:.2} (no daily budget)", spent_today);
+    }
+    if rows.is_empty() {
+        println!("No usage recorded.");
+        return;
+    }
+    for row in rows {
+        let unpriced = match row.unpriced {
+            0 => String::new(),
+            n => format!("  ({} unpriced)", n),
+        };
+        let cached = match row.cache_read_tokens {
+            0 => String::new(),
+            n => format!("  ({} in from cache)", n),
+        };
+        println!(
+            "  {:<10}  {:<24}  {:>5} requests  {:>9} in  {:>9} out  ${:>8.2}{}{}",
+            row.period,
+            row.group.as_deref().unwrap_or(""),
+            row.requests,
+            row.prompt_tokens,
+            row.completion_tokens,
+            row.cost,
+            cached,
+            unpriced
+        );
+    }
+}
+
+fn print_sessions(sessions: &[SessionSummary]) {
+    if sessions.is_empty() {
+        println!("No saved sessions.");
+        return;
+    }
+    println!("Saved sessions:");
+    for s in sessions {
+        println!(
+            "  {}  {}  {}/{}  {} turns  {}",
+            &s.id[..8.min(s.id.len())],
+            s.updated_at.format("%Y-%m-%d %H:%M"),
+            s.provider,
+            s.model,
+            s.turns,
+            s.title
+        );
+        println!("            {}", s.working_dir.display());
+    }
+}
+
+/// Replace the active REPL session with a loaded one
+fn switch_session(session: &mut Session, loaded: Session, provider_name: &str) {
+    if loaded.provider != provider_name {
+        println!("Note: session was recorded with {}, continuing with {}", loaded.provider, provider_name);
+    }
+    println!("✓ Resumed session {} ({} turns)", loaded.id, loaded.turn_count());
+    *session = loaded;
+}
+
+/// The model the REPL sends to: the provider's configured default, or the
+/// provider's own default when none is set
+fn repl_model(config_manager: &ConfigManager, provider_name: &str, provider: &dyn Provider) -> String {
+    config_manager.get()
+        .providers.get(provider_name)
+        .and_then(|p| p.default_model.clone())
+        .unwrap_or_else(|| provider.info().default_model)
+}
+
 fn print_help() {
     println!("\nNexus CLI Commands:");
     println!("  /help       - Show this help message");
@@ -2135,6 +2860,13 @@ fn print_help() {
     println!("  /mcp tools   - List available MCP tools");
     println!("  /mcp call <server> <tool>  - Call an MCP tool");
     println!("  /mcp server start  - Start MCP server");
+    println!("  /sessions   - List saved sessions");
+    println!("  /resume [id]  - Resume a saved session");
+    println!("  /fork [turn]  - Branch the session, keeping the first <turn> turns");
+    println!("  /checkpoints  - List the agent's file-change checkpoints");
+    println!("  /undo       - Undo the last agent turn's file changes");
+    println!("  /restore <n>  - Undo checkpoint <n> and everything after it");
+    println!("  /attach <image>  - Attach an image to your next message (/attach clear to drop)");
     println!("  /config     - View current configuration");
     println!("  /edit       - Edit a provider's API key");
     println!("  /auto on|off - Toggle automatic shadow run mode");
@@ -39,6 +39,7 @@ pub async fn run_consolidation(memory: &mut MemorySystem) -> Result<Consolidatio
             MemoryEvent::FileModified { timestamp, .. } => *timestamp,
             MemoryEvent::Error { timestamp, .. } => *timestamp,
             MemoryEvent::ProjectInit { timestamp, .. } => *timestamp,
+            MemoryEvent::ContextCompacted { timestamp, .. } => *timestamp,
         };
         
         if timestamp < cutoff {
@@ -98,6 +99,7 @@ pub fn calculate_priority(event: &MemoryEvent) -> f32 {
         MemoryEvent::FactStored { .. } => 0.8, // High priority: facts
         MemoryEvent::ProcedureLearned { .. } => 0.7, // Medium priority: procedures
         MemoryEvent::ProjectInit { .. } => 0.6,
+        MemoryEvent::ContextCompacted { .. } => 0.6,
         MemoryEvent::FileModified { .. } => 0.5,
         MemoryEvent::ToolCall { success, .. } => {
             if *success { 0.4 } else { 0.7 } // Failed tools are important
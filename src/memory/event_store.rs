@@ -65,6 +65,12 @@ pub enum MemoryEvent {
         context: String,
         timestamp: SystemTime,
     },
+    ContextCompacted {
+        session_id: String,
+        summary: String,
+        messages_compacted: usize,
+        timestamp: SystemTime,
+    },
 }
 
 impl EventStore {
@@ -142,6 +148,7 @@ impl EventStore {
                     MemoryEvent::ProcedureLearned { .. } => "procedure_learned",
                     MemoryEvent::FileModified { .. } => "file_modified",
                     MemoryEvent::Error { .. } => "error",
+                    MemoryEvent::ContextCompacted { .. } => "context_compacted",
                 };
                 type_str == event_type
             })
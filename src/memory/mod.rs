@@ -96,6 +96,32 @@ impl MemorySystem {
         Ok(())
     }
 
+    /// Record the summary that replaced compacted conversation history
+    pub async fn record_compaction(
+        &mut self,
+        summary: &str,
+        messages_compacted: usize,
+    ) -> Result<()> {
+        self.event_store.log_event(MemoryEvent::ContextCompacted {
+            session_id: self.session_id.clone(),
+            summary: summary.to_string(),
+            messages_compacted,
+            timestamp: SystemTime::now(),
+        }).await?;
+
+        // Index so later sessions can recall what was discussed
+        self.vector.index_document(
+            &format!("compaction_{}", uuid::Uuid::new_v4()),
+            &format!("Conversation summary: {}", summary),
+            HashMap::from([
+                ("type".to_string(), "compaction".to_string()),
+                ("session".to_string(), self.session_id.clone()),
+            ]),
+        ).await?;
+
+        Ok(())
+    }
+
     /// Store a fact about the user or project (semantic memory)
     pub async fn remember_fact(
         &mut self,
@@ -73,6 +73,9 @@ impl ContextBundle {
                                     context.push_str(&format!("- {}\n", response));
                                 }
                             }
+                            crate::memory::MemoryEvent::ContextCompacted { summary, .. } => {
+                                context.push_str(&format!("- Earlier conversation: {}\n", summary));
+                            }
                             _ => {
                                 context.push_str(&format!("- Past event: {:?}\n", event));
                             }
@@ -1,6 +1,9 @@
 use crate::config::ProviderConfig;
 use crate::error::{NexusError, Result};
-use crate::providers::{CompletionRequest, CompletionResponse, Message, ModelInfo, ModelPricing, Provider, ProviderInfo, Role, Usage};
+use crate::executor::tools::{Tool, ToolCall};
+use crate::providers::retry;
+use crate::providers::sse::SseStream;
+use crate::providers::{CompletionRequest, CompletionResponse, ContentPart, Message, ModelInfo, ModelPricing, Provider, ProviderInfo, Role, StreamChunk, Usage};
 use async_trait::async_trait;
 use oauth2::{
     AuthUrl, ClientId, ClientSecret, CsrfToken, RedirectUrl, Scope,
@@ -10,6 +13,7 @@ use oauth2::{
 };
 use reqwest::Client;
 use serde_json;
+use std::collections::{HashMap, HashSet};
 use std::io::{BufRead, BufReader, Write};
 use std::net::TcpListener;
 
@@ -230,58 +234,145 @@ impl ClaudeProvider {
         Ok(())
     }
 
-    fn convert_messages(&self, messages: &[Message]) -> Vec<serde_json::Value> {
-        messages.iter().map(|msg| {
+    /// Convert messages to Anthropic's format.
+    ///
+    /// Content parts map onto Anthropic content blocks, with tool results sent as
+    /// `tool_result` blocks in the user turn that follows the assistant's `tool_use`.
+    /// Consecutive messages with the same role are merged, since the Messages API
+    /// requires the conversation to alternate between user and assistant.
+    fn convert_messages(messages: &[Message]) -> Vec<serde_json::Value> {
+        let mut converted: Vec<(&'static str, Vec<serde_json::Value>)> = Vec::new();
+        let mut pending_tool_ids: HashSet<String> = HashSet::new();
+
+        for msg in messages {
+            let role = match msg.role {
+                Role::System => continue,
+                Role::Assistant => {
+                    pending_tool_ids.clear();
+                    "assistant"
+                }
+                _ => "user",
+            };
+
+            let blocks: Vec<serde_json::Value> = msg.content.iter().filter_map(|part| match part {
+                ContentPart::Text { text } if text.is_empty() => None,
+                ContentPart::Text { text } => Some(serde_json::json!({"type": "text", "text": text})),
+                ContentPart::Image { media_type, data } => Some(serde_json::json!({
+                    "type": "image",
+                    "source": {"type": "base64", "media_type": media_type, "data": data},
+                })),
+                ContentPart::ToolUse { id, name, input } => {
+                    pending_tool_ids.insert(id.clone());
+                    Some(serde_json::json!({"type": "tool_use", "id": id, "name": name, "input": input}))
+                }
+                // Only thread results back to calls the model actually made
+                ContentPart::ToolResult { tool_use_id, content, is_error } if pending_tool_ids.contains(tool_use_id) => {
+                    Some(serde_json::json!({
+                        "type": "tool_result",
+                        "tool_use_id": tool_use_id,
+                        "content": content,
+                        "is_error": is_error,
+                    }))
+                }
+                ContentPart::ToolResult { content, .. } => Some(serde_json::json!({"type": "text", "text": content})),
+            }).collect();
+
+            if blocks.is_empty() {
+                continue;
+            }
+
+            match converted.last_mut() {
+                Some((last_role, last_blocks)) if *last_role == role => last_blocks.extend(blocks),
+                _ => converted.push((role, blocks)),
+            }
+        }
+
+        converted.into_iter().map(|(role, blocks)| {
+            serde_json::json!({"role": role, "content": blocks})
+        }).collect()
+    }
+
+    /// Convert tool definitions to Anthropic's `tools` schema
+    fn convert_tools(tools: &[Tool]) -> Vec<serde_json::Value> {
+        tools.iter().map(|tool| {
             serde_json::json!({
-                "role": match msg.role {
-                    Role::User => "user",
-                    Role::Assistant => "assistant",
-                    _ => "user",
-                },
-                "content": msg.content
+                "name": tool.name,
+                "description": tool.description,
+                "input_schema": tool.parameters,
             })
         }).collect()
     }
 
-    fn extract_system_message(&self, messages: &[Message]) -> Option<String> {
+    fn extract_system_message(messages: &[Message]) -> Option<String> {
         messages.iter()
             .find(|m| m.role == Role::System)
-            .map(|m| m.content.clone())
+            .map(|m| m.text_content())
     }
 
-    fn get_auth_headers(&self) -> Result<Vec<(String, String)>> {
-        let mut headers = vec![];
-        
-        if let Some(token) = &self.oauth_token {
-            headers.push(("Authorization".to_string(), format!("Bearer {}", token)));
-        } else if let Some(key) = &self.api_key {
-            headers.push(("x-api-key".to_string(), key.clone()));
-        } else {
-            return Err(NexusError::Authentication(
-                "Claude API key or OAuth token not configured".to_string()
-            ));
+    /// Parse a Messages API response, collecting text and `tool_use` blocks
+    fn parse_response(data: &serde_json::Value, requested_model: &str) -> CompletionResponse {
+        let mut content = String::new();
+        let mut tool_calls = Vec::new();
+
+        for block in data["content"].as_array().into_iter().flatten() {
+            match block["type"].as_str() {
+                Some("text") => content.push_str(block["text"].as_str().unwrap_or("")),
+                Some("tool_use") => tool_calls.push(ToolCall {
+                    id: block["id"].as_str().unwrap_or_default().to_string(),
+                    name: block["name"].as_str().unwrap_or_default().to_string(),
+                    arguments: block["input"].clone(),
+                }),
+                _ => {}
+            }
         }
 
-        headers.push(("anthropic-version".to_string(), self.version.clone()));
-        
-        Ok(headers)
+        let finish_reason = data["stop_reason"]
+            .as_str()
+            .map(|s| s.to_string());
+
+        let usage = data.get("usage").map(Self::parse_usage);
+
+        CompletionResponse {
+            id: data["id"].as_str().unwrap_or("unknown").to_string(),
+            model: data["model"].as_str().unwrap_or(requested_model).to_string(),
+            content,
+            finish_reason,
+            usage,
+            tool_calls: if tool_calls.is_empty() { None } else { Some(tool_calls) },
+        }
     }
-}
 
-#[async_trait]
-impl Provider for ClaudeProvider {
-    fn info(&self) -> ProviderInfo {
-        Self::static_info()
+    /// Anthropic counts cached prompt tokens separately from `input_tokens`;
+    /// fold them back in so `prompt_tokens` covers the whole prompt
+    fn parse_usage(usage: &serde_json::Value) -> Usage {
+        let count = |key: &str| usage[key].as_u64().unwrap_or(0) as u32;
+        let cache_read_tokens = count("cache_read_input_tokens");
+        let cache_write_tokens = count("cache_creation_input_tokens");
+        let prompt_tokens = count("input_tokens") + cache_read_tokens + cache_write_tokens;
+        let completion_tokens = count("output_tokens");
+        Usage {
+            prompt_tokens,
+            completion_tokens,
+            total_tokens: prompt_tokens + completion_tokens,
+            cache_read_tokens,
+            cache_write_tokens,
+        }
     }
 
-    async fn complete(&self, request: CompletionRequest) -> Result<CompletionResponse> {
-        let system_message = self.extract_system_message(&request.messages);
-        let messages = self.convert_messages(
-            &request.messages.iter()
-                .filter(|m| m.role != Role::System)
-                .cloned()
-                .collect::<Vec<_>>()
-        );
+    fn build_body(request: &CompletionRequest, stream: bool) -> serde_json::Value {
+        let cache = request.cache.clone().unwrap_or_default();
+        let ephemeral = serde_json::json!({"type": "ephemeral"});
+        let mut messages = Self::convert_messages(&request.messages);
+
+        // Mark the last block of the cached history. Conversion only looks
+        // backwards, so converting the prefix alone yields the same blocks.
+        if let Some(history) = cache.history.filter(|&n| n > 0 && n <= request.messages.len()) {
+            let prefix = Self::convert_messages(&request.messages[..history]);
+            if let Some(last) = prefix.last() {
+                let block = last["content"].as_array().map_or(0, |blocks| blocks.len()).saturating_sub(1);
+                messages[prefix.len() - 1]["content"][block]["cache_control"] = ephemeral.clone();
+            }
+        }
 
         let mut body = serde_json::json!({
             "model": request.model,
@@ -290,14 +381,31 @@ impl Provider for ClaudeProvider {
             "temperature": request.temperature.unwrap_or(0.7),
         });
 
-        if let Some(system) = system_message {
-            body["system"] = serde_json::json!(system);
+        if let Some(tools) = request.tools.as_deref().filter(|t| !t.is_empty()) {
+            let mut tools = Self::convert_tools(tools);
+            if cache.tools && let Some(last) = tools.last_mut() {
+                last["cache_control"] = ephemeral.clone();
+            }
+            body["tools"] = serde_json::json!(tools);
+        }
+
+        if let Some(system) = Self::extract_system_message(&request.messages) {
+            body["system"] = if cache.system {
+                serde_json::json!([{"type": "text", "text": system, "cache_control": ephemeral}])
+            } else {
+                serde_json::json!(system)
+            };
         }
 
-        if request.stream == Some(true) {
+        if stream {
             body["stream"] = serde_json::json!(true);
         }
 
+        body
+    }
+
+    /// POST to the Messages API, turning non-success statuses into errors
+    async fn send_messages(&self, body: &serde_json::Value) -> Result<reqwest::Response> {
         let headers = self.get_auth_headers()?;
         
         let mut req = self.client
@@ -309,48 +417,76 @@ impl Provider for ClaudeProvider {
         }
 
         let response = req
-            .json(&body)
+            .json(body)
             .send()
             .await?;
 
         if !response.status().is_success() {
-            let error_text = response.text().await.unwrap_or_default();
-            return Err(NexusError::ApiRequest(format!(
-                "Claude API error: {}",
-                error_text
-            )));
+            return Err(retry::error_for_status("Claude API error", response).await);
         }
 
-        let data: serde_json::Value = response.json().await?;
+        Ok(response)
+    }
+
+    fn get_auth_headers(&self) -> Result<Vec<(String, String)>> {
+        let mut headers = vec![];
         
-        let content = data["content"][0]["text"]
-            .as_str()
-            .unwrap_or("")
-            .to_string();
+        if let Some(token) = &self.oauth_token {
+            headers.push(("Authorization".to_string(), format!("Bearer {}", token)));
+        } else if let Some(key) = &self.api_key {
+            headers.push(("x-api-key".to_string(), key.clone()));
+        } else {
+            return Err(NexusError::Authentication(
+                "Claude API key or OAuth token not configured".to_string()
+            ));
+        }
 
-        let finish_reason = data["stop_reason"]
-            .as_str()
-            .map(|s| s.to_string());
+        headers.push(("anthropic-version".to_string(), self.version.clone()));
+        
+        Ok(headers)
+    }
+}
 
-        let usage = if let Some(usage) = data.get("usage") {
-            Some(Usage {
-                prompt_tokens: usage["input_tokens"].as_u64().unwrap_or(0) as u32,
-                completion_tokens: usage["output_tokens"].as_u64().unwrap_or(0) as u32,
-                total_tokens: usage["input_tokens"].as_u64().unwrap_or(0) as u32 + 
-                           usage["output_tokens"].as_u64().unwrap_or(0) as u32,
-            })
-        } else {
-            None
-        };
+#[async_trait]
+impl Provider for ClaudeProvider {
+    fn info(&self) -> ProviderInfo {
+        Self::static_info()
+    }
 
-        Ok(CompletionResponse {
-            id: data["id"].as_str().unwrap_or("unknown").to_string(),
-            model: data["model"].as_str().unwrap_or(&request.model).to_string(),
-            content,
-            finish_reason,
-            usage,
-            tool_calls: None,
-        })
+    async fn complete(&self, request: CompletionRequest) -> Result<CompletionResponse> {
+        let body = Self::build_body(&request, false);
+        let response = self.send_messages(&body).await?;
+        let data: serde_json::Value = response.json().await?;
+
+        Ok(Self::parse_response(&data, &request.model))
+    }
+
+    async fn complete_stream(
+        &self,
+        request: CompletionRequest,
+        tx: tokio::sync::mpsc::Sender<StreamChunk>,
+    ) -> Result<()> {
+        let body = Self::build_body(&request, true);
+        let response = self.send_messages(&body).await?;
+
+        let mut events = SseStream::new(response);
+        let mut state = StreamState::default();
+
+        while let Some(event) = events.next_event().await? {
+            let Ok(data) = serde_json::from_str::<serde_json::Value>(&event.data) else {
+                continue;
+            };
+            for chunk in state.handle(&data)? {
+                let done = matches!(chunk, StreamChunk::Done);
+                let _ = tx.send(chunk).await;
+                if done {
+                    return Ok(());
+                }
+            }
+        }
+
+        let _ = tx.send(StreamChunk::Done).await;
+        Ok(())
     }
 
     async fn list_available_models(&self) -> Result<Vec<ModelInfo>> {
@@ -366,7 +502,7 @@ impl Provider for ClaudeProvider {
                     prompt: Some(3.0),      // $3 per million input tokens
                     completion: Some(15.0), // $15 per million output tokens
                 }),
-                supports_vision: true,
+                supports_vision: Some(true),
                 supports_streaming: true,
                 supports_function_calling: true,
             },
@@ -379,7 +515,7 @@ impl Provider for ClaudeProvider {
                     prompt: Some(15.0),     // $15 per million input tokens
                     completion: Some(75.0), // $75 per million output tokens
                 }),
-                supports_vision: true,
+                supports_vision: Some(true),
                 supports_streaming: true,
                 supports_function_calling: true,
             },
@@ -392,7 +528,7 @@ impl Provider for ClaudeProvider {
                     prompt: Some(0.8),     // $0.80 per million input tokens
                     completion: Some(4.0), // $4 per million output tokens
                 }),
-                supports_vision: true,
+                supports_vision: Some(true),
                 supports_streaming: true,
                 supports_function_calling: true,
             },
@@ -406,7 +542,7 @@ impl Provider for ClaudeProvider {
                     prompt: Some(3.0),
                     completion: Some(15.0),
                 }),
-                supports_vision: true,
+                supports_vision: Some(true),
                 supports_streaming: true,
                 supports_function_calling: true,
             },
@@ -419,7 +555,7 @@ impl Provider for ClaudeProvider {
                     prompt: Some(15.0),
                     completion: Some(75.0),
                 }),
-                supports_vision: true,
+                supports_vision: Some(true),
                 supports_streaming: true,
                 supports_function_calling: true,
             },
@@ -432,7 +568,7 @@ impl Provider for ClaudeProvider {
                     prompt: Some(0.25),
                     completion: Some(1.25),
                 }),
-                supports_vision: true,
+                supports_vision: Some(true),
                 supports_streaming: true,
                 supports_function_calling: true,
             },
@@ -480,8 +616,358 @@ impl Provider for ClaudeProvider {
     }
 }
 
+/// Tracks partially streamed content blocks in a Messages API event stream
+#[derive(Debug, Default)]
+struct StreamState {
+    /// In-progress `tool_use` blocks by index: (id, name, partial JSON input)
+    tool_blocks: HashMap<u64, (String, String, String)>,
+    usage: Usage,
+}
+
+impl StreamState {
+    /// Translate one stream event into chunks for the caller
+    fn handle(&mut self, data: &serde_json::Value) -> Result<Vec<StreamChunk>> {
+        let mut chunks = Vec::new();
+
+        match data["type"].as_str().unwrap_or_default() {
+            "message_start" => {
+                self.usage = ClaudeProvider::parse_usage(&data["message"]["usage"]);
+            }
+            "content_block_start" => {
+                let index = data["index"].as_u64().unwrap_or(0);
+                let block = &data["content_block"];
+                match block["type"].as_str() {
+                    Some("tool_use") => {
+                        self.tool_blocks.insert(index, (
+                            block["id"].as_str().unwrap_or_default().to_string(),
+                            block["name"].as_str().unwrap_or_default().to_string(),
+                            String::new(),
+                        ));
+                    }
+                    Some("text") => {
+                        if let Some(text) = block["text"].as_str().filter(|t| !t.is_empty()) {
+                            chunks.push(StreamChunk::ContentDelta(text.to_string()));
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            "content_block_delta" => {
+                let delta = &data["delta"];
+                match delta["type"].as_str() {
+                    Some("text_delta") => {
+                        if let Some(text) = delta["text"].as_str().filter(|t| !t.is_empty()) {
+                            chunks.push(StreamChunk::ContentDelta(text.to_string()));
+                        }
+                    }
+                    Some("input_json_delta") => {
+                        let index = data["index"].as_u64().unwrap_or(0);
+                        if let Some((_, _, input)) = self.tool_blocks.get_mut(&index) {
+                            input.push_str(delta["partial_json"].as_str().unwrap_or_default());
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            "content_block_stop" => {
+                let index = data["index"].as_u64().unwrap_or(0);
+                if let Some((id, name, input)) = self.tool_blocks.remove(&index) {
+                    let arguments = if input.trim().is_empty() {
+                        serde_json::json!({})
+                    } else {
+                        serde_json::from_str(&input).map_err(|e| {
+                            NexusError::ApiRequest(format!("Invalid tool input for {}: {}", name, e))
+                        })?
+                    };
+                    chunks.push(StreamChunk::ToolCall(ToolCall { id, name, arguments }));
+                }
+            }
+            "message_delta" => {
+                if let Some(output) = data["usage"]["output_tokens"].as_u64() {
+                    self.usage.completion_tokens = output as u32;
+                    self.usage.total_tokens = self.usage.prompt_tokens + self.usage.completion_tokens;
+                }
+                chunks.push(StreamChunk::Usage(self.usage.clone()));
+            }
+            "message_stop" => chunks.push(StreamChunk::Done),
+            "error" => {
+                return Err(NexusError::ApiRequest(format!(
+                    "Claude stream error: {}",
+                    data["error"]["message"].as_str().unwrap_or("unknown error")
+                )));
+            }
+            // ping and unknown event types
+            _ => {}
+        }
+
+        Ok(chunks)
+    }
+}
+
 impl ClaudeProvider {
     pub fn get_refresh_token(&self) -> Option<&String> {
         self.oauth_refresh_token.as_ref()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executor::tools::ToolResult;
+    use crate::providers::CacheControl;
+    use serde_json::json;
+
+    #[test]
+    fn test_convert_tools_uses_input_schema() {
+        let tools = vec![Tool {
+            name: "read_file".to_string(),
+            description: "Read a file".to_string(),
+            parameters: json!({"type": "object", "properties": {"path": {"type": "string"}}}),
+        }];
+
+        let converted = ClaudeProvider::convert_tools(&tools);
+        assert_eq!(converted[0]["name"], "read_file");
+        assert_eq!(converted[0]["input_schema"]["properties"]["path"]["type"], "string");
+    }
+
+    #[test]
+    fn test_tool_results_threaded_to_tool_use() {
+        let calls = vec![
+            ToolCall { id: "toolu_1".to_string(), name: "read_file".to_string(), arguments: json!({"path": "a.rs"}) },
+            ToolCall { id: "toolu_2".to_string(), name: "read_file".to_string(), arguments: json!({"path": "b.rs"}) },
+        ];
+        let results = vec![
+            ToolResult { tool_call_id: "toolu_1".to_string(), success: true, output: "fn a() {}".to_string(), error: None },
+            ToolResult { tool_call_id: "toolu_2".to_string(), success: false, output: String::new(), error: Some("not found".to_string()) },
+        ];
+
+        let messages = vec![
+            Message::system("system prompt"),
+            Message::user("read both files"),
+            Message::assistant_with_tool_calls("Let me look.", &calls),
+            Message::tool_results(&results),
+        ];
+        let converted = ClaudeProvider::convert_messages(&messages);
+
+        assert_eq!(converted.len(), 3);
+        assert_eq!(converted[1]["role"], "assistant");
+        assert_eq!(converted[1]["content"][1]["type"], "tool_use");
+        assert_eq!(converted[1]["content"][1]["id"], "toolu_1");
+        assert_eq!(converted[2]["role"], "user");
+        assert_eq!(converted[2]["content"][0]["type"], "tool_result");
+        assert_eq!(converted[2]["content"][0]["tool_use_id"], "toolu_1");
+        assert_eq!(converted[2]["content"][1]["tool_use_id"], "toolu_2");
+        assert_eq!(converted[2]["content"][1]["is_error"], true);
+    }
+
+    #[test]
+    fn test_consecutive_user_messages_merged() {
+        let messages = vec![Message::user("first"), Message::user("second")];
+        let converted = ClaudeProvider::convert_messages(&messages);
+        assert_eq!(converted.len(), 1);
+        assert_eq!(converted[0]["content"][1]["text"], "second");
+    }
+
+    #[test]
+    fn test_unmatched_tool_result_sent_as_text() {
+        let result = ToolResult { tool_call_id: "call_parsed".to_string(), success: true, output: "output".to_string(), error: None };
+        let messages = vec![Message::assistant("```json ...```"), Message::tool_results(&[result])];
+
+        let converted = ClaudeProvider::convert_messages(&messages);
+        assert_eq!(converted[1]["content"][0]["type"], "text");
+    }
+
+    #[test]
+    fn test_image_part_converted_to_base64_source() {
+        let mut message = Message::user("what is this?");
+        message.content.push(ContentPart::Image { media_type: "image/png".to_string(), data: "iVBORw0KGgo=".to_string() });
+
+        let converted = ClaudeProvider::convert_messages(&[message]);
+        assert_eq!(converted[0]["content"][1]["type"], "image");
+        assert_eq!(converted[0]["content"][1]["source"]["media_type"], "image/png");
+    }
+
+    #[test]
+    fn test_parse_response_with_tool_use() {
+        let data = json!({
+            "id": "msg_1",
+            "model": "claude-sonnet-4-5",
+            "stop_reason": "tool_use",
+            "content": [
+                {"type": "text", "text": "Reading the file."},
+                {"type": "tool_use", "id": "toolu_1", "name": "read_file", "input": {"path": "src/main.rs"}}
+            ],
+            "usage": {"input_tokens": 10, "output_tokens": 5}
+        });
+
+        let response = ClaudeProvider::parse_response(&data, "claude-sonnet-4-5");
+        assert_eq!(response.content, "Reading the file.");
+        let calls = response.tool_calls.unwrap();
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].id, "toolu_1");
+        assert_eq!(calls[0].arguments["path"], "src/main.rs");
+        assert_eq!(response.usage.unwrap().total_tokens, 15);
+    }
+
+    #[test]
+    fn test_cache_breakpoints_on_tools_system_and_history() {
+        let tools = vec![
+            Tool { name: "read_file".to_string(), description: "Read a file".to_string(), parameters: json!({}) },
+            Tool { name: "write_file".to_string(), description: "Write a file".to_string(), parameters: json!({}) },
+        ];
+        let request = CompletionRequest {
+            model: "claude-sonnet-4-5".to_string(),
+            messages: vec![
+                Message::system("system prompt"),
+                Message::user("first"),
+                Message::assistant("reply"),
+                Message::user("second"),
+            ],
+            temperature: None,
+            max_tokens: None,
+            stream: None,
+            tools: Some(tools),
+            extra_params: None,
+            cache: Some(CacheControl::prefix(3)),
+        };
+
+        let body = ClaudeProvider::build_body(&request, false);
+        assert!(body["tools"][0].get("cache_control").is_none());
+        assert_eq!(body["tools"][1]["cache_control"]["type"], "ephemeral");
+        assert_eq!(body["system"][0]["cache_control"]["type"], "ephemeral");
+        assert_eq!(body["messages"][1]["content"][0]["cache_control"]["type"], "ephemeral");
+        assert!(body["messages"][2]["content"][0].get("cache_control").is_none());
+
+        // Without hints the system prompt stays a plain string
+        let body = ClaudeProvider::build_body(&CompletionRequest { cache: None, ..request }, false);
+        assert_eq!(body["system"], "system prompt");
+    }
+
+    #[test]
+    fn test_usage_includes_cached_prompt_tokens() {
+        let usage = ClaudeProvider::parse_usage(&json!({
+            "input_tokens": 50,
+            "cache_creation_input_tokens": 200,
+            "cache_read_input_tokens": 1000,
+            "output_tokens": 30
+        }));
+        assert_eq!(usage.prompt_tokens, 1250);
+        assert_eq!(usage.cache_read_tokens, 1000);
+        assert_eq!(usage.cache_write_tokens, 200);
+        assert_eq!(usage.total_tokens, 1280);
+    }
+
+    #[test]
+    fn test_parse_response_text_only() {
+        let data = json!({"id": "msg_2", "content": [{"type": "text", "text": "Done."}]});
+        let response = ClaudeProvider::parse_response(&data, "claude-sonnet-4-5");
+        assert_eq!(response.content, "Done.");
+        assert!(response.tool_calls.is_none());
+        assert_eq!(response.model, "claude-sonnet-4-5");
+    }
+
+    const STREAM_BODY: &str = "event: message_start
+data: {\"type\":\"message_start\",\"message\":{\"id\":\"msg_1\",\"usage\":{\"input_tokens\":25,\"output_tokens\":1}}}
+
+event: content_block_start
+data: {\"type\":\"content_block_start\",\"index\":0,\"content_block\":{\"type\":\"text\",\"text\":\"\"}}
+
+event: ping
+data: {\"type\":\"ping\"}
+
+event: content_block_delta
+data: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"text_delta\",\"text\":\"Let me \"}}
+
+event: content_block_delta
+data: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"text_delta\",\"text\":\"check.\"}}
+
+event: content_block_stop
+data: {\"type\":\"content_block_stop\",\"index\":0}
+
+event: content_block_start
+data: {\"type\":\"content_block_start\",\"index\":1,\"content_block\":{\"type\":\"tool_use\",\"id\":\"toolu_1\",\"name\":\"read_file\",\"input\":{}}}
+
+event: content_block_delta
+data: {\"type\":\"content_block_delta\",\"index\":1,\"delta\":{\"type\":\"input_json_delta\",\"partial_json\":\"{\\\"path\\\": \\\"src/\"}}
+
+event: content_block_delta
+data: {\"type\":\"content_block_delta\",\"index\":1,\"delta\":{\"type\":\"input_json_delta\",\"partial_json\":\"main.rs\\\"}\"}}
+
+event: content_block_stop
+data: {\"type\":\"content_block_stop\",\"index\":1}
+
+event: message_delta
+data: {\"type\":\"message_delta\",\"delta\":{\"stop_reason\":\"tool_use\"},\"usage\":{\"output_tokens\":42}}
+
+event: message_stop
+data: {\"type\":\"message_stop\"}
+
+";
+
+    fn test_provider(base_url: String) -> ClaudeProvider {
+        ClaudeProvider::new(&ProviderConfig {
+            provider_type: crate::config::ProviderType::Claude,
+            api_key: Some("test-key".to_string()),
+            oauth_token: None,
+            oauth_client_id: None,
+            oauth_client_secret: None,
+            oauth_refresh_token: None,
+            oauth_expires_at: None,
+            base_url: Some(base_url),
+            default_model: None,
+            timeout_secs: None,
+            cassette: None,
+            headers: Default::default(),
+            models: Vec::new(),
+            fallback: Vec::new(),
+        })
+    }
+
+    #[tokio::test]
+    async fn test_complete_stream_against_mock_server() {
+        let base_url = crate::providers::sse::testing::serve_sse("/v1/messages", STREAM_BODY).await;
+        let provider = test_provider(base_url);
+        let request = CompletionRequest {
+            model: "claude-sonnet-4-5".to_string(),
+            messages: vec![Message::user("read main.rs")],
+            temperature: None,
+            max_tokens: None,
+            stream: Some(true),
+            tools: None,
+            extra_params: None,
+            cache: None,
+        };
+
+        let (tx, mut rx) = tokio::sync::mpsc::channel(32);
+        provider.complete_stream(request, tx).await.unwrap();
+
+        let mut text = String::new();
+        let mut calls = Vec::new();
+        let mut usage = None;
+        let mut done = false;
+        while let Some(chunk) = rx.recv().await {
+            match chunk {
+                StreamChunk::ContentDelta(delta) => text.push_str(&delta),
+                StreamChunk::ToolCall(call) => calls.push(call),
+                StreamChunk::Usage(u) => usage = Some(u),
+                StreamChunk::Done => done = true,
+            }
+        }
+
+        assert_eq!(text, "Let me check.");
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].id, "toolu_1");
+        assert_eq!(calls[0].arguments["path"], "src/main.rs");
+        let usage = usage.unwrap();
+        assert_eq!(usage.prompt_tokens, 25);
+        assert_eq!(usage.completion_tokens, 42);
+        assert!(done);
+    }
+
+    #[test]
+    fn test_stream_error_event() {
+        let mut state = StreamState::default();
+        let err = state.handle(&json!({"type": "error", "error": {"type": "overloaded_error", "message": "Overloaded"}}));
+        assert!(err.unwrap_err().to_string().contains("Overloaded"));
+    }
+}
@@ -0,0 +1,410 @@
+//! Ordered provider fallback with circuit breaking
+//!
+//! [`FallbackProvider`] sends each request to the first healthy provider in its
+//! chain. A provider that fails with a transient error (rate limit, 5xx,
+//! timeout, connection failure) is skipped and the next one is tried. After
+//! [`FAILURE_THRESHOLD`] failures in a row its circuit opens and it is left out
+//! until a cool-down passes, or for as long as its `Retry-After` asked. The first
+//! request after that is a trial: success closes the circuit, failure opens it
+//! again.
+//!
+//! Chains hang off the primary provider's config:
+//!
+//! ```toml
+//! [providers.claude]
+//! provider_type = "claude"
+//! fallback = ["openrouter", "local"]
+//! ```
+//!
+//! Fallback providers use their own `default_model`, since model names differ
+//! between providers.
+
+use crate::config::ConfigManager;
+use crate::error::{NexusError, Result};
+use crate::providers::retry::is_retryable;
+use crate::providers::{create_provider, CompletionRequest, CompletionResponse, ModelInfo, Provider, ProviderInfo, StreamChunk};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+use tracing::{info, warn};
+
+/// Failures in a row that open a provider's circuit
+pub const FAILURE_THRESHOLD: u32 = 3;
+
+/// How long an open circuit stays open when the server gave no `Retry-After`
+const COOL_DOWN: Duration = Duration::from_secs(60);
+
+/// Which provider answered a request, for the session log
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServedRequest {
+    pub at: DateTime<Utc>,
+    pub provider: String,
+    pub model: String,
+    /// Providers tried or skipped first, with the reason
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub skipped: Vec<String>,
+}
+
+/// Shared record of which provider served each request
+pub type ServedLog = Arc<Mutex<Vec<ServedRequest>>>;
+
+#[derive(Debug, Default)]
+struct Circuit {
+    failures: u32,
+    open_until: Option<Instant>,
+}
+
+struct Member {
+    name: String,
+    provider: Box<dyn Provider + Send + Sync>,
+    /// Model to ask for; `None` keeps the request's model
+    model: Option<String>,
+    circuit: Mutex<Circuit>,
+}
+
+impl Member {
+    /// Time left before an open circuit lets a trial request through
+    fn open_for(&self) -> Option<Duration> {
+        let circuit = self.circuit.lock().unwrap_or_else(|e| e.into_inner());
+        circuit.open_until
+            .map(|until| until.saturating_duration_since(Instant::now()))
+            .filter(|left| !left.is_zero())
+    }
+
+    fn succeeded(&self) {
+        *self.circuit.lock().unwrap_or_else(|e| e.into_inner()) = Circuit::default();
+    }
+
+    fn failed(&self, err: &NexusError, cool_down: Duration) {
+        let mut circuit = self.circuit.lock().unwrap_or_else(|e| e.into_inner());
+        circuit.failures += 1;
+        // A server that says when to come back is taken at its word straight away
+        let wait = match err.retry_after() {
+            Some(retry_after) => Some(retry_after),
+            None if circuit.failures >= FAILURE_THRESHOLD => Some(cool_down),
+            None => None,
+        };
+        if let Some(wait) = wait {
+            warn!(provider = %self.name, failures = circuit.failures, ?wait, "Opening provider circuit");
+            circuit.open_until = Some(Instant::now() + wait);
+        }
+    }
+}
+
+/// Tries each provider in order until one answers
+pub struct FallbackProvider {
+    members: Vec<Member>,
+    cool_down: Duration,
+    log: Option<ServedLog>,
+}
+
+impl FallbackProvider {
+    /// `primary` serves the request's model; each fallback uses the model
+    /// given with it
+    pub fn new(primary: (String, Box<dyn Provider + Send + Sync>)) -> Self {
+        Self {
+            members: vec![Member {
+                name: primary.0,
+                provider: primary.1,
+                model: None,
+                circuit: Mutex::new(Circuit::default()),
+            }],
+            cool_down: COOL_DOWN,
+            log: None,
+        }
+    }
+
+    pub fn with_fallback(mut self, name: String, provider: Box<dyn Provider + Send + Sync>, model: String) -> Self {
+        self.members.push(Member { name, provider, model: Some(model), circuit: Mutex::new(Circuit::default()) });
+        self
+    }
+
+    /// Record which provider served each request in `log`
+    pub fn with_log(mut self, log: ServedLog) -> Self {
+        self.log = Some(log);
+        self
+    }
+
+    fn request_for(member: &Member, request: &CompletionRequest) -> CompletionRequest {
+        let mut request = request.clone();
+        if let Some(model) = &member.model {
+            request.model = model.clone();
+        }
+        request
+    }
+
+    /// Count a failure against `member`. A provider with nothing to fall back
+    /// to keeps its circuit closed: failing fast would only hide the error.
+    fn failed(&self, member: &Member, err: &NexusError) {
+        if self.members.len() > 1 {
+            member.failed(err, self.cool_down);
+        }
+    }
+
+    fn served(&self, member: &Member, model: &str, skipped: Vec<String>) {
+        if !skipped.is_empty() {
+            info!(provider = %member.name, skipped = ?skipped, "Request served by fallback provider");
+        }
+        if let Some(log) = &self.log {
+            log.lock().unwrap_or_else(|e| e.into_inner()).push(ServedRequest {
+                at: Utc::now(),
+                provider: member.name.clone(),
+                model: model.to_string(),
+                skipped,
+            });
+        }
+    }
+
+    /// The error once every provider failed or was skipped. It keeps a 503 and
+    /// the time until the first circuit closes, so callers retry then.
+    fn exhausted(&self, skipped: &[String]) -> NexusError {
+        let retry_after = self.members.iter().filter_map(|m| m.open_for()).min();
+        NexusError::ApiStatus {
+            status: 503,
+            message: format!("Every provider in the fallback chain failed: {}", skipped.join("; ")),
+            retry_after,
+        }
+    }
+}
+
+#[async_trait]
+impl Provider for FallbackProvider {
+    fn info(&self) -> ProviderInfo {
+        self.members[0].provider.info()
+    }
+
+    async fn complete(&self, request: CompletionRequest) -> Result<CompletionResponse> {
+        let mut skipped = Vec::new();
+        for member in &self.members {
+            if let Some(left) = member.open_for() {
+                skipped.push(format!("{}: circuit open for {}s", member.name, left.as_secs()));
+                continue;
+            }
+            let request = Self::request_for(member, &request);
+            let model = request.model.clone();
+            match member.provider.complete(request).await {
+                Ok(response) => {
+                    member.succeeded();
+                    self.served(member, &model, skipped);
+                    return Ok(response);
+                }
+                Err(e) if is_retryable(&e) => {
+                    self.failed(member, &e);
+                    skipped.push(format!("{}: {}", member.name, e));
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Err(self.exhausted(&skipped))
+    }
+
+    /// Falls over only while nothing has been streamed; once output has
+    /// reached the caller, a failure is returned as is.
+    async fn complete_stream(&self, request: CompletionRequest, tx: mpsc::Sender<StreamChunk>) -> Result<()> {
+        let mut skipped = Vec::new();
+        for member in &self.members {
+            if let Some(left) = member.open_for() {
+                skipped.push(format!("{}: circuit open for {}s", member.name, left.as_secs()));
+                continue;
+            }
+            let request = Self::request_for(member, &request);
+            let model = request.model.clone();
+
+            let (inner_tx, mut inner_rx) = mpsc::channel(64);
+            let forward = async {
+                let mut forwarded = false;
+                while let Some(chunk) = inner_rx.recv().await {
+                    forwarded = true;
+                    let _ = tx.send(chunk).await;
+                }
+                forwarded
+            };
+            let (result, forwarded) = tokio::join!(member.provider.complete_stream(request, inner_tx), forward);
+
+            match result {
+                Ok(()) => {
+                    member.succeeded();
+                    self.served(member, &model, skipped);
+                    return Ok(());
+                }
+                Err(e) if is_retryable(&e) && !forwarded => {
+                    self.failed(member, &e);
+                    skipped.push(format!("{}: {}", member.name, e));
+                }
+                Err(e) => {
+                    // Only failures another provider could avoid count toward the circuit
+                    if is_retryable(&e) {
+                        self.failed(member, &e);
+                    }
+                    return Err(e);
+                }
+            }
+        }
+        Err(self.exhausted(&skipped))
+    }
+
+    async fn list_available_models(&self) -> Result<Vec<ModelInfo>> {
+        self.members[0].provider.list_available_models().await
+    }
+
+    async fn authenticate(&mut self) -> Result<()> {
+        for member in &mut self.members {
+            if !member.provider.is_authenticated()
+                && let Err(e) = member.provider.authenticate().await
+            {
+                warn!(provider = %member.name, error = %e, "Could not authenticate fallback provider");
+            }
+        }
+        Ok(())
+    }
+
+    async fn refresh_auth(&mut self) -> Result<()> {
+        for member in &mut self.members {
+            member.provider.refresh_auth().await?;
+        }
+        Ok(())
+    }
+
+    fn is_authenticated(&self) -> bool {
+        self.members[0].provider.is_authenticated()
+    }
+}
+
+/// Create the configured provider `name`, wrapped in a [`FallbackProvider`] if
+/// its config lists fallbacks or `log` is given. `log` receives which
+/// provider served each request.
+pub fn create_chain(
+    config_manager: &ConfigManager,
+    name: &str,
+    log: Option<ServedLog>,
+) -> Result<Box<dyn Provider + Send + Sync>> {
+    let resolve = |name: &str| {
+        config_manager.get_provider_resolved(name)?
+            .ok_or_else(|| NexusError::ProviderNotConfigured(name.to_string()))
+    };
+    let primary = resolve(name)?;
+    let provider = create_provider(&primary.provider_type, &primary)?;
+    if primary.fallback.is_empty() && log.is_none() {
+        return Ok(provider);
+    }
+
+    let mut chain = FallbackProvider::new((name.to_string(), provider));
+    let mut seen = vec![name.to_string()];
+    for fallback in &primary.fallback {
+        if seen.contains(fallback) {
+            continue;
+        }
+        seen.push(fallback.clone());
+        let config = resolve(fallback)?;
+        let provider = create_provider(&config.provider_type, &config)?;
+        let model = config.default_model.clone().unwrap_or_else(|| provider.info().default_model);
+        chain = chain.with_fallback(fallback.clone(), provider, model);
+    }
+    if let Some(log) = log {
+        chain = chain.with_log(log);
+    }
+    Ok(Box::new(chain))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::mock::MockProvider;
+    use crate::providers::Message;
+
+    fn request() -> CompletionRequest {
+        CompletionRequest::new("primary-model".to_string(), vec![Message::user("hi")])
+    }
+
+    #[tokio::test]
+    async fn test_falls_over_and_opens_circuit() {
+        let log = ServedLog::default();
+        let chain = FallbackProvider::new(("claude".to_string(), Box::new(MockProvider::replying(&[
+            Err("503 overloaded"),
+            Err("502 bad gateway"),
+            Err("500 internal error"),
+        ]))))
+            .with_fallback("local".to_string(), Box::new(MockProvider::replying(&[Ok("one"), Ok("two"), Ok("three"), Ok("four")])), "qwen".to_string())
+            .with_log(log.clone());
+
+        for expected in ["one", "two", "three", "four"] {
+            assert_eq!(chain.complete(request()).await.unwrap().content, expected);
+        }
+
+        let log = log.lock().unwrap();
+        assert!(log.iter().all(|served| served.provider == "local" && served.model == "qwen"));
+        assert!(log[0].skipped[0].starts_with("claude: "));
+        // Three failures open the circuit, so the fourth request doesn't try claude
+        assert!(log[3].skipped[0].contains("circuit open"));
+    }
+
+    #[tokio::test]
+    async fn test_retry_after_and_non_retryable_errors() {
+        let primary = FallbackProvider::new(("a".to_string(), Box::new(MockProvider::replying(&[Ok("unused")]))));
+        // A 429 with Retry-After opens the circuit at once
+        primary.members[0].failed(
+            &NexusError::ApiStatus { status: 429, message: "slow down".into(), retry_after: Some(Duration::from_secs(30)) },
+            COOL_DOWN,
+        );
+        let err = primary.complete(request()).await.unwrap_err();
+        assert!(err.retry_after().unwrap() > Duration::from_secs(25));
+        assert!(err.to_string().contains("a: circuit open"));
+
+        // Errors another provider wouldn't fix are returned without falling over
+        let chain = FallbackProvider::new(("a".to_string(), Box::new(MockProvider::replying(&[Err("401 unauthorized")]))))
+            .with_fallback("b".to_string(), Box::new(MockProvider::replying(&[Ok("from b")])), "m".to_string());
+        assert!(chain.complete(request()).await.unwrap_err().to_string().contains("401"));
+
+        // The circuit closes again after the cool-down
+        let mut chain = FallbackProvider::new(("a".to_string(), Box::new(MockProvider::replying(&[Err("503"), Err("503"), Err("503"), Ok("back")]))))
+            .with_fallback("b".to_string(), Box::new(MockProvider::replying(&[Ok("b1"), Ok("b2"), Ok("b3")])), "m".to_string());
+        chain.cool_down = Duration::from_millis(20);
+        for _ in 0..3 {
+            chain.complete(request()).await.unwrap();
+        }
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        assert_eq!(chain.complete(request()).await.unwrap().content, "back");
+    }
+
+    #[tokio::test]
+    async fn test_stream_errors_open_circuit_only_when_retryable() {
+        let chain = FallbackProvider::new(("a".to_string(), Box::new(MockProvider::replying(&[Err("401"), Err("401"), Err("401"), Ok("still a")]))))
+            .with_fallback("b".to_string(), Box::new(MockProvider::replying(&[Ok("from b")])), "m".to_string());
+        for _ in 0..3 {
+            let (tx, _rx) = mpsc::channel(8);
+            assert!(chain.complete_stream(request(), tx).await.unwrap_err().to_string().contains("401"));
+        }
+        assert!(chain.members[0].open_for().is_none());
+
+        let (tx, mut rx) = mpsc::channel(8);
+        chain.complete_stream(request(), tx).await.unwrap();
+        let mut streamed = String::new();
+        while let Some(chunk) = rx.recv().await {
+            if let StreamChunk::ContentDelta(delta) = chunk {
+                streamed.push_str(&delta);
+            }
+        }
+        assert_eq!(streamed, "still a");
+    }
+
+    #[tokio::test]
+    async fn test_lone_provider_is_logged_and_never_short_circuited() {
+        let log = ServedLog::default();
+        let lone = FallbackProvider::new(("claude".to_string(), Box::new(MockProvider::replying(&[Ok("hi"), Err("503"), Err("503"), Err("503"), Ok("back")]))))
+            .with_log(log.clone());
+
+        assert_eq!(lone.complete(request()).await.unwrap().content, "hi");
+        for _ in 0..3 {
+            assert!(lone.complete(request()).await.is_err());
+        }
+        // With nothing to fall back to, the next request still goes through
+        assert_eq!(lone.complete(request()).await.unwrap().content, "back");
+
+        let log = log.lock().unwrap();
+        assert_eq!(log.len(), 2);
+        assert!(log.iter().all(|served| served.provider == "claude" && served.skipped.is_empty()));
+    }
+}
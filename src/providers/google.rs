@@ -5,13 +5,14 @@
 use crate::config::ProviderConfig;
 use crate::error::{NexusError, Result};
 use crate::providers::{
-    CompletionRequest, CompletionResponse, Message, ModelInfo, Provider, ProviderInfo,
-    StreamChunk, Usage,
+    retry, CompletionRequest, CompletionResponse, ContentPart, Message, ModelInfo, Provider,
+    ProviderInfo, StreamChunk, Usage,
 };
 use async_trait::async_trait;
 use futures_util::StreamExt;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::path::PathBuf;
 use std::sync::Mutex;
 
@@ -275,48 +276,46 @@ impl GoogleProvider {
     fn convert_messages(messages: &[Message]) -> Vec<serde_json::Value> {
         // Separate system messages from conversation
         let mut contents = Vec::new();
+        // functionResponse parts are keyed by function name, not call id
+        let mut call_names: HashMap<&str, &str> = HashMap::new();
 
         for msg in messages {
-            match msg.role {
+            let role = match msg.role {
                 crate::providers::Role::System => {
                     // System messages become the first user message or are prepended
                     // Code Assist doesn't have a separate system role in contents
                     // We'll handle it via systemInstruction in the request
                     continue;
                 }
-                crate::providers::Role::User => {
-                    contents.push(serde_json::json!({
-                        "role": "user",
-                        "parts": [{"text": &msg.content}]
-                    }));
+                crate::providers::Role::Assistant => "model",
+                _ => "user",
+            };
+
+            let parts: Vec<serde_json::Value> = msg.content.iter().map(|part| match part {
+                ContentPart::Text { text } => serde_json::json!({"text": text}),
+                ContentPart::Image { media_type, data } => serde_json::json!({
+                    "inlineData": {"mimeType": media_type, "data": data}
+                }),
+                ContentPart::ToolUse { id, name, input } => {
+                    call_names.insert(id.as_str(), name.as_str());
+                    serde_json::json!({"functionCall": {"name": name, "args": input}})
                 }
-                crate::providers::Role::Assistant => {
-                    contents.push(serde_json::json!({
-                        "role": "model",
-                        "parts": [{"text": &msg.content}]
-                    }));
-                }
-                crate::providers::Role::Tool => {
+                ContentPart::ToolResult { tool_use_id, content, .. } => {
                     // Tool responses need to be formatted as functionResponse
-                    let function_name = msg.name.as_deref().unwrap_or("unknown");
-                    contents.push(serde_json::json!({
-                        "role": "user",
-                        "parts": [{
-                            "functionResponse": {
-                                "name": function_name,
-                                "response": {
-                                    "result": &msg.content
-                                }
+                    let function_name = call_names.get(tool_use_id.as_str()).copied().unwrap_or("unknown");
+                    serde_json::json!({
+                        "functionResponse": {
+                            "name": function_name,
+                            "response": {
+                                "result": content
                             }
-                        }]
-                    }));
-                }
-                _ => {
-                    contents.push(serde_json::json!({
-                        "role": "user",
-                        "parts": [{"text": &msg.content}]
-                    }));
+                        }
+                    })
                 }
+            }).collect();
+
+            if !parts.is_empty() {
+                contents.push(serde_json::json!({"role": role, "parts": parts}));
             }
         }
 
@@ -335,7 +334,7 @@ impl GoogleProvider {
 
         let combined: String = system_msgs
             .iter()
-            .map(|m| m.content.as_str())
+            .map(|m| m.text_content())
             .collect::<Vec<_>>()
             .join("\n\n");
 
@@ -345,15 +344,13 @@ impl GoogleProvider {
         }))
     }
 
-    /// Build the Code Assist request envelope
-    fn build_request(
-        model: &str,
-        project: &str,
-        messages: &[Message],
-        temperature: Option<f32>,
-        max_tokens: Option<u32>,
-        tools: Option<&[crate::executor::tools::Tool]>,
-    ) -> serde_json::Value {
+    /// Build the Code Assist request envelope.
+    ///
+    /// Code Assist has no cache API and no `cachedContent` field, so the
+    /// system prompt and tools always go inline. Gemini 2.5 caches repeated
+    /// prefixes on its own and reports the hits in `cachedContentTokenCount`.
+    fn build_request(model: &str, project: &str, completion: &CompletionRequest) -> serde_json::Value {
+        let messages = &completion.messages;
         let mut contents = Self::convert_messages(messages);
         let system_instruction = Self::extract_system_instruction(messages);
 
@@ -368,10 +365,10 @@ impl GoogleProvider {
         let user_prompt_id = uuid::Uuid::new_v4().to_string();
 
         let mut gen_config = serde_json::json!({});
-        if let Some(temp) = temperature {
+        if let Some(temp) = completion.temperature {
             gen_config["temperature"] = serde_json::json!(temp);
         }
-        if let Some(max) = max_tokens {
+        if let Some(max) = completion.max_tokens {
             gen_config["maxOutputTokens"] = serde_json::json!(max);
         }
 
@@ -383,20 +380,8 @@ impl GoogleProvider {
         if let Some(sys) = system_instruction {
             request["systemInstruction"] = sys;
         }
-
-        // Add tools if provided (Gemini native function calling)
-        if let Some(tools_list) = tools {
-            let function_declarations: Vec<serde_json::Value> = tools_list.iter().map(|tool| {
-                serde_json::json!({
-                    "name": tool.name,
-                    "description": tool.description,
-                    "parameters": tool.parameters,
-                })
-            }).collect();
-
-            request["tools"] = serde_json::json!([{
-                "function_declarations": function_declarations
-            }]);
+        if let Some(tools_list) = completion.tools.as_deref() {
+            request["tools"] = Self::convert_tools(tools_list);
         }
 
         serde_json::json!({
@@ -407,6 +392,33 @@ impl GoogleProvider {
         })
     }
 
+    /// Gemini native function calling declarations
+    fn convert_tools(tools: &[crate::executor::tools::Tool]) -> serde_json::Value {
+        let function_declarations: Vec<serde_json::Value> = tools.iter().map(|tool| {
+            serde_json::json!({
+                "name": tool.name,
+                "description": tool.description,
+                "parameters": tool.parameters,
+            })
+        }).collect();
+
+        serde_json::json!([{
+            "function_declarations": function_declarations
+        }])
+    }
+
+    /// `promptTokenCount` already includes `cachedContentTokenCount`
+    fn parse_usage(usage: &serde_json::Value) -> Usage {
+        let count = |key: &str| usage[key].as_u64().unwrap_or(0) as u32;
+        Usage {
+            prompt_tokens: count("promptTokenCount"),
+            completion_tokens: count("candidatesTokenCount"),
+            total_tokens: count("totalTokenCount"),
+            cache_read_tokens: count("cachedContentTokenCount"),
+            cache_write_tokens: 0,
+        }
+    }
+
     /// Build URL with optional query parameters
     fn build_url(&self, method: &str, query_params: Option<&[(&str, &str)]>) -> String {
         let mut url = format!("{}:{}", self.base_url(), method);
@@ -607,24 +619,12 @@ impl Provider for GoogleProvider {
             request.model.clone()
         };
 
-        let body = Self::build_request(
-            &model,
-            &project,
-            &request.messages,
-            request.temperature,
-            request.max_tokens,
-            request.tools.as_deref(),
-        );
+        let body = Self::build_request(&model, &project, &request);
 
         let resp = self.request_with_retry("generateContent", &body, None).await?;
 
         if !resp.status().is_success() {
-            let status = resp.status();
-            let err = resp.text().await.unwrap_or_default();
-            return Err(NexusError::ApiRequest(format!(
-                "Code Assist API error ({}): {}",
-                status, err
-            )));
+            return Err(retry::error_for_status("Code Assist API error", resp).await);
         }
 
         let data: serde_json::Value = resp.json().await?;
@@ -663,15 +663,7 @@ impl Provider for GoogleProvider {
             .as_str()
             .map(|s| s.to_string());
 
-        let usage = if let Some(u) = response.get("usageMetadata") {
-            Some(Usage {
-                prompt_tokens: u["promptTokenCount"].as_u64().unwrap_or(0) as u32,
-                completion_tokens: u["candidatesTokenCount"].as_u64().unwrap_or(0) as u32,
-                total_tokens: u["totalTokenCount"].as_u64().unwrap_or(0) as u32,
-            })
-        } else {
-            None
-        };
+        let usage = response.get("usageMetadata").map(Self::parse_usage);
 
         let id = data["traceId"]
             .as_str()
@@ -700,26 +692,14 @@ impl Provider for GoogleProvider {
             request.model.clone()
         };
 
-        let body = Self::build_request(
-            &model,
-            &project,
-            &request.messages,
-            request.temperature,
-            request.max_tokens,
-            request.tools.as_deref(),
-        );
+        let body = Self::build_request(&model, &project, &request);
 
         let resp = self
             .request_with_retry("streamGenerateContent", &body, Some(&[("alt", "sse")]))
             .await?;
 
         if !resp.status().is_success() {
-            let status = resp.status();
-            let err = resp.text().await.unwrap_or_default();
-            return Err(NexusError::ApiRequest(format!(
-                "Code Assist streaming error ({}): {}",
-                status, err
-            )));
+            return Err(retry::error_for_status("Code Assist streaming error", resp).await);
         }
 
         // Parse SSE stream
@@ -747,34 +727,29 @@ impl Provider for GoogleProvider {
                     if let Ok(data) = serde_json::from_str::<serde_json::Value>(&json_str) {
                         let response = &data["response"];
 
-                        // Extract text delta
-                        if let Some(text) =
-                            response["candidates"][0]["content"]["parts"][0]["text"].as_str()
-                        {
-                            if !text.is_empty() {
-                                let _ = tx.send(StreamChunk::ContentDelta(text.to_string())).await;
+                        // Extract text deltas and function calls
+                        let parts = response["candidates"][0]["content"]["parts"].as_array();
+                        for part in parts.into_iter().flatten() {
+                            if let Some(text) = part["text"].as_str() {
+                                if !text.is_empty() {
+                                    let _ = tx.send(StreamChunk::ContentDelta(text.to_string())).await;
+                                }
+                            }
+                            if let Some(name) = part["functionCall"]["name"].as_str() {
+                                let _ = tx
+                                    .send(StreamChunk::ToolCall(crate::executor::tools::ToolCall {
+                                        id: format!("call_{}", uuid::Uuid::new_v4()),
+                                        name: name.to_string(),
+                                        arguments: part["functionCall"]["args"].clone(),
+                                    }))
+                                    .await;
                             }
                         }
 
                         // Extract usage if present (usually in last chunk)
                         if let Some(u) = response.get("usageMetadata") {
                             if u["totalTokenCount"].as_u64().unwrap_or(0) > 0 {
-                                let _ = tx
-                                    .send(StreamChunk::Usage(Usage {
-                                        prompt_tokens: u["promptTokenCount"]
-                                            .as_u64()
-                                            .unwrap_or(0)
-                                            as u32,
-                                        completion_tokens: u["candidatesTokenCount"]
-                                            .as_u64()
-                                            .unwrap_or(0)
-                                            as u32,
-                                        total_tokens: u["totalTokenCount"]
-                                            .as_u64()
-                                            .unwrap_or(0)
-                                            as u32,
-                                    }))
-                                    .await;
+                                let _ = tx.send(StreamChunk::Usage(Self::parse_usage(u))).await;
                             }
                         }
                     }
@@ -800,7 +775,7 @@ impl Provider for GoogleProvider {
                 description: Some("Most capable Gemini 2.5 model".to_string()),
                 context_length: Some(1048576),
                 pricing: None,
-                supports_vision: true,
+                supports_vision: Some(true),
                 supports_streaming: true,
                 supports_function_calling: true,
             },
@@ -810,7 +785,7 @@ impl Provider for GoogleProvider {
                 description: Some("Fast and efficient Gemini 2.5 model".to_string()),
                 context_length: Some(1048576),
                 pricing: None,
-                supports_vision: true,
+                supports_vision: Some(true),
                 supports_streaming: true,
                 supports_function_calling: true,
             },
@@ -820,7 +795,7 @@ impl Provider for GoogleProvider {
                 description: Some("Lightweight Gemini 2.5 model".to_string()),
                 context_length: Some(1048576),
                 pricing: None,
-                supports_vision: true,
+                supports_vision: Some(true),
                 supports_streaming: true,
                 supports_function_calling: true,
             },
@@ -835,7 +810,7 @@ impl Provider for GoogleProvider {
                     description: Some("Next-gen Gemini Pro with extended thinking (preview)".to_string()),
                     context_length: Some(1048576),
                     pricing: None,
-                    supports_vision: true,
+                    supports_vision: Some(true),
                     supports_streaming: true,
                     supports_function_calling: true,
                 },
@@ -845,7 +820,7 @@ impl Provider for GoogleProvider {
                     description: Some("Next-gen Gemini Flash with extended thinking (preview)".to_string()),
                     context_length: Some(1048576),
                     pricing: None,
-                    supports_vision: true,
+                    supports_vision: Some(true),
                     supports_streaming: true,
                     supports_function_calling: true,
                 },
@@ -887,3 +862,43 @@ impl Provider for GoogleProvider {
             || self.oauth_refresh_token.is_some()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executor::tools::Tool;
+    use crate::providers::CacheControl;
+
+    #[test]
+    fn test_cache_control_keeps_system_prompt_and_tools_inline() {
+        let request = CompletionRequest {
+            model: "gemini-2.5-pro".to_string(),
+            messages: vec![Message::system("repo context"), Message::user("fix the build")],
+            temperature: None,
+            max_tokens: None,
+            stream: None,
+            tools: Some(vec![Tool { name: "read_file".to_string(), description: "Read a file".to_string(), parameters: serde_json::json!({}) }]),
+            extra_params: None,
+            cache: Some(CacheControl::prefix(2)),
+        };
+
+        let body = GoogleProvider::build_request("gemini-2.5-pro", "proj", &request);
+        assert!(body["request"].get("cachedContent").is_none());
+        assert_eq!(body["request"]["systemInstruction"]["parts"][0]["text"], "repo context");
+        assert_eq!(body["request"]["tools"][0]["function_declarations"][0]["name"], "read_file");
+        assert_eq!(body["request"]["contents"][0]["parts"][0]["text"], "fix the build");
+    }
+
+    #[test]
+    fn test_usage_reports_cached_tokens() {
+        let usage = GoogleProvider::parse_usage(&serde_json::json!({
+            "promptTokenCount": 9000,
+            "cachedContentTokenCount": 8000,
+            "candidatesTokenCount": 100,
+            "totalTokenCount": 9100
+        }));
+        assert_eq!(usage.prompt_tokens, 9000);
+        assert_eq!(usage.cache_read_tokens, 8000);
+        assert_eq!(usage.total_tokens, 9100);
+    }
+}
@@ -0,0 +1,426 @@
+//! Scripted providers for offline runs and tests
+//!
+//! [`MockProvider`] plays back responses from a cassette file instead of
+//! calling an API, so the agent loop, swarm and healer can run without a
+//! network. [`RecordingProvider`] wraps a real provider and saves every
+//! exchange to a cassette that the mock can replay later. Setting
+//! `NEXUS_RECORD_CASSETTE=<path>` records whatever provider nexus creates.
+//!
+//! A cassette is JSON or YAML (by extension):
+//!
+//! ```yaml
+//! interactions:
+//!   - when: "fix the bug"        # optional: the last message must contain this
+//!     response:
+//!       content: "Reading the file."
+//!       tool_calls:
+//!         - name: read_file
+//!           arguments: { path: src/lib.rs }
+//!   - response:
+//!       content: "Done."
+//!   - error: "rate limited"      # fail the request instead
+//! ```
+//!
+//! Each request takes the first unused interaction whose `when` matches, so
+//! concurrent callers such as swarm workers can be scripted independently.
+
+use crate::config::ProviderConfig;
+use crate::error::{NexusError, Result};
+use crate::executor::tools::ToolCall;
+use crate::providers::{CompletionRequest, CompletionResponse, Provider, ProviderInfo, StreamChunk, Usage};
+use async_trait::async_trait;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc;
+use tracing::warn;
+
+/// Environment variable naming a cassette to record every provider into
+pub const RECORD_ENV: &str = "NEXUS_RECORD_CASSETTE";
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Cassette {
+    #[serde(default)]
+    pub interactions: Vec<Interaction>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Interaction {
+    /// Only answer requests whose last message contains this text
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub when: Option<String>,
+    /// The request that was recorded; not used for matching
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request: Option<CompletionRequest>,
+    #[serde(default)]
+    pub response: ScriptedResponse,
+    /// Fail the request with this message instead of responding
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ScriptedResponse {
+    #[serde(default)]
+    pub content: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tool_calls: Vec<ScriptedToolCall>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage: Option<Usage>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub finish_reason: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScriptedToolCall {
+    /// Generated when left out
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    pub name: String,
+    #[serde(default)]
+    pub arguments: serde_json::Value,
+}
+
+impl Cassette {
+    /// Read a cassette; `.yaml`/`.yml` files are YAML, anything else JSON
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path).map_err(|e| {
+            NexusError::Configuration(format!("Failed to read cassette {}: {}", path.display(), e))
+        })?;
+        let yaml = matches!(path.extension().and_then(|e| e.to_str()), Some("yaml" | "yml"));
+        let parsed = match yaml {
+            true => ::config::Config::builder()
+                .add_source(::config::File::from_str(&text, ::config::FileFormat::Yaml))
+                .build()
+                .and_then(|c| c.try_deserialize())
+                .map_err(|e| e.to_string()),
+            false => serde_json::from_str(&text).map_err(|e| e.to_string()),
+        };
+        parsed.map_err(|e| NexusError::Configuration(format!("Invalid cassette {}: {}", path.display(), e)))
+    }
+
+    /// Write the cassette as JSON
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+/// Plays back a cassette
+pub struct MockProvider {
+    interactions: Mutex<Vec<Option<Interaction>>>,
+    calls: Mutex<usize>,
+}
+
+impl MockProvider {
+    pub fn new(cassette: Cassette) -> Self {
+        Self {
+            interactions: Mutex::new(cassette.interactions.into_iter().map(Some).collect()),
+            calls: Mutex::new(0),
+        }
+    }
+
+    /// Answer each request in turn with the given content, or fail it with
+    /// the given error
+    #[cfg(test)]
+    pub fn replying(replies: &[std::result::Result<&str, &str>]) -> Self {
+        let interactions = replies.iter().map(|reply| match reply {
+            Ok(content) => Interaction {
+                response: ScriptedResponse { content: content.to_string(), ..ScriptedResponse::default() },
+                ..Interaction::default()
+            },
+            Err(error) => Interaction { error: Some(error.to_string()), ..Interaction::default() },
+        }).collect();
+        Self::new(Cassette { interactions })
+    }
+
+    pub fn from_config(config: &ProviderConfig) -> Result<Self> {
+        let path = config.cassette.as_ref().ok_or_else(|| {
+            NexusError::Configuration("The mock provider needs a `cassette` file".to_string())
+        })?;
+        Ok(Self::new(Cassette::load(path)?))
+    }
+
+    pub fn static_info() -> ProviderInfo {
+        ProviderInfo {
+            name: "mock".to_string(),
+            display_name: "Mock (cassette replay)".to_string(),
+            supports_oauth: false,
+            default_model: "mock".to_string(),
+            available_models: vec!["mock".to_string()],
+        }
+    }
+
+    fn next(&self, request: &CompletionRequest) -> Option<Interaction> {
+        let last = request.messages.last().map(|m| m.flattened_text()).unwrap_or_default();
+        let mut interactions = self.interactions.lock().unwrap_or_else(|e| e.into_inner());
+        interactions
+            .iter_mut()
+            .find(|slot| slot.as_ref().is_some_and(|i| i.when.as_ref().is_none_or(|when| last.contains(when.as_str()))))
+            .and_then(Option::take)
+    }
+}
+
+#[async_trait]
+impl Provider for MockProvider {
+    fn info(&self) -> ProviderInfo {
+        Self::static_info()
+    }
+
+    async fn complete(&self, request: CompletionRequest) -> Result<CompletionResponse> {
+        let call = {
+            let mut calls = self.calls.lock().unwrap_or_else(|e| e.into_inner());
+            *calls += 1;
+            *calls
+        };
+        let Some(interaction) = self.next(&request) else {
+            let last = request.messages.last().map(|m| m.flattened_text()).unwrap_or_default();
+            return Err(NexusError::ApiRequest(format!(
+                "Mock cassette has no response for request {} (last message: {:.80})",
+                call, last
+            )));
+        };
+        if let Some(error) = interaction.error {
+            return Err(NexusError::ApiRequest(error));
+        }
+
+        let scripted = interaction.response;
+        let tool_calls: Vec<ToolCall> = scripted.tool_calls.into_iter().enumerate().map(|(i, call_spec)| ToolCall {
+            id: call_spec.id.unwrap_or_else(|| format!("mock_{}_{}", call, i)),
+            name: call_spec.name,
+            arguments: call_spec.arguments,
+        }).collect();
+        let mut response = CompletionResponse::new(format!("mock-{}", call), request.model, scripted.content);
+        response.finish_reason = scripted.finish_reason;
+        response.usage = scripted.usage;
+        response.tool_calls = (!tool_calls.is_empty()).then_some(tool_calls);
+        Ok(response)
+    }
+
+    async fn authenticate(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    async fn refresh_auth(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn is_authenticated(&self) -> bool {
+        true
+    }
+}
+
+/// Cassettes being recorded in this process, by path. Every recorder on a
+/// path shares one, so a fallback chain or router recording several
+/// providers to `NEXUS_RECORD_CASSETTE` keeps all their exchanges.
+static RECORDING: Lazy<Mutex<HashMap<PathBuf, Arc<Mutex<Cassette>>>>> = Lazy::new(Default::default);
+
+/// Passes requests to another provider and saves each exchange to a cassette
+pub struct RecordingProvider {
+    inner: Box<dyn Provider + Send + Sync>,
+    path: PathBuf,
+    cassette: Arc<Mutex<Cassette>>,
+}
+
+impl RecordingProvider {
+    /// Record into `path`, adding to the cassette already there
+    pub fn new(inner: Box<dyn Provider + Send + Sync>, path: PathBuf) -> Self {
+        let path = std::path::absolute(&path).unwrap_or(path);
+        let cassette = RECORDING
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entry(path.clone())
+            .or_insert_with(|| Arc::new(Mutex::new(Cassette::load(&path).unwrap_or_default())))
+            .clone();
+        Self { inner, path, cassette }
+    }
+
+    fn record(&self, request: CompletionRequest, result: std::result::Result<ScriptedResponse, String>) {
+        let interaction = match result {
+            Ok(response) => Interaction { request: Some(request), response, ..Interaction::default() },
+            Err(error) => Interaction { request: Some(request), error: Some(error), ..Interaction::default() },
+        };
+        let mut cassette = self.cassette.lock().unwrap_or_else(|e| e.into_inner());
+        cassette.interactions.push(interaction);
+        if let Err(e) = cassette.save(&self.path) {
+            warn!(error = %e, path = %self.path.display(), "Failed to save cassette");
+        }
+    }
+}
+
+impl From<&CompletionResponse> for ScriptedResponse {
+    fn from(response: &CompletionResponse) -> Self {
+        Self {
+            content: response.content.clone(),
+            tool_calls: response.tool_calls.iter().flatten().cloned().map(ScriptedToolCall::from).collect(),
+            usage: response.usage.clone(),
+            finish_reason: response.finish_reason.clone(),
+        }
+    }
+}
+
+impl From<ToolCall> for ScriptedToolCall {
+    fn from(call: ToolCall) -> Self {
+        Self { id: Some(call.id), name: call.name, arguments: call.arguments }
+    }
+}
+
+#[async_trait]
+impl Provider for RecordingProvider {
+    fn info(&self) -> ProviderInfo {
+        self.inner.info()
+    }
+
+    async fn complete(&self, request: CompletionRequest) -> Result<CompletionResponse> {
+        let result = self.inner.complete(request.clone()).await;
+        self.record(request, result.as_ref().map(ScriptedResponse::from).map_err(|e| e.to_string()));
+        result
+    }
+
+    async fn complete_stream(&self, request: CompletionRequest, tx: mpsc::Sender<StreamChunk>) -> Result<()> {
+        let (inner_tx, mut inner_rx) = mpsc::channel(64);
+        let forward = async {
+            let mut response = ScriptedResponse::default();
+            while let Some(chunk) = inner_rx.recv().await {
+                match &chunk {
+                    StreamChunk::ContentDelta(delta) => response.content.push_str(delta),
+                    StreamChunk::ToolCall(call) => response.tool_calls.push(call.clone().into()),
+                    StreamChunk::Usage(usage) => response.usage = Some(usage.clone()),
+                    StreamChunk::Done => {}
+                }
+                let _ = tx.send(chunk).await;
+            }
+            response
+        };
+        let (result, response) = tokio::join!(self.inner.complete_stream(request.clone(), inner_tx), forward);
+        self.record(request, result.as_ref().map(|_| response).map_err(|e| e.to_string()));
+        result
+    }
+
+    async fn list_available_models(&self) -> Result<Vec<crate::providers::ModelInfo>> {
+        self.inner.list_available_models().await
+    }
+
+    async fn authenticate(&mut self) -> Result<()> {
+        self.inner.authenticate().await
+    }
+
+    async fn refresh_auth(&mut self) -> Result<()> {
+        self.inner.refresh_auth().await
+    }
+
+    fn is_authenticated(&self) -> bool {
+        self.inner.is_authenticated()
+    }
+}
+
+/// Wrap `provider` in a [`RecordingProvider`] when [`RECORD_ENV`] is set
+pub fn record_if_requested(provider: Box<dyn Provider + Send + Sync>) -> Box<dyn Provider + Send + Sync> {
+    match std::env::var_os(RECORD_ENV) {
+        Some(path) if !path.is_empty() => Box::new(RecordingProvider::new(provider, PathBuf::from(path))),
+        _ => provider,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::Message;
+    use tempfile::TempDir;
+
+    fn request(text: &str) -> CompletionRequest {
+        CompletionRequest::new("mock".to_string(), vec![Message::user(text)])
+    }
+
+    #[tokio::test]
+    async fn test_replays_yaml_cassette_by_match() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("cassette.yaml");
+        std::fs::write(&path, r#"
+interactions:
+  - when: "second"
+    response:
+      content: "for the second"
+  - response:
+      content: "Reading it."
+      tool_calls:
+        - name: read_file
+          arguments:
+            path: src/lib.rs
+      usage: { prompt_tokens: 3, completion_tokens: 2, total_tokens: 5 }
+  - error: "rate limited"
+"#).unwrap();
+        let provider = MockProvider::new(Cassette::load(&path).unwrap());
+
+        let first = provider.complete(request("first")).await.unwrap();
+        assert_eq!(first.content, "Reading it.");
+        let calls = first.tool_calls.unwrap();
+        assert_eq!(calls[0].name, "read_file");
+        assert_eq!(calls[0].arguments["path"], "src/lib.rs");
+        assert_eq!(first.usage.unwrap().total_tokens, 5);
+
+        assert_eq!(provider.complete(request("the second one")).await.unwrap().content, "for the second");
+        assert!(provider.complete(request("third")).await.unwrap_err().to_string().contains("rate limited"));
+        assert!(provider.complete(request("fourth")).await.unwrap_err().to_string().contains("no response for request 4"));
+    }
+
+    #[tokio::test]
+    async fn test_recorded_stream_replays() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("recorded.json");
+        let script = Cassette {
+            interactions: vec![Interaction {
+                response: ScriptedResponse {
+                    content: "Done.".to_string(),
+                    tool_calls: vec![ScriptedToolCall { id: Some("call_1".to_string()), name: "glob".to_string(), arguments: serde_json::json!({"pattern": "*.rs"}) }],
+                    ..ScriptedResponse::default()
+                },
+                ..Interaction::default()
+            }],
+        };
+        let recorder = RecordingProvider::new(Box::new(MockProvider::new(script)), path.clone());
+
+        let (tx, mut rx) = mpsc::channel(16);
+        recorder.complete_stream(request("list files"), tx).await.unwrap();
+        let mut chunks = 0;
+        while rx.recv().await.is_some() {
+            chunks += 1;
+        }
+        assert_eq!(chunks, 3);
+        assert!(recorder.complete(request("again")).await.is_err());
+
+        let cassette = Cassette::load(&path).unwrap();
+        assert_eq!(cassette.interactions.len(), 2);
+        assert_eq!(cassette.interactions[0].request.as_ref().unwrap().messages[0].text_content(), "list files");
+        assert!(cassette.interactions[1].error.is_some());
+
+        let replay = MockProvider::new(cassette).complete(request("list files")).await.unwrap();
+        assert_eq!(replay.content, "Done.");
+        assert_eq!(replay.tool_calls.unwrap()[0].id, "call_1");
+    }
+
+    #[tokio::test]
+    async fn test_recorders_share_a_cassette_path() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("shared.json");
+        let script = |reply: &str| Cassette {
+            interactions: vec![Interaction {
+                response: ScriptedResponse { content: reply.to_string(), ..ScriptedResponse::default() },
+                ..Interaction::default()
+            }],
+        };
+        let primary = RecordingProvider::new(Box::new(MockProvider::new(script("from primary"))), path.clone());
+        let fallback = RecordingProvider::new(Box::new(MockProvider::new(script("from fallback"))), path.clone());
+
+        primary.complete(request("one")).await.unwrap();
+        fallback.complete(request("two")).await.unwrap();
+
+        let replies: Vec<String> = Cassette::load(&path).unwrap().interactions.into_iter().map(|i| i.response.content).collect();
+        assert_eq!(replies, vec!["from primary", "from fallback"]);
+    }
+}
@@ -1,21 +1,30 @@
-use crate::error::Result;
+use crate::error::{NexusError, Result};
+use crate::executor::tools::{ToolCall, ToolResult};
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
 pub mod claude;
+pub mod fallback;
 pub mod google;
+pub mod mock;
 pub mod model_capabilities;
+pub mod openai_compatible;
+pub mod openai_wire;
 pub mod opencode;
 pub mod openrouter;
 pub mod retry;
+pub mod sse;
 pub mod token_budget;
+pub mod tokenizer;
 
 /// A chunk from a streaming completion response
 #[derive(Debug, Clone)]
 pub enum StreamChunk {
     /// Incremental content delta
     ContentDelta(String),
+    /// A complete native tool call, emitted once all of its deltas have arrived
+    ToolCall(crate::executor::tools::ToolCall),
     /// Usage information (sent at the end)
     Usage(Usage),
     /// Stream is done
@@ -25,11 +34,160 @@ pub enum StreamChunk {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Message {
     pub role: Role,
-    pub content: String,
+    pub content: Vec<ContentPart>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub name: Option<String>,
 }
 
+/// A typed piece of message content. Providers translate these to their own wire format.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ContentPart {
+    Text {
+        text: String,
+    },
+    /// Base64-encoded image data
+    Image {
+        media_type: String,
+        data: String,
+    },
+    /// A native tool call requested by the assistant
+    ToolUse {
+        id: String,
+        name: String,
+        input: serde_json::Value,
+    },
+    /// The result of a tool call, tied to the `ToolUse` that requested it
+    ToolResult {
+        tool_use_id: String,
+        content: String,
+        #[serde(default)]
+        is_error: bool,
+    },
+}
+
+/// Largest image we send. Anthropic's per-image limit is the strictest.
+const MAX_IMAGE_BYTES: usize = 5 * 1024 * 1024;
+
+impl ContentPart {
+    /// Read an image file into a base64 `Image` part. The format is taken from
+    /// the file's contents, not its extension.
+    pub fn image_file(path: &std::path::Path) -> Result<Self> {
+        use base64::Engine;
+
+        let bytes = std::fs::read(path)
+            .map_err(|e| NexusError::Image(format!("{}: {}", path.display(), e)))?;
+        let media_type = image_media_type(&bytes).ok_or_else(|| NexusError::Image(format!(
+            "{}: not a PNG, JPEG, GIF or WebP image", path.display()
+        )))?;
+        if bytes.len() > MAX_IMAGE_BYTES {
+            return Err(NexusError::Image(format!(
+                "{}: {:.1} MB is over the {} MB limit", path.display(),
+                bytes.len() as f64 / (1024.0 * 1024.0), MAX_IMAGE_BYTES / (1024 * 1024)
+            )));
+        }
+        Ok(ContentPart::Image {
+            media_type: media_type.to_string(),
+            data: base64::engine::general_purpose::STANDARD.encode(&bytes),
+        })
+    }
+}
+
+/// Sniff the formats every vision provider accepts
+fn image_media_type(bytes: &[u8]) -> Option<&'static str> {
+    match bytes {
+        [0x89, b'P', b'N', b'G', ..] => Some("image/png"),
+        [0xFF, 0xD8, 0xFF, ..] => Some("image/jpeg"),
+        [b'G', b'I', b'F', b'8', ..] => Some("image/gif"),
+        [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'E', b'B', b'P', ..] => Some("image/webp"),
+        _ => None,
+    }
+}
+
+impl Message {
+    /// Create a message with a single text part
+    pub fn text(role: Role, text: impl Into<String>) -> Self {
+        Self {
+            role,
+            content: vec![ContentPart::Text { text: text.into() }],
+            name: None,
+        }
+    }
+
+    pub fn system(text: impl Into<String>) -> Self {
+        Self::text(Role::System, text)
+    }
+
+    pub fn user(text: impl Into<String>) -> Self {
+        Self::text(Role::User, text)
+    }
+
+    pub fn assistant(text: impl Into<String>) -> Self {
+        Self::text(Role::Assistant, text)
+    }
+
+    /// Create a user message with `images` after the text
+    pub fn user_with_images(text: impl Into<String>, images: Vec<ContentPart>) -> Self {
+        let mut message = Self::user(text);
+        message.content.extend(images);
+        message
+    }
+
+    pub fn has_images(&self) -> bool {
+        self.content.iter().any(|part| matches!(part, ContentPart::Image { .. }))
+    }
+
+    /// Create an assistant message carrying native tool calls alongside any text
+    pub fn assistant_with_tool_calls(text: impl Into<String>, tool_calls: &[ToolCall]) -> Self {
+        let text = text.into();
+        let mut content = Vec::with_capacity(tool_calls.len() + 1);
+        if !text.is_empty() {
+            content.push(ContentPart::Text { text });
+        }
+        content.extend(tool_calls.iter().map(|call| ContentPart::ToolUse {
+            id: call.id.clone(),
+            name: call.name.clone(),
+            input: call.arguments.clone(),
+        }));
+        Self {
+            role: Role::Assistant,
+            content,
+            name: None,
+        }
+    }
+
+    /// Create a tool message carrying the results of one assistant turn's tool calls
+    pub fn tool_results(results: &[ToolResult]) -> Self {
+        Self {
+            role: Role::Tool,
+            content: results.iter().map(|result| ContentPart::ToolResult {
+                tool_use_id: result.tool_call_id.clone(),
+                content: result.render(),
+                is_error: !result.success,
+            }).collect(),
+            name: None,
+        }
+    }
+
+    /// Concatenated text of all text parts
+    pub fn text_content(&self) -> String {
+        self.content.iter().filter_map(|part| match part {
+            ContentPart::Text { text } => Some(text.as_str()),
+            _ => None,
+        }).collect::<Vec<_>>().join("\n")
+    }
+
+    /// Rough text rendering of every part, used for token estimates and logs
+    pub fn flattened_text(&self) -> String {
+        self.content.iter().map(|part| match part {
+            ContentPart::Text { text } => text.clone(),
+            ContentPart::Image { media_type, .. } => format!("[image: {}]", media_type),
+            ContentPart::ToolUse { name, input, .. } => format!("{} {}", name, input),
+            ContentPart::ToolResult { content, .. } => content.clone(),
+        }).collect::<Vec<_>>().join("\n")
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "lowercase")]
 pub enum Role {
@@ -54,6 +212,37 @@ pub struct CompletionRequest {
     pub tools: Option<Vec<crate::executor::tools::Tool>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub extra_params: Option<HashMap<String, serde_json::Value>>,
+    /// Prompt caching breakpoints; providers without caching ignore them
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache: Option<CacheControl>,
+}
+
+/// Which stable prefix of a request the provider should cache. Anthropic marks
+/// the chosen blocks with `cache_control`; providers that cache on their own
+/// ignore it.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct CacheControl {
+    /// Cache the tool definitions
+    #[serde(default)]
+    pub tools: bool,
+    /// Cache the system prompt (and the tools, which come before it)
+    #[serde(default)]
+    pub system: bool,
+    /// Cache the conversation up to and including this many messages
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub history: Option<usize>,
+}
+
+impl CacheControl {
+    /// Cache everything a multi-turn task resends unchanged: tools, system
+    /// prompt and the first `history` messages
+    pub fn prefix(history: usize) -> Self {
+        Self {
+            tools: true,
+            system: true,
+            history: (history > 0).then_some(history),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -67,6 +256,22 @@ pub struct CompletionResponse {
     pub tool_calls: Option<Vec<crate::executor::tools::ToolCall>>,
 }
 
+impl CompletionRequest {
+    /// A request with every option left to the provider
+    pub fn new(model: String, messages: Vec<Message>) -> Self {
+        Self {
+            model,
+            messages,
+            temperature: None,
+            max_tokens: None,
+            stream: None,
+            tools: None,
+            extra_params: None,
+            cache: None,
+        }
+    }
+}
+
 impl CompletionResponse {
     pub fn new(id: String, model: String, content: String) -> Self {
         Self {
@@ -80,11 +285,18 @@ impl CompletionResponse {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Usage {
+    /// All prompt tokens, including those read from or written to the cache
     pub prompt_tokens: u32,
     pub completion_tokens: u32,
     pub total_tokens: u32,
+    /// Prompt tokens served from the provider's prompt cache
+    #[serde(default)]
+    pub cache_read_tokens: u32,
+    /// Prompt tokens written to the provider's prompt cache
+    #[serde(default)]
+    pub cache_write_tokens: u32,
 }
 
 #[derive(Debug, Clone)]
@@ -107,8 +319,9 @@ pub struct ModelInfo {
     pub context_length: Option<u32>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub pricing: Option<ModelPricing>,
+    /// Whether the model takes images; `None` when the listing doesn't say
     #[serde(default)]
-    pub supports_vision: bool,
+    pub supports_vision: Option<bool>,
     #[serde(default)]
     pub supports_streaming: bool,
     #[serde(default)]
@@ -132,11 +345,15 @@ pub trait Provider: Send + Sync {
     /// Stream a completion response. Default wraps `complete()` as a single chunk.
     async fn complete_stream(
         &self,
-        request: CompletionRequest,
+        mut request: CompletionRequest,
         tx: tokio::sync::mpsc::Sender<StreamChunk>,
     ) -> Result<()> {
+        request.stream = Some(false);
         let resp = self.complete(request).await?;
         let _ = tx.send(StreamChunk::ContentDelta(resp.content)).await;
+        for call in resp.tool_calls.into_iter().flatten() {
+            let _ = tx.send(StreamChunk::ToolCall(call)).await;
+        }
         if let Some(usage) = resp.usage {
             let _ = tx.send(StreamChunk::Usage(usage)).await;
         }
@@ -161,29 +378,18 @@ pub trait Provider: Send + Sync {
 pub fn create_provider(
     provider_type: &crate::config::ProviderType,
     config: &crate::config::ProviderConfig,
-) -> Result<Box<dyn Provider>> {
-    use crate::config::ProviderType;
-    
-    match provider_type {
-        ProviderType::Opencode => Ok(Box::new(opencode::OpencodeProvider::new(config))),
-        ProviderType::Openrouter => Ok(Box::new(openrouter::OpenRouterProvider::new(config))),
-        ProviderType::Google => Ok(Box::new(google::GoogleProvider::new(config))),
-        ProviderType::Claude => Ok(Box::new(claude::ClaudeProvider::new(config))),
-    }
-}
-
-pub fn create_provider_arc(
-    provider_type: &crate::config::ProviderType,
-    config: &crate::config::ProviderConfig,
-) -> Result<std::sync::Arc<dyn Provider + Send + Sync>> {
+) -> Result<Box<dyn Provider + Send + Sync>> {
     use crate::config::ProviderType;
     
-    match provider_type {
-        ProviderType::Opencode => Ok(std::sync::Arc::new(opencode::OpencodeProvider::new(config))),
-        ProviderType::Openrouter => Ok(std::sync::Arc::new(openrouter::OpenRouterProvider::new(config))),
-        ProviderType::Google => Ok(std::sync::Arc::new(google::GoogleProvider::new(config))),
-        ProviderType::Claude => Ok(std::sync::Arc::new(claude::ClaudeProvider::new(config))),
-    }
+    let provider: Box<dyn Provider + Send + Sync> = match provider_type {
+        ProviderType::Opencode => Box::new(opencode::OpencodeProvider::new(config)),
+        ProviderType::Openrouter => Box::new(openrouter::OpenRouterProvider::new(config)),
+        ProviderType::Google => Box::new(google::GoogleProvider::new(config)),
+        ProviderType::Claude => Box::new(claude::ClaudeProvider::new(config)),
+        ProviderType::OpenaiCompatible => Box::new(openai_compatible::OpenAiCompatibleProvider::new(config)),
+        ProviderType::Mock => return Ok(Box::new(mock::MockProvider::from_config(config)?)),
+    };
+    Ok(mock::record_if_requested(provider))
 }
 
 pub fn list_available_providers() -> Vec<ProviderInfo> {
@@ -192,5 +398,25 @@ pub fn list_available_providers() -> Vec<ProviderInfo> {
         openrouter::OpenRouterProvider::static_info(),
         google::GoogleProvider::static_info(),
         claude::ClaudeProvider::static_info(),
+        openai_compatible::OpenAiCompatibleProvider::static_info(),
     ]
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_image_file_sniffs_format_and_encodes() {
+        let dir = tempfile::tempdir().unwrap();
+        let png = dir.path().join("shot.jpg");
+        std::fs::write(&png, b"\x89PNG\r\n\x1a\nrest").unwrap();
+        let text = dir.path().join("notes.png");
+        std::fs::write(&text, "not an image").unwrap();
+
+        // The contents decide the type, whatever the extension says
+        let part = ContentPart::image_file(&png).unwrap();
+        assert_eq!(part, ContentPart::Image { media_type: "image/png".to_string(), data: "iVBORw0KGgpyZXN0".to_string() });
+        assert!(matches!(ContentPart::image_file(&text), Err(NexusError::Image(_))));
+    }
+}
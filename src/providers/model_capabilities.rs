@@ -1,5 +1,14 @@
+use crate::error::Result;
+use crate::hierarchy;
+use crate::providers::ModelInfo;
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use once_cell::sync::Lazy;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+use tracing::warn;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ModelCapabilities {
@@ -10,13 +19,24 @@ pub struct ModelCapabilities {
     pub reasoning_score: u8,       // 1-10 (10 = best reasoning)
     pub coding_score: u8,          // 1-10 (10 = best at code)
     pub cost_per_1m_tokens: f64,   // Estimated cost in USD
-    pub context_window: u32,       // Max tokens
+    pub context_window: u32,       // Max tokens, 0 if unknown
     pub supports_streaming: bool,
     pub supports_tools: bool,
+    /// Whether the model takes images; `None` when nothing says either way
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub supports_vision: Option<bool>,
+    /// Listed price per million prompt tokens, when a provider reported one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prompt_cost_per_1m: Option<f64>,
+    /// Listed price per million completion tokens
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub completion_cost_per_1m: Option<f64>,
     pub release_date: String,      // "2024-12-01"
 }
 
 impl ModelCapabilities {
+    /// The compiled-in table. Prefer [`ModelRegistry::current`], which layers
+    /// provider listings and user overrides on top of it.
     pub fn get_all() -> &'static [ModelCapabilities] {
         &MODEL_RANKINGS
     }
@@ -29,48 +49,265 @@ impl ModelCapabilities {
         MODEL_RANKINGS.iter().filter(|m| m.provider == provider).collect()
     }
 
+    /// An entry for a model the compiled-in table doesn't know, with middling
+    /// scores until an override says otherwise
+    fn unknown(id: &str) -> Self {
+        Self {
+            id: id.to_string(),
+            provider: String::new(),
+            display_name: id.to_string(),
+            speed_score: 5,
+            reasoning_score: 5,
+            coding_score: 5,
+            cost_per_1m_tokens: 0.0,
+            context_window: 0,
+            supports_streaming: true,
+            supports_tools: false,
+            supports_vision: None,
+            prompt_cost_per_1m: None,
+            completion_cost_per_1m: None,
+            release_date: String::new(),
+        }
+    }
+}
+
+/// Some fields of a [`ModelCapabilities`]: what a provider listing reported,
+/// or a user override. Unset fields leave the layer below alone.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ModelPatch {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub provider: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub display_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub speed_score: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reasoning_score: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub coding_score: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cost_per_1m_tokens: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prompt_cost_per_1m: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub completion_cost_per_1m: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub context_window: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub supports_streaming: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub supports_tools: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub supports_vision: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub release_date: Option<String>,
+}
+
+impl ModelPatch {
+    /// What `provider`'s listing says about a model. Listings report `false`
+    /// for capabilities they know nothing about, so only `true` is taken.
+    pub fn from_listing(provider: &str, info: &ModelInfo) -> Self {
+        let pricing = info.pricing.as_ref();
+        let prompt = pricing.and_then(|p| p.prompt);
+        Self {
+            provider: Some(provider.to_string()),
+            display_name: Some(info.name.clone()).filter(|name| name != &info.id),
+            // The compiled-in table's blended cost tracks the prompt price
+            cost_per_1m_tokens: prompt,
+            prompt_cost_per_1m: prompt,
+            completion_cost_per_1m: pricing.and_then(|p| p.completion),
+            context_window: info.context_length,
+            supports_streaming: info.supports_streaming.then_some(true),
+            supports_tools: info.supports_function_calling.then_some(true),
+            supports_vision: info.supports_vision,
+            ..Self::default()
+        }
+    }
+
+    fn apply(&self, model: &mut ModelCapabilities) {
+        fn set<T: Clone>(field: &mut T, value: &Option<T>) {
+            if let Some(value) = value {
+                *field = value.clone();
+            }
+        }
+        set(&mut model.provider, &self.provider);
+        set(&mut model.display_name, &self.display_name);
+        set(&mut model.speed_score, &self.speed_score);
+        set(&mut model.reasoning_score, &self.reasoning_score);
+        set(&mut model.coding_score, &self.coding_score);
+        set(&mut model.cost_per_1m_tokens, &self.cost_per_1m_tokens);
+        set(&mut model.context_window, &self.context_window);
+        set(&mut model.supports_streaming, &self.supports_streaming);
+        set(&mut model.supports_tools, &self.supports_tools);
+        set(&mut model.release_date, &self.release_date);
+        if self.supports_vision.is_some() {
+            model.supports_vision = self.supports_vision;
+        }
+        if self.prompt_cost_per_1m.is_some() {
+            model.prompt_cost_per_1m = self.prompt_cost_per_1m;
+        }
+        if self.completion_cost_per_1m.is_some() {
+            model.completion_cost_per_1m = self.completion_cost_per_1m;
+        }
+    }
+}
+
+/// What the last refresh learned from provider listings (`models.json`)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DiscoveredModels {
+    pub refreshed_at: Option<DateTime<Utc>>,
+    pub models: BTreeMap<String, ModelPatch>,
+}
+
+/// Model capabilities in three layers: the compiled-in table, what provider
+/// listings reported (refreshed with `nexus models refresh`), and the user's
+/// overrides in `model_overrides.json`, keyed by model id:
+///
+/// ```json
+/// { "qwen2.5-coder": { "coding_score": 8, "context_window": 32768 } }
+/// ```
+pub struct ModelRegistry {
+    discovered: DiscoveredModels,
+    overrides: BTreeMap<String, ModelPatch>,
+    merged: HashMap<String, ModelCapabilities>,
+}
+
+const DISCOVERED_FILE: &str = "models.json";
+const OVERRIDES_FILE: &str = "model_overrides.json";
+
+static CURRENT: Lazy<RwLock<Arc<ModelRegistry>>> = Lazy::new(|| {
+    let registry = ModelRegistry::load(&hierarchy::config_dir()).unwrap_or_else(|e| {
+        warn!(error = %e, "Could not load the model registry; using built-in model data");
+        ModelRegistry::builtin()
+    });
+    RwLock::new(Arc::new(registry))
+});
+
+impl ModelRegistry {
+    /// Just the compiled-in table
+    pub fn builtin() -> Self {
+        Self::from_layers(DiscoveredModels::default(), BTreeMap::new())
+    }
+
+    pub fn load(config_dir: &Path) -> Result<Self> {
+        let read = |name: &str| -> Result<Option<String>> {
+            let path = config_dir.join(name);
+            if path.exists() { Ok(Some(fs::read_to_string(path)?)) } else { Ok(None) }
+        };
+        let discovered = match read(DISCOVERED_FILE)? {
+            Some(json) => serde_json::from_str(&json)?,
+            None => DiscoveredModels::default(),
+        };
+        let overrides = match read(OVERRIDES_FILE)? {
+            Some(json) => serde_json::from_str(&json)?,
+            None => BTreeMap::new(),
+        };
+        Ok(Self::from_layers(discovered, overrides))
+    }
+
+    /// Save what was discovered. Overrides are the user's file and are left alone.
+    pub fn save(&self, config_dir: &Path) -> Result<()> {
+        fs::create_dir_all(config_dir)?;
+        fs::write(config_dir.join(DISCOVERED_FILE), serde_json::to_string_pretty(&self.discovered)?)?;
+        Ok(())
+    }
+
+    /// The registry loaded from the config directory, shared by the process
+    pub fn current() -> Arc<Self> {
+        CURRENT.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Replace the shared registry, e.g. after a refresh
+    pub fn set_current(registry: Self) {
+        *CURRENT.write().unwrap_or_else(|e| e.into_inner()) = Arc::new(registry);
+    }
+
+    fn from_layers(discovered: DiscoveredModels, overrides: BTreeMap<String, ModelPatch>) -> Self {
+        let mut merged: HashMap<String, ModelCapabilities> = MODEL_RANKINGS.iter()
+            .map(|m| (m.id.clone(), m.clone()))
+            .collect();
+        for (id, patch) in discovered.models.iter().chain(&overrides) {
+            let model = merged.entry(id.clone()).or_insert_with(|| ModelCapabilities::unknown(id));
+            patch.apply(model);
+        }
+        Self { discovered, overrides, merged }
+    }
+
+    /// Take in `provider`'s model listing, replacing what an earlier refresh
+    /// learned about the same models
+    pub fn merge_listing(&mut self, provider: &str, models: &[ModelInfo]) {
+        for info in models {
+            self.discovered.models.insert(info.id.clone(), ModelPatch::from_listing(provider, info));
+        }
+        self.discovered.refreshed_at = Some(Utc::now());
+        *self = Self::from_layers(std::mem::take(&mut self.discovered), std::mem::take(&mut self.overrides));
+    }
+
+    pub fn get(&self, id: &str) -> Option<&ModelCapabilities> {
+        self.merged.get(id)
+    }
+
+    /// Every known model, sorted by id
+    pub fn all(&self) -> Vec<&ModelCapabilities> {
+        let mut models: Vec<_> = self.merged.values().collect();
+        models.sort_by(|a, b| a.id.cmp(&b.id));
+        models
+    }
+
+    /// Whether `model` can be sent images. A model nothing says takes them,
+    /// such as a local one whose server doesn't report it, is refused until
+    /// an override sets `supports_vision`.
+    pub fn accepts_images(&self, model: &str) -> bool {
+        self.get(model).and_then(|m| m.supports_vision) == Some(true)
+    }
+
+    pub fn refreshed_at(&self) -> Option<DateTime<Utc>> {
+        self.discovered.refreshed_at
+    }
+
     /// Ranking algorithm for heartbeat tasks (prioritize speed + low cost)
-    pub fn rank_for_heartbeat(models: &[String]) -> Vec<String> {
-        rank_by_score(models, |cap| {
+    pub fn rank_for_heartbeat(&self, models: &[String]) -> Vec<String> {
+        self.rank_by_score(models, |cap| {
             cap.speed_score as f64 - (cap.cost_per_1m_tokens * 10.0)
         })
     }
 
     /// Ranking algorithm for planning tasks (prioritize reasoning - medium cost tolerance)
-    pub fn rank_for_planning(models: &[String]) -> Vec<String> {
-        rank_by_score(models, |cap| {
+    pub fn rank_for_planning(&self, models: &[String]) -> Vec<String> {
+        self.rank_by_score(models, |cap| {
             cap.reasoning_score as f64 - (cap.cost_per_1m_tokens * 2.0)
         })
     }
 
     /// Ranking algorithm for coding tasks (prioritize coding + reasoning)
-    pub fn rank_for_coding(models: &[String]) -> Vec<String> {
-        rank_by_score(models, |cap| {
+    pub fn rank_for_coding(&self, models: &[String]) -> Vec<String> {
+        self.rank_by_score(models, |cap| {
             (cap.coding_score + cap.reasoning_score) as f64
         })
     }
 
     /// Ranking algorithm for review tasks (balanced approach)
-    pub fn rank_for_review(models: &[String]) -> Vec<String> {
-        rank_by_score(models, |cap| {
+    pub fn rank_for_review(&self, models: &[String]) -> Vec<String> {
+        self.rank_by_score(models, |cap| {
             ((cap.coding_score + cap.reasoning_score) as f64 / 2.0) - (cap.cost_per_1m_tokens * 1.0)
         })
     }
-}
 
-fn rank_by_score<F>(models: &[String], score_fn: F) -> Vec<String>
-where
-    F: Fn(&ModelCapabilities) -> f64,
-{
-    let mut ranked: Vec<_> = models
-        .iter()
-        .filter_map(|id| {
-            ModelCapabilities::get_by_id(id).map(|cap| (id.clone(), score_fn(cap)))
-        })
-        .collect();
+    fn rank_by_score<F>(&self, models: &[String], score_fn: F) -> Vec<String>
+    where
+        F: Fn(&ModelCapabilities) -> f64,
+    {
+        let mut ranked: Vec<_> = models
+            .iter()
+            .filter_map(|id| {
+                self.get(id).map(|cap| (id.clone(), score_fn(cap)))
+            })
+            .collect();
 
-    ranked.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
-    ranked.into_iter().map(|(id, _)| id).collect()
+        ranked.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+        ranked.into_iter().map(|(id, _)| id).collect()
+    }
 }
 
 // Lazy static model rankings database (based on research from Feb 2026)
@@ -88,6 +325,9 @@ static MODEL_RANKINGS: Lazy<Vec<ModelCapabilities>> = Lazy::new(|| {
             context_window: 200_000,
             supports_streaming: true,
             supports_tools: true,
+            supports_vision: Some(true),
+            prompt_cost_per_1m: None,
+            completion_cost_per_1m: None,
             release_date: "2024-12-01".to_string(),
         },
         ModelCapabilities {
@@ -101,6 +341,9 @@ static MODEL_RANKINGS: Lazy<Vec<ModelCapabilities>> = Lazy::new(|| {
             context_window: 200_000,
             supports_streaming: true,
             supports_tools: true,
+            supports_vision: Some(true),
+            prompt_cost_per_1m: None,
+            completion_cost_per_1m: None,
             release_date: "2024-10-22".to_string(),
         },
         ModelCapabilities {
@@ -114,6 +357,9 @@ static MODEL_RANKINGS: Lazy<Vec<ModelCapabilities>> = Lazy::new(|| {
             context_window: 200_000,
             supports_streaming: true,
             supports_tools: true,
+            supports_vision: Some(true),
+            prompt_cost_per_1m: None,
+            completion_cost_per_1m: None,
             release_date: "2024-08-01".to_string(),
         },
         // Google Gemini Models
@@ -128,6 +374,9 @@ static MODEL_RANKINGS: Lazy<Vec<ModelCapabilities>> = Lazy::new(|| {
             context_window: 1_000_000,
             supports_streaming: true,
             supports_tools: true,
+            supports_vision: Some(true),
+            prompt_cost_per_1m: None,
+            completion_cost_per_1m: None,
             release_date: "2024-12-11".to_string(),
         },
         ModelCapabilities {
@@ -141,6 +390,9 @@ static MODEL_RANKINGS: Lazy<Vec<ModelCapabilities>> = Lazy::new(|| {
             context_window: 2_000_000,
             supports_streaming: true,
             supports_tools: true,
+            supports_vision: Some(true),
+            prompt_cost_per_1m: None,
+            completion_cost_per_1m: None,
             release_date: "2024-05-14".to_string(),
         },
         ModelCapabilities {
@@ -154,6 +406,9 @@ static MODEL_RANKINGS: Lazy<Vec<ModelCapabilities>> = Lazy::new(|| {
             context_window: 1_000_000,
             supports_streaming: true,
             supports_tools: true,
+            supports_vision: Some(true),
+            prompt_cost_per_1m: None,
+            completion_cost_per_1m: None,
             release_date: "2024-05-14".to_string(),
         },
         // OpenAI Models
@@ -168,6 +423,9 @@ static MODEL_RANKINGS: Lazy<Vec<ModelCapabilities>> = Lazy::new(|| {
             context_window: 128_000,
             supports_streaming: true,
             supports_tools: true,
+            supports_vision: Some(true),
+            prompt_cost_per_1m: None,
+            completion_cost_per_1m: None,
             release_date: "2024-05-13".to_string(),
         },
         ModelCapabilities {
@@ -181,6 +439,9 @@ static MODEL_RANKINGS: Lazy<Vec<ModelCapabilities>> = Lazy::new(|| {
             context_window: 128_000,
             supports_streaming: true,
             supports_tools: true,
+            supports_vision: Some(true),
+            prompt_cost_per_1m: None,
+            completion_cost_per_1m: None,
             release_date: "2024-07-18".to_string(),
         },
         ModelCapabilities {
@@ -194,6 +455,9 @@ static MODEL_RANKINGS: Lazy<Vec<ModelCapabilities>> = Lazy::new(|| {
             context_window: 200_000,
             supports_streaming: false,
             supports_tools: false,
+            supports_vision: Some(true),
+            prompt_cost_per_1m: None,
+            completion_cost_per_1m: None,
             release_date: "2024-12-17".to_string(),
         },
         ModelCapabilities {
@@ -207,6 +471,9 @@ static MODEL_RANKINGS: Lazy<Vec<ModelCapabilities>> = Lazy::new(|| {
             context_window: 128_000,
             supports_streaming: false,
             supports_tools: false,
+            supports_vision: Some(false),
+            prompt_cost_per_1m: None,
+            completion_cost_per_1m: None,
             release_date: "2024-09-12".to_string(),
         },
         // Other Providers
@@ -221,6 +488,9 @@ static MODEL_RANKINGS: Lazy<Vec<ModelCapabilities>> = Lazy::new(|| {
             context_window: 131_072,
             supports_streaming: true,
             supports_tools: true,
+            supports_vision: Some(false),
+            prompt_cost_per_1m: None,
+            completion_cost_per_1m: None,
             release_date: "2024-11-04".to_string(),
         },
         ModelCapabilities {
@@ -234,6 +504,9 @@ static MODEL_RANKINGS: Lazy<Vec<ModelCapabilities>> = Lazy::new(|| {
             context_window: 128_000,
             supports_streaming: true,
             supports_tools: false,
+            supports_vision: Some(false),
+            prompt_cost_per_1m: None,
+            completion_cost_per_1m: None,
             release_date: "2024-01-01".to_string(),
         },
         ModelCapabilities {
@@ -247,6 +520,9 @@ static MODEL_RANKINGS: Lazy<Vec<ModelCapabilities>> = Lazy::new(|| {
             context_window: 128_000,
             supports_streaming: true,
             supports_tools: true,
+            supports_vision: Some(false),
+            prompt_cost_per_1m: None,
+            completion_cost_per_1m: None,
             release_date: "2024-01-01".to_string(),
         },
         ModelCapabilities {
@@ -260,7 +536,81 @@ static MODEL_RANKINGS: Lazy<Vec<ModelCapabilities>> = Lazy::new(|| {
             context_window: 64_000,
             supports_streaming: true,
             supports_tools: true,
+            supports_vision: Some(false),
+            prompt_cost_per_1m: None,
+            completion_cost_per_1m: None,
             release_date: "2024-01-01".to_string(),
         },
     ]
 });
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::ModelPricing;
+
+    fn listing(id: &str, context_length: Option<u32>, prompt: Option<f64>) -> ModelInfo {
+        ModelInfo {
+            id: id.to_string(),
+            name: id.to_string(),
+            description: None,
+            context_length,
+            pricing: prompt.map(|prompt| ModelPricing { prompt: Some(prompt), completion: Some(prompt * 4.0) }),
+            supports_vision: None,
+            supports_streaming: true,
+            supports_function_calling: true,
+        }
+    }
+
+    #[test]
+    fn test_layers_listing_and_overrides() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join(OVERRIDES_FILE),
+            r#"{"qwen2.5-coder": {"coding_score": 10, "reasoning_score": 8}, "gpt-4o": {"context_window": 64000}}"#,
+        ).unwrap();
+
+        let mut registry = ModelRegistry::load(dir.path()).unwrap();
+        registry.merge_listing("openai_compatible", &[
+            listing("qwen2.5-coder", Some(32_768), None),
+            listing("gpt-4o", Some(128_000), Some(2.5)),
+        ]);
+        registry.save(dir.path()).unwrap();
+
+        // Reloading gives the same view
+        let registry = ModelRegistry::load(dir.path()).unwrap();
+        assert!(registry.refreshed_at().is_some());
+
+        let qwen = registry.get("qwen2.5-coder").unwrap();
+        assert_eq!((qwen.context_window, qwen.coding_score, qwen.supports_tools), (32_768, 10, true));
+        assert_eq!(qwen.provider, "openai_compatible");
+
+        // The listing's price replaces the compiled-in one; the override beats the listing
+        let gpt = registry.get("gpt-4o").unwrap();
+        assert_eq!((gpt.prompt_cost_per_1m, gpt.completion_cost_per_1m), (Some(2.5), Some(10.0)));
+        assert_eq!(gpt.context_window, 64_000);
+        // A listing that doesn't report vision leaves what the table knows
+        assert_eq!(gpt.supports_vision, Some(true));
+        assert!(registry.accepts_images("gpt-4o"));
+
+        // Models nothing vouches for are refused images
+        assert_eq!(qwen.supports_vision, None);
+        assert!(!registry.accepts_images("qwen2.5-coder"));
+        assert!(!registry.accepts_images("nope"));
+        assert!(!registry.accepts_images("o1-mini"));
+
+        // A listing that does report vision is taken at its word
+        let mut registry = registry;
+        registry.merge_listing("openrouter", &[
+            ModelInfo { supports_vision: Some(true), ..listing("llava", None, None) },
+            ModelInfo { supports_vision: Some(false), ..listing("gpt-4o", None, None) },
+        ]);
+        assert!(registry.accepts_images("llava"));
+        assert!(!registry.accepts_images("gpt-4o"));
+
+        assert_eq!(
+            registry.rank_for_coding(&["gpt-4o".to_string(), "qwen2.5-coder".to_string(), "nope".to_string()]),
+            vec!["qwen2.5-coder", "gpt-4o"],
+        );
+    }
+}
@@ -0,0 +1,320 @@
+//! Any server that speaks the OpenAI `/chat/completions` API
+//!
+//! Covers OpenAI itself and local servers such as vLLM, llama.cpp's
+//! `llama-server`, LM Studio and Ollama's `/v1` endpoint. The base URL, extra
+//! headers and model list come from the provider config. The API key is
+//! optional, since local servers usually don't check one.
+
+use crate::config::ProviderConfig;
+use crate::error::Result;
+use crate::providers::{openai_wire, retry, CompletionRequest, CompletionResponse, ModelInfo, Provider, ProviderInfo, StreamChunk};
+use async_trait::async_trait;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::{Client, RequestBuilder};
+use serde_json::Value;
+use std::time::Duration;
+use tracing::warn;
+
+const DEFAULT_BASE_URL: &str = "https://api.openai.com/v1";
+const DEFAULT_MODEL: &str = "gpt-4o";
+
+pub struct OpenAiCompatibleProvider {
+    api_key: Option<String>,
+    base_url: String,
+    headers: HeaderMap,
+    /// Models from the config, offered even if the server can't list them
+    models: Vec<String>,
+    client: Client,
+    default_model: String,
+}
+
+impl OpenAiCompatibleProvider {
+    pub fn new(config: &ProviderConfig) -> Self {
+        let mut headers = HeaderMap::new();
+        for (name, value) in &config.headers {
+            match (HeaderName::from_bytes(name.as_bytes()), HeaderValue::from_str(value)) {
+                (Ok(name), Ok(value)) => {
+                    headers.insert(name, value);
+                }
+                _ => warn!(header = %name, "Ignoring invalid provider header"),
+            }
+        }
+
+        let mut client = Client::builder();
+        if let Some(secs) = config.timeout_secs {
+            client = client.timeout(Duration::from_secs(secs));
+        }
+
+        Self {
+            api_key: config.api_key.clone().filter(|key| !key.is_empty()),
+            base_url: config.base_url.clone()
+                .unwrap_or_else(|| DEFAULT_BASE_URL.to_string())
+                .trim_end_matches('/')
+                .to_string(),
+            headers,
+            models: config.models.clone(),
+            client: client.build().unwrap_or_default(),
+            default_model: config.default_model.clone()
+                .or_else(|| config.models.first().cloned())
+                .unwrap_or_else(|| DEFAULT_MODEL.to_string()),
+        }
+    }
+
+    pub fn static_info() -> ProviderInfo {
+        ProviderInfo {
+            name: "openai_compatible".to_string(),
+            display_name: "OpenAI-compatible (OpenAI, vLLM, llama.cpp, LM Studio, Ollama)".to_string(),
+            supports_oauth: false,
+            default_model: DEFAULT_MODEL.to_string(),
+            available_models: Vec::new(),
+        }
+    }
+
+    /// A request to `path` under the base URL, with auth and configured headers
+    fn request(&self, method: reqwest::Method, path: &str) -> RequestBuilder {
+        let mut request = self.client
+            .request(method, format!("{}{}", self.base_url, path))
+            .headers(self.headers.clone());
+        if let Some(key) = &self.api_key {
+            request = request.bearer_auth(key);
+        }
+        request
+    }
+
+    async fn send(&self, body: &Value) -> Result<reqwest::Response> {
+        let response = self.request(reqwest::Method::POST, "/chat/completions")
+            .json(body)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(retry::error_for_status(&self.base_url, response).await);
+        }
+        Ok(response)
+    }
+
+    async fn discover_models(&self) -> Result<Vec<ModelInfo>> {
+        let response = self.request(reqwest::Method::GET, "/models").send().await?;
+        if !response.status().is_success() {
+            return Err(retry::error_for_status(&self.base_url, response).await);
+        }
+
+        let data: Value = response.json().await?;
+        Ok(data["data"].as_array().into_iter().flatten().filter_map(parse_model).collect())
+    }
+
+    fn configured_model(id: &str) -> ModelInfo {
+        ModelInfo {
+            id: id.to_string(),
+            name: id.to_string(),
+            description: None,
+            context_length: None,
+            pricing: None,
+            supports_vision: None,
+            supports_streaming: true,
+            supports_function_calling: true,
+        }
+    }
+}
+
+/// One entry of a `/models` listing. Servers report the context window under
+/// different names: `context_length` (LM Studio, OpenRouter style),
+/// `max_model_len` (vLLM) or `meta.n_ctx_train` (llama.cpp).
+fn parse_model(model: &Value) -> Option<ModelInfo> {
+    let id = model["id"].as_str()?.to_string();
+    let context_length = [&model["context_length"], &model["max_model_len"], &model["meta"]["n_ctx_train"]]
+        .into_iter()
+        .find_map(|v| v.as_u64())
+        .map(|n| n as u32);
+    Some(ModelInfo {
+        name: model["name"].as_str().unwrap_or(&id).to_string(),
+        id,
+        description: None,
+        context_length,
+        pricing: None,
+        supports_vision: None,
+        supports_streaming: true,
+        // The API accepts `tools`; how well a local model uses them is up to the model
+        supports_function_calling: true,
+    })
+}
+
+#[async_trait]
+impl Provider for OpenAiCompatibleProvider {
+    fn info(&self) -> ProviderInfo {
+        ProviderInfo {
+            display_name: format!("OpenAI-compatible ({})", self.base_url),
+            default_model: self.default_model.clone(),
+            available_models: self.models.clone(),
+            ..Self::static_info()
+        }
+    }
+
+    async fn complete(&self, request: CompletionRequest) -> Result<CompletionResponse> {
+        let body = openai_wire::build_body(
+            &request.model,
+            &request.messages,
+            request.temperature,
+            request.max_tokens,
+            request.tools.as_deref(),
+            false,
+        );
+        let data: Value = self.send(&body).await?.json().await?;
+        Ok(openai_wire::parse_response(&data, request.model))
+    }
+
+    async fn complete_stream(
+        &self,
+        request: CompletionRequest,
+        tx: tokio::sync::mpsc::Sender<StreamChunk>,
+    ) -> Result<()> {
+        let body = openai_wire::build_body(
+            &request.model,
+            &request.messages,
+            request.temperature,
+            request.max_tokens,
+            request.tools.as_deref(),
+            true,
+        );
+        let response = self.send(&body).await?;
+        openai_wire::stream_response(response, &tx).await
+    }
+
+    /// Models the server lists, plus configured ones it didn't mention. Falls
+    /// back to the configured list if the server has no `/models` endpoint.
+    async fn list_available_models(&self) -> Result<Vec<ModelInfo>> {
+        let mut models = match self.discover_models().await {
+            Ok(models) => models,
+            Err(e) if !self.models.is_empty() => {
+                warn!(error = %e, "Model discovery failed; using the configured models");
+                Vec::new()
+            }
+            Err(e) => return Err(e),
+        };
+        for id in &self.models {
+            if !models.iter().any(|m| &m.id == id) {
+                models.push(Self::configured_model(id));
+            }
+        }
+        Ok(models)
+    }
+
+    async fn authenticate(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    async fn refresh_auth(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn is_authenticated(&self) -> bool {
+        // Local servers don't need a key; a hosted API rejects the request itself
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ProviderType;
+    use crate::executor::tools::Tool;
+    use crate::providers::Message;
+    use axum::http::HeaderMap as AxumHeaders;
+    use axum::routing::{get, post};
+    use axum::{Json, Router};
+    use std::collections::HashMap;
+
+    fn config(base_url: String) -> ProviderConfig {
+        ProviderConfig {
+            provider_type: ProviderType::OpenaiCompatible,
+            api_key: None,
+            oauth_token: None,
+            oauth_client_id: None,
+            oauth_client_secret: None,
+            oauth_refresh_token: None,
+            oauth_expires_at: None,
+            base_url: Some(format!("{}/v1/", base_url)),
+            default_model: None,
+            timeout_secs: Some(5),
+            cassette: None,
+            headers: HashMap::from([("X-Team".to_string(), "nexus".to_string())]),
+            models: vec!["qwen2.5-coder".to_string(), "offline-only".to_string()],
+            fallback: Vec::new(),
+        }
+    }
+
+    async fn serve() -> String {
+        let app = Router::new()
+            .route("/v1/models", get(|| async {
+                Json(serde_json::json!({"object": "list", "data": [
+                    {"id": "qwen2.5-coder", "object": "model", "max_model_len": 32768},
+                    {"id": "llama-3.1-8b", "object": "model", "meta": {"n_ctx_train": 131072}}
+                ]}))
+            }))
+            .route("/v1/chat/completions", post(|headers: AxumHeaders, Json(body): Json<Value>| async move {
+                // Echo what the server saw so the test can check it
+                let seen = format!(
+                    "team={} auth={} tools={}",
+                    headers.get("x-team").and_then(|v| v.to_str().ok()).unwrap_or("-"),
+                    headers.contains_key("authorization"),
+                    body["tools"][0]["function"]["name"].as_str().unwrap_or("-"),
+                );
+                Json(serde_json::json!({
+                    "id": "chatcmpl-1",
+                    "choices": [{"message": {"role": "assistant", "content": seen, "tool_calls": [{
+                        "id": "call_1", "type": "function",
+                        "function": {"name": "read_file", "arguments": "{\"path\":\"main.rs\"}"}
+                    }]}, "finish_reason": "tool_calls"}]
+                }))
+            }));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+        format!("http://{}", addr)
+    }
+
+    #[tokio::test]
+    async fn test_complete_sends_headers_and_native_tools() {
+        let provider = OpenAiCompatibleProvider::new(&config(serve().await));
+        assert_eq!(provider.info().default_model, "qwen2.5-coder");
+
+        let response = provider.complete(CompletionRequest {
+            model: "qwen2.5-coder".to_string(),
+            messages: vec![Message::user("read main.rs")],
+            temperature: None,
+            max_tokens: None,
+            stream: None,
+            tools: Some(vec![Tool {
+                name: "read_file".to_string(),
+                description: "Read a file".to_string(),
+                parameters: serde_json::json!({"type": "object"}),
+            }]),
+            extra_params: None,
+            cache: None,
+        }).await.unwrap();
+
+        assert_eq!(response.content, "team=nexus auth=false tools=read_file");
+        assert_eq!(response.tool_calls.unwrap()[0].arguments["path"], "main.rs");
+    }
+
+    #[tokio::test]
+    async fn test_list_models_merges_discovered_and_configured() {
+        let provider = OpenAiCompatibleProvider::new(&config(serve().await));
+        let models = provider.list_available_models().await.unwrap();
+
+        let summary: Vec<_> = models.iter().map(|m| (m.id.as_str(), m.context_length)).collect();
+        assert_eq!(summary, vec![
+            ("qwen2.5-coder", Some(32768)),
+            ("llama-3.1-8b", Some(131072)),
+            ("offline-only", None),
+        ]);
+
+        // A server that can't be reached still offers the configured models
+        let mut offline = config("http://127.0.0.1:9".to_string());
+        offline.timeout_secs = Some(1);
+        let models = OpenAiCompatibleProvider::new(&offline).list_available_models().await.unwrap();
+        assert_eq!(models.len(), 2);
+    }
+}
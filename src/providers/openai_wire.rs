@@ -0,0 +1,483 @@
+//! Shared wire format for OpenAI-compatible `/chat/completions` APIs
+//!
+//! OpenCode Zen, OpenRouter and generic `openai_compatible` servers all speak the
+//! OpenAI chat format, including native function calling. These helpers translate
+//! between that format and Nexus types.
+
+use crate::error::{NexusError, Result};
+use crate::executor::tools::{Tool, ToolCall};
+use crate::providers::sse::SseStream;
+use crate::providers::{CompletionResponse, ContentPart, Message, Role, StreamChunk, Usage};
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashSet};
+use tokio::sync::mpsc;
+use tracing::warn;
+
+/// Convert messages to OpenAI chat format.
+///
+/// Tool use parts become the assistant's `tool_calls` array, and each tool result
+/// becomes its own `role: tool` message with the matching `tool_call_id`. Results
+/// with no matching call are sent as user text instead. Images are sent as data URLs.
+pub fn convert_messages(messages: &[Message]) -> Vec<Value> {
+    let mut converted = Vec::with_capacity(messages.len());
+    let mut pending_tool_ids: HashSet<&str> = HashSet::new();
+
+    for msg in messages {
+        let role = match msg.role {
+            Role::System => "system",
+            Role::Assistant => {
+                pending_tool_ids.clear();
+                "assistant"
+            }
+            _ => "user",
+        };
+
+        let mut parts = Vec::new();
+        let mut tool_calls = Vec::new();
+        let mut has_image = false;
+
+        for part in &msg.content {
+            match part {
+                ContentPart::Text { text } => parts.push(json!({"type": "text", "text": text})),
+                ContentPart::Image { media_type, data } => {
+                    has_image = true;
+                    parts.push(json!({
+                        "type": "image_url",
+                        "image_url": {"url": format!("data:{};base64,{}", media_type, data)},
+                    }));
+                }
+                ContentPart::ToolUse { id, name, input } => {
+                    pending_tool_ids.insert(id.as_str());
+                    tool_calls.push(json!({
+                        "id": id,
+                        "type": "function",
+                        "function": {
+                            "name": name,
+                            // OpenAI expects the arguments as a JSON-encoded string
+                            "arguments": input.to_string(),
+                        }
+                    }));
+                }
+                ContentPart::ToolResult { tool_use_id, content, .. } if pending_tool_ids.contains(tool_use_id.as_str()) => {
+                    converted.push(json!({
+                        "role": "tool",
+                        "tool_call_id": tool_use_id,
+                        "content": content,
+                    }));
+                }
+                ContentPart::ToolResult { content, .. } => {
+                    converted.push(json!({"role": "user", "content": content}));
+                }
+            }
+        }
+
+        if parts.is_empty() && tool_calls.is_empty() {
+            continue;
+        }
+
+        let content = if has_image {
+            json!(parts)
+        } else if parts.is_empty() {
+            Value::Null
+        } else {
+            json!(msg.text_content())
+        };
+
+        let mut message = json!({"role": role, "content": content});
+        if !tool_calls.is_empty() {
+            message["tool_calls"] = json!(tool_calls);
+        }
+        converted.push(message);
+    }
+
+    converted
+}
+
+/// Convert tool definitions to the OpenAI `tools` array
+pub fn convert_tools(tools: &[Tool]) -> Vec<Value> {
+    tools.iter().map(|tool| {
+        json!({
+            "type": "function",
+            "function": {
+                "name": tool.name,
+                "description": tool.description,
+                "parameters": tool.parameters,
+            }
+        })
+    }).collect()
+}
+
+/// Build a `/chat/completions` request body
+pub fn build_body(
+    model: &str,
+    messages: &[Message],
+    temperature: Option<f32>,
+    max_tokens: Option<u32>,
+    tools: Option<&[Tool]>,
+    stream: bool,
+) -> Value {
+    let mut body = json!({
+        "model": model,
+        "messages": convert_messages(messages),
+        "temperature": temperature.unwrap_or(0.7),
+        "max_tokens": max_tokens,
+        "stream": stream,
+    });
+
+    if let Some(tools) = tools.filter(|t| !t.is_empty()) {
+        body["tools"] = json!(convert_tools(tools));
+        body["tool_choice"] = json!("auto");
+    }
+
+    if stream {
+        // Ask for a final chunk carrying token usage
+        body["stream_options"] = json!({"include_usage": true});
+    }
+
+    body
+}
+
+/// Parse the arguments string of a function call into a JSON value
+fn parse_arguments(name: &str, arguments: &str) -> Value {
+    if arguments.trim().is_empty() {
+        return json!({});
+    }
+    serde_json::from_str(arguments).unwrap_or_else(|e| {
+        warn!(tool = %name, error = %e, "Tool call arguments are not valid JSON");
+        json!({})
+    })
+}
+
+/// Parse `message.tool_calls` from a non-streaming response
+pub fn parse_tool_calls(message: &Value) -> Option<Vec<ToolCall>> {
+    let calls: Vec<ToolCall> = message["tool_calls"]
+        .as_array()?
+        .iter()
+        .filter_map(|call| {
+            let name = call["function"]["name"].as_str()?.to_string();
+            let arguments = match &call["function"]["arguments"] {
+                Value::String(s) => parse_arguments(&name, s),
+                Value::Null => json!({}),
+                other => other.clone(),
+            };
+            Some(ToolCall {
+                id: call["id"]
+                    .as_str()
+                    .map(|s| s.to_string())
+                    .unwrap_or_else(|| format!("call_{}", uuid::Uuid::new_v4())),
+                name,
+                arguments,
+            })
+        })
+        .collect();
+
+    if calls.is_empty() { None } else { Some(calls) }
+}
+
+pub fn parse_usage(data: &Value) -> Option<Usage> {
+    data.get("usage").filter(|u| u.is_object()).map(|usage| Usage {
+        prompt_tokens: usage["prompt_tokens"].as_u64().unwrap_or(0) as u32,
+        completion_tokens: usage["completion_tokens"].as_u64().unwrap_or(0) as u32,
+        total_tokens: usage["total_tokens"].as_u64().unwrap_or(0) as u32,
+        // OpenAI caches long prompt prefixes automatically and reports the hits here
+        cache_read_tokens: usage["prompt_tokens_details"]["cached_tokens"].as_u64().unwrap_or(0) as u32,
+        cache_write_tokens: 0,
+    })
+}
+
+/// Parse a non-streaming `/chat/completions` response
+pub fn parse_response(data: &Value, model: String) -> CompletionResponse {
+    let message = &data["choices"][0]["message"];
+
+    CompletionResponse {
+        id: data["id"].as_str().unwrap_or("unknown").to_string(),
+        model,
+        content: message["content"].as_str().unwrap_or("").to_string(),
+        finish_reason: data["choices"][0]["finish_reason"].as_str().map(|s| s.to_string()),
+        usage: parse_usage(data),
+        tool_calls: parse_tool_calls(message),
+    }
+}
+
+/// Reassembles tool calls from streamed `delta.tool_calls` fragments.
+///
+/// The first fragment of each call carries its `index`, `id` and function name;
+/// later fragments with the same index append to the JSON arguments string.
+#[derive(Debug, Default)]
+pub struct ToolCallAccumulator {
+    calls: BTreeMap<u64, PartialToolCall>,
+}
+
+#[derive(Debug, Default)]
+struct PartialToolCall {
+    id: Option<String>,
+    name: String,
+    arguments: String,
+}
+
+impl ToolCallAccumulator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feed the `delta` object of a streamed chunk
+    pub fn push_delta(&mut self, delta: &Value) {
+        for fragment in delta["tool_calls"].as_array().into_iter().flatten() {
+            let index = fragment["index"].as_u64().unwrap_or(0);
+            let call = self.calls.entry(index).or_default();
+            if let Some(id) = fragment["id"].as_str() {
+                call.id = Some(id.to_string());
+            }
+            if let Some(name) = fragment["function"]["name"].as_str() {
+                call.name.push_str(name);
+            }
+            if let Some(args) = fragment["function"]["arguments"].as_str() {
+                call.arguments.push_str(args);
+            }
+        }
+    }
+
+    /// Take the completed tool calls, in index order
+    pub fn finish(&mut self) -> Vec<ToolCall> {
+        std::mem::take(&mut self.calls)
+            .into_values()
+            .filter(|call| !call.name.is_empty())
+            .map(|call| ToolCall {
+                id: call.id.unwrap_or_else(|| format!("call_{}", uuid::Uuid::new_v4())),
+                arguments: parse_arguments(&call.name, &call.arguments),
+                name: call.name,
+            })
+            .collect()
+    }
+}
+
+/// Forward a streaming `/chat/completions` response as `StreamChunk`s.
+///
+/// Text deltas are sent as they arrive; tool calls are sent once the stream ends,
+/// since their arguments arrive in fragments.
+pub async fn stream_response(response: reqwest::Response, tx: &mpsc::Sender<StreamChunk>) -> Result<()> {
+    let mut events = SseStream::new(response);
+    let mut tool_calls = ToolCallAccumulator::new();
+
+    while let Some(event) = events.next_event().await? {
+        if event.data == "[DONE]" {
+            break;
+        }
+        let Ok(chunk) = serde_json::from_str::<Value>(&event.data) else {
+            warn!(data = %event.data, "Skipping unparseable stream chunk");
+            continue;
+        };
+        if let Some(error) = chunk.get("error") {
+            return Err(NexusError::ApiRequest(format!("Stream error: {}", error)));
+        }
+
+        let delta = &chunk["choices"][0]["delta"];
+        if let Some(content) = delta["content"].as_str()
+            && !content.is_empty()
+        {
+            let _ = tx.send(StreamChunk::ContentDelta(content.to_string())).await;
+        }
+        tool_calls.push_delta(delta);
+        if let Some(usage) = parse_usage(&chunk) {
+            let _ = tx.send(StreamChunk::Usage(usage)).await;
+        }
+    }
+
+    for call in tool_calls.finish() {
+        let _ = tx.send(StreamChunk::ToolCall(call)).await;
+    }
+    let _ = tx.send(StreamChunk::Done).await;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executor::tools::ToolResult;
+
+    #[test]
+    fn test_build_body_includes_tools() {
+        let tools = vec![Tool {
+            name: "read_file".to_string(),
+            description: "Read a file".to_string(),
+            parameters: json!({"type": "object"}),
+        }];
+        let body = build_body("gpt-5", &[Message::user("hi")], None, Some(100), Some(&tools), false);
+
+        assert_eq!(body["tools"][0]["type"], "function");
+        assert_eq!(body["tools"][0]["function"]["name"], "read_file");
+        assert_eq!(body["tool_choice"], "auto");
+
+        let body = build_body("gpt-5", &[Message::user("hi")], None, None, None, false);
+        assert!(body.get("tools").is_none());
+    }
+
+    #[test]
+    fn test_tool_results_use_tool_role() {
+        let calls = vec![ToolCall {
+            id: "call_1".to_string(),
+            name: "read_file".to_string(),
+            arguments: json!({"path": "a.rs"}),
+        }];
+        let results = vec![
+            ToolResult { tool_call_id: "call_1".to_string(), success: true, output: "fn a() {}".to_string(), error: None },
+            ToolResult { tool_call_id: "call_unknown".to_string(), success: true, output: "parsed from text".to_string(), error: None },
+        ];
+
+        let converted = convert_messages(&[
+            Message::assistant_with_tool_calls("", &calls),
+            Message::tool_results(&results),
+        ]);
+
+        assert_eq!(converted[0]["content"], Value::Null);
+        assert_eq!(converted[0]["tool_calls"][0]["function"]["arguments"], r#"{"path":"a.rs"}"#);
+        assert_eq!(converted[1]["role"], "tool");
+        assert_eq!(converted[1]["tool_call_id"], "call_1");
+        assert_eq!(converted[2]["role"], "user");
+    }
+
+    #[test]
+    fn test_image_parts_use_content_array() {
+        let mut message = Message::user("describe");
+        message.content.push(ContentPart::Image { media_type: "image/jpeg".to_string(), data: "abc=".to_string() });
+
+        let converted = convert_messages(&[message]);
+        assert_eq!(converted[0]["content"][0]["type"], "text");
+        assert_eq!(converted[0]["content"][1]["image_url"]["url"], "data:image/jpeg;base64,abc=");
+    }
+
+    #[test]
+    fn test_parse_response_tool_calls() {
+        let data = json!({
+            "id": "chatcmpl-1",
+            "choices": [{
+                "message": {
+                    "role": "assistant",
+                    "content": null,
+                    "tool_calls": [{
+                        "id": "call_abc",
+                        "type": "function",
+                        "function": {"name": "execute_command", "arguments": "{\"command\":\"ls\"}"}
+                    }]
+                },
+                "finish_reason": "tool_calls"
+            }],
+            "usage": {"prompt_tokens": 12, "completion_tokens": 3, "total_tokens": 15}
+        });
+
+        let response = parse_response(&data, "gpt-5".to_string());
+        assert_eq!(response.content, "");
+        assert_eq!(response.finish_reason.as_deref(), Some("tool_calls"));
+        let calls = response.tool_calls.unwrap();
+        assert_eq!(calls[0].id, "call_abc");
+        assert_eq!(calls[0].arguments["command"], "ls");
+        assert_eq!(response.usage.unwrap().total_tokens, 15);
+    }
+
+    #[test]
+    fn test_accumulator_joins_streamed_fragments() {
+        let mut acc = ToolCallAccumulator::new();
+        acc.push_delta(&json!({"tool_calls": [{"index": 0, "id": "call_1", "function": {"name": "read_file", "arguments": ""}}]}));
+        acc.push_delta(&json!({"tool_calls": [{"index": 0, "function": {"arguments": "{\"path\":"}}]}));
+        acc.push_delta(&json!({"tool_calls": [{"index": 1, "id": "call_2", "function": {"name": "list_dir", "arguments": "{}"}}]}));
+        acc.push_delta(&json!({"tool_calls": [{"index": 0, "function": {"arguments": "\"main.rs\"}"}}]}));
+        acc.push_delta(&json!({"content": "no tool calls here"}));
+
+        let calls = acc.finish();
+        assert_eq!(calls.len(), 2);
+        assert_eq!(calls[0].id, "call_1");
+        assert_eq!(calls[0].arguments["path"], "main.rs");
+        assert_eq!(calls[1].name, "list_dir");
+        assert!(acc.finish().is_empty());
+    }
+
+    const STREAM_BODY: &str = concat!(
+        "data: {\"id\":\"c1\",\"choices\":[{\"index\":0,\"delta\":{\"role\":\"assistant\",\"content\":\"Running \"}}]}\n\n",
+        "data: {\"id\":\"c1\",\"choices\":[{\"index\":0,\"delta\":{\"content\":\"ls.\"}}]}\n\n",
+        "data: {\"id\":\"c1\",\"choices\":[{\"index\":0,\"delta\":{\"tool_calls\":[{\"index\":0,\"id\":\"call_1\",\"type\":\"function\",\"function\":{\"name\":\"execute_command\",\"arguments\":\"\"}}]}}]}\n\n",
+        "data: {\"id\":\"c1\",\"choices\":[{\"index\":0,\"delta\":{\"tool_calls\":[{\"index\":0,\"function\":{\"arguments\":\"{\\\"command\\\":\"}}]}}]}\n\n",
+        "data: {\"id\":\"c1\",\"choices\":[{\"index\":0,\"delta\":{\"tool_calls\":[{\"index\":0,\"function\":{\"arguments\":\"\\\"ls\\\"}\"}}]}}]}\n\n",
+        "data: {\"id\":\"c1\",\"choices\":[{\"index\":0,\"delta\":{},\"finish_reason\":\"tool_calls\"}],\"usage\":null}\n\n",
+        "data: {\"id\":\"c1\",\"choices\":[],\"usage\":{\"prompt_tokens\":30,\"completion_tokens\":9,\"total_tokens\":39}}\n\n",
+        "data: [DONE]\n\n",
+    );
+
+    fn test_config(provider_type: crate::config::ProviderType, base_url: String) -> crate::config::ProviderConfig {
+        crate::config::ProviderConfig {
+            provider_type,
+            api_key: Some("test-key".to_string()),
+            oauth_token: None,
+            oauth_client_id: None,
+            oauth_client_secret: None,
+            oauth_refresh_token: None,
+            oauth_expires_at: None,
+            base_url: Some(base_url),
+            default_model: None,
+            timeout_secs: None,
+            cassette: None,
+            headers: Default::default(),
+            models: Vec::new(),
+            fallback: Vec::new(),
+        }
+    }
+
+    async fn collect_stream(provider: &dyn crate::providers::Provider) -> (String, Vec<ToolCall>, Option<Usage>) {
+        let request = crate::providers::CompletionRequest {
+            model: "test-model".to_string(),
+            messages: vec![Message::user("list files")],
+            temperature: None,
+            max_tokens: None,
+            stream: Some(true),
+            tools: None,
+            extra_params: None,
+            cache: None,
+        };
+        let (tx, mut rx) = mpsc::channel(32);
+        provider.complete_stream(request, tx).await.unwrap();
+
+        let (mut text, mut calls, mut usage) = (String::new(), Vec::new(), None);
+        while let Some(chunk) = rx.recv().await {
+            match chunk {
+                StreamChunk::ContentDelta(delta) => text.push_str(&delta),
+                StreamChunk::ToolCall(call) => calls.push(call),
+                StreamChunk::Usage(u) => usage = Some(u),
+                StreamChunk::Done => break,
+            }
+        }
+        (text, calls, usage)
+    }
+
+    #[tokio::test]
+    async fn test_openrouter_stream_against_mock_server() {
+        let base_url = crate::providers::sse::testing::serve_sse("/chat/completions", STREAM_BODY).await;
+        let provider = crate::providers::openrouter::OpenRouterProvider::new(
+            &test_config(crate::config::ProviderType::Openrouter, base_url),
+        );
+
+        let (text, calls, usage) = collect_stream(&provider).await;
+        assert_eq!(text, "Running ls.");
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].id, "call_1");
+        assert_eq!(calls[0].arguments["command"], "ls");
+        assert_eq!(usage.unwrap().total_tokens, 39);
+    }
+
+    #[tokio::test]
+    async fn test_opencode_stream_against_mock_server() {
+        let base_url = crate::providers::sse::testing::serve_sse("/chat/completions", STREAM_BODY).await;
+        let provider = crate::providers::opencode::OpencodeProvider::new(
+            &test_config(crate::config::ProviderType::Opencode, base_url),
+        );
+
+        let (text, calls, usage) = collect_stream(&provider).await;
+        assert_eq!(text, "Running ls.");
+        assert_eq!(calls[0].name, "execute_command");
+        assert_eq!(usage.unwrap().prompt_tokens, 30);
+    }
+
+    #[test]
+    fn test_stream_body_requests_usage() {
+        let body = build_body("gpt-5", &[Message::user("hi")], None, None, None, true);
+        assert_eq!(body["stream_options"]["include_usage"], true);
+    }
+}
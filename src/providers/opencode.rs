@@ -1,6 +1,6 @@
 use crate::config::ProviderConfig;
 use crate::error::{NexusError, Result};
-use crate::providers::{CompletionRequest, CompletionResponse, ModelInfo, ModelPricing, Provider, ProviderInfo, StreamChunk, Usage};
+use crate::providers::{openai_wire, retry, CompletionRequest, CompletionResponse, ModelInfo, ModelPricing, Provider, ProviderInfo, StreamChunk};
 use async_trait::async_trait;
 use reqwest::Client;
 use serde_json;
@@ -80,13 +80,14 @@ impl Provider for OpencodeProvider {
         let api_key = self.api_key.as_ref().unwrap();
         
         // Use model ID as-is (already plain format without prefix)
-        let body = serde_json::json!({
-            "model": request.model,
-            "messages": request.messages,
-            "temperature": request.temperature.unwrap_or(0.7),
-            "max_tokens": request.max_tokens,
-            "stream": request.stream.unwrap_or(false),
-        });
+        let body = openai_wire::build_body(
+            &request.model,
+            &request.messages,
+            request.temperature,
+            request.max_tokens,
+            request.tools.as_deref(),
+            request.stream.unwrap_or(false),
+        );
 
         let response = self.client
             .post(format!("{}/chat/completions", self.base_url))
@@ -97,42 +98,12 @@ impl Provider for OpencodeProvider {
             .await?;
 
         if !response.status().is_success() {
-            let error_text = response.text().await.unwrap_or_default();
-            return Err(NexusError::ApiRequest(format!(
-                "OpenCode API error: {}",
-                error_text
-            )));
+            return Err(retry::error_for_status("OpenCode API error", response).await);
         }
 
         let data: serde_json::Value = response.json().await?;
-        
-        let content = data["choices"][0]["message"]["content"]
-            .as_str()
-            .unwrap_or("")
-            .to_string();
-        
-        let finish_reason = data["choices"][0]["finish_reason"]
-            .as_str()
-            .map(|s| s.to_string());
 
-        let usage = if let Some(usage) = data.get("usage") {
-            Some(Usage {
-                prompt_tokens: usage["prompt_tokens"].as_u64().unwrap_or(0) as u32,
-                completion_tokens: usage["completion_tokens"].as_u64().unwrap_or(0) as u32,
-                total_tokens: usage["total_tokens"].as_u64().unwrap_or(0) as u32,
-            })
-        } else {
-            None
-        };
-
-        Ok(CompletionResponse {
-            id: data["id"].as_str().unwrap_or("unknown").to_string(),
-            model: request.model,
-            content,
-            finish_reason,
-            usage,
-            tool_calls: None,
-        })
+        Ok(openai_wire::parse_response(&data, request.model))
     }
 
     async fn complete_stream(
@@ -145,13 +116,14 @@ impl Provider for OpencodeProvider {
         }
 
         let api_key = self.api_key.as_ref().unwrap();
-        let body = serde_json::json!({
-            "model": request.model,
-            "messages": request.messages,
-            "temperature": request.temperature.unwrap_or(0.7),
-            "max_tokens": request.max_tokens,
-            "stream": true,
-        });
+        let body = openai_wire::build_body(
+            &request.model,
+            &request.messages,
+            request.temperature,
+            request.max_tokens,
+            request.tools.as_deref(),
+            true,
+        );
 
         let response = self.client
             .post(format!("{}/chat/completions", self.base_url))
@@ -162,50 +134,10 @@ impl Provider for OpencodeProvider {
             .await?;
 
         if !response.status().is_success() {
-            let error_text = response.text().await.unwrap_or_default();
-            return Err(NexusError::ApiRequest(format!("OpenCode streaming error: {}", error_text)));
+            return Err(retry::error_for_status("OpenCode streaming error", response).await);
         }
 
-        // Parse SSE stream
-        use futures_util::StreamExt;
-        let mut stream = response.bytes_stream();
-        let mut buffer = String::new();
-
-        while let Some(chunk) = stream.next().await {
-            let bytes = chunk.map_err(|e| NexusError::ApiRequest(e.to_string()))?;
-            buffer.push_str(&String::from_utf8_lossy(&bytes));
-
-            // Process complete SSE lines
-            while let Some(pos) = buffer.find('\n') {
-                let line = buffer[..pos].trim().to_string();
-                buffer = buffer[pos + 1..].to_string();
-
-                if line.starts_with("data: ") {
-                    let data = &line[6..];
-                    if data == "[DONE]" {
-                        let _ = tx.send(StreamChunk::Done).await;
-                        return Ok(());
-                    }
-                    if let Ok(json) = serde_json::from_str::<serde_json::Value>(data) {
-                        if let Some(delta) = json["choices"][0]["delta"]["content"].as_str() {
-                            if !delta.is_empty() {
-                                let _ = tx.send(StreamChunk::ContentDelta(delta.to_string())).await;
-                            }
-                        }
-                        if let Some(usage) = json.get("usage") {
-                            let _ = tx.send(StreamChunk::Usage(Usage {
-                                prompt_tokens: usage["prompt_tokens"].as_u64().unwrap_or(0) as u32,
-                                completion_tokens: usage["completion_tokens"].as_u64().unwrap_or(0) as u32,
-                                total_tokens: usage["total_tokens"].as_u64().unwrap_or(0) as u32,
-                            })).await;
-                        }
-                    }
-                }
-            }
-        }
-
-        let _ = tx.send(StreamChunk::Done).await;
-        Ok(())
+        openai_wire::stream_response(response, &tx).await
     }
 
     async fn list_available_models(&self) -> Result<Vec<ModelInfo>> {
@@ -218,7 +150,7 @@ impl Provider for OpencodeProvider {
                 description: Some("Latest Kimi model with enhanced capabilities".to_string()),
                 context_length: Some(128000),
                 pricing: None,
-                supports_vision: false,
+                supports_vision: Some(false),
                 supports_streaming: true,
                 supports_function_calling: true,
             },
@@ -231,7 +163,7 @@ impl Provider for OpencodeProvider {
                     prompt: Some(0.0),
                     completion: Some(0.0),
                 }),
-                supports_vision: false,
+                supports_vision: Some(false),
                 supports_streaming: true,
                 supports_function_calling: true,
             },
@@ -241,7 +173,7 @@ impl Provider for OpencodeProvider {
                 description: Some("DeepSeek's latest coding-focused model".to_string()),
                 context_length: Some(64000),
                 pricing: None,
-                supports_vision: false,
+                supports_vision: Some(false),
                 supports_streaming: true,
                 supports_function_calling: true,
             },
@@ -251,7 +183,7 @@ impl Provider for OpencodeProvider {
                 description: Some("Qwen's specialized coding model".to_string()),
                 context_length: Some(32000),
                 pricing: None,
-                supports_vision: false,
+                supports_vision: Some(false),
                 supports_streaming: true,
                 supports_function_calling: true,
             },
@@ -261,7 +193,7 @@ impl Provider for OpencodeProvider {
                 description: Some("ChatGLM latest generation".to_string()),
                 context_length: Some(128000),
                 pricing: None,
-                supports_vision: false,
+                supports_vision: Some(false),
                 supports_streaming: true,
                 supports_function_calling: true,
             },
@@ -274,7 +206,7 @@ impl Provider for OpencodeProvider {
                     prompt: Some(0.0),
                     completion: Some(0.0),
                 }),
-                supports_vision: false,
+                supports_vision: Some(false),
                 supports_streaming: true,
                 supports_function_calling: true,
             },
@@ -1,6 +1,6 @@
 use crate::config::ProviderConfig;
 use crate::error::{NexusError, Result};
-use crate::providers::{CompletionRequest, CompletionResponse, Message, ModelInfo, ModelPricing, Provider, ProviderInfo, Role, Usage};
+use crate::providers::{openai_wire, retry, CompletionRequest, CompletionResponse, ModelInfo, ModelPricing, Provider, ProviderInfo, StreamChunk};
 use async_trait::async_trait;
 use reqwest::Client;
 use serde_json;
@@ -61,11 +61,7 @@ impl OpenRouterProvider {
             .await?;
 
         if !response.status().is_success() {
-            let error_text = response.text().await.unwrap_or_default();
-            return Err(NexusError::ApiRequest(format!(
-                "OpenRouter API error: {}",
-                error_text
-            )));
+            return Err(retry::error_for_status("OpenRouter API error", response).await);
         }
 
         let data: serde_json::Value = response.json().await?;
@@ -95,13 +91,14 @@ impl Provider for OpenRouterProvider {
 
         let api_key = self.api_key.as_ref().unwrap();
         
-        let body = serde_json::json!({
-            "model": request.model,
-            "messages": request.messages,
-            "temperature": request.temperature.unwrap_or(0.7),
-            "max_tokens": request.max_tokens,
-            "stream": request.stream.unwrap_or(false),
-        });
+        let body = openai_wire::build_body(
+            &request.model,
+            &request.messages,
+            request.temperature,
+            request.max_tokens,
+            request.tools.as_deref(),
+            request.stream.unwrap_or(false),
+        );
 
         let response = self.client
             .post(format!("{}/chat/completions", self.base_url))
@@ -112,42 +109,46 @@ impl Provider for OpenRouterProvider {
             .await?;
 
         if !response.status().is_success() {
-            let error_text = response.text().await.unwrap_or_default();
-            return Err(NexusError::ApiRequest(format!(
-                "OpenRouter API error: {}",
-                error_text
-            )));
+            return Err(retry::error_for_status("OpenRouter API error", response).await);
         }
 
         let data: serde_json::Value = response.json().await?;
-        
-        let content = data["choices"][0]["message"]["content"]
-            .as_str()
-            .unwrap_or("")
-            .to_string();
-        
-        let finish_reason = data["choices"][0]["finish_reason"]
-            .as_str()
-            .map(|s| s.to_string());
-
-        let usage = if let Some(usage) = data.get("usage") {
-            Some(Usage {
-                prompt_tokens: usage["prompt_tokens"].as_u64().unwrap_or(0) as u32,
-                completion_tokens: usage["completion_tokens"].as_u64().unwrap_or(0) as u32,
-                total_tokens: usage["total_tokens"].as_u64().unwrap_or(0) as u32,
-            })
-        } else {
-            None
-        };
-
-        Ok(CompletionResponse {
-            id: data["id"].as_str().unwrap_or("unknown").to_string(),
-            model: request.model,
-            content,
-            finish_reason,
-            usage,
-            tool_calls: None,
-        })
+
+        Ok(openai_wire::parse_response(&data, request.model))
+    }
+
+    async fn complete_stream(
+        &self,
+        request: CompletionRequest,
+        tx: tokio::sync::mpsc::Sender<StreamChunk>,
+    ) -> Result<()> {
+        if !self.is_authenticated() {
+            return Err(NexusError::Authentication("OpenRouter API key not configured".into()));
+        }
+
+        let api_key = self.api_key.as_ref().unwrap();
+        let body = openai_wire::build_body(
+            &request.model,
+            &request.messages,
+            request.temperature,
+            request.max_tokens,
+            request.tools.as_deref(),
+            true,
+        );
+
+        let response = self.client
+            .post(format!("{}/chat/completions", self.base_url))
+            .header("Authorization", format!("Bearer {}", api_key))
+            .header("Content-Type", "application/json")
+            .json(&body)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(retry::error_for_status("OpenRouter streaming error", response).await);
+        }
+
+        openai_wire::stream_response(response, &tx).await
     }
 
     async fn list_available_models(&self) -> Result<Vec<ModelInfo>> {
@@ -157,11 +158,7 @@ impl Provider for OpenRouterProvider {
             .await?;
 
         if !response.status().is_success() {
-            let error_text = response.text().await.unwrap_or_default();
-            return Err(NexusError::ApiRequest(format!(
-                "OpenRouter API error: {}",
-                error_text
-            )));
+            return Err(retry::error_for_status("OpenRouter API error", response).await);
         }
 
         let data: serde_json::Value = response.json().await?;
@@ -199,8 +196,7 @@ impl Provider for OpenRouterProvider {
                     supports_vision: m["architecture"].as_object()
                         .and_then(|a| a.get("modality"))
                         .and_then(|mod_| mod_.as_str())
-                        .map(|s| s.contains("image"))
-                        .unwrap_or(false),
+                        .map(|s| s.contains("image")),
                     supports_streaming: true, // OpenRouter supports streaming for all models
                     supports_function_calling: m["top_provider"].as_object()
                         .and_then(|p| p.get("supports_function_calling"))
@@ -4,18 +4,31 @@ use std::time::Duration;
 /// Maximum backoff cap to prevent excessively long waits.
 const MAX_DELAY: Duration = Duration::from_secs(30);
 
-/// Determine whether an error is retryable based on its display string.
+/// Longest `Retry-After` we wait out; asking for more fails the request instead.
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(120);
+
+/// Determine whether an error is retryable.
+///
+/// HTTP errors are judged by status: 408, 429 and 5xx are retryable. Other
+/// errors are judged by their display string.
 ///
 /// Retryable patterns: rate limiting (429), server errors (500, 502, 503, 504),
 /// timeouts, and connection issues.
 ///
 /// Non-retryable patterns: client errors (400, 401, 403, 404), invalid input,
-/// and unauthorized access.
-fn is_retryable(err: &NexusError) -> bool {
+/// unauthorized access, and streams interrupted after partial output.
+pub fn is_retryable(err: &NexusError) -> bool {
+    match err {
+        NexusError::ApiStatus { status, .. } => return matches!(status, 408 | 429 | 500..=599),
+        NexusError::Http(e) if e.is_connect() || e.is_timeout() => return true,
+        NexusError::BudgetExceeded { .. } => return false,
+        _ => {}
+    }
+
     let msg = err.to_string().to_lowercase();
 
     // Non-retryable patterns take priority
-    let non_retryable = ["400", "401", "403", "404", "invalid", "unauthorized"];
+    let non_retryable = ["400", "401", "403", "404", "invalid", "unauthorized", "interrupted"];
     for pattern in &non_retryable {
         if msg.contains(pattern) {
             return false;
@@ -42,11 +55,40 @@ fn is_retryable(err: &NexusError) -> bool {
     false
 }
 
+/// Build the error for a failed HTTP response, keeping its status and any
+/// `Retry-After` so callers can decide whether and when to try again.
+pub async fn error_for_status(label: &str, response: reqwest::Response) -> NexusError {
+    let status = response.status().as_u16();
+    let retry_after = response.headers()
+        .get(reqwest::header::RETRY_AFTER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(parse_retry_after);
+    let body = response.text().await.unwrap_or_default();
+    NexusError::ApiStatus {
+        status,
+        message: format!("{}: {}", label, body.trim()),
+        retry_after,
+    }
+}
+
+/// Parse a `Retry-After` value: either seconds or an HTTP date
+fn parse_retry_after(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+    let at = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    let wait = at.with_timezone(&chrono::Utc) - chrono::Utc::now();
+    Some(wait.to_std().unwrap_or(Duration::ZERO))
+}
+
 /// Retry an async operation with exponential backoff.
 ///
 /// Starts with `initial_delay` and doubles it each attempt, capping at 30 seconds.
 /// Only retries errors classified as transient (rate limits, server errors,
 /// timeouts, connection issues). Non-retryable errors are returned immediately.
+/// A `Retry-After` from the server is waited out in place of the backoff delay,
+/// unless it is longer than two minutes, in which case the error is returned.
 ///
 /// # Arguments
 /// * `max_retries` - Maximum number of retry attempts (0 means execute once with no retries)
@@ -77,16 +119,22 @@ where
                     return Err(err);
                 }
 
+                let wait = match err.retry_after() {
+                    Some(retry_after) if retry_after > MAX_RETRY_AFTER => return Err(err),
+                    Some(retry_after) => retry_after.max(delay),
+                    None => delay,
+                };
+
                 // Log the retry attempt
                 eprintln!(
                     "[retry] Attempt {}/{} failed ({}), retrying in {:?}...",
                     attempt + 1,
                     max_retries + 1,
                     err,
-                    delay,
+                    wait,
                 );
 
-                tokio::time::sleep(delay).await;
+                tokio::time::sleep(wait).await;
 
                 // Double the delay for exponential backoff, capped at MAX_DELAY
                 delay = (delay * 2).min(MAX_DELAY);
@@ -182,8 +230,51 @@ mod tests {
         assert!(!is_retryable(&NexusError::ApiRequest("404 Not Found".into())));
         assert!(!is_retryable(&NexusError::ApiRequest("invalid API key".into())));
         assert!(!is_retryable(&NexusError::ApiRequest("unauthorized access".into())));
+        assert!(!is_retryable(&NexusError::ApiRequest("Stream interrupted after partial output: 503".into())));
 
         // Unknown errors are not retried
         assert!(!is_retryable(&NexusError::ApiRequest("some random error".into())));
+
+        // HTTP errors go by status, whatever the body says
+        let status = |status| NexusError::ApiStatus { status, message: "invalid request id".into(), retry_after: None };
+        assert!(is_retryable(&status(503)));
+        assert!(is_retryable(&status(429)));
+        assert!(!is_retryable(&status(400)));
+    }
+
+    #[test]
+    fn test_parse_retry_after() {
+        assert_eq!(parse_retry_after("7"), Some(Duration::from_secs(7)));
+        assert_eq!(parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"), Some(Duration::ZERO));
+        let later = (chrono::Utc::now() + chrono::Duration::seconds(90)).to_rfc2822();
+        assert!(parse_retry_after(&later).unwrap() > Duration::from_secs(80));
+        assert_eq!(parse_retry_after("soon"), None);
+    }
+
+    #[tokio::test]
+    async fn test_waits_out_retry_after() {
+        let counter = AtomicU32::new(0);
+        let start = std::time::Instant::now();
+
+        let result = retry_with_backoff(3, Duration::from_millis(1), || {
+            let attempt = counter.fetch_add(1, Ordering::SeqCst);
+            async move {
+                match attempt {
+                    0 => Err(NexusError::ApiStatus { status: 429, message: "slow down".into(), retry_after: Some(Duration::from_millis(50)) }),
+                    _ => Ok(attempt),
+                }
+            }
+        })
+        .await;
+
+        assert_eq!(result.unwrap(), 1);
+        assert!(start.elapsed() >= Duration::from_millis(50));
+
+        // Too long a wait fails straight away
+        let result = retry_with_backoff(3, Duration::from_millis(1), || async {
+            Err::<u32, _>(NexusError::ApiStatus { status: 429, message: "quota".into(), retry_after: Some(Duration::from_secs(3600)) })
+        })
+        .await;
+        assert!(result.unwrap_err().retry_after().is_some());
     }
 }
@@ -0,0 +1,180 @@
+//! Incremental Server-Sent Events parsing for streaming providers
+
+use crate::error::{NexusError, Result};
+use futures_util::stream::BoxStream;
+use futures_util::StreamExt;
+use std::collections::VecDeque;
+
+/// A single dispatched SSE event
+#[derive(Debug, Clone, PartialEq)]
+pub struct SseEvent {
+    /// The `event:` field, if the server set one
+    pub event: Option<String>,
+    /// All `data:` lines of the event, joined with newlines
+    pub data: String,
+}
+
+/// Buffers raw bytes from a response body and yields complete events.
+///
+/// Events are dispatched on a blank line, as the SSE spec requires, so a JSON
+/// payload split across network chunks is only parsed once it is complete. Bytes
+/// are buffered until a full line arrives, so multi-byte characters split across
+/// chunks decode correctly.
+#[derive(Debug, Default)]
+pub struct SseParser {
+    buffer: Vec<u8>,
+    event: Option<String>,
+    data: Vec<String>,
+}
+
+impl SseParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feed a chunk of the response body, returning any events it completed
+    pub fn push(&mut self, chunk: &[u8]) -> Vec<SseEvent> {
+        self.buffer.extend_from_slice(chunk);
+        let mut events = Vec::new();
+
+        while let Some(pos) = self.buffer.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.buffer.drain(..=pos).collect();
+            let line = String::from_utf8_lossy(&line);
+            let line = line.trim_end_matches(['\n', '\r']);
+
+            if line.is_empty() {
+                if let Some(event) = self.dispatch() {
+                    events.push(event);
+                }
+            } else if let Some(value) = line.strip_prefix("data:") {
+                self.data.push(value.strip_prefix(' ').unwrap_or(value).to_string());
+            } else if let Some(value) = line.strip_prefix("event:") {
+                self.event = Some(value.trim().to_string());
+            }
+            // Comments (":") and other fields (id, retry) are ignored
+        }
+
+        events
+    }
+
+    /// Flush a final event that was not terminated by a blank line
+    pub fn finish(&mut self) -> Option<SseEvent> {
+        if !self.buffer.is_empty() {
+            let mut rest = std::mem::take(&mut self.buffer);
+            rest.push(b'\n');
+            self.push(&rest);
+        }
+        self.dispatch()
+    }
+
+    fn dispatch(&mut self) -> Option<SseEvent> {
+        let event = self.event.take();
+        if self.data.is_empty() {
+            return None;
+        }
+        Some(SseEvent {
+            event,
+            data: std::mem::take(&mut self.data).join("\n"),
+        })
+    }
+}
+
+/// Pulls SSE events out of a streaming HTTP response
+pub struct SseStream {
+    body: BoxStream<'static, reqwest::Result<Vec<u8>>>,
+    parser: SseParser,
+    pending: VecDeque<SseEvent>,
+    finished: bool,
+}
+
+impl SseStream {
+    pub fn new(response: reqwest::Response) -> Self {
+        Self {
+            body: response.bytes_stream().map(|chunk| chunk.map(|bytes| bytes.to_vec())).boxed(),
+            parser: SseParser::new(),
+            pending: VecDeque::new(),
+            finished: false,
+        }
+    }
+
+    /// The next event, or `None` once the body has been fully read
+    pub async fn next_event(&mut self) -> Result<Option<SseEvent>> {
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                return Ok(Some(event));
+            }
+            if self.finished {
+                return Ok(None);
+            }
+            match self.body.next().await {
+                Some(chunk) => {
+                    let bytes = chunk.map_err(|e| NexusError::ApiRequest(format!("Stream read failed: {}", e)))?;
+                    self.pending.extend(self.parser.push(&bytes));
+                }
+                None => {
+                    self.finished = true;
+                    self.pending.extend(self.parser.finish());
+                }
+            }
+        }
+    }
+}
+
+/// A throwaway HTTP server that replays canned SSE bodies, for provider tests
+#[cfg(test)]
+pub(crate) mod testing {
+    use axum::http::header;
+    use axum::response::IntoResponse;
+    use axum::routing::post;
+    use axum::Router;
+
+    /// Serve `body` as `text/event-stream` on POST `path`, returning the base URL
+    pub async fn serve_sse(path: &str, body: &'static str) -> String {
+        let app = Router::new().route(
+            path,
+            post(move || async move { ([(header::CONTENT_TYPE, "text/event-stream")], body).into_response() }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+        format!("http://{}", addr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_split_across_chunks() {
+        let mut parser = SseParser::new();
+        assert!(parser.push(b"event: content_block_delta\ndata: {\"a\":").is_empty());
+        let events = parser.push(b"1}\n\ndata: [DONE]\n\n");
+
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].event.as_deref(), Some("content_block_delta"));
+        assert_eq!(events[0].data, "{\"a\":1}");
+        assert_eq!(events[1].event, None);
+        assert_eq!(events[1].data, "[DONE]");
+    }
+
+    #[test]
+    fn test_crlf_comments_and_unterminated_event() {
+        let mut parser = SseParser::new();
+        let events = parser.push(b": keep-alive\r\n\r\ndata: first\r\n\r\ndata: last");
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].data, "first");
+        assert_eq!(parser.finish().unwrap().data, "last");
+    }
+
+    #[test]
+    fn test_multibyte_character_split_across_chunks() {
+        let mut parser = SseParser::new();
+        let bytes = "data: héllo\n\n".as_bytes();
+        assert!(parser.push(&bytes[..8]).is_empty());
+        let events = parser.push(&bytes[8..]);
+        assert_eq!(events[0].data, "héllo");
+    }
+}
@@ -1,8 +1,10 @@
+use crate::providers::Message;
+use crate::providers::tokenizer::{self, TokenCounter};
+
 /// Tracks token usage across a multi-turn agent session and enforces budget limits.
 ///
-/// The estimator uses a simple `len / 4` heuristic (roughly 4 bytes per token for
-/// English text), which is intentionally cheap so it can be called on every turn
-/// without adding latency.
+/// Estimates come from a [`TokenCounter`] for the model family (see
+/// [`TokenBudget::for_model`]); a default budget falls back to the byte heuristic.
 #[derive(Debug, Clone)]
 pub struct TokenBudget {
     /// Hard cap on cumulative input (prompt) tokens.
@@ -13,6 +15,8 @@ pub struct TokenBudget {
     pub used_input_tokens: u32,
     /// Running total of output tokens consumed so far.
     pub used_output_tokens: u32,
+    /// Counter used for estimates when the provider reports no usage.
+    counter: &'static dyn TokenCounter,
 }
 
 impl Default for TokenBudget {
@@ -29,12 +33,39 @@ impl TokenBudget {
             max_total_tokens: max_total,
             used_input_tokens: 0,
             used_output_tokens: 0,
+            counter: tokenizer::heuristic(),
         }
     }
 
-    /// Cheap heuristic: ~4 bytes per token for English text.
+    /// Default caps, counting tokens with the tokenizer for `model`'s family.
+    pub fn for_model(model: &str) -> Self {
+        Self {
+            counter: tokenizer::counter_for_model(model),
+            ..Self::default()
+        }
+    }
+
+    /// Replace the caps, keeping the tokenizer.
+    pub fn with_limits(mut self, max_input: u32, max_total: u32) -> Self {
+        self.max_input_tokens = max_input;
+        self.max_total_tokens = max_total;
+        self
+    }
+
+    /// Cheap heuristic that needs no model: ~4 bytes per ASCII token, one token
+    /// per non-ASCII character.
     pub fn estimate_tokens(text: &str) -> u32 {
-        (text.len() as u32) / 4
+        tokenizer::heuristic().count(text)
+    }
+
+    /// Count `text` with this budget's tokenizer.
+    pub fn count_tokens(&self, text: &str) -> u32 {
+        self.counter.count(text)
+    }
+
+    /// Count a full prompt, including per-message framing.
+    pub fn count_messages(&self, messages: &[Message]) -> u32 {
+        self.counter.count_messages(messages)
     }
 
     /// How many total tokens remain before the budget is exhausted.
@@ -85,6 +116,14 @@ mod tests {
         assert_eq!(TokenBudget::estimate_tokens(""), 0);
     }
 
+    #[test]
+    fn test_for_model_uses_bpe() {
+        let b = TokenBudget::for_model("claude-sonnet-4-5");
+        // "fn main() {}" is 12 bytes, but BPE splits the punctuation finer
+        assert!(b.count_tokens("fn main() {}") > TokenBudget::estimate_tokens("fn main() {}"));
+        assert_eq!(b.max_total_tokens, TokenBudget::default().max_total_tokens);
+    }
+
     #[test]
     fn test_record_usage_and_remaining() {
         let mut b = TokenBudget::new(1000, 2000);
@@ -0,0 +1,143 @@
+//! Token counting per model family
+//!
+//! OpenAI-style BPE vocabularies are bundled with `tiktoken-rs`, so counting works
+//! offline. Models without a published tokenizer (Claude, Gemini, Kimi, ...) are
+//! counted with `cl100k_base`, which tracks them far more closely than a byte
+//! heuristic. The heuristic is only used if a vocabulary fails to load.
+
+use crate::providers::Message;
+use once_cell::sync::Lazy;
+use tiktoken_rs::CoreBPE;
+
+/// Tokens added per message for role and framing
+const MESSAGE_OVERHEAD: u32 = 4;
+
+/// Counts tokens for one model family
+pub trait TokenCounter: std::fmt::Debug + Send + Sync {
+    /// Name of the encoding, for logs
+    fn name(&self) -> &str;
+
+    fn count(&self, text: &str) -> u32;
+
+    fn count_message(&self, message: &Message) -> u32 {
+        self.count(&message.flattened_text()) + MESSAGE_OVERHEAD
+    }
+
+    fn count_messages(&self, messages: &[Message]) -> u32 {
+        messages.iter().map(|m| self.count_message(m)).sum()
+    }
+}
+
+/// Byte-based estimate, used when no BPE vocabulary is available.
+///
+/// ASCII text averages about 4 bytes per token, while other scripts (CJK,
+/// emoji, accented text) come close to one token per character.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HeuristicCounter;
+
+impl TokenCounter for HeuristicCounter {
+    fn name(&self) -> &str {
+        "heuristic"
+    }
+
+    fn count(&self, text: &str) -> u32 {
+        let ascii = text.bytes().filter(|b| b.is_ascii()).count();
+        let other = text.chars().filter(|c| !c.is_ascii()).count();
+        (ascii / 4 + other) as u32
+    }
+}
+
+/// Exact counts from a byte-pair encoding
+pub struct BpeCounter {
+    name: &'static str,
+    bpe: CoreBPE,
+}
+
+impl std::fmt::Debug for BpeCounter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BpeCounter").field("name", &self.name).finish()
+    }
+}
+
+impl TokenCounter for BpeCounter {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn count(&self, text: &str) -> u32 {
+        self.bpe.encode_ordinary(text).len() as u32
+    }
+}
+
+static CL100K: Lazy<Option<BpeCounter>> = Lazy::new(|| {
+    tiktoken_rs::cl100k_base().ok().map(|bpe| BpeCounter { name: "cl100k_base", bpe })
+});
+
+static O200K: Lazy<Option<BpeCounter>> = Lazy::new(|| {
+    tiktoken_rs::o200k_base().ok().map(|bpe| BpeCounter { name: "o200k_base", bpe })
+});
+
+static HEURISTIC: HeuristicCounter = HeuristicCounter;
+
+/// The counter for `model`, falling back to the heuristic if its vocabulary
+/// cannot be loaded.
+pub fn counter_for_model(model: &str) -> &'static dyn TokenCounter {
+    let encoding = match uses_o200k(model) {
+        true => O200K.as_ref(),
+        false => CL100K.as_ref(),
+    };
+    match encoding {
+        Some(counter) => counter,
+        None => &HEURISTIC,
+    }
+}
+
+/// The cheap heuristic counter, for callers that do not know the model
+pub fn heuristic() -> &'static dyn TokenCounter {
+    &HEURISTIC
+}
+
+/// GPT-4o and later OpenAI models use `o200k_base`
+fn uses_o200k(model: &str) -> bool {
+    let id = model.rsplit('/').next().unwrap_or(model);
+    ["gpt-4o", "gpt-4.1", "gpt-4.5", "gpt-5", "o1", "o3", "o4"]
+        .iter()
+        .any(|prefix| id.starts_with(prefix))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_family_selection() {
+        assert_eq!(counter_for_model("gpt-5.1-codex").name(), "o200k_base");
+        assert_eq!(counter_for_model("openai/gpt-4o-mini").name(), "o200k_base");
+        assert_eq!(counter_for_model("claude-sonnet-4-5").name(), "cl100k_base");
+        assert_eq!(counter_for_model("kimi-k2.5").name(), "cl100k_base");
+    }
+
+    #[test]
+    fn test_bpe_counts_code_and_json_closer_than_bytes() {
+        let counter = counter_for_model("claude-sonnet-4-5");
+        assert_eq!(counter.count("hello world"), 2);
+
+        // Dense punctuation tokenizes far worse than 4 bytes per token
+        let json = r#"{"a":[1,2,3],"b":{"c":null}}"#;
+        assert!(counter.count(json) > (json.len() / 4) as u32);
+    }
+
+    #[test]
+    fn test_heuristic_counts_non_ascii_per_character() {
+        assert_eq!(HeuristicCounter.count("hello world!"), 3);
+        assert_eq!(HeuristicCounter.count("你好世界"), 4);
+        assert!(counter_for_model("gpt-4o").count("你好世界") >= 2);
+    }
+
+    #[test]
+    fn test_message_overhead() {
+        let counter = heuristic();
+        let messages = vec![Message::user("12345678"), Message::assistant("")];
+        assert_eq!(counter.count_messages(&messages), 2 + 2 * MESSAGE_OVERHEAD);
+    }
+}
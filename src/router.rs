@@ -0,0 +1,651 @@
+//! Routes completions through the model hierarchy
+//!
+//! [`ModelRouter`] classifies each request into a [`TaskCategory`], sends it to
+//! that category's first tier and moves up a tier when the
+//! [`EscalationPolicy`] says the answer isn't good enough: the provider failed,
+//! the model refused, or the code it wrote doesn't parse. Reported test
+//! failures raise the tier a category starts at until its tests pass again.
+//! Every escalation is logged to `~/.config/nexus/escalations.jsonl`, and
+//! usage is recorded in the cost ledger under the category it was routed as.
+//!
+//! Callers get a [`Provider`] from [`ModelRouter::provider`], either pinned to
+//! a category (the swarm architect plans, workers code) or classifying each
+//! request from its last user message.
+
+use crate::config::{ConfigManager, ProviderType};
+use crate::error::Result;
+use crate::hierarchy::{self, classify_task, EscalationPolicy, ModelHierarchy, TaskCategory};
+use crate::ledger::Meter;
+use crate::providers::{fallback, CompletionRequest, CompletionResponse, ModelInfo, Provider, ProviderInfo, Role};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::iter::Peekable;
+use std::path::PathBuf;
+use std::str::Chars;
+use std::sync::{Arc, Mutex};
+use tracing::{debug, warn};
+
+/// Why a request moved up a tier
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum EscalationReason {
+    Error { message: String },
+    Refusal,
+    SyntaxError { detail: String },
+    TestFailure,
+}
+
+impl fmt::Display for EscalationReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EscalationReason::Error { message } => write!(f, "error: {}", message),
+            EscalationReason::Refusal => write!(f, "model refused"),
+            EscalationReason::SyntaxError { detail } => write!(f, "syntax error: {}", detail),
+            EscalationReason::TestFailure => write!(f, "tests failed"),
+        }
+    }
+}
+
+/// One entry of the escalation log
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Escalation {
+    pub at: DateTime<Utc>,
+    pub category: TaskCategory,
+    pub from_model: String,
+    pub to_model: String,
+    pub reason: EscalationReason,
+}
+
+/// A model and the provider that serves it
+#[derive(Clone)]
+pub struct Route {
+    pub provider_name: String,
+    pub model: String,
+    pub max_tokens: Option<u32>,
+    provider: Arc<dyn Provider + Send + Sync>,
+}
+
+impl Route {
+    pub fn new(provider_name: impl Into<String>, provider: Arc<dyn Provider + Send + Sync>, model: impl Into<String>) -> Self {
+        Self {
+            provider_name: provider_name.into(),
+            model: model.into(),
+            max_tokens: None,
+            provider,
+        }
+    }
+
+    pub fn with_max_tokens(mut self, max_tokens: Option<u32>) -> Self {
+        self.max_tokens = max_tokens;
+        self
+    }
+
+    fn request(&self, request: &CompletionRequest) -> CompletionRequest {
+        let mut request = request.clone();
+        request.model = self.model.clone();
+        request.max_tokens = match (request.max_tokens, self.max_tokens) {
+            (Some(asked), Some(cap)) => Some(asked.min(cap)),
+            (asked, cap) => asked.or(cap),
+        };
+        request
+    }
+}
+
+pub struct ModelRouter {
+    /// Tiers per category, cheapest first. Categories without a usable tier
+    /// use `default`.
+    tiers: HashMap<TaskCategory, Vec<Route>>,
+    default: Route,
+    policy: EscalationPolicy,
+    /// Tier each category starts at, raised by reported test failures
+    floors: Mutex<HashMap<TaskCategory, usize>>,
+    log_path: Option<PathBuf>,
+    meter: Option<Arc<Meter>>,
+}
+
+impl ModelRouter {
+    /// A router that sends everything to `default` until tiers are added
+    pub fn new(default: Route, policy: EscalationPolicy) -> Self {
+        Self {
+            tiers: HashMap::new(),
+            default,
+            policy,
+            floors: Mutex::new(HashMap::new()),
+            log_path: None,
+            meter: None,
+        }
+    }
+
+    /// Add the next tier up for `category`
+    pub fn with_tier(mut self, category: TaskCategory, route: Route) -> Self {
+        self.tiers.entry(category).or_default().push(route);
+        self
+    }
+
+    /// Append each escalation to `path` as a JSON line
+    pub fn with_log(mut self, path: PathBuf) -> Self {
+        self.log_path = Some(path);
+        self
+    }
+
+    /// Record usage with `meter` and stop at its daily budget
+    pub fn with_meter(mut self, meter: Arc<Meter>) -> Self {
+        self.meter = Some(meter);
+        self
+    }
+
+    /// Build the router from the saved hierarchy and escalation policy.
+    /// `default_provider` serves categories none of whose tiers map to a
+    /// configured provider.
+    pub async fn from_config(config_manager: &ConfigManager, default_provider: &str) -> Result<Self> {
+        let config_dir = hierarchy::config_dir();
+        let hierarchy = ModelHierarchy::load(&config_dir)?;
+        let policy = EscalationPolicy::load(&config_dir)?;
+
+        let mut provider = fallback::create_chain(config_manager, default_provider, None)?;
+        if !provider.is_authenticated() {
+            provider.authenticate().await?;
+        }
+        let model = config_manager.get_provider(default_provider)
+            .and_then(|p| p.default_model.clone())
+            .unwrap_or_else(|| provider.info().default_model);
+        let mut router = Self::new(Route::new(default_provider, Arc::from(provider), model), policy)
+            .with_log(config_dir.join("escalations.jsonl"))
+            .with_meter(Arc::new(Meter::from_config()?));
+
+        // Providers are shared between tiers; `None` marks one that couldn't be set up
+        let mut providers: HashMap<String, Option<Arc<dyn Provider + Send + Sync>>> = HashMap::new();
+        providers.insert(default_provider.to_string(), Some(router.default.provider.clone()));
+
+        for category in TaskCategory::ALL {
+            for tier in hierarchy.tiers(category) {
+                if tier.model_id.is_empty() {
+                    continue;
+                }
+                let Some(name) = tier.provider.clone().or_else(|| provider_for_model(config_manager, &tier.model_id)) else {
+                    debug!(model = %tier.model_id, category = category.as_str(), "No configured provider serves this tier");
+                    continue;
+                };
+                if !providers.contains_key(&name) {
+                    let provider = connect(config_manager, &name).await;
+                    providers.insert(name.clone(), provider);
+                }
+                if let Some(Some(provider)) = providers.get(&name) {
+                    let route = Route::new(name, provider.clone(), tier.model_id.clone()).with_max_tokens(tier.max_tokens);
+                    router = router.with_tier(category, route);
+                }
+            }
+        }
+        Ok(router)
+    }
+
+    /// A provider that routes through this router. With a category, every
+    /// request is treated as that kind of task; without one, each request is
+    /// classified from its last user message.
+    pub fn provider(self: &Arc<Self>, category: Option<TaskCategory>) -> Arc<dyn Provider + Send + Sync> {
+        Arc::new(RoutedProvider { router: self.clone(), category })
+    }
+
+    /// Tiers a request in `category` can go through, never empty
+    pub fn tiers(&self, category: TaskCategory) -> &[Route] {
+        match self.tiers.get(&category) {
+            Some(tiers) if !tiers.is_empty() => tiers,
+            _ => std::slice::from_ref(&self.default),
+        }
+    }
+
+    /// The model a request in `category` starts with
+    pub fn model_for(&self, category: TaskCategory) -> &str {
+        let tiers = self.tiers(category);
+        &tiers[self.floor(category).min(tiers.len() - 1)].model
+    }
+
+    fn floor(&self, category: TaskCategory) -> usize {
+        self.floors.lock().unwrap_or_else(|e| e.into_inner()).get(&category).copied().unwrap_or(0)
+    }
+
+    /// Record the outcome of tests run on work done in `category`. With
+    /// `escalate_on_test_failure`, a failure makes later requests in that
+    /// category start one tier higher; passing tests reset it.
+    pub fn report_test_result(&self, category: TaskCategory, passed: bool) {
+        let mut floors = self.floors.lock().unwrap_or_else(|e| e.into_inner());
+        if passed {
+            floors.remove(&category);
+            return;
+        }
+        if !self.policy.enabled || !self.policy.escalate_on_test_failure {
+            return;
+        }
+        let tiers = self.tiers(category);
+        let floor = floors.entry(category).or_default();
+        if *floor + 1 < tiers.len() && *floor < self.policy.max_escalations {
+            self.log(category, &tiers[*floor], &tiers[*floor + 1], EscalationReason::TestFailure);
+            *floor += 1;
+        }
+    }
+
+    /// Why `response` should be retried on a stronger model, if it should
+    fn check(&self, response: &CompletionResponse) -> Option<EscalationReason> {
+        if self.policy.escalate_on_refusal && is_refusal(response) {
+            return Some(EscalationReason::Refusal);
+        }
+        if self.policy.escalate_on_syntax_error {
+            return syntax_problem(&response.content).map(|detail| EscalationReason::SyntaxError { detail });
+        }
+        None
+    }
+
+    async fn complete(&self, category: TaskCategory, request: CompletionRequest) -> Result<CompletionResponse> {
+        let tiers = self.tiers(category);
+        let mut index = self.floor(category).min(tiers.len() - 1);
+        let mut escalations = 0;
+        loop {
+            // Checked before every tier, since an escalation spends again
+            if let Some(meter) = &self.meter {
+                meter.check_budget()?;
+            }
+            let route = &tiers[index];
+            let result = route.provider.complete(route.request(&request)).await;
+            if let (Some(meter), Ok(CompletionResponse { usage: Some(usage), .. })) = (&self.meter, &result) {
+                meter.record(&route.provider_name, route.provider.as_ref(), category, &route.model, usage).await;
+            }
+            let reason = match &result {
+                Ok(response) => self.check(response),
+                Err(e) if self.policy.escalate_on_error => Some(EscalationReason::Error { message: e.to_string() }),
+                Err(_) => None,
+            };
+            match reason {
+                Some(reason)
+                    if self.policy.enabled
+                        && escalations < self.policy.max_escalations
+                        && index + 1 < tiers.len() =>
+                {
+                    self.log(category, route, &tiers[index + 1], reason);
+                    index += 1;
+                    escalations += 1;
+                }
+                _ => return result,
+            }
+        }
+    }
+
+    fn log(&self, category: TaskCategory, from: &Route, to: &Route, reason: EscalationReason) {
+        warn!(
+            category = category.as_str(),
+            from = %format!("{}/{}", from.provider_name, from.model),
+            to = %format!("{}/{}", to.provider_name, to.model),
+            reason = %reason,
+            "Escalating to the next model tier"
+        );
+        let Some(path) = &self.log_path else {
+            return;
+        };
+        let entry = Escalation {
+            at: Utc::now(),
+            category,
+            from_model: from.model.clone(),
+            to_model: to.model.clone(),
+            reason,
+        };
+        let written = serde_json::to_string(&entry).map_err(std::io::Error::from).and_then(|line| {
+            OpenOptions::new().create(true).append(true).open(path)
+                .and_then(|mut file| writeln!(file, "{}", line))
+        });
+        if let Err(e) = written {
+            warn!(path = %path.display(), error = %e, "Could not write escalation log");
+        }
+    }
+}
+
+/// Create and authenticate the configured provider `name`, or `None` with a
+/// warning if that fails, so one broken provider doesn't take the rest down
+async fn connect(config_manager: &ConfigManager, name: &str) -> Option<Arc<dyn Provider + Send + Sync>> {
+    let connected = async {
+        let mut provider = fallback::create_chain(config_manager, name, None)?;
+        if !provider.is_authenticated() {
+            provider.authenticate().await?;
+        }
+        Ok::<_, crate::error::NexusError>(provider)
+    };
+    match connected.await {
+        Ok(provider) => Some(Arc::from(provider)),
+        Err(e) => {
+            warn!(provider = %name, error = %e, "Skipping hierarchy tiers served by this provider");
+            None
+        }
+    }
+}
+
+/// The configured provider that serves `model`: one that lists it, or else
+/// one of the type its name suggests
+fn provider_for_model(config_manager: &ConfigManager, model: &str) -> Option<String> {
+    let providers = &config_manager.get().providers;
+    let mut names: Vec<&String> = providers.keys().collect();
+    names.sort();
+
+    let lists_model = names.iter().find(|name| {
+        let config = &providers[name.as_str()];
+        config.default_model.as_deref() == Some(model) || config.models.iter().any(|m| m == model)
+    });
+    if let Some(name) = lists_model {
+        return Some(name.to_string());
+    }
+
+    let provider_type = if model.starts_with("claude-") {
+        ProviderType::Claude
+    } else if model.starts_with("gemini-") {
+        ProviderType::Google
+    } else if model.contains('/') {
+        ProviderType::Openrouter
+    } else if model.starts_with("gpt-") || model.starts_with("o1") || model.starts_with("o3") {
+        ProviderType::OpenaiCompatible
+    } else {
+        return None;
+    };
+    names.into_iter()
+        .find(|name| providers[name.as_str()].provider_type == provider_type)
+        .cloned()
+}
+
+/// Whether the model declined the task instead of attempting it
+fn is_refusal(response: &CompletionResponse) -> bool {
+    const OPENINGS: &[&str] = &[
+        "i can't help",
+        "i cannot help",
+        "i can't assist",
+        "i cannot assist",
+        "i'm unable to",
+        "i am unable to",
+        "i won't be able to",
+        "i'm sorry, but i can't",
+        "i'm sorry, but i cannot",
+        "sorry, i can't",
+    ];
+    if response.tool_calls.as_ref().is_some_and(|calls| !calls.is_empty()) {
+        return false;
+    }
+    let opening = response.content.trim_start().to_lowercase().replace('\u{2019}', "'");
+    OPENINGS.iter().any(|refusal| opening.starts_with(refusal))
+}
+
+/// A problem with the code blocks in `content`: a block cut off before its
+/// closing fence, JSON that doesn't parse, or unbalanced brackets
+fn syntax_problem(content: &str) -> Option<String> {
+    const CHECKED: &[&str] = &[
+        "rust", "rs", "javascript", "js", "jsx", "typescript", "ts", "tsx", "python", "py",
+        "go", "java", "kotlin", "swift", "c", "cpp", "csharp", "cs",
+    ];
+    let mut lines = content.lines();
+    while let Some(line) = lines.next() {
+        let Some(lang) = line.trim_start().strip_prefix("```") else {
+            continue;
+        };
+        let lang = lang.trim().to_lowercase();
+        let mut body = String::new();
+        let mut closed = false;
+        for line in lines.by_ref() {
+            if line.trim_start().starts_with("```") {
+                closed = true;
+                break;
+            }
+            body.push_str(line);
+            body.push('\n');
+        }
+        if !closed {
+            return Some("code block is not closed".to_string());
+        }
+        if lang == "json" {
+            if let Err(e) = serde_json::from_str::<serde_json::Value>(&body) {
+                return Some(format!("invalid JSON: {}", e));
+            }
+        } else if CHECKED.contains(&lang.as_str())
+            && let Some(problem) = unbalanced(&body, &lang)
+        {
+            return Some(format!("{} block: {}", lang, problem));
+        }
+    }
+    None
+}
+
+/// First bracket mismatch in `code`, skipping the strings, character
+/// literals and comments `lang` has
+fn unbalanced(code: &str, lang: &str) -> Option<String> {
+    let python = matches!(lang, "python" | "py");
+    let javascript = matches!(lang, "javascript" | "js" | "jsx" | "typescript" | "ts" | "tsx");
+    let mut open = Vec::new();
+    let mut chars = code.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => skip_quoted(&mut chars, '"'),
+            '\'' if python || javascript => skip_quoted(&mut chars, '\''),
+            '\'' => skip_char_literal(&mut chars),
+            // Template literals, and Go's raw strings
+            '`' if javascript || lang == "go" => skip_quoted(&mut chars, '`'),
+            '#' if python => {
+                chars.by_ref().find(|&c| c == '\n');
+            }
+            '/' if !python && chars.peek() == Some(&'/') => {
+                chars.by_ref().find(|&c| c == '\n');
+            }
+            '/' if !python && chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut previous = ' ';
+                chars.by_ref().find(|&c| std::mem::replace(&mut previous, c) == '*' && c == '/');
+            }
+            '(' | '[' | '{' => open.push(c),
+            ')' | ']' | '}' => {
+                let expected = match c {
+                    ')' => '(',
+                    ']' => '[',
+                    _ => '{',
+                };
+                if open.pop() != Some(expected) {
+                    return Some(format!("unexpected '{}'", c));
+                }
+            }
+            _ => {}
+        }
+    }
+    open.last().map(|c| format!("unclosed '{}'", c))
+}
+
+/// Skip past the `close` ending a string, minding backslash escapes
+fn skip_quoted(chars: &mut Peekable<Chars>, close: char) {
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            chars.next();
+        } else if c == close {
+            break;
+        }
+    }
+}
+
+/// Skip a character literal like `'x'` or `'\n'`. A quote that doesn't start
+/// one, such as a Rust lifetime, is left alone.
+fn skip_char_literal(chars: &mut Peekable<Chars>) {
+    let mut ahead = chars.clone();
+    let length = match ahead.next() {
+        Some('\\') => {
+            ahead.next();
+            ahead.take(10).position(|c| c == '\'').map(|n| n + 3)
+        }
+        Some(_) => (ahead.next() == Some('\'')).then_some(2),
+        None => None,
+    };
+    for _ in 0..length.unwrap_or(0) {
+        chars.next();
+    }
+}
+
+/// A [`Provider`] view of a [`ModelRouter`]
+struct RoutedProvider {
+    router: Arc<ModelRouter>,
+    category: Option<TaskCategory>,
+}
+
+impl RoutedProvider {
+    fn category(&self, request: &CompletionRequest) -> TaskCategory {
+        self.category.unwrap_or_else(|| {
+            let input = request.messages.iter().rev()
+                .find(|m| m.role == Role::User)
+                .map(|m| m.text_content())
+                .unwrap_or_default();
+            classify_task(&input, false)
+        })
+    }
+}
+
+#[async_trait]
+impl Provider for RoutedProvider {
+    fn info(&self) -> ProviderInfo {
+        let default = &self.router.default;
+        ProviderInfo {
+            display_name: format!("{} (model hierarchy)", default.provider.info().display_name),
+            default_model: self.router.model_for(self.category.unwrap_or(TaskCategory::Daily)).to_string(),
+            ..default.provider.info()
+        }
+    }
+
+    /// Escalation needs the whole answer, so streaming uses the trait's
+    /// default, which wraps this
+    async fn complete(&self, request: CompletionRequest) -> Result<CompletionResponse> {
+        let category = self.category(&request);
+        self.router.complete(category, request).await
+    }
+
+    async fn list_available_models(&self) -> Result<Vec<ModelInfo>> {
+        self.router.default.provider.list_available_models().await
+    }
+
+    async fn authenticate(&mut self) -> Result<()> {
+        // Routes are authenticated when the router is built
+        Ok(())
+    }
+
+    async fn refresh_auth(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn is_authenticated(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::NexusError;
+    use crate::ledger::CostLedger;
+    use crate::providers::mock::{Cassette, Interaction, MockProvider, ScriptedResponse};
+    use crate::providers::model_capabilities::ModelRegistry;
+    use crate::providers::{Message, Usage};
+
+    fn request(text: &str) -> CompletionRequest {
+        CompletionRequest::new("ignored".to_string(), vec![Message::system("You are helpful"), Message::user(text)])
+    }
+
+    fn read_log(path: &std::path::Path) -> Vec<Escalation> {
+        std::fs::read_to_string(path).unwrap_or_default()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_escalates_on_error_refusal_and_syntax_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = dir.path().join("escalations.jsonl");
+        let router = Arc::new(
+            ModelRouter::new(Route::new("default", Arc::new(MockProvider::replying(&[Ok("daily answer")])), "flash"), EscalationPolicy::default())
+                .with_tier(TaskCategory::Coding, Route::new("a", Arc::new(MockProvider::replying(&[Err("500 internal error")])), "small"))
+                .with_tier(TaskCategory::Coding, Route::new("b", Arc::new(MockProvider::replying(&[Ok("I can't help with that.")])), "medium"))
+                .with_tier(TaskCategory::Coding, Route::new("c", Arc::new(MockProvider::replying(&[Ok("```rust\nfn main() {\n```")])), "large"))
+                .with_tier(TaskCategory::Coding, Route::new("d", Arc::new(MockProvider::replying(&[Ok("```rust\nfn main() {}\n```")])), "largest"))
+                .with_log(log.clone()),
+        );
+
+        // Classified from the last user message; tiers 0-2 each fail the policy
+        let provider = router.provider(None);
+        let response = provider.complete(request("implement the parser")).await.unwrap();
+        assert_eq!(response.content, "```rust\nfn main() {}\n```");
+
+        let reasons: Vec<_> = read_log(&log).into_iter().map(|e| (e.from_model, e.to_model, e.reason)).collect();
+        assert!(matches!(&reasons[0], (from, to, EscalationReason::Error { .. }) if from == "small" && to == "medium"));
+        assert_eq!(reasons[1].2, EscalationReason::Refusal);
+        assert!(matches!(&reasons[2].2, EscalationReason::SyntaxError { detail } if detail.contains("unclosed '{'")));
+
+        // A category with no tiers uses the default route
+        let response = provider.complete(request("what time is it")).await.unwrap();
+        assert_eq!(response.content, "daily answer");
+    }
+
+    #[tokio::test]
+    async fn test_escalation_limits_and_test_failures() {
+        let policy = EscalationPolicy { max_escalations: 1, escalate_on_test_failure: true, ..EscalationPolicy::default() };
+        let router = Arc::new(
+            ModelRouter::new(Route::new("default", Arc::new(MockProvider::replying(&[])), "flash"), policy)
+                .with_tier(TaskCategory::Planning, Route::new("a", Arc::new(MockProvider::replying(&[Err("503"), Ok("unused")])), "small"))
+                .with_tier(TaskCategory::Planning, Route::new("b", Arc::new(MockProvider::replying(&[Err("503 again"), Ok("planned")])), "medium"))
+                .with_tier(TaskCategory::Planning, Route::new("c", Arc::new(MockProvider::replying(&[Ok("unused")])), "large")),
+        );
+        let planner = router.provider(Some(TaskCategory::Planning));
+
+        // One escalation allowed, so the second tier's error is returned
+        assert!(planner.complete(request("anything")).await.unwrap_err().to_string().contains("503 again"));
+
+        // A test failure makes the next request start a tier up
+        router.report_test_result(TaskCategory::Planning, false);
+        assert_eq!(router.model_for(TaskCategory::Planning), "medium");
+        assert_eq!(planner.complete(request("anything")).await.unwrap().content, "planned");
+        router.report_test_result(TaskCategory::Planning, true);
+        assert_eq!(router.model_for(TaskCategory::Planning), "small");
+    }
+
+    #[tokio::test]
+    async fn test_budget_checked_before_each_tier() {
+        let dir = tempfile::tempdir().unwrap();
+        // Half a million Sonnet tokens cost $1.50, over the $1 budget
+        let refusal = Interaction {
+            response: ScriptedResponse {
+                content: "I can't help with that.".to_string(),
+                usage: Some(Usage { prompt_tokens: 500_000, total_tokens: 500_000, ..Usage::default() }),
+                ..ScriptedResponse::default()
+            },
+            ..Interaction::default()
+        };
+        let meter = Meter::new(CostLedger::open(dir.path().join("usage.jsonl")), 1.0)
+            .with_registry(Arc::new(ModelRegistry::builtin()));
+        let router = Arc::new(
+            ModelRouter::new(Route::new("default", Arc::new(MockProvider::replying(&[])), "flash"), EscalationPolicy::default())
+                .with_tier(TaskCategory::Coding, Route::new("a", Arc::new(MockProvider::new(Cassette { interactions: vec![refusal] })), "claude-sonnet-4-5"))
+                .with_tier(TaskCategory::Coding, Route::new("b", Arc::new(MockProvider::replying(&[Ok("unreached")])), "large"))
+                .with_meter(Arc::new(meter)),
+        );
+
+        let err = router.provider(None).complete(request("implement the parser")).await.unwrap_err();
+        assert!(matches!(err, NexusError::BudgetExceeded { limit, .. } if limit == 1.0));
+    }
+
+    #[test]
+    fn test_syntax_problem() {
+        assert_eq!(syntax_problem("no code here"), None);
+        assert_eq!(syntax_problem("```rust\nlet s = \"{\";\nfoo(bar[0]);\n```"), None);
+        assert_eq!(syntax_problem("```bash\ncase $x in a) ;; esac\n```"), None);
+        assert!(syntax_problem("```json\n{\"tasks\": [}\n```").unwrap().starts_with("invalid JSON"));
+        assert_eq!(syntax_problem("```py\nprint(1]\n```").unwrap(), "py block: unexpected ']'");
+        assert_eq!(syntax_problem("```rust\nfn main() {}").unwrap(), "code block is not closed");
+
+        // Brackets in literals and comments don't count
+        let rust = "```rust\nfn f<'a>(s: &'a str) -> bool {\n    // close with )\n    s.contains('(') || s.ends_with('\\'') /* [ */\n}\n```";
+        assert_eq!(syntax_problem(rust), None);
+        assert_eq!(syntax_problem("```js\nconst s = '(' + `[${x}` // {\n```"), None);
+        assert_eq!(syntax_problem("```python\nprint(')')  # (\n```"), None);
+        assert_eq!(syntax_problem("```go\nre := `[`\n```"), None);
+        assert_eq!(syntax_problem("```java\nchar c = '{'; int x = (1;\n```").unwrap(), "java block: unclosed '('");
+    }
+}
@@ -1,5 +1,6 @@
 pub mod docker;
 pub mod hydration;
+pub mod test_results;
 pub mod validator;
 
 use crate::error::{NexusError, Result};
@@ -250,6 +251,8 @@ pub struct SandboxManager {
     docker: docker::DockerSandbox,
     interceptor: CommandInterceptor,
     validator: validator::Validator,
+    /// Run commands directly instead of in a container
+    on_host: bool,
 }
 
 impl SandboxManager {
@@ -258,15 +261,37 @@ impl SandboxManager {
             docker: docker::DockerSandbox::new(),
             interceptor: CommandInterceptor::new(),
             validator: validator::Validator::new(),
+            on_host: false,
         }
     }
 
+    /// A manager that runs commands on the host, for tests without Docker
+    #[cfg(test)]
+    pub fn on_host() -> Self {
+        Self { on_host: true, ..Self::new() }
+    }
+
+    fn run_on_host(&self, command: &str, working_dir: &std::path::Path) -> Result<docker::DockerResult> {
+        let start = std::time::Instant::now();
+        let output = Command::new("sh").arg("-c").arg(command).current_dir(working_dir).output()?;
+        Ok(docker::DockerResult {
+            exit_code: output.status.code().unwrap_or(-1),
+            stdout: String::from_utf8_lossy(&output.stdout).to_string(),
+            stderr: String::from_utf8_lossy(&output.stderr).to_string(),
+            duration_ms: start.elapsed().as_millis() as u64,
+            container_id: String::new(),
+        })
+    }
+
     pub async fn shadow_run(&self, command: &str, working_dir: &std::path::Path) -> Result<ShadowRunResult> {
         // Intercept and classify the command
         let _cmd_type = self.interceptor.intercept(command)?;
         
         // Run in Docker sandbox (no network)
-        let result = self.docker.execute(command, working_dir).await?;
+        let result = match self.on_host {
+            true => self.run_on_host(command, working_dir)?,
+            false => self.docker.execute(command, working_dir).await?,
+        };
         
         // Validate the result
         let validation = self.validator.validate(&result);
@@ -282,7 +307,10 @@ impl SandboxManager {
     
     pub async fn shadow_run_with_network(&self, command: &str, working_dir: &std::path::Path) -> Result<ShadowRunResult> {
         // Run in Docker sandbox with network enabled (for package managers)
-        let result = self.docker.execute_with_network(command, working_dir).await?;
+        let result = match self.on_host {
+            true => self.run_on_host(command, working_dir)?,
+            false => self.docker.execute_with_network(command, working_dir).await?,
+        };
         
         // Validate the result
         let validation = self.validator.validate(&result);
@@ -0,0 +1,398 @@
+//! Structured test results
+//!
+//! Parses the output of `cargo test`, pytest, jest, vitest and `go test` into
+//! pass/fail/ignored counts plus the name and message of each failing test, so
+//! callers get a real verdict instead of grepping for "FAILED".
+
+use regex::Regex;
+use serde::Serialize;
+use std::sync::LazyLock;
+
+/// Longest failure message kept per test
+const MAX_MESSAGE_CHARS: usize = 500;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TestFramework {
+    Cargo,
+    Pytest,
+    Jest,
+    Vitest,
+    GoTest,
+}
+
+impl TestFramework {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TestFramework::Cargo => "cargo test",
+            TestFramework::Pytest => "pytest",
+            TestFramework::Jest => "jest",
+            TestFramework::Vitest => "vitest",
+            TestFramework::GoTest => "go test",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TestFailure {
+    pub name: String,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TestReport {
+    pub framework: TestFramework,
+    pub passed: usize,
+    pub failed: usize,
+    pub ignored: usize,
+    pub failures: Vec<TestFailure>,
+}
+
+impl TestReport {
+    fn new(framework: TestFramework) -> Self {
+        Self {
+            framework,
+            passed: 0,
+            failed: 0,
+            ignored: 0,
+            failures: Vec::new(),
+        }
+    }
+
+    pub fn success(&self) -> bool {
+        self.failed == 0 && self.failures.is_empty()
+    }
+
+    /// Counts followed by one line per failing test
+    pub fn summary(&self) -> String {
+        let mut summary = format!(
+            "{}: {} passed, {} failed, {} ignored",
+            self.framework.as_str(),
+            self.passed,
+            self.failed,
+            self.ignored
+        );
+        if !self.failures.is_empty() {
+            summary.push_str("\n\nFailures:");
+            for failure in &self.failures {
+                match failure.message.is_empty() {
+                    true => summary.push_str(&format!("\n- {}", failure.name)),
+                    false => summary.push_str(&format!("\n- {}: {}", failure.name, failure.message)),
+                }
+            }
+        }
+        summary
+    }
+
+    fn add_failure(&mut self, name: &str, message: String) {
+        if !self.failures.iter().any(|f| f.name == name) {
+            self.failures.push(TestFailure {
+                name: name.to_string(),
+                message: clip(message.trim()),
+            });
+        }
+    }
+}
+
+/// Parse test runner output, or `None` if no known framework's output is found
+pub fn parse(stdout: &str, stderr: &str) -> Option<TestReport> {
+    let output = format!("{}\n{}", stdout, stderr);
+    parse_cargo(&output)
+        .or_else(|| parse_go(&output))
+        .or_else(|| parse_pytest(&output))
+        .or_else(|| parse_js(&output))
+}
+
+/// The command that runs the full test suite for a project type, as returned by
+/// `watcher::filesystem::detect_project_type`
+pub fn test_command(project_type: &str) -> Option<&'static str> {
+    match project_type {
+        "rust" => Some("cargo test"),
+        "javascript" | "typescript" => Some("npm test"),
+        "python" => Some("python -m pytest"),
+        "go" => Some("go test ./..."),
+        _ => None,
+    }
+}
+
+fn regex(pattern: &str) -> Regex {
+    Regex::new(pattern).expect("invalid regex")
+}
+
+fn parse_cargo(output: &str) -> Option<TestReport> {
+    static SUMMARY: LazyLock<Regex> = LazyLock::new(|| {
+        regex(r"(?m)^test result: \w+\. (\d+) passed; (\d+) failed; (\d+) ignored")
+    });
+    static FAILED_TEST: LazyLock<Regex> = LazyLock::new(|| regex(r"(?m)^test (\S+) \.\.\. FAILED$"));
+    static STDOUT_BLOCK: LazyLock<Regex> = LazyLock::new(|| regex(r"^---- (\S+) stdout ----$"));
+
+    let mut report = TestReport::new(TestFramework::Cargo);
+    let mut found = false;
+    for caps in SUMMARY.captures_iter(output) {
+        found = true;
+        report.passed += caps[1].parse::<usize>().unwrap_or(0);
+        report.failed += caps[2].parse::<usize>().unwrap_or(0);
+        report.ignored += caps[3].parse::<usize>().unwrap_or(0);
+    }
+    if !found {
+        return None;
+    }
+
+    // Panic messages are printed in "---- name stdout ----" blocks
+    let lines: Vec<&str> = output.lines().collect();
+    for (i, line) in lines.iter().enumerate() {
+        let Some(caps) = STDOUT_BLOCK.captures(line) else { continue };
+        let message: Vec<&str> = lines[i + 1..]
+            .iter()
+            .take_while(|l| !l.starts_with("---- ") && l.trim() != "failures:")
+            .filter(|l| !l.trim().is_empty() && !l.starts_with("note: run with `RUST_BACKTRACE"))
+            .copied()
+            .collect();
+        report.add_failure(&caps[1], message.join("\n"));
+    }
+    for caps in FAILED_TEST.captures_iter(output) {
+        report.add_failure(&caps[1], String::new());
+    }
+    Some(report)
+}
+
+fn parse_go(output: &str) -> Option<TestReport> {
+    static RESULT: LazyLock<Regex> = LazyLock::new(|| regex(r"^(\s*)--- (PASS|FAIL|SKIP): (\S+)"));
+    static PACKAGE_FAIL: LazyLock<Regex> = LazyLock::new(|| regex(r"(?m)^FAIL\s+(\S+)\s+\[(.+)\]"));
+    static PACKAGE_OK: LazyLock<Regex> = LazyLock::new(|| regex(r"(?m)^ok\s+\S+\s+(?:[\d.]+s|\(cached\))"));
+
+    let mut report = TestReport::new(TestFramework::GoTest);
+    let mut found = false;
+    let lines: Vec<&str> = output.lines().collect();
+    for (i, line) in lines.iter().enumerate() {
+        let Some(caps) = RESULT.captures(line) else { continue };
+        found = true;
+        match &caps[2] {
+            "PASS" => report.passed += 1,
+            "SKIP" => report.ignored += 1,
+            _ => {
+                report.failed += 1;
+                // Without -v the failure's log follows its "--- FAIL" line,
+                // indented deeper. With -v it streams after "=== RUN" instead.
+                let indent = caps[1].len();
+                let mut message: Vec<&str> = lines[i + 1..]
+                    .iter()
+                    .take_while(|l| leading_whitespace(l) > indent && !RESULT.is_match(l))
+                    .map(|l| l.trim())
+                    .collect();
+                let run_line = format!("=== RUN   {}", &caps[3]);
+                if message.is_empty()
+                    && let Some(start) = lines[..i].iter().rposition(|l| l.trim() == run_line)
+                {
+                    message = lines[start + 1..i]
+                        .iter()
+                        .filter(|l| leading_whitespace(l) > indent)
+                        .map(|l| l.trim())
+                        .collect();
+                }
+                report.add_failure(&caps[3], message.join("\n"));
+            }
+        }
+    }
+    for caps in PACKAGE_FAIL.captures_iter(output) {
+        found = true;
+        report.add_failure(&caps[1], caps[2].to_string());
+    }
+    (found || PACKAGE_OK.is_match(output)).then_some(report)
+}
+
+fn parse_pytest(output: &str) -> Option<TestReport> {
+    static SUMMARY: LazyLock<Regex> = LazyLock::new(|| {
+        regex(r"(?m)^=*\s*((?:\d+ (?:passed|failed|skipped|errors?|xfailed|xpassed|deselected|warnings?)(?:, )?)+) in [\d.]+s")
+    });
+    static COUNT: LazyLock<Regex> = LazyLock::new(|| regex(r"(\d+) (\w+)"));
+    static FAILED_TEST: LazyLock<Regex> = LazyLock::new(|| regex(r"(?m)^(?:FAILED|ERROR) (\S+)(?: - (.*))?$"));
+
+    let summary = SUMMARY.captures_iter(output).last()?;
+    let mut report = TestReport::new(TestFramework::Pytest);
+    for caps in COUNT.captures_iter(&summary[1]) {
+        let count = caps[1].parse::<usize>().unwrap_or(0);
+        match &caps[2] {
+            "passed" | "xpassed" => report.passed += count,
+            "failed" | "error" | "errors" => report.failed += count,
+            "skipped" | "xfailed" => report.ignored += count,
+            _ => {}
+        }
+    }
+    for caps in FAILED_TEST.captures_iter(output) {
+        report.add_failure(&caps[1], caps.get(2).map(|m| m.as_str().to_string()).unwrap_or_default());
+    }
+    Some(report)
+}
+
+/// Jest prints `Tests: 1 failed, 4 passed, 5 total`; vitest prints
+/// `Tests  1 failed | 4 passed (5)`.
+fn parse_js(output: &str) -> Option<TestReport> {
+    static SUMMARY: LazyLock<Regex> = LazyLock::new(|| regex(r"(?m)^\s*Tests(:?)\s+(\d+ \w+.*)$"));
+    static COUNT: LazyLock<Regex> = LazyLock::new(|| regex(r"(\d+) (\w+)"));
+    static JEST_FAILURE: LazyLock<Regex> = LazyLock::new(|| regex(r"^\s*● (.+)$"));
+    static VITEST_FAILURE: LazyLock<Regex> = LazyLock::new(|| regex(r"^\s*FAIL\s+(.+ > .+)$"));
+
+    let summary = SUMMARY.captures_iter(output).last()?;
+    let (framework, failure_header) = match &summary[1] {
+        ":" => (TestFramework::Jest, &*JEST_FAILURE),
+        _ => (TestFramework::Vitest, &*VITEST_FAILURE),
+    };
+    let mut report = TestReport::new(framework);
+    for caps in COUNT.captures_iter(&summary[2]) {
+        let count = caps[1].parse::<usize>().unwrap_or(0);
+        match &caps[2] {
+            "passed" => report.passed += count,
+            "failed" => report.failed += count,
+            "skipped" | "todo" | "pending" => report.ignored += count,
+            _ => {}
+        }
+    }
+
+    // The first non-empty line after a failure header is the assertion message
+    let lines: Vec<&str> = output.lines().collect();
+    for (i, line) in lines.iter().enumerate() {
+        let Some(caps) = failure_header.captures(line) else { continue };
+        let name = caps[1].trim();
+        if name.starts_with("Console") {
+            continue;
+        }
+        let message = lines[i + 1..]
+            .iter()
+            .map(|l| l.trim())
+            .find(|l| !l.is_empty() && !l.starts_with('⎯'))
+            .unwrap_or_default();
+        report.add_failure(name, message.to_string());
+    }
+    Some(report)
+}
+
+fn leading_whitespace(line: &str) -> usize {
+    // Go indents with tabs or 4 spaces; count a tab as 4
+    line.chars()
+        .take_while(|c| c.is_whitespace())
+        .map(|c| if c == '\t' { 4 } else { 1 })
+        .sum()
+}
+
+fn clip(message: &str) -> String {
+    if message.chars().count() > MAX_MESSAGE_CHARS {
+        format!("{}...", message.chars().take(MAX_MESSAGE_CHARS).collect::<String>())
+    } else {
+        message.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_cargo() {
+        let stdout = "\
+running 3 tests
+test config::tests::loads ... ok
+test parser::tests::parses ... FAILED
+test slow::tests::big ... ignored
+
+failures:
+
+---- parser::tests::parses stdout ----
+thread 'parser::tests::parses' panicked at src/parser.rs:10:5:
+assertion `left == right` failed
+  left: 1
+ right: 2
+note: run with `RUST_BACKTRACE=1` environment variable to display a backtrace
+
+
+failures:
+    parser::tests::parses
+
+test result: FAILED. 1 passed; 1 failed; 1 ignored; 0 measured; 0 filtered out; finished in 0.01s
+";
+        let report = parse(stdout, "").unwrap();
+        assert_eq!(report.framework, TestFramework::Cargo);
+        assert_eq!((report.passed, report.failed, report.ignored), (1, 1, 1));
+        assert_eq!(report.failures.len(), 1);
+        assert_eq!(report.failures[0].name, "parser::tests::parses");
+        assert!(report.failures[0].message.contains("left: 1"));
+        assert!(!report.failures[0].message.contains("RUST_BACKTRACE"));
+        assert!(!report.success());
+    }
+
+    #[test]
+    fn test_parse_pytest() {
+        let stdout = "\
+tests/test_math.py .F.s                                                  [100%]
+
+=========================== short test summary info ============================
+FAILED tests/test_math.py::test_div - ZeroDivisionError: division by zero
+==================== 1 failed, 2 passed, 1 skipped in 0.05s ====================
+";
+        let report = parse(stdout, "").unwrap();
+        assert_eq!(report.framework, TestFramework::Pytest);
+        assert_eq!((report.passed, report.failed, report.ignored), (2, 1, 1));
+        assert_eq!(report.failures[0].name, "tests/test_math.py::test_div");
+        assert_eq!(report.failures[0].message, "ZeroDivisionError: division by zero");
+    }
+
+    #[test]
+    fn test_parse_jest_and_vitest() {
+        let jest = "\
+ FAIL  src/sum.test.js
+  ● math › adds numbers
+
+    expect(received).toBe(expected) // Object.is equality
+
+Tests:       1 failed, 1 skipped, 3 passed, 5 total
+";
+        let report = parse("", jest).unwrap();
+        assert_eq!(report.framework, TestFramework::Jest);
+        assert_eq!((report.passed, report.failed, report.ignored), (3, 1, 1));
+        assert_eq!(report.failures[0].name, "math › adds numbers");
+        assert!(report.failures[0].message.starts_with("expect(received).toBe"));
+
+        let vitest = "\
+ FAIL  src/sum.test.ts > math > adds numbers
+AssertionError: expected 3 to be 4 // Object.is equality
+⎯⎯⎯⎯⎯⎯⎯⎯⎯⎯⎯⎯⎯⎯⎯⎯⎯⎯⎯⎯⎯⎯⎯⎯⎯⎯⎯⎯⎯⎯⎯⎯⎯⎯⎯⎯⎯⎯⎯⎯⎯⎯⎯⎯⎯⎯⎯⎯⎯
+ Test Files  1 failed (1)
+      Tests  1 failed | 4 passed (5)
+";
+        let report = parse(vitest, "").unwrap();
+        assert_eq!(report.framework, TestFramework::Vitest);
+        assert_eq!((report.passed, report.failed), (4, 1));
+        assert_eq!(report.failures[0].name, "src/sum.test.ts > math > adds numbers");
+        assert_eq!(report.failures[0].message, "AssertionError: expected 3 to be 4 // Object.is equality");
+    }
+
+    #[test]
+    fn test_parse_go() {
+        let stdout = "\
+=== RUN   TestAdd
+--- PASS: TestAdd (0.00s)
+=== RUN   TestDiv
+    math_test.go:14: expected 2, got 0
+--- FAIL: TestDiv (0.00s)
+--- SKIP: TestSlow (0.00s)
+FAIL
+FAIL\texample.com/math\t0.002s
+FAIL\texample.com/broken [build failed]
+";
+        let report = parse(stdout, "").unwrap();
+        assert_eq!(report.framework, TestFramework::GoTest);
+        assert_eq!((report.passed, report.failed, report.ignored), (1, 1, 1));
+        assert_eq!(report.failures[0].name, "TestDiv");
+        assert_eq!(report.failures[0].message, "math_test.go:14: expected 2, got 0");
+        assert_eq!(report.failures[1].name, "example.com/broken");
+        assert_eq!(report.failures[1].message, "build failed");
+
+        let quiet = "--- FAIL: TestDiv (0.00s)\n    math_test.go:14: expected 2, got 0\nFAIL\n";
+        assert_eq!(parse(quiet, "").unwrap().failures[0].message, "math_test.go:14: expected 2, got 0");
+    }
+
+    #[test]
+    fn test_unrecognized_output() {
+        assert!(parse("Compiling foo v0.1.0\nFinished dev", "").is_none());
+    }
+}
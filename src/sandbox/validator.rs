@@ -123,32 +123,8 @@ impl Validator {
     }
 
     fn check_test_results(&self, stdout: &str, stderr: &str) -> bool {
-        let combined = format!("{} {}", stdout, stderr);
-
-        // Common test framework patterns
-        let test_patterns = [
-            // Jest
-            ("Tests:", "failed"),
-            // Pytest
-            ("passed", "failed"),
-            // Cargo test
-            ("test result:", "FAILED"),
-            // Go test
-            ("PASS", "FAIL"),
-            // Mocha/Jasmine
-            ("passing", "failing"),
-        ];
-
-        for (pass_indicator, fail_indicator) in &test_patterns {
-            if combined.contains(pass_indicator) || combined.contains(fail_indicator) {
-                // If we see the fail indicator, tests failed
-                if combined.contains(fail_indicator) {
-                    return false;
-                }
-            }
-        }
-
-        true
+        // Output without a recognisable test summary counts as passing
+        super::test_results::parse(stdout, stderr).is_none_or(|report| report.success())
     }
 
     pub fn enable_check(&mut self, check_name: &str) {
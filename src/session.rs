@@ -0,0 +1,326 @@
+//! Persistent REPL sessions
+//!
+//! Each session is stored as a JSON file under `~/.config/nexus/sessions`, holding
+//! the provider, model, working directory and full message history, including tool
+//! calls and results. Sessions can be resumed later or forked from an earlier turn.
+
+use crate::context::compaction::Compactor;
+use crate::error::{NexusError, Result};
+use crate::providers::fallback::ServedRequest;
+use crate::providers::Message;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Where a forked session branched from
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ForkOrigin {
+    pub session_id: String,
+    /// Number of user turns kept from the parent
+    pub turn: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Session {
+    pub id: String,
+    pub provider: String,
+    pub model: String,
+    pub working_dir: PathBuf,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub forked_from: Option<ForkOrigin>,
+    pub messages: Vec<Message>,
+    /// Index into `messages` of each user prompt, one per turn
+    #[serde(default)]
+    pub turn_starts: Vec<usize>,
+    /// Requests a fallback provider answered instead of `provider`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub served: Vec<ServedRequest>,
+}
+
+/// Lightweight view of a session for listings
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionSummary {
+    pub id: String,
+    pub provider: String,
+    pub model: String,
+    pub working_dir: PathBuf,
+    pub updated_at: DateTime<Utc>,
+    pub turns: usize,
+    pub title: String,
+}
+
+impl Session {
+    pub fn new(provider: &str, model: &str, working_dir: PathBuf, messages: Vec<Message>) -> Self {
+        let now = Utc::now();
+        Self {
+            id: uuid::Uuid::new_v4().simple().to_string(),
+            provider: provider.to_string(),
+            model: model.to_string(),
+            working_dir,
+            created_at: now,
+            updated_at: now,
+            forked_from: None,
+            messages,
+            turn_starts: Vec::new(),
+            served: Vec::new(),
+        }
+    }
+
+    /// Append a user prompt, marking the start of a new turn
+    pub fn push_user_turn(&mut self, message: Message) {
+        self.turn_starts.push(self.messages.len());
+        self.messages.push(message);
+    }
+
+    /// Find the turn markers again after the history was rewritten in place,
+    /// as compaction does. Compacted turns drop out; the rest keep their order.
+    pub fn reindex_turns(&mut self) {
+        self.turn_starts = Compactor::turn_starts(&self.messages);
+    }
+
+    pub fn turn_count(&self) -> usize {
+        self.turn_starts.len()
+    }
+
+    /// First user prompt, shortened for display
+    pub fn title(&self) -> String {
+        let first = self.turn_starts.first()
+            .and_then(|&i| self.messages.get(i))
+            .map(|m| m.text_content())
+            .unwrap_or_default();
+        let first = first.lines().next().unwrap_or_default();
+        if first.chars().count() > 60 {
+            format!("{}...", first.chars().take(60).collect::<String>())
+        } else {
+            first.to_string()
+        }
+    }
+
+    /// Branch a new session that keeps the first `turns` turns of this one.
+    ///
+    /// `None` keeps the whole history.
+    pub fn fork(&self, turns: Option<usize>) -> Result<Session> {
+        let turns = turns.unwrap_or(self.turn_count());
+        if turns > self.turn_count() {
+            return Err(NexusError::Configuration(format!(
+                "Session {} only has {} turns",
+                self.id,
+                self.turn_count()
+            )));
+        }
+
+        // Keep everything before the first dropped turn's prompt
+        let cut = self.turn_starts.get(turns).copied().unwrap_or(self.messages.len());
+        let mut forked = Session::new(&self.provider, &self.model, self.working_dir.clone(), self.messages[..cut].to_vec());
+        forked.turn_starts = self.turn_starts[..turns].to_vec();
+        forked.forked_from = Some(ForkOrigin {
+            session_id: self.id.clone(),
+            turn: turns,
+        });
+        Ok(forked)
+    }
+
+    pub fn summary(&self) -> SessionSummary {
+        SessionSummary {
+            id: self.id.clone(),
+            provider: self.provider.clone(),
+            model: self.model.clone(),
+            working_dir: self.working_dir.clone(),
+            updated_at: self.updated_at,
+            turns: self.turn_count(),
+            title: self.title(),
+        }
+    }
+}
+
+/// Reads and writes sessions in the config directory
+pub struct SessionStore {
+    dir: PathBuf,
+}
+
+impl SessionStore {
+    pub fn new() -> Result<Self> {
+        let dir = std::env::var("HOME")
+            .map(|h| PathBuf::from(h).join(".config/nexus/sessions"))
+            .unwrap_or_else(|_| PathBuf::from("~/.config/nexus/sessions"));
+        Self::with_dir(dir)
+    }
+
+    pub fn with_dir(dir: PathBuf) -> Result<Self> {
+        fs::create_dir_all(&dir)?;
+        Ok(Self { dir })
+    }
+
+    fn path_for(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", id))
+    }
+
+    /// Save a session, bumping its `updated_at`.
+    ///
+    /// Writes to a temporary file first so a crash mid-write never corrupts the
+    /// previous copy.
+    pub fn save(&self, session: &mut Session) -> Result<()> {
+        session.updated_at = Utc::now();
+        let path = self.path_for(&session.id);
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, serde_json::to_string_pretty(session)?)?;
+        fs::rename(&tmp, &path)?;
+        Ok(())
+    }
+
+    /// Load a session by id or unique id prefix
+    pub fn load(&self, id: &str) -> Result<Session> {
+        let exact = self.path_for(id);
+        if exact.exists() {
+            return Self::read(&exact);
+        }
+
+        let matches: Vec<PathBuf> = self.session_files()?
+            .into_iter()
+            .filter(|p| p.file_stem().and_then(|s| s.to_str()).is_some_and(|s| s.starts_with(id)))
+            .collect();
+
+        match matches.as_slice() {
+            [path] => Self::read(path),
+            [] => Err(NexusError::Configuration(format!("Session not found: {}", id))),
+            _ => Err(NexusError::Configuration(format!("Session id '{}' is ambiguous", id))),
+        }
+    }
+
+    /// All sessions, most recently updated first
+    pub fn list(&self) -> Result<Vec<SessionSummary>> {
+        let mut sessions: Vec<SessionSummary> = self.session_files()?
+            .iter()
+            .filter_map(|path| Self::read(path).ok())
+            .map(|session| session.summary())
+            .collect();
+        sessions.sort_by_key(|s| std::cmp::Reverse(s.updated_at));
+        Ok(sessions)
+    }
+
+    fn session_files(&self) -> Result<Vec<PathBuf>> {
+        Ok(fs::read_dir(&self.dir)?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+            .collect())
+    }
+
+    fn read(path: &Path) -> Result<Session> {
+        let content = fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executor::tools::{ToolCall, ToolResult};
+    use tempfile::TempDir;
+
+    fn sample_session() -> Session {
+        let mut session = Session::new("claude", "claude-sonnet-4-5", PathBuf::from("/tmp/project"), vec![
+            Message::system("system prompt"),
+        ]);
+        session.push_user_turn(Message::user("read main.rs"));
+        let call = ToolCall {
+            id: "toolu_1".to_string(),
+            name: "read_file".to_string(),
+            arguments: serde_json::json!({"path": "main.rs"}),
+        };
+        session.messages.push(Message::assistant_with_tool_calls("", std::slice::from_ref(&call)));
+        session.messages.push(Message::tool_results(&[ToolResult {
+            tool_call_id: call.id,
+            success: true,
+            output: "fn main() {}".to_string(),
+            error: None,
+        }]));
+        session.messages.push(Message::assistant("It is empty."));
+        session.push_user_turn(Message::user("now add a test"));
+        session.messages.push(Message::assistant("Done."));
+        session
+    }
+
+    #[test]
+    fn test_save_and_load_round_trip() {
+        let dir = TempDir::new().unwrap();
+        let store = SessionStore::with_dir(dir.path().to_path_buf()).unwrap();
+        let mut session = sample_session();
+        store.save(&mut session).unwrap();
+
+        let loaded = store.load(&session.id).unwrap();
+        assert_eq!(loaded.messages.len(), session.messages.len());
+        assert_eq!(loaded.messages[2].content, session.messages[2].content);
+        assert_eq!(loaded.turn_starts, vec![1, 5]);
+
+        // Unique prefixes resolve too
+        let loaded = store.load(&session.id[..8]).unwrap();
+        assert_eq!(loaded.id, session.id);
+    }
+
+    #[tokio::test]
+    async fn test_fork_after_compaction() {
+        use crate::providers::mock::{Cassette, Interaction, MockProvider, ScriptedResponse};
+
+        let mut session = sample_session();
+        session.push_user_turn(Message::user("run it"));
+        session.messages.push(Message::assistant("Passed."));
+        let summary = Interaction {
+            response: ScriptedResponse { content: "Read main.rs, it was empty.".to_string(), ..ScriptedResponse::default() },
+            ..Interaction::default()
+        };
+        let provider = MockProvider::new(Cassette { interactions: vec![summary] });
+
+        // The first turn is summarized away, shortening the history under the markers
+        Compactor::default().compact(&mut session.messages, &provider, "test").await.unwrap().unwrap();
+        session.reindex_turns();
+        assert_eq!(session.turn_starts, vec![2, 4]);
+        assert_eq!(session.title(), "now add a test");
+
+        let forked = session.fork(Some(1)).unwrap();
+        assert_eq!(forked.messages.len(), 4);
+        assert_eq!(forked.messages.last().unwrap().text_content(), "Done.");
+        assert_eq!(session.fork(None).unwrap().messages.len(), session.messages.len());
+    }
+
+    #[test]
+    fn test_list_sorted_by_recency() {
+        let dir = TempDir::new().unwrap();
+        let store = SessionStore::with_dir(dir.path().to_path_buf()).unwrap();
+        let mut older = sample_session();
+        store.save(&mut older).unwrap();
+        let mut newer = Session::new("openrouter", "openrouter/auto", PathBuf::from("/tmp"), vec![]);
+        newer.push_user_turn(Message::user("hello"));
+        store.save(&mut newer).unwrap();
+
+        let sessions = store.list().unwrap();
+        assert_eq!(sessions.len(), 2);
+        assert_eq!(sessions[0].id, newer.id);
+        assert_eq!(sessions[1].title, "read main.rs");
+        assert_eq!(sessions[1].turns, 2);
+    }
+
+    #[test]
+    fn test_fork_keeps_earlier_turns() {
+        let session = sample_session();
+        let forked = session.fork(Some(1)).unwrap();
+
+        assert_ne!(forked.id, session.id);
+        assert_eq!(forked.messages.len(), 5);
+        assert_eq!(forked.turn_count(), 1);
+        assert_eq!(forked.forked_from.as_ref().unwrap().session_id, session.id);
+
+        assert_eq!(session.fork(None).unwrap().messages.len(), session.messages.len());
+        assert!(session.fork(Some(3)).is_err());
+    }
+
+    #[test]
+    fn test_load_missing_session() {
+        let dir = TempDir::new().unwrap();
+        let store = SessionStore::with_dir(dir.path().to_path_buf()).unwrap();
+        assert!(store.load("nope").is_err());
+    }
+}
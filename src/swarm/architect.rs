@@ -1,5 +1,5 @@
 use crate::error::{NexusError, Result};
-use crate::providers::{CompletionRequest, Message, Provider, Role};
+use crate::providers::{CompletionRequest, Message, Provider};
 use crate::swarm::SwarmTask;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -66,16 +66,8 @@ impl ArchitectAgent {
         let prompt = self.build_decomposition_prompt(swarm_task);
         
         let messages = vec![
-            Message {
-                role: Role::System,
-                content: ARCHITECT_SYSTEM_PROMPT.to_string(),
-                name: None,
-            },
-            Message {
-                role: Role::User,
-                content: prompt,
-                name: None,
-            },
+            Message::system(ARCHITECT_SYSTEM_PROMPT.to_string()),
+            Message::user(prompt),
         ];
 
         let request = CompletionRequest {
@@ -86,6 +78,7 @@ impl ArchitectAgent {
             stream: Some(false),
                 tools: None,
             extra_params: None,
+            cache: None,
         };
 
         let response = self.provider.complete(request).await?;
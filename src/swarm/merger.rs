@@ -430,6 +430,7 @@ mod tests {
                 output: "Created login form".to_string(),
                 files_modified: vec!["src/components/Login.tsx".to_string()],
                 execution_time_ms: 1000,
+                tests_passed: None,
             },
             SubtaskResult {
                 task_id: "task-2".to_string(),
@@ -441,6 +442,7 @@ mod tests {
                     "src/components/Login.tsx".to_string(), // Conflict!
                 ],
                 execution_time_ms: 2000,
+                tests_passed: None,
             },
         ];
 
@@ -3,8 +3,11 @@ pub mod merger;
 pub mod scheduler;
 pub mod worker;
 
+use crate::config::AgentProfile;
 use crate::error::{NexusError, Result};
+use crate::hierarchy::TaskCategory;
 use crate::providers::Provider;
+use crate::router::ModelRouter;
 use crate::swarm::architect::{ArchitectAgent, Task, TaskStatus};
 use crate::swarm::scheduler::{ExecutionPlan, Scheduler};
 use crate::swarm::merger::GitMerger;
@@ -26,6 +29,8 @@ pub struct SwarmConfig {
     pub task_timeout_secs: u64,
     /// Enable automatic merging of conflicts
     pub auto_merge: bool,
+    /// Limits applied to each worker
+    pub agent_profile: AgentProfile,
 }
 
 impl Default for SwarmConfig {
@@ -35,6 +40,7 @@ impl Default for SwarmConfig {
             max_retries: 3,
             task_timeout_secs: 300,
             auto_merge: true,
+            agent_profile: AgentProfile::default(),
         }
     }
 }
@@ -84,6 +90,8 @@ pub struct SubtaskResult {
     pub output: String,
     pub files_modified: Vec<String>,
     pub execution_time_ms: u64,
+    /// Whether the worker's last `run_tests` passed, if it ran any
+    pub tests_passed: Option<bool>,
 }
 
 /// Conflicts detected during merge phase
@@ -112,6 +120,8 @@ pub struct SwarmOrchestrator {
     active_tasks: Arc<RwLock<HashMap<String, TaskHandle>>>,
     provider: Arc<dyn Provider + Send + Sync>,
     model: String,
+    /// Told about test results when the swarm runs on the model hierarchy
+    router: Option<Arc<ModelRouter>>,
 }
 
 struct TaskHandle {
@@ -126,17 +136,37 @@ impl SwarmOrchestrator {
         provider: Arc<dyn Provider + Send + Sync>,
         model: String,
     ) -> Result<Self> {
-        let architect = ArchitectAgent::new(provider.clone(), model.clone())?;
+        Self::build(config, model, None, |_| provider.clone())
+    }
+
+    /// Run every role on the model hierarchy: the architect on planning
+    /// models, the QA worker on review models and the rest on coding models
+    pub fn routed(config: SwarmConfig, router: Arc<ModelRouter>) -> Result<Self> {
+        let model = router.model_for(TaskCategory::Coding).to_string();
+        Self::build(config, model, Some(router.clone()), |category| router.provider(Some(category)))
+    }
+
+    fn build(
+        config: SwarmConfig,
+        model: String,
+        router: Option<Arc<ModelRouter>>,
+        provider_for: impl Fn(TaskCategory) -> Arc<dyn Provider + Send + Sync>,
+    ) -> Result<Self> {
+        let architect = ArchitectAgent::new(provider_for(TaskCategory::Planning), model.clone())?;
         let scheduler = Scheduler::new(config.max_concurrent_workers);
         let merger = GitMerger::new(config.auto_merge);
 
         let mut workers = HashMap::new();
         for worker_type in [WorkerType::Frontend, WorkerType::Backend, WorkerType::QA] {
+            let category = match worker_type {
+                WorkerType::QA => TaskCategory::Review,
+                _ => TaskCategory::Coding,
+            };
             let worker = WorkerAgent::new(
                 worker_type,
-                provider.clone(),
+                provider_for(category),
                 model.clone(),
-            )?;
+            )?.with_profile(config.agent_profile.clone());
             workers.insert(worker_type, Arc::new(worker));
         }
 
@@ -147,8 +177,9 @@ impl SwarmOrchestrator {
             merger,
             workers,
             active_tasks: Arc::new(RwLock::new(HashMap::new())),
-            provider,
-            model: model.into(),
+            provider: provider_for(TaskCategory::Coding),
+            model,
+            router,
         })
     }
 
@@ -168,6 +199,9 @@ impl SwarmOrchestrator {
         // Phase 3: Execution - Run workers in parallel
         println!("[SWARM] Phase 3: Executing subtasks in parallel...");
         let execution_results = self.execute_plan(&execution_plan, &swarm_task.working_dir).await?;
+        if let Some((router, passed)) = self.router.as_ref().zip(tests_passed(&execution_results)) {
+            router.report_test_result(TaskCategory::Coding, passed);
+        }
 
         // Phase 4: Merging - Resolve conflicts between worker outputs
         println!("[SWARM] Phase 4: Merging results...");
@@ -205,12 +239,11 @@ impl SwarmOrchestrator {
         
         while !pending_tasks.is_empty() {
             // Find tasks with satisfied dependencies
+            let completed = completed_tasks.lock().await.clone();
             let ready_tasks: Vec<_> = pending_tasks
                 .iter()
                 .filter(|t| {
-                    t.dependencies.iter().all(|dep| {
-                        completed_tasks.blocking_lock().get(dep).copied().unwrap_or(false)
-                    })
+                    t.dependencies.iter().all(|dep| completed.get(dep).copied().unwrap_or(false))
                 })
                 .cloned()
                 .collect();
@@ -329,6 +362,7 @@ impl SwarmOrchestrator {
                         output: result.output,
                         files_modified: result.files_modified,
                         execution_time_ms: start_time.elapsed().as_millis() as u64,
+                        tests_passed: result.tests_passed,
                     });
                 }
                 Ok(Err(e)) => {
@@ -340,6 +374,7 @@ impl SwarmOrchestrator {
                             output: format!("Failed after {} attempts: {}", attempts, e),
                             files_modified: Vec::new(),
                             execution_time_ms: start_time.elapsed().as_millis() as u64,
+                            tests_passed: None,
                         });
                     }
                     println!("  [WORKER] Task {} failed (attempt {}/{}), retrying...", 
@@ -355,6 +390,7 @@ impl SwarmOrchestrator {
                                 timeout_secs, attempts),
                             files_modified: Vec::new(),
                             execution_time_ms: start_time.elapsed().as_millis() as u64,
+                            tests_passed: None,
                         });
                     }
                     println!("  [WORKER] Task {} timed out (attempt {}/{}), retrying...", 
@@ -413,3 +449,68 @@ impl SwarmOrchestrator {
         }
     }
 }
+
+/// One verdict for the whole swarm: failed if any worker's tests failed,
+/// passed if any ran and none failed
+fn tests_passed(results: &[SubtaskResult]) -> Option<bool> {
+    results.iter().filter_map(|r| r.tests_passed).reduce(|all, passed| all && passed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::mock::{Cassette, MockProvider};
+
+    #[tokio::test]
+    async fn test_execute_against_mock_provider() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let decomposition = serde_json::json!({
+            "subtasks": [
+                {"id": "backend-1", "description": "Write the greeting file", "type": "backend", "dependencies": [], "estimated_minutes": 5},
+                {"id": "qa-1", "description": "Test the greeting file", "type": "qa", "dependencies": ["backend-1"], "estimated_minutes": 5}
+            ],
+            "overall_strategy": "Write it, then check it"
+        });
+        let cassette: Cassette = serde_json::from_value(serde_json::json!({
+            "interactions": [
+                {"when": "Decompose", "response": {"content": decomposition.to_string()}},
+                {"when": "Task ID: backend-1", "response": {"content": serde_json::json!({
+                    "tool": "create_file", "arguments": {"path": "greeting.txt", "content": "hello"}
+                }).to_string()}},
+                {"when": "Tool 'create_file' result", "response": {"content": "Created file: greeting.txt"}},
+                {"when": "Task ID: qa-1", "response": {"content": "The greeting looks right. All tests passed."}}
+            ]
+        })).unwrap();
+        let provider = Arc::new(MockProvider::new(cassette));
+        let swarm = SwarmOrchestrator::new(SwarmConfig::default(), provider.clone(), "mock".to_string()).unwrap();
+
+        let result = swarm.execute(SwarmTask::new("Add a greeting", dir.path())).await.unwrap();
+
+        assert!(result.success);
+        assert_eq!(std::fs::read_to_string(dir.path().join("greeting.txt")).unwrap(), "hello");
+        let mut finished: Vec<_> = result.subtask_results.iter().map(|r| (r.task_id.as_str(), r.success)).collect();
+        finished.sort();
+        assert_eq!(finished, vec![("backend-1", true), ("qa-1", true)]);
+        // Saying the tests passed is not a verdict; only a `run_tests` report is
+        assert!(result.subtask_results.iter().all(|r| r.tests_passed.is_none()));
+        assert_eq!(result.merged_files, vec!["greeting.txt".to_string()]);
+    }
+
+    #[test]
+    fn test_tests_passed_is_combined_across_workers() {
+        let result = |tests_passed| SubtaskResult {
+            task_id: "t".to_string(),
+            worker_type: WorkerType::Backend,
+            success: true,
+            output: String::new(),
+            files_modified: Vec::new(),
+            execution_time_ms: 0,
+            tests_passed,
+        };
+        assert_eq!(tests_passed(&[result(None)]), None);
+        assert_eq!(tests_passed(&[result(Some(true)), result(None)]), Some(true));
+        // A failure counts whichever worker finished last
+        assert_eq!(tests_passed(&[result(Some(false)), result(Some(true))]), Some(false));
+        assert_eq!(tests_passed(&[result(Some(true)), result(Some(false))]), Some(false));
+    }
+}
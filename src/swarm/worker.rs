@@ -1,19 +1,20 @@
+use crate::config::AgentProfile;
 use crate::context::FileAccessTracker;
 use crate::error::Result;
-use crate::executor::tools::{ToolCall, ToolResult, create_tool_system_prompt, parse_tool_calls};
-use crate::providers::{CompletionRequest, Message, Provider, Role};
+use crate::executor::registry::{ToolContext, ToolRegistry};
+use crate::executor::tools::{create_tool_system_prompt, parse_tool_calls};
+use crate::providers::{CompletionRequest, Message, Provider};
 use crate::providers::retry::retry_with_backoff;
 use crate::providers::token_budget::TokenBudget;
 use crate::sandbox::SandboxManager;
-use crate::sandbox::hydration::{FileChange, HydrationPlan, Hydrator};
+use crate::sandbox::hydration::Hydrator;
+use crate::sandbox::test_results::TestReport;
 use crate::swarm::architect::Task;
-use std::path::PathBuf;
-use std::sync::Arc;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
-/// Maximum tool-calling turns before forcing termination
-const MAX_TURNS: usize = 20;
-
 /// Types of specialized workers
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum WorkerType {
@@ -57,6 +58,8 @@ pub struct WorkerAgent {
     sandbox: SandboxManager,
     hydrator: Hydrator,
     file_tracker: FileAccessTracker,
+    profile: AgentProfile,
+    tools: ToolRegistry,
 }
 
 impl WorkerAgent {
@@ -72,9 +75,17 @@ impl WorkerAgent {
             sandbox: SandboxManager::new(),
             hydrator: Hydrator::new()?,
             file_tracker: FileAccessTracker::new(),
+            profile: AgentProfile::default(),
+            tools: ToolRegistry::builtin(),
         })
     }
 
+    /// Use `profile` for turn, token and per-tool limits
+    pub fn with_profile(mut self, profile: AgentProfile) -> Self {
+        self.profile = profile;
+        self
+    }
+
     pub fn worker_type(&self) -> WorkerType {
         self.worker_type
     }
@@ -114,23 +125,20 @@ impl WorkerAgent {
         );
 
         let mut messages = vec![
-            Message {
-                role: Role::System,
-                content: system_prompt,
-                name: None,
-            },
-            Message {
-                role: Role::User,
-                content: user_message,
-                name: None,
-            },
+            Message::system(system_prompt),
+            Message::user(user_message),
         ];
 
         let mut final_response = String::new();
-        let mut budget = TokenBudget::default();
+        let mut budget = TokenBudget::for_model(&self.model)
+            .with_limits(self.profile.max_input_tokens(), self.profile.max_total_tokens());
+        let max_turns = self.profile.max_turns();
+        let mut tool_calls_made: HashMap<String, usize> = HashMap::new();
+        // The last `run_tests` report is the verdict on the task
+        let test_report = Mutex::new(None);
 
         // Multi-turn tool calling loop
-        for turn in 0..MAX_TURNS {
+        for turn in 0..max_turns {
             if !budget.can_continue() {
                 eprintln!("  [WORKER {:?}] Token budget exhausted after {} turns", self.worker_type, turn);
                 break;
@@ -139,11 +147,12 @@ impl WorkerAgent {
             let request = CompletionRequest {
                 model: self.model.clone(),
                 messages: messages.clone(),
-                temperature: Some(0.7),
+                temperature: Some(self.profile.temperature()),
                 max_tokens: Some(budget.dynamic_max_tokens()),
                 stream: Some(false),
                 tools: None,
                 extra_params: None,
+                cache: None,
             };
 
             let response = {
@@ -157,8 +166,8 @@ impl WorkerAgent {
             };
 
             // Record token usage
-            let input_est: u32 = messages.iter().map(|m| TokenBudget::estimate_tokens(&m.content)).sum();
-            let output_est = TokenBudget::estimate_tokens(&response.content);
+            let input_est = budget.count_messages(&messages);
+            let output_est = budget.count_tokens(&response.content);
             if let Some(ref usage) = response.usage {
                 budget.record_usage(usage.prompt_tokens, usage.completion_tokens);
             } else {
@@ -170,11 +179,7 @@ impl WorkerAgent {
 
             if tool_calls.is_empty() {
                 // No tools — done, this is the final response
-                messages.push(Message {
-                    role: Role::Assistant,
-                    content: response.content.clone(),
-                    name: None,
-                });
+                messages.push(Message::assistant(response.content.clone()));
                 final_response = response.content;
                 break;
             }
@@ -189,7 +194,9 @@ impl WorkerAgent {
             let mut tool_results = Vec::new();
 
             for tool_call in &tool_calls {
-                let result = self.execute_tool(tool_call, working_dir).await?;
+                let result = self.tools
+                    .execute_with_limits(tool_call, &self.tool_context(working_dir, &test_report), &self.profile, &mut tool_calls_made)
+                    .await;
 
                 if result.success {
                     println!("    ✓ {} - Success", tool_call.name);
@@ -205,41 +212,34 @@ impl WorkerAgent {
             }
 
             // Add assistant message (with tool calls)
-            messages.push(Message {
-                role: Role::Assistant,
-                content: response.content,
-                name: None,
-            });
-
-            // Add tool results as tool messages
-            for result in &tool_results {
-                let tool_content = format!(
+            messages.push(Message::assistant(response.content));
+
+            // Tool calls are parsed from text, so report the results as text
+            let report = tool_results.iter().zip(&tool_calls).map(|(result, call)| {
+                format!(
                     "Tool '{}' result:\nSuccess: {}\nOutput: {}\nError: {}",
-                    result.tool_call_id,
+                    call.name,
                     result.success,
                     result.output,
-                    result.error.as_ref().unwrap_or(&"None".to_string())
-                );
-
-                messages.push(Message {
-                    role: Role::Tool,
-                    content: tool_content,
-                    name: Some(result.tool_call_id.clone()),
-                });
-            }
+                    result.error.as_deref().unwrap_or("None")
+                )
+            }).collect::<Vec<_>>().join("\n\n");
+            messages.push(Message::user(report));
 
             // If this is the last allowed turn, record what we have
-            if turn == MAX_TURNS - 1 {
+            if turn == max_turns - 1 {
                 final_response = format!(
-                    "[Worker hit MAX_TURNS limit ({})]\nLast tool results processed.",
-                    MAX_TURNS
+                    "[Worker hit max turns limit ({})]\nLast tool results processed.",
+                    max_turns
                 );
             }
         }
 
         // Parse the final response to extract file modifications
         let files_modified = self.extract_files_modified(&final_response);
-        let tests_passed = self.check_tests_passed(&final_response);
+        let tests_passed = test_report.into_inner()
+            .unwrap_or_else(|e| e.into_inner())
+            .map(|report| report.success());
 
         println!(
             "  [WORKER {:?}] Completed task {} - Modified {} files",
@@ -256,169 +256,17 @@ impl WorkerAgent {
         })
     }
 
-    /// Execute a single tool call (mirrors Agent::execute_tool)
-    async fn execute_tool(&self, tool_call: &ToolCall, working_dir: &PathBuf) -> Result<ToolResult> {
-        let result = match tool_call.name.as_str() {
-            "execute_command" => {
-                let command = tool_call
-                    .arguments
-                    .get("command")
-                    .and_then(|c| c.as_str())
-                    .unwrap_or_default();
-
-                let is_package_command = command.contains("npm")
-                    || command.contains("pip")
-                    || command.contains("apk")
-                    || command.contains("apt")
-                    || command.contains("dnf")
-                    || command.contains("yarn")
-                    || command.contains("cargo install");
-
-                let shadow_result = if is_package_command {
-                    self.sandbox
-                        .shadow_run_with_network(command, working_dir)
-                        .await?
-                } else {
-                    self.sandbox.shadow_run(command, working_dir).await?
-                };
-
-                ToolResult {
-                    tool_call_id: tool_call.id.clone(),
-                    success: shadow_result.success,
-                    output: format!(
-                        "stdout: {}\nstderr: {}",
-                        shadow_result.stdout, shadow_result.stderr
-                    ),
-                    error: if shadow_result.success {
-                        None
-                    } else {
-                        Some(format!("Exit code: {}", shadow_result.exit_code))
-                    },
-                }
-            }
-            "create_file" => {
-                let path = tool_call
-                    .arguments
-                    .get("path")
-                    .and_then(|p| p.as_str())
-                    .unwrap_or_default();
-                let content = tool_call
-                    .arguments
-                    .get("content")
-                    .and_then(|c| c.as_str())
-                    .unwrap_or_default();
-
-                let plan = HydrationPlan {
-                    files_to_create: vec![FileChange {
-                        path: PathBuf::from(path),
-                        content: content.to_string(),
-                        backup_path: None,
-                    }],
-                    files_to_update: Vec::new(),
-                    files_to_delete: Vec::new(),
-                    directories_to_create: Vec::new(),
-                };
-
-                match self.hydrator.execute_plan(&plan) {
-                    Ok(_) => ToolResult {
-                        tool_call_id: tool_call.id.clone(),
-                        success: true,
-                        output: format!("Created file: {}", path),
-                        error: None,
-                    },
-                    Err(e) => ToolResult {
-                        tool_call_id: tool_call.id.clone(),
-                        success: false,
-                        output: String::new(),
-                        error: Some(e.to_string()),
-                    },
-                }
-            }
-            "edit_file" => {
-                let path = tool_call
-                    .arguments
-                    .get("path")
-                    .and_then(|p| p.as_str())
-                    .unwrap_or_default();
-                let content = tool_call
-                    .arguments
-                    .get("content")
-                    .and_then(|c| c.as_str())
-                    .unwrap_or_default();
-
-                let full_path = working_dir.join(path);
-
-                // Check staleness
-                if let Err(e) = self.file_tracker.check_staleness(&full_path) {
-                    return Ok(ToolResult {
-                        tool_call_id: tool_call.id.clone(),
-                        success: false,
-                        output: String::new(),
-                        error: Some(e.to_string()),
-                    });
-                }
-
-                let plan = HydrationPlan {
-                    files_to_create: Vec::new(),
-                    files_to_update: vec![FileChange {
-                        path: PathBuf::from(path),
-                        content: content.to_string(),
-                        backup_path: None,
-                    }],
-                    files_to_delete: Vec::new(),
-                    directories_to_create: Vec::new(),
-                };
-
-                match self.hydrator.execute_plan(&plan) {
-                    Ok(_) => ToolResult {
-                        tool_call_id: tool_call.id.clone(),
-                        success: true,
-                        output: format!("Updated file: {}", path),
-                        error: None,
-                    },
-                    Err(e) => ToolResult {
-                        tool_call_id: tool_call.id.clone(),
-                        success: false,
-                        output: String::new(),
-                        error: Some(e.to_string()),
-                    },
-                }
-            }
-            "read_file" => {
-                let path = tool_call
-                    .arguments
-                    .get("path")
-                    .and_then(|p| p.as_str())
-                    .unwrap_or_default();
-
-                let full_path = working_dir.join(path);
-                match std::fs::read_to_string(&full_path) {
-                    Ok(content) => {
-                        self.file_tracker.record_read(&full_path);
-                        ToolResult {
-                            tool_call_id: tool_call.id.clone(),
-                            success: true,
-                            output: content,
-                            error: None,
-                        }
-                    }
-                    Err(e) => ToolResult {
-                        tool_call_id: tool_call.id.clone(),
-                        success: false,
-                        output: String::new(),
-                        error: Some(format!("Failed to read file: {}", e)),
-                    },
-                }
-            }
-            _ => ToolResult {
-                tool_call_id: tool_call.id.clone(),
-                success: false,
-                output: String::new(),
-                error: Some(format!("Unknown tool: {}", tool_call.name)),
-            },
-        };
-
-        Ok(result)
+    /// Context for tool calls made while running this worker's task; `run_tests` reports land in `test_report`
+    fn tool_context<'a>(&'a self, working_dir: &'a Path, test_report: &'a Mutex<Option<TestReport>>) -> ToolContext<'a> {
+        ToolContext {
+            working_dir,
+            sandbox: &self.sandbox,
+            hydrator: &self.hydrator,
+            file_tracker: &self.file_tracker,
+            reviewer: None,
+            checkpoint: None,
+            test_report: Some(test_report),
+        }
     }
 
     /// Extract file paths from the worker's response
@@ -449,27 +297,6 @@ impl WorkerAgent {
 
         files
     }
-
-    /// Check if tests passed based on the response
-    fn check_tests_passed(&self, content: &str) -> Option<bool> {
-        let content_lower = content.to_lowercase();
-
-        if content_lower.contains("test passed")
-            || content_lower.contains("tests passed")
-            || content_lower.contains("✓ all tests")
-            || content_lower.contains("validation successful")
-        {
-            Some(true)
-        } else if content_lower.contains("test failed")
-            || content_lower.contains("tests failed")
-            || content_lower.contains("✗ test")
-            || content_lower.contains("validation failed")
-        {
-            Some(false)
-        } else {
-            None
-        }
-    }
 }
 
 const FRONTEND_WORKER_PROMPT: &str = r#"You are a Frontend Worker in a software development swarm.
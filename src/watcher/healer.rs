@@ -5,18 +5,22 @@
 //! that the fixes work.
 
 use crate::agent::Agent;
-use crate::context::FileAccessTracker;
+use crate::context::{ContextManager, FileAccessTracker};
 use crate::error::{NexusError, Result};
 use crate::memory::{MemorySystem, types::MemoryResult};
-use crate::providers::{Message, Provider, Role};
+use crate::providers::{Message, Provider};
+use crate::providers::tokenizer::counter_for_model;
 use crate::sandbox::{SandboxManager, ShadowRunResult};
 use crate::sandbox::hydration::{HydrationPlan, Hydrator};
+use crate::sandbox::test_results::{self, TestReport};
 use crate::swarm::{SwarmOrchestrator, SwarmTask};
+use crate::router::ModelRouter;
+use crate::hierarchy::TaskCategory;
 use crate::watcher::logs::LogErrorEvent;
 use crate::watcher::filesystem::FileChangeEvent;
 use crate::watcher::patterns::{DetectedError, ErrorType, ErrorSeverity};
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tokio::sync::{mpsc, RwLock};
 use uuid::Uuid;
@@ -83,6 +87,8 @@ pub struct VerificationResult {
     pub test_output: String,
     pub error_resolved: bool,
     pub new_errors: Vec<String>,
+    /// Parsed results of the project's test suite, if it ran
+    pub test_report: Option<TestReport>,
 }
 
 /// Learning entry for future reference
@@ -123,11 +129,14 @@ impl Default for HealerConfig {
     }
 }
 
+/// Tokens of source files given to the model when analyzing an error
+const CONTEXT_FILE_TOKENS: u32 = 16_000;
+
 /// The main healer that orchestrates error investigation and fixing
 pub struct Healer {
     config: HealerConfig,
     memory: Arc<RwLock<MemorySystem>>,
-    sandbox: SandboxManager,
+    sandbox: Arc<SandboxManager>,
     hydrator: Hydrator,
     file_tracker: FileAccessTracker,
     provider: Arc<dyn Provider + Send + Sync>,
@@ -135,6 +144,8 @@ pub struct Healer {
     sessions: Arc<RwLock<HashMap<String, HealingSession>>>,
     event_tx: mpsc::Sender<HealerEvent>,
     swarm: Option<Arc<SwarmOrchestrator>>,
+    /// Settable through a shared `Arc<Healer>`
+    router: std::sync::RwLock<Option<Arc<ModelRouter>>>,
 }
 
 /// Events emitted by the healer
@@ -161,7 +172,7 @@ impl Healer {
         Ok(Self {
             config,
             memory,
-            sandbox: SandboxManager::new(),
+            sandbox: Arc::new(SandboxManager::new()),
             hydrator: Hydrator::new()?,
             file_tracker: FileAccessTracker::new(),
             provider,
@@ -169,6 +180,7 @@ impl Healer {
             sessions: Arc::new(RwLock::new(HashMap::new())),
             event_tx,
             swarm: None,
+            router: std::sync::RwLock::new(None),
         })
     }
     
@@ -176,6 +188,12 @@ impl Healer {
     pub fn set_swarm(&mut self, swarm: Arc<SwarmOrchestrator>) {
         self.swarm = Some(swarm);
     }
+
+    /// Report verification results to the model hierarchy, so failed fixes
+    /// move later ones to a stronger model
+    pub fn set_router(&self, router: Arc<ModelRouter>) {
+        *self.router.write().unwrap_or_else(|e| e.into_inner()) = Some(router);
+    }
     
     /// Start a healing session for an error
     pub async fn heal(&self, error_event: ErrorEvent) -> Result<String> {
@@ -216,12 +234,14 @@ impl Healer {
         // Start healing process in background
         let self_clone = self.clone();
         let session_id_clone = session_id.clone();
-        let local_set = tokio::task::LocalSet::new();
-        local_set.spawn_local(async move {
+        // The session holds memory locks across awaits, so it can't be a
+        // `Send` task; give it a blocking thread of its own instead
+        let runtime = tokio::runtime::Handle::current();
+        tokio::task::spawn_blocking(move || runtime.block_on(async move {
             if let Err(e) = self_clone.run_healing_session(&session_id_clone).await {
                 eprintln!("[HEALER] Session {} failed: {}", session_id_clone, e);
             }
-        });
+        }));
         
         Ok(session_id)
     }
@@ -302,7 +322,7 @@ impl Healer {
         };
         
         // Read file contents for context
-        let file_contents = self.read_file_contents(&context_files).await?;
+        let file_contents = self.read_file_contents(&context_files);
         
         // Use AI to analyze with full context
         let analysis = self.analyze_error_with_ai(
@@ -381,29 +401,22 @@ impl Healer {
     }
     
     /// Read contents of context files for AI analysis
-    async fn read_file_contents(&self, file_paths: &[String]) -> Result<HashMap<String, String>> {
-        let mut contents = HashMap::new();
-        
-        for path_str in file_paths {
-            let path = PathBuf::from(path_str);
-            if path.exists() {
-                // Limit file size for context (first 100 lines)
-                match tokio::fs::read_to_string(&path).await {
-                    Ok(content) => {
-                        let lines: Vec<&str> = content.lines().take(100).collect();
-                        contents.insert(path_str.clone(), lines.join("\n"));
-                        
-                        // Record that we read this file for staleness detection
-                        self.file_tracker.record_read(&path);
-                    }
-                    Err(e) => {
-                        eprintln!("[HEALER] Failed to read {}: {}", path_str, e);
-                    }
-                }
-            }
-        }
-        
-        Ok(contents)
+    /// Read as many of `file_paths`, in order, as fit in the analysis budget
+    fn read_file_contents(&self, file_paths: &[String]) -> Vec<(String, String)> {
+        let paths: Vec<PathBuf> = file_paths.iter().map(PathBuf::from).collect();
+        let counter = counter_for_model(&self.model);
+        let selected = ContextManager::new(PathBuf::from("."))
+            .files_within_budget(&paths, CONTEXT_FILE_TOKENS, counter);
+        println!("[HEALER] {} of {} files fit in {} tokens ({})",
+            selected.len(), paths.len(), CONTEXT_FILE_TOKENS, counter.name());
+
+        selected.into_iter()
+            .map(|(path, content)| {
+                // Record that we read this file for staleness detection
+                self.file_tracker.record_read(&path);
+                (path.to_string_lossy().into_owned(), content)
+            })
+            .collect()
     }
     
     /// Find relevant procedures from memory
@@ -596,13 +609,38 @@ impl Healer {
         let verify_command = self.get_verify_command(&project_type);
         
         let shadow_result = self.sandbox.shadow_run(&verify_command, &working_dir).await?;
-        
-        let verification = VerificationResult {
+        let mut verification = VerificationResult {
             success: shadow_result.success,
             test_output: shadow_result.stdout.clone(),
             error_resolved: shadow_result.success && !shadow_result.stderr.contains("error"),
             new_errors: self.extract_errors_from_output(&shadow_result.stderr),
+            test_report: None,
         };
+
+        // Once it builds, the test suite gives the real verdict
+        if shadow_result.success
+            && let Some(test_command) = test_results::test_command(&project_type)
+        {
+            let test_result = self.sandbox.shadow_run(test_command, &working_dir).await?;
+            let report = test_results::parse(&test_result.stdout, &test_result.stderr);
+            let passed = match &report {
+                Some(report) => report.success() && test_result.exit_code == 0,
+                None => test_result.exit_code == 0,
+            };
+            verification.success = passed;
+            verification.error_resolved = passed;
+            verification.test_output = test_result.stdout;
+            if let Some(report) = &report {
+                verification.new_errors.extend(
+                    report.failures.iter().map(|f| format!("{}: {}", f.name, f.message)),
+                );
+            }
+            verification.test_report = report;
+        }
+
+        if let Some(router) = self.router.read().unwrap_or_else(|e| e.into_inner()).as_ref() {
+            router.report_test_result(TaskCategory::Coding, verification.success);
+        }
         
         self.event_tx.send(HealerEvent::VerificationComplete {
             session_id: session_id.to_string(),
@@ -623,6 +661,11 @@ impl Healer {
                 severity,
             }).await.ok();
         }
+
+        let mut sessions = self.sessions.write().await;
+        if let Some(fix) = sessions.get_mut(session_id).and_then(|s| s.fixes.last_mut()) {
+            fix.verification_result = Some(verification);
+        }
         
         Ok(())
     }
@@ -727,8 +770,8 @@ impl Healer {
     
     fn get_working_dir(&self, error_event: &ErrorEvent) -> Result<PathBuf> {
         match error_event {
-            ErrorEvent::LogError(e) => Ok(e.detected_error.file_path.as_ref()
-                .map(|p| PathBuf::from(p))
+            ErrorEvent::LogError(e) => Ok(e.detected_error.file_path.as_deref()
+                .map(project_root)
                 .unwrap_or_else(|| PathBuf::from("."))),
             ErrorEvent::FileChange(e) => Ok(PathBuf::from(".")),
             ErrorEvent::BuildError { project_path, .. } => Ok(project_path.clone()),
@@ -741,21 +784,12 @@ impl Healer {
         error: &DetectedError,
         similar_errors: &[MemoryResult],
         context_files: &[String],
-        file_contents: &HashMap<String, String>,
+        file_contents: &[(String, String)],
     ) -> Result<String> {
-        // Build file context
+        // Build file context; `read_file_contents` already kept it within budget
         let file_context = file_contents
             .iter()
-            .take(3) // Limit to first 3 files to avoid token limits
-            .map(|(path, content)| {
-                format!("--- {} ---\n{}", path, 
-                    if content.len() > 2000 {
-                        &content[..2000]
-                    } else {
-                        content
-                    }
-                )
-            })
+            .map(|(path, content)| format!("--- {} ---\n{}", path, content))
             .collect::<Vec<_>>()
             .join("\n\n");
         
@@ -791,14 +825,8 @@ impl Healer {
             .unwrap_or_default();
         
         let mut messages = vec![
-            Message {
-                role: Role::System,
-                content: "You are an expert software engineer analyzing code errors. Provide a concise root cause analysis with specific technical details. Focus on: 1) What caused the error, 2) Why it happened, 3) What files are involved.".to_string(),
-                name: None,
-            },
-            Message {
-                role: Role::User,
-                content: format!(
+            Message::system("You are an expert software engineer analyzing code errors. Provide a concise root cause analysis with specific technical details. Focus on: 1) What caused the error, 2) Why it happened, 3) What files are involved."),
+            Message::user(format!(
                     "Analyze this error:\n\nType: {:?}\nSeverity: {:?}\nMessage: {}\nFile: {:?}\nLine: {:?}\nColumn: {:?}{}\n\nContext files: {:?}\n\nFile contents:\n{}\n\nSimilar past errors from memory:\n{}\n\nProvide a detailed root cause analysis.",
                     error.error_type, 
                     error.severity, 
@@ -810,9 +838,7 @@ impl Healer {
                     context_files,
                     if file_context.is_empty() { "No file contents available" } else { &file_context },
                     similar_context
-                ),
-                name: None,
-            },
+                )),
         ];
         
         let request = crate::providers::CompletionRequest {
@@ -823,6 +849,7 @@ impl Healer {
             stream: Some(false),
                 tools: None,
             extra_params: None,
+            cache: None,
         };
         
         let response = self.provider.complete(request).await?;
@@ -875,14 +902,8 @@ impl Healer {
         };
         
         let mut messages = vec![
-            Message {
-                role: Role::System,
-                content: "You are an expert software engineer. Generate a specific fix for the error. Provide: 1) A clear description of the fix, 2) The exact code changes needed. Be specific about what lines to change.".to_string(),
-                name: None,
-            },
-            Message {
-                role: Role::User,
-                content: format!(
+            Message::system("You are an expert software engineer. Generate a specific fix for the error. Provide: 1) A clear description of the fix, 2) The exact code changes needed. Be specific about what lines to change."),
+            Message::user(format!(
                     "Error Details:\nType: {:?}\nSeverity: {:?}\nMessage: {}\nFile: {:?}\nLine: {:?}\n\nRoot Cause Analysis:\n{}{}{}\n\nContext Files:\n{}\n\nGenerate a specific fix. Format your response as:\n\n## Description\nBrief description of the fix\n\n## Changes\nFor each file, specify the changes:
 - File: [path]
   - Action: [add|modify|delete]
@@ -897,9 +918,7 @@ impl Healer {
                     suggested_fixes,
                     similar_fixes,
                     file_context
-                ),
-                name: None,
-            },
+                )),
         ];
         
         let request = crate::providers::CompletionRequest {
@@ -910,6 +929,7 @@ impl Healer {
             stream: Some(false),
                 tools: None,
             extra_params: None,
+            cache: None,
         };
         
         let response = self.provider.complete(request).await?;
@@ -1150,13 +1170,26 @@ impl Healer {
     }
 }
 
+/// The nearest directory above `file` that holds a project, or failing that
+/// the file's own directory
+fn project_root(file: &str) -> PathBuf {
+    use crate::watcher::filesystem::detect_project_type;
+    let path = Path::new(file);
+    path.ancestors()
+        .skip(1)
+        .find(|dir| detect_project_type(dir).is_some())
+        .or_else(|| path.parent())
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .map_or_else(|| PathBuf::from("."), Path::to_path_buf)
+}
+
 // Manual Clone implementation for Healer
 impl Clone for Healer {
     fn clone(&self) -> Self {
         Self {
             config: self.config.clone(),
             memory: self.memory.clone(),
-            sandbox: SandboxManager::new(),
+            sandbox: self.sandbox.clone(),
             hydrator: Hydrator::new().expect("Failed to create hydrator"),
             file_tracker: self.file_tracker.clone(),
             provider: self.provider.clone(),
@@ -1164,6 +1197,7 @@ impl Clone for Healer {
             sessions: self.sessions.clone(),
             event_tx: self.event_tx.clone(),
             swarm: self.swarm.clone(),
+            router: std::sync::RwLock::new(self.router.read().unwrap_or_else(|e| e.into_inner()).clone()),
         }
     }
 }
@@ -1171,6 +1205,85 @@ impl Clone for Healer {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::hierarchy::EscalationPolicy;
+    use crate::providers::mock::{Cassette, MockProvider};
+    use crate::router::Route;
+
+    const BROKEN: &str = "pub fn add(a: i32, b: i32) -> i32 {\n    a - b\n}\n#[test]\nfn adds() {\n    assert_eq!(add(2, 2), 4);\n}\n";
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_heal_applies_and_verifies_fix() {
+        let dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(dir.path().join("Cargo.toml"), "[package]\nname = \"healme\"\nversion = \"0.1.0\"\nedition = \"2021\"\n").unwrap();
+        std::fs::create_dir(dir.path().join("src")).unwrap();
+        let lib = dir.path().join("src/lib.rs");
+        std::fs::write(&lib, BROKEN).unwrap();
+        let lib_path = lib.to_string_lossy().into_owned();
+
+        let fixed = BROKEN.replace("a - b", "a + b");
+        let fix = format!("## Description\nAdd instead of subtracting\n\n## Changes\n- File: {}\n  - Action: modify\n```rust\n{}```\n", lib_path, fixed);
+        let cassette: Cassette = serde_json::from_value(serde_json::json!({
+            "interactions": [
+                {"response": {"content": "Root cause: add subtracts its arguments."}},
+                {"when": "Generate a specific fix", "response": {"content": fix}}
+            ]
+        })).unwrap();
+
+        let memory = Arc::new(RwLock::new(MemorySystem::new(dir.path().join("memory")).unwrap()));
+        let (event_tx, mut events) = mpsc::channel(100);
+        let config = HealerConfig { use_shadow_run: false, ..HealerConfig::default() };
+        let mut healer = Healer::new(config, memory, Arc::new(MockProvider::new(cassette)), "mock".to_string(), event_tx).unwrap();
+        healer.sandbox = Arc::new(SandboxManager::on_host());
+        let healer = Arc::new(healer);
+
+        // A failing test earlier pushed coding up a tier; a verified fix brings it back
+        let mock = || Arc::new(MockProvider::new(Cassette::default())) as Arc<dyn Provider + Send + Sync>;
+        let policy = EscalationPolicy { escalate_on_test_failure: true, ..EscalationPolicy::default() };
+        let router = Arc::new(
+            ModelRouter::new(Route::new("default", mock(), "flash"), policy)
+                .with_tier(TaskCategory::Coding, Route::new("a", mock(), "small"))
+                .with_tier(TaskCategory::Coding, Route::new("b", mock(), "large")),
+        );
+        router.report_test_result(TaskCategory::Coding, false);
+        assert_eq!(router.model_for(TaskCategory::Coding), "large");
+        healer.set_router(router.clone());
+
+        let error = DetectedError {
+            error_type: ErrorType::RustCompilation,
+            severity: ErrorSeverity::Error,
+            message: "test adds ... FAILED".to_string(),
+            file_path: Some(lib_path.clone()),
+            line_number: Some(2),
+            column: None,
+            stack_trace: None,
+            suggested_fix: None,
+        };
+        let session_id = healer.heal(ErrorEvent::LogError(LogErrorEvent {
+            source_id: "cargo test".to_string(),
+            timestamp: chrono::Utc::now(),
+            detected_error: error,
+            raw_log_line: "test adds ... FAILED".to_string(),
+            context_lines: Vec::new(),
+        })).await.unwrap();
+
+        let verified = tokio::time::timeout(std::time::Duration::from_secs(120), async {
+            let mut verified = None;
+            loop {
+                match events.recv().await.expect("healer stopped before finishing") {
+                    HealerEvent::VerificationComplete { success, error_resolved, .. } => verified = Some((success, error_resolved)),
+                    HealerEvent::SessionComplete { .. } => break verified,
+                    _ => {}
+                }
+            }
+        }).await.unwrap();
+        assert_eq!(verified, Some((true, true)));
+
+        assert_eq!(std::fs::read_to_string(&lib).unwrap().trim_end(), fixed.trim_end());
+        let session = healer.get_session(&session_id).await.unwrap();
+        let report = session.fixes.last().unwrap().verification_result.as_ref().unwrap().test_report.clone().unwrap();
+        assert_eq!((report.passed, report.failed), (1, 0));
+        assert_eq!(router.model_for(TaskCategory::Coding), "small");
+    }
     
     // Note: These would need mocked dependencies in a real test suite
     
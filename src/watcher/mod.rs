@@ -54,6 +54,7 @@ use crate::error::{NexusError, Result};
 use crate::memory::MemorySystem;
 use crate::providers::Provider;
 use crate::swarm::SwarmOrchestrator;
+use crate::router::ModelRouter;
 use std::collections::HashMap;
 use std::path::PathBuf;
 use std::sync::Arc;
@@ -637,6 +638,14 @@ impl WatcherEngine {
         Ok(())
     }
     
+    /// Report healing verification results to the model hierarchy
+    pub fn with_router(self, router: Arc<ModelRouter>) -> Self {
+        if let Some(healer) = &self.healer {
+            healer.set_router(router);
+        }
+        self
+    }
+    
     /// Update configuration
     pub async fn update_config(&mut self, new_config: WatcherEngineConfig) -> Result<()> {
         // Stop if running